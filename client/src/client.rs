@@ -124,7 +124,7 @@ impl Client {
 
         // Clients may choose to auto trust servers to save some messing around with rejected certs
         if config.trust_server_certs {
-            certificate_store.trust_unknown_certs = true;
+            certificate_store.validation_options.trust_unknown_certs = true;
         }
 
         let session_timeout = config.session_timeout as f64;
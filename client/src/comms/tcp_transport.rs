@@ -54,6 +54,8 @@ struct ReadState {
     pub message_queue: Arc<RwLock<MessageQueue>>,
     /// Last decoded sequence number
     last_received_sequence_number: u32,
+    /// The maximum size of a message the client will receive, as sent in its hello, or 0 for no limit
+    max_message_size: usize,
 }
 
 impl Drop for ReadState {
@@ -68,7 +70,7 @@ impl ReadState {
         let secure_channel = trace_read_lock_unwrap!(self.secure_channel);
         self.last_received_sequence_number = Chunker::validate_chunks(self.last_received_sequence_number + 1, &secure_channel, chunks)?;
         // Now decode
-        Chunker::decode(&chunks, &secure_channel, None)
+        Chunker::decode_with_max_message_size(&chunks, &secure_channel, self.max_message_size, None)
     }
 
     fn process_chunk(&mut self, chunk: MessageChunk) -> Result<Option<SupportedMessage>, StatusCode> {
@@ -589,9 +591,9 @@ impl TcpTransport {
     /// This is the main processing loop for the connection. It writes requests and reads responses
     /// over the socket to the server.
     fn spawn_looping_tasks(reader: ReadHalf<WrappedTcpStream>, writer: WriteHalf<WrappedTcpStream>, connection_state: Arc<RwLock<ConnectionState>>, session_state: Arc<RwLock<SessionState>>, secure_channel: Arc<RwLock<SecureChannel>>, message_queue: Arc<RwLock<MessageQueue>>) {
-        let (receive_buffer_size, send_buffer_size, id) = {
+        let (receive_buffer_size, send_buffer_size, max_message_size, id) = {
             let session_state = trace_read_lock_unwrap!(session_state);
-            (session_state.receive_buffer_size(), session_state.send_buffer_size(), session_state.id())
+            (session_state.receive_buffer_size(), session_state.send_buffer_size(), session_state.max_message_size(), session_state.id())
         };
 
         // Create the message receiver that will drive writes
@@ -613,6 +615,7 @@ impl TcpTransport {
                 secure_channel: secure_channel.clone(),
                 state: connection_state.clone(),
                 last_received_sequence_number: 0,
+                max_message_size,
                 message_queue: message_queue.clone(),
             };
             Self::spawn_reading_task(reader, sender, finished_flag, receive_buffer_size, read_connection, id);
@@ -374,12 +374,18 @@ impl SessionState {
             debug!("Setting transport's security token");
             {
                 let mut secure_channel = trace_write_lock_unwrap!(self.secure_channel);
-                secure_channel.set_security_token(response.security_token.clone());
-
                 if security_policy != SecurityPolicy::None && (security_mode == MessageSecurityMode::Sign || security_mode == MessageSecurityMode::SignAndEncrypt) {
                     secure_channel.set_remote_nonce_from_byte_string(&response.server_nonce)?;
-                    secure_channel.derive_keys();
+                    if request_type == SecurityTokenRequestType::Renew {
+                        // Responses the server secured with the old token are accepted until it expires
+                        let client_nonce = secure_channel.local_nonce().to_vec();
+                        let server_nonce = secure_channel.remote_nonce().to_vec();
+                        secure_channel.renew_token(&client_nonce, &server_nonce);
+                    } else {
+                        secure_channel.derive_keys();
+                    }
                 }
+                secure_channel.set_security_token(response.security_token.clone());
             }
             Ok(())
         } else {
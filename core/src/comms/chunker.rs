@@ -146,6 +146,15 @@ impl Chunker {
     /// Decodes a series of chunks to create a message. The message must be of a `SupportedMessage`
     /// type otherwise an error will occur.
     pub fn decode(chunks: &[MessageChunk], secure_channel: &SecureChannel, expected_node_id: Option<NodeId>) -> std::result::Result<SupportedMessage, StatusCode> {
+        Self::decode_with_max_message_size(chunks, secure_channel, 0, expected_node_id)
+    }
+
+    /// Does the same as `decode()` but fails if the message is larger than the max message size.
+    ///
+    /// max_message_size refers to the maximum byte length of a message or 0 for no limit. It is
+    /// measured the same way as for `encode()`, i.e. without the node id that prefixes the body,
+    /// and is tested before the chunks are assembled.
+    pub fn decode_with_max_message_size(chunks: &[MessageChunk], secure_channel: &SecureChannel, max_message_size: usize, expected_node_id: Option<NodeId>) -> std::result::Result<SupportedMessage, StatusCode> {
         // Calculate the size of data held in all chunks
         let mut data_size: usize = 0;
         for (i, chunk) in chunks.iter().enumerate() {
@@ -165,14 +174,38 @@ impl Chunker {
             data_size += chunk.data[body_start..body_end].len();
         }
 
-        // Read the data into a contiguous buffer. The assumption is the data is decrypted / verified by now
+        let decoding_limits = secure_channel.decoding_limits();
+
+        // The extension object prefix is just the node id. A point the spec rather unhelpfully doesn't
+        // elaborate on. Probably because people enjoy debugging why the stream pos is out by 1 byte
+        // for hours.
+
+        // Read node id from the body of the first chunk
+        let chunk_info = chunks[0].chunk_info(secure_channel)?;
+        let mut body = Cursor::new(&chunks[0].data[chunk_info.body_offset..chunk_info.body_offset + chunk_info.body_length]);
+        let node_id = NodeId::decode(&mut body, &decoding_limits)?;
+        let node_id_size = body.position() as usize;
+
+        if max_message_size > 0 {
+            let message_size = data_size.saturating_sub(node_id_size);
+            if message_size > max_message_size {
+                error!("Max message size is {} and message {} exceeds that", max_message_size, message_size);
+                // A client receives responses, a server receives requests
+                return Err(if secure_channel.is_client_role() { StatusCode::BadResponseTooLarge } else { StatusCode::BadRequestTooLarge });
+            }
+        }
+
+        let object_id = Self::object_id_from_node_id(node_id, expected_node_id)?;
+
+        // Read the data that follows the node id into a contiguous buffer. The assumption is the
+        // data is decrypted / verified by now
         // TODO this buffer should be externalized so it is not allocated each time
-        let mut data = Vec::with_capacity(data_size);
-        for chunk in chunks.iter() {
+        let mut data = Vec::with_capacity(data_size - node_id_size);
+        for (i, chunk) in chunks.iter().enumerate() {
             let chunk_info = chunk.chunk_info(secure_channel)?;
 
-            let body_start = chunk_info.body_offset;
-            let body_end = body_start + chunk_info.body_length;
+            let body_start = if i == 0 { chunk_info.body_offset + node_id_size } else { chunk_info.body_offset };
+            let body_end = chunk_info.body_offset + chunk_info.body_length;
             let body_data = &chunk.data[body_start..body_end];
             data.extend_from_slice(body_data);
         }
@@ -180,16 +213,6 @@ impl Chunker {
         // Make a stream around the data
         let mut data = Cursor::new(data);
 
-        // The extension object prefix is just the node id. A point the spec rather unhelpfully doesn't
-        // elaborate on. Probably because people enjoy debugging why the stream pos is out by 1 byte
-        // for hours.
-
-        let decoding_limits = secure_channel.decoding_limits();
-
-        // Read node id from stream
-        let node_id = NodeId::decode(&mut data, &decoding_limits)?;
-        let object_id = Self::object_id_from_node_id(node_id, expected_node_id)?;
-
         // Now decode the payload using the node id.
         match SupportedMessage::decode_by_object_id(&mut data, object_id, &decoding_limits) {
             Ok(decoded_message) => {
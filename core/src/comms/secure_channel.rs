@@ -8,6 +8,8 @@ use opcua_crypto::{
     CertificateStore,
     pkey::{KeySize, PrivateKey, PublicKey},
     random,
+    secret::SecretBytes,
+    DerivedKeys,
     SecurityPolicy,
     x509::X509,
 };
@@ -23,6 +25,31 @@ use crate::comms::{
     security_header::{AsymmetricSecurityHeader, SecurityHeader, SymmetricSecurityHeader},
 };
 
+// Extra padding required for keysize > 2048 bits (256 bytes)
+fn minimum_padding(signature_len: usize) -> usize {
+    if signature_len > 256 { 2 } else { 1 }
+}
+
+/// Computes the padding to add to a plain text of `plaintext_len` bytes, i.e. the sequence header
+/// and body, so that the plain text, padding and signature fill a whole number of blocks.
+///
+/// Returns the padding byte and the total number of padding bytes. The padding byte is the
+/// PaddingSize and is written out for each byte of padding. When the signature is over 256 bytes,
+/// i.e. the key is over 2048 bits, the last padding byte is instead the ExtraPadding byte holding
+/// the high byte of the PaddingSize.
+///
+/// PaddingSize = PlainTextBlockSize – ((BytesToWrite + SignatureSize + 1) % PlainTextBlockSize);
+pub fn compute_padding(plaintext_len: usize, signature_len: usize, block_size: usize) -> (u8, usize) {
+    let minimum_padding = minimum_padding(signature_len);
+    let encrypt_size = plaintext_len + signature_len + minimum_padding;
+    let padding_size = if encrypt_size % block_size != 0 {
+        block_size - (encrypt_size % block_size)
+    } else {
+        0
+    };
+    ((padding_size & 0xff) as u8, minimum_padding + padding_size)
+}
+
 #[derive(Debug, PartialEq)]
 pub enum Role {
     Unknown,
@@ -30,6 +57,19 @@ pub enum Role {
     Server,
 }
 
+/// The keys of a security token that has been renewed. The other end may have secured messages
+/// with the old token before it saw the renewal, so those are still decrypted until the old
+/// token expires.
+#[derive(Debug)]
+struct RenewedToken {
+    /// Token identifier of the old token
+    token_id: u32,
+    /// Their keys derived for the old token
+    remote_keys: DerivedKeys,
+    /// The time the old token expires, after which its messages are rejected
+    expires_at: DateTime,
+}
+
 /// Holds all of the security information related to this session
 #[derive(Debug)]
 pub struct SecureChannel {
@@ -51,16 +91,20 @@ pub struct SecureChannel {
     cert: Option<X509>,
     /// Our private key
     private_key: Option<PrivateKey>,
+    /// Certificates and private keys used instead of our own by particular security policies
+    policy_certs: Vec<(SecurityPolicy, X509, PrivateKey)>,
     /// Their certificate
     remote_cert: Option<X509>,
     /// Their nonce provided by open secure channel
-    remote_nonce: Vec<u8>,
+    remote_nonce: SecretBytes,
     /// Our nonce generated while handling open secure channel
-    local_nonce: Vec<u8>,
+    local_nonce: SecretBytes,
     /// Client (i.e. other end's set of keys) Symmetric Signing Key, Encrypt Key, IV
-    remote_keys: Option<(Vec<u8>, AesKey, Vec<u8>)>,
+    remote_keys: Option<DerivedKeys>,
     /// Server (i.e. our end's set of keys) Symmetric Signing Key, Decrypt Key, IV
-    local_keys: Option<(Vec<u8>, AesKey, Vec<u8>)>,
+    local_keys: Option<DerivedKeys>,
+    /// The previous token, if the token has been renewed and the old one has not expired
+    renewed_token: Option<RenewedToken>,
     /// Decoding limits
     decoding_limits: DecodingLimits,
 }
@@ -75,13 +119,15 @@ impl From<(SecurityPolicy, MessageSecurityMode)> for SecureChannel {
             token_id: 0,
             token_created_at: DateTime::now(),
             token_lifetime: 0,
-            local_nonce: Vec::new(),
-            remote_nonce: Vec::new(),
+            local_nonce: SecretBytes::default(),
+            remote_nonce: SecretBytes::default(),
             cert: None,
             private_key: None,
+            policy_certs: Vec::new(),
             remote_cert: None,
             local_keys: None,
             remote_keys: None,
+            renewed_token: None,
             decoding_limits: DecodingLimits::default(),
         }
     }
@@ -112,13 +158,15 @@ impl SecureChannel {
             token_id: 0,
             token_created_at: DateTime::now(),
             token_lifetime: 0,
-            local_nonce: Vec::new(),
-            remote_nonce: Vec::new(),
+            local_nonce: SecretBytes::default(),
+            remote_nonce: SecretBytes::default(),
             cert,
             private_key,
+            policy_certs: Vec::new(),
             remote_cert: None,
             local_keys: None,
             remote_keys: None,
+            renewed_token: None,
             decoding_limits,
         }
     }
@@ -127,6 +175,12 @@ impl SecureChannel {
         self.role == Role::Client
     }
 
+    /// For testing purposes only
+    #[cfg(test)]
+    pub fn set_role(&mut self, role: Role) {
+        self.role = role;
+    }
+
     pub fn set_cert(&mut self, cert: Option<X509>) {
         self.cert = cert;
     }
@@ -147,6 +201,12 @@ impl SecureChannel {
         self.private_key = private_key;
     }
 
+    /// Sets the certificates and private keys to use instead of our own when the other end opens
+    /// the channel with one of their security policies.
+    pub fn set_policy_certs(&mut self, policy_certs: Vec<(SecurityPolicy, X509, PrivateKey)>) {
+        self.policy_certs = policy_certs;
+    }
+
     pub fn security_mode(&self) -> MessageSecurityMode {
         self.security_mode
     }
@@ -168,6 +228,7 @@ impl SecureChannel {
         self.token_id = 0;
         self.token_created_at = DateTime::now();
         self.token_lifetime = 0;
+        self.renewed_token = None;
     }
 
     pub fn set_security_token(&mut self, channel_token: ChannelSecurityToken) {
@@ -254,11 +315,11 @@ impl SecureChannel {
     /// Creates a nonce for the connection. The nonce should be the same size as the symmetric key
     pub fn create_random_nonce(&mut self) {
         if self.security_policy != SecurityPolicy::None && (self.security_mode == MessageSecurityMode::Sign || self.security_mode == MessageSecurityMode::SignAndEncrypt) {
-            self.local_nonce = vec![0u8; self.security_policy.secure_channel_nonce_length()];
+            self.local_nonce = SecretBytes::zeroed(self.security_policy.secure_channel_nonce_length());
             random::bytes(&mut self.local_nonce);
         } else {
             // Empty nonce
-            self.local_nonce = Vec::new();
+            self.local_nonce = SecretBytes::default();
         }
     }
 
@@ -289,7 +350,7 @@ impl SecureChannel {
                     error!("Remote nonce is invalid length {}, expecting {}. {:?}", remote_nonce.len(), self.security_policy.secure_channel_nonce_length(), remote_nonce);
                     Err(StatusCode::BadNonceInvalid)
                 } else {
-                    self.remote_nonce = remote_nonce.to_vec();
+                    self.remote_nonce = SecretBytes::from(remote_nonce.as_slice());
                     Ok(())
                 }
             } else {
@@ -344,6 +405,51 @@ impl SecureChannel {
         trace!("Derived local keys = {:?}", self.local_keys);
     }
 
+    /// Renews the security token with fresh nonces. The keys are derived again from the new nonces
+    /// and replace the current keys. Their keys for the current token are kept until the current
+    /// token expires so messages the other end secured before the renewal can still be decrypted.
+    ///
+    /// The new token id should be set after this call so messages secured with the old token id
+    /// are recognised.
+    pub fn renew_token(&mut self, new_client_nonce: &[u8], new_server_nonce: &[u8]) {
+        if let Some(remote_keys) = self.remote_keys.take() {
+            let token_created_at: chrono::DateTime<chrono::Utc> = self.token_created_at.clone().into();
+            let expires_at = token_created_at + chrono::Duration::milliseconds(self.token_lifetime as i64);
+            self.renewed_token = Some(RenewedToken {
+                token_id: self.token_id,
+                remote_keys,
+                expires_at: DateTime::from(expires_at),
+            });
+        }
+        let (local_nonce, remote_nonce) = if self.role == Role::Client {
+            (new_client_nonce, new_server_nonce)
+        } else {
+            (new_server_nonce, new_client_nonce)
+        };
+        self.set_local_nonce(local_nonce);
+        self.set_remote_nonce(remote_nonce);
+        self.derive_keys();
+    }
+
+    /// Returns their keys for the token id in a symmetric security header. A message secured
+    /// with a token that has been renewed uses the keys of the old token until it expires.
+    fn remote_keys_for_token(&self, token_id: u32) -> Result<Option<&DerivedKeys>, StatusCode> {
+        match self.renewed_token {
+            Some(ref renewed_token) if renewed_token.token_id == token_id && token_id != self.token_id => {
+                let now: chrono::DateTime<chrono::Utc> = DateTime::now().into();
+                let expires_at: chrono::DateTime<chrono::Utc> = renewed_token.expires_at.clone().into();
+                if now < expires_at {
+                    trace!("Message is secured with renewed token {}", token_id);
+                    Ok(Some(&renewed_token.remote_keys))
+                } else {
+                    error!("Message is secured with token {} which has expired since it was renewed", token_id);
+                    Err(StatusCode::BadSecureChannelTokenUnknown)
+                }
+            }
+            _ => Ok(self.remote_keys.as_ref())
+        }
+    }
+
     /// Test if the token has expired yet
     pub fn token_has_expired(&self) -> bool {
         let now: chrono::DateTime<chrono::Utc> = DateTime::now().into();
@@ -372,15 +478,16 @@ impl SecureChannel {
         }
     }
 
-    // Extra padding required for keysize > 2048 bits (256 bytes)
-    fn minimum_padding(signature_size: usize) -> usize {
-        if signature_size > 256 { 2 } else { 1 }
-    }
-
     /// Calculate the padding size
     ///
     /// Padding adds bytes to the body to make it a multiple of the block size so it can be encrypted.
     pub fn padding_size(&self, security_header: &SecurityHeader, body_size: usize, signature_size: usize) -> usize {
+        self.padding(security_header, body_size, signature_size).1
+    }
+
+    /// Calculate the padding byte and the padding size, see `compute_padding()`. There is no
+    /// padding when the message is not secured.
+    fn padding(&self, security_header: &SecurityHeader, body_size: usize, signature_size: usize) -> (u8, usize) {
         if self.security_policy != SecurityPolicy::None && self.security_mode != MessageSecurityMode::None {
             // Signature size in bytes
             let plain_text_block_size = match security_header {
@@ -390,9 +497,8 @@ impl SecureChannel {
                         self.security_policy.plain_block_size()
                     } else {
                         // Padding requires we look at the sending key and security policy
-                        let padding = self.security_policy.asymmetric_encryption_padding();
                         let x509 = X509::from_byte_string(&security_header.sender_certificate).unwrap();
-                        x509.public_key().unwrap().plain_text_block_size(padding)
+                        self.security_policy.asymmetric_plaintext_block_size(&x509.public_key().unwrap())
                     }
                 }
                 SecurityHeader::Symmetric(_) => {
@@ -401,18 +507,11 @@ impl SecureChannel {
                 }
             };
 
-            // PaddingSize = PlainTextBlockSize – ((BytesToWrite + SignatureSize + 1) % PlainTextBlockSize);
-            let minimum_padding = Self::minimum_padding(signature_size);
-            let encrypt_size = 8 + body_size + signature_size + minimum_padding;
-            let padding_size = if encrypt_size % plain_text_block_size != 0 {
-                plain_text_block_size - (encrypt_size % plain_text_block_size)
-            } else {
-                0
-            };
-            trace!("sequence_header(8) + body({}) + signature ({}) = plain text size = {} / with padding {} = {}, plain_text_block_size = {}", body_size, signature_size, encrypt_size, padding_size, encrypt_size + padding_size, plain_text_block_size);
-            minimum_padding + padding_size
+            let (padding_byte, padding_size) = compute_padding(8 + body_size, signature_size, plain_text_block_size);
+            trace!("sequence_header(8) + body({}) + signature ({}) with padding {}, plain_text_block_size = {}", body_size, signature_size, padding_size, plain_text_block_size);
+            (padding_byte, padding_size)
         } else {
-            0
+            (0, 0)
         }
     }
 
@@ -436,18 +535,16 @@ impl SecureChannel {
         // Write padding
         let body_size = chunk_info.body_length;
 
-        let padding_size = self.padding_size(&security_header, body_size, signature_size);
+        let (padding_byte, padding_size) = self.padding(&security_header, body_size, signature_size);
         if padding_size > 0 {
             // A number of bytes are written out equal to the padding size.
             // Each byte is the padding size. So if padding size is 15 then
             // there will be 15 bytes all with the value 15
-            let minimum_padding = Self::minimum_padding(signature_size);
+            let minimum_padding = minimum_padding(signature_size);
             if minimum_padding == 1 {
-                let padding_byte = ((padding_size - 1) & 0xff) as u8;
                 let _ = write_bytes(&mut stream, padding_byte, padding_size)?;
             } else if minimum_padding == 2 {
                 // Padding and then extra padding
-                let padding_byte = ((padding_size - 2) & 0xff) as u8;
                 let extra_padding_byte = ((padding_size - 2) >> 8) as u8;
                 trace!("adding extra padding - padding_byte = {}, extra_padding_byte = {}", padding_byte, extra_padding_byte);
                 let _ = write_bytes(&mut stream, padding_byte, padding_size - 1)?;
@@ -595,6 +692,12 @@ impl SecureChannel {
             }
             self.security_policy = security_policy;
 
+            // A certificate configured for the policy is used instead of our own
+            if let Some((_, cert, private_key)) = self.policy_certs.iter().find(|(p, _, _)| *p == security_policy) {
+                self.cert = Some(cert.clone());
+                self.private_key = Some(private_key.clone());
+            }
+
             // Asymmetric decrypt and verify
 
             // The OpenSecureChannel Messages are always signed and encrypted if the SecurityMode
@@ -614,11 +717,14 @@ impl SecureChannel {
             let sender_certificate = X509::from_byte_string(&security_header.sender_certificate)?;
 
             let verification_key = sender_certificate.public_key()?;
-            let receiver_thumbprint = security_header.receiver_certificate_thumbprint;
-            trace!("Receiver thumbprint = {:?}", receiver_thumbprint);
+            trace!("Receiver thumbprint = {:?}", security_header.receiver_certificate_thumbprint);
+
+            // The receiver certificate thumbprint identifies which of our certs was used by the client
+            // to encrypt the message.
+            security_header.validate_receiver_thumbprint(self.cert.as_ref().unwrap())?;
 
             let mut decrypted_data = vec![0u8; message_size];
-            let decrypted_size = self.asymmetric_decrypt_and_verify(security_policy, &verification_key, receiver_thumbprint, src, encrypted_range, their_key, &mut decrypted_data)?;
+            let decrypted_size = self.asymmetric_decrypt_and_verify(security_policy, &verification_key, src, encrypted_range, their_key, &mut decrypted_data)?;
 
             Self::update_message_size_and_truncate(decrypted_data, decrypted_size, &self.decoding_limits)?
         } else if self.security_policy != SecurityPolicy::None && (self.security_mode == MessageSecurityMode::Sign || self.security_mode == MessageSecurityMode::SignAndEncrypt) {
@@ -628,8 +734,14 @@ impl SecureChannel {
             let signed_range = 0..(message_size - signature_size);
             trace!("Decrypting block with signature info {:?} and encrypt info {:?}", signed_range, encrypted_range);
 
+            let token_id = match security_header {
+                SecurityHeader::Symmetric(ref security_header) => security_header.token_id,
+                _ => self.token_id
+            };
+            let remote_keys = self.remote_keys_for_token(token_id)?;
+
             let mut decrypted_data = vec![0u8; message_size];
-            let decrypted_size = self.symmetric_decrypt_and_verify(src, signed_range, encrypted_range, &mut decrypted_data)?;
+            let decrypted_size = self.symmetric_decrypt_and_verify_with_keys(remote_keys, src, signed_range, encrypted_range, &mut decrypted_data)?;
 
             // Now we need to strip off signature
             Self::update_message_size_and_truncate(decrypted_data, decrypted_size - signature_size, &self.decoding_limits)?
@@ -737,7 +849,7 @@ impl SecureChannel {
         Ok(padding_range)
     }
 
-    fn asymmetric_decrypt_and_verify(&self, security_policy: SecurityPolicy, verification_key: &PublicKey, receiver_thumbprint: ByteString, src: &[u8], encrypted_range: Range<usize>, their_key: Option<PrivateKey>, dst: &mut [u8]) -> Result<usize, StatusCode> {
+    fn asymmetric_decrypt_and_verify(&self, security_policy: SecurityPolicy, verification_key: &PublicKey, src: &[u8], encrypted_range: Range<usize>, their_key: Option<PrivateKey>, dst: &mut [u8]) -> Result<usize, StatusCode> {
         // Asymmetric encrypt requires the caller supply the security policy
         let _ = match security_policy {
             SecurityPolicy::Basic128Rsa15 | SecurityPolicy::Basic256 | SecurityPolicy::Basic256Sha256 |
@@ -752,57 +864,48 @@ impl SecureChannel {
         // of security mode. This is part of the OpenSecureChannel request on a sign / signencrypt
         // mode connection.
 
-        // The sender_certificate is is the cert used to sign the message, i.e. the client's cert
-        //
-        // The receiver certificate thumbprint identifies which of our certs was used by the client
-        // to encrypt the message. We have to work out from the thumbprint which cert to use
+        // The sender_certificate is is the cert used to sign the message, i.e. the client's cert. The
+        // receiver certificate thumbprint has already been checked against our cert by the caller.
 
-        let our_cert = self.cert.as_ref().unwrap();
-        let our_thumbprint = our_cert.thumbprint();
-        if our_thumbprint.value() != receiver_thumbprint.as_ref() {
-            error!("Supplied thumbprint does not match application certificate's thumbprint");
-            Err(StatusCode::BadNoValidCertificates)
-        } else {
-            // Copy message, security header
-            dst[..encrypted_range.start].copy_from_slice(&src[..encrypted_range.start]);
+        // Copy message, security header
+        dst[..encrypted_range.start].copy_from_slice(&src[..encrypted_range.start]);
 
-            // Decrypt and copy encrypted block
-            // Note that the unencrypted size can be less than the encrypted size due to removal
-            // of padding, so the ranges that were supplied to this function must be offset to compensate.
-            let encrypted_size = encrypted_range.end - encrypted_range.start;
-            trace!("Decrypting message range {:?}", encrypted_range);
-            let mut decrypted_tmp = vec![0u8; encrypted_size];
+        // Decrypt and copy encrypted block
+        // Note that the unencrypted size can be less than the encrypted size due to removal
+        // of padding, so the ranges that were supplied to this function must be offset to compensate.
+        let encrypted_size = encrypted_range.end - encrypted_range.start;
+        trace!("Decrypting message range {:?}", encrypted_range);
+        let mut decrypted_tmp = vec![0u8; encrypted_size];
 
-            let private_key = self.private_key.as_ref().unwrap();
-            let decrypted_size = security_policy.asymmetric_decrypt(private_key, &src[encrypted_range.clone()], &mut decrypted_tmp)?;
-            trace!("Decrypted bytes = {} compared to encrypted range {}", decrypted_size, encrypted_size);
-            // Self::log_crypto_data("Decrypted Bytes = ", &decrypted_tmp[..decrypted_size]);
+        let private_key = self.private_key.as_ref().unwrap();
+        let decrypted_size = security_policy.asymmetric_decrypt(private_key, &src[encrypted_range.clone()], &mut decrypted_tmp)?;
+        trace!("Decrypted bytes = {} compared to encrypted range {}", decrypted_size, encrypted_size);
+        // Self::log_crypto_data("Decrypted Bytes = ", &decrypted_tmp[..decrypted_size]);
 
-            let verification_key_signature_size = verification_key.size();
-            trace!("Verification key size = {}", verification_key_signature_size);
+        let verification_key_signature_size = verification_key.size();
+        trace!("Verification key size = {}", verification_key_signature_size);
 
-            // Copy the bytes to dst
-            dst[encrypted_range.start..(encrypted_range.start + decrypted_size)].copy_from_slice(&decrypted_tmp[0..decrypted_size]);
+        // Copy the bytes to dst
+        dst[encrypted_range.start..(encrypted_range.start + decrypted_size)].copy_from_slice(&decrypted_tmp[0..decrypted_size]);
 
-            // The signature range is at the end of the decrypted block for the verification key's signature
-            let signature_dst_offset = encrypted_range.start + decrypted_size - verification_key_signature_size;
-            let signature_range_dst = signature_dst_offset..(signature_dst_offset + verification_key_signature_size);
+        // The signature range is at the end of the decrypted block for the verification key's signature
+        let signature_dst_offset = encrypted_range.start + decrypted_size - verification_key_signature_size;
+        let signature_range_dst = signature_dst_offset..(signature_dst_offset + verification_key_signature_size);
 
-            // The signed range is from 0 to the end of the plaintext except for key size
-            let signed_range_dst = 0..signature_dst_offset;
+        // The signed range is from 0 to the end of the plaintext except for key size
+        let signed_range_dst = 0..signature_dst_offset;
 
-            // Self::log_crypto_data("Decrypted data = ", &dst[..signature_range_dst.end]);
+        // Self::log_crypto_data("Decrypted data = ", &dst[..signature_range_dst.end]);
 
-            // Verify signature (contained encrypted portion) using verification key
-            trace!("Verifying signature range {:?} with signature at {:?}", signed_range_dst, signature_range_dst);
-            security_policy.asymmetric_verify_signature(verification_key, &dst[signed_range_dst.clone()], &dst[signature_range_dst.clone()], their_key)?;
+        // Verify signature (contained encrypted portion) using verification key
+        trace!("Verifying signature range {:?} with signature at {:?}", signed_range_dst, signature_range_dst);
+        security_policy.asymmetric_verify_signature(verification_key, &dst[signed_range_dst.clone()], &dst[signature_range_dst.clone()], their_key)?;
 
-            // Verify that the padding is correct
-            let padding_range = self.verify_padding(dst, verification_key.size(), signature_range_dst.start)?;
+        // Verify that the padding is correct
+        let padding_range = self.verify_padding(dst, verification_key.size(), signature_range_dst.start)?;
 
-            // Decrypted and verified into dst
-            Ok(padding_range.start)
-        }
+        // Decrypted and verified into dst
+        Ok(padding_range.start)
     }
 
     pub fn local_nonce(&self) -> &[u8] {
@@ -810,21 +913,19 @@ impl SecureChannel {
     }
 
     pub fn set_local_nonce(&mut self, local_nonce: &[u8]) {
-        self.local_nonce.clear();
-        self.local_nonce.extend_from_slice(local_nonce);
+        self.local_nonce = SecretBytes::from(local_nonce);
     }
 
     pub fn local_nonce_as_byte_string(&self) -> ByteString {
         if self.local_nonce.is_empty() {
             ByteString::null()
         } else {
-            ByteString::from(&self.local_nonce)
+            ByteString::from(&self.local_nonce[..])
         }
     }
 
     pub fn set_remote_nonce(&mut self, remote_nonce: &[u8]) {
-        self.remote_nonce.clear();
-        self.remote_nonce.extend_from_slice(remote_nonce);
+        self.remote_nonce = SecretBytes::from(remote_nonce);
     }
 
     pub fn remote_nonce(&self) -> &[u8] {
@@ -835,34 +936,30 @@ impl SecureChannel {
         if self.remote_nonce.is_empty() {
             ByteString::null()
         } else {
-            ByteString::from(&self.remote_nonce)
+            ByteString::from(&self.remote_nonce[..])
         }
     }
 
-    fn local_keys(&self) -> &(Vec<u8>, AesKey, Vec<u8>) {
+    fn local_keys(&self) -> &DerivedKeys {
         self.local_keys.as_ref().unwrap()
     }
 
-    fn remote_keys(&self) -> &(Vec<u8>, AesKey, Vec<u8>) {
-        self.remote_keys.as_ref().unwrap()
-    }
-
     fn encryption_keys(&self) -> (&AesKey, &[u8]) {
         let keys = self.local_keys();
-        (&keys.1, &keys.2)
+        (&keys.1, &keys.2[..])
     }
 
     fn signing_key(&self) -> &[u8] {
         &(self.local_keys()).0
     }
 
-    fn decryption_keys(&self) -> (&AesKey, &[u8]) {
-        let keys = self.remote_keys();
-        (&keys.1, &keys.2)
+    fn decryption_keys(remote_keys: Option<&DerivedKeys>) -> (&AesKey, &[u8]) {
+        let keys = remote_keys.unwrap();
+        (&keys.1, &keys.2[..])
     }
 
-    fn verification_key(&self) -> &[u8] {
-        &(self.remote_keys()).0
+    fn verification_key(remote_keys: Option<&DerivedKeys>) -> &[u8] {
+        &remote_keys.unwrap().0
     }
 
     /// Encode data using security. Destination buffer is expected to be same size as src and expected
@@ -870,6 +967,10 @@ impl SecureChannel {
     ///
     /// Signing is done first and then encryption
     ///
+    /// Every chunk is encrypted on its own, starting from the IV derived for the channel. OPC UA
+    /// does not chain CBC across chunks, i.e. the last cipher block of one chunk is not the IV of
+    /// the next, so each chunk can be decrypted independently of the others.
+    ///
     /// S - Message Header
     /// S - Security Header
     /// S - Sequence Header - E
@@ -944,6 +1045,10 @@ impl SecureChannel {
     /// S - Padding         - E
     ///     Signature       - E
     pub fn symmetric_decrypt_and_verify(&self, src: &[u8], signed_range: Range<usize>, encrypted_range: Range<usize>, dst: &mut [u8]) -> Result<usize, StatusCode> {
+        self.symmetric_decrypt_and_verify_with_keys(self.remote_keys.as_ref(), src, signed_range, encrypted_range, dst)
+    }
+
+    fn symmetric_decrypt_and_verify_with_keys(&self, remote_keys: Option<&DerivedKeys>, src: &[u8], signed_range: Range<usize>, encrypted_range: Range<usize>, dst: &mut [u8]) -> Result<usize, StatusCode> {
         match self.security_mode {
             MessageSecurityMode::None => {
                 // Just copy everything from src to dst
@@ -958,7 +1063,7 @@ impl SecureChannel {
                 dst[all].copy_from_slice(&src[all]);
                 // Verify signature
                 trace!("Verifying range from {:?} to signature {}..", signed_range, signed_range.end);
-                let verification_key = self.verification_key();
+                let verification_key = Self::verification_key(remote_keys);
                 self.security_policy.symmetric_verify_signature(verification_key, &dst[signed_range.clone()], &dst[signed_range.end..])?;
 
                 Ok(encrypted_range.end)
@@ -978,7 +1083,7 @@ impl SecureChannel {
 
                 // Decrypt encrypted portion
                 let mut decrypted_tmp = vec![0u8; ciphertext_size + 16]; // tmp includes +16 for blocksize
-                let (key, iv) = self.decryption_keys();
+                let (key, iv) = Self::decryption_keys(remote_keys);
 
                 trace!("Secure decrypt called with encrypted range {:?}", encrypted_range);
                 let decrypted_size = self.security_policy.symmetric_decrypt(key, iv, &src[encrypted_range.clone()], &mut decrypted_tmp[..])?;
@@ -991,7 +1096,7 @@ impl SecureChannel {
                 // Verify signature (after encrypted portion)
                 let signature_range = (encrypted_range.end - self.security_policy.symmetric_signature_size())..encrypted_range.end;
                 trace!("signed range = {:?}, signature range = {:?}", signed_range, signature_range);
-                let verification_key = self.verification_key();
+                let verification_key = Self::verification_key(remote_keys);
                 self.security_policy.symmetric_verify_signature(verification_key, &dst[signed_range.clone()], &dst[signature_range])?;
                 Ok(encrypted_range.end)
             }
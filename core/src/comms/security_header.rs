@@ -127,6 +127,22 @@ impl AsymmetricSecurityHeader {
             receiver_certificate_thumbprint,
         }
     }
+
+    /// Validates that the receiver certificate thumbprint identifies our own certificate, i.e.
+    /// that the message was encrypted for us.
+    ///
+    /// # Errors
+    ///
+    /// BadSecurityChecksFailed if the thumbprint is missing or belongs to another certificate.
+    pub fn validate_receiver_thumbprint(&self, our_cert: &X509) -> Result<(), StatusCode> {
+        let our_thumbprint = our_cert.thumbprint();
+        if our_thumbprint.value() != self.receiver_certificate_thumbprint.as_ref() {
+            error!("Supplied thumbprint does not match application certificate's thumbprint");
+            Err(StatusCode::BadSecurityChecksFailed)
+        } else {
+            Ok(())
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
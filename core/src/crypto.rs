@@ -0,0 +1,22 @@
+// OPCUA for Rust
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (C) 2017-2020 Adam Lock
+
+//! The crypto types that clients and servers commonly need, re-exported from `opcua_crypto` at a
+//! stable path so an implementation does not have to depend on the crypto crate directly.
+//!
+//! ```
+//! use opcua_core::crypto::{CertificateStore, SecurityPolicy};
+//!
+//! let security_policy = SecurityPolicy::from_uri(SecurityPolicy::Basic256Sha256.to_uri());
+//! assert_eq!(security_policy, SecurityPolicy::Basic256Sha256);
+//! let _ = CertificateStore::new(&std::env::temp_dir().join("pki"));
+//! ```
+
+pub use opcua_crypto::{
+    certificate_store::{CertificateStore, CertificateValidationResult},
+    pkey::{PrivateKey, PublicKey},
+    security_policy::SecurityPolicy,
+    x509::{X509, X509Data},
+    verify_signature_data,
+};
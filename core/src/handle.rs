@@ -33,6 +33,22 @@ impl Handle {
         next
     }
 
+    /// Returns the next handle that is not in use, skipping over any that are. This ensures that a
+    /// handle still in use is never issued again after the sequence wraps. Returns `None` if every
+    /// handle is in use.
+    pub fn next_unused<F>(&mut self, in_use: F) -> Option<u32> where F: Fn(u32) -> bool {
+        let range = (u32::MAX - self.first) as u64 + 1;
+        let mut checked = 0u64;
+        while checked < range {
+            let next = self.next();
+            if !in_use(next) {
+                return Some(next);
+            }
+            checked += 1;
+        }
+        None
+    }
+
     pub fn set_next(&mut self, next: u32) {
         self.next = next;
     }
@@ -64,3 +80,29 @@ fn handle_wrap() {
     assert_eq!(h.next(), u32::MAX);
     assert_eq!(h.next(), u32::MAX - 2);
 }
+
+#[test]
+fn handle_wrap_from_one() {
+    // Sequence numbers wrap back to 1, never 0
+    let mut h = Handle::new(1);
+    h.set_next(u32::MAX);
+    assert_eq!(h.next(), u32::MAX);
+    assert_eq!(h.next(), 1);
+    assert_eq!(h.next(), 2);
+}
+
+#[test]
+fn handle_next_unused() {
+    // Handles in use are skipped after wrapping around
+    let in_use = [1u32, 2, 4];
+    let mut h = Handle::new(1);
+    h.set_next(u32::MAX - 1);
+    assert_eq!(h.next_unused(|n| in_use.contains(&n)), Some(u32::MAX - 1));
+    assert_eq!(h.next_unused(|n| in_use.contains(&n)), Some(u32::MAX));
+    assert_eq!(h.next_unused(|n| in_use.contains(&n)), Some(3));
+    assert_eq!(h.next_unused(|n| in_use.contains(&n)), Some(5));
+
+    // Every handle in use
+    let mut h = Handle::new(u32::MAX - 2);
+    assert_eq!(h.next_unused(|_| true), None);
+}
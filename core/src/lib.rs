@@ -147,6 +147,7 @@ mod tests;
 
 pub mod comms;
 pub mod config;
+pub mod crypto;
 pub mod handle;
 pub mod runtime;
 pub mod completion_pact;
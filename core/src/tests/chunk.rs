@@ -92,6 +92,7 @@ fn chunk_multi_encode_decode() {
         max_string_length: 65535,
         max_byte_string_length: 65535,
         max_array_length: 20000, // Need to bump this up because large response uses a large array
+        ..DecodingLimits::default()
     });
 
     let response = make_large_read_response();
@@ -160,6 +161,43 @@ fn max_message_size() {
     assert_eq!(err, StatusCode::BadResponseTooLarge);
 }
 
+/// Decode a large message spread over several chunks against a max message size that it matches
+/// and exceeds and expect the appropriate response for each role
+#[test]
+fn decode_max_message_size() {
+    let _ = Test::setup();
+
+    let mut secure_channel = SecureChannel::new_no_certificate_store();
+    secure_channel.set_(DecodingLimits {
+        max_array_length: 20000, // Need to bump this up because large response uses a large array
+        ..DecodingLimits::default()
+    });
+
+    let response = make_large_read_response();
+
+    let max_message_size = response.byte_len();
+
+    let sequence_number = 1000;
+    let request_id = 100;
+    let chunks = Chunker::encode(sequence_number, request_id, 0, MIN_CHUNK_SIZE, &secure_channel, &response).unwrap();
+    assert!(chunks.len() > 1);
+
+    // At the boundary it decodes
+    let _ = Chunker::decode_with_max_message_size(&chunks, &secure_channel, max_message_size, None).unwrap();
+
+    // Just over and a server rejects it as a request
+    let err = Chunker::decode_with_max_message_size(&chunks, &secure_channel, max_message_size - 1, None).unwrap_err();
+    assert_eq!(err, StatusCode::BadRequestTooLarge);
+
+    // Just over and a client rejects it as a response
+    secure_channel.set_role(Role::Client);
+    let err = Chunker::decode_with_max_message_size(&chunks, &secure_channel, max_message_size - 1, None).unwrap_err();
+    assert_eq!(err, StatusCode::BadResponseTooLarge);
+
+    // Decoding without a max message size has no limit
+    let _ = Chunker::decode(&chunks, &secure_channel, None).unwrap();
+}
+
 /// Encode a large message and then ensure verification throws error for secure channel id mismatch
 #[test]
 fn validate_chunks_secure_channel_id() {
@@ -1,12 +1,14 @@
 use std::io::*;
 
 use opcua_crypto::SecurityPolicy;
-use opcua_types::*;
+use opcua_types::{*, status_code::StatusCode};
 
 use crate::comms::{
     secure_channel::*,
+    security_header::AsymmetricSecurityHeader,
     tcp_types::*,
 };
+use crate::tests::{make_test_cert_2048, serialize_test};
 
 fn hello_data() -> Vec<u8> {
     vec![
@@ -67,3 +69,20 @@ pub fn secure_channel_nonce() {
     // Nonce which is 32 bytes long is good
     assert!(sc.set_remote_nonce_from_byte_string(&ByteString::from(b"01234567890123456789012345678901")).is_ok());
 }
+
+#[test]
+pub fn asymmetric_security_header() {
+    let (cert, _) = make_test_cert_2048();
+    let (receiver_cert, _) = make_test_cert_2048();
+    let (other_cert, _) = make_test_cert_2048();
+
+    // Round trip the header
+    let security_header = AsymmetricSecurityHeader::new(SecurityPolicy::Basic256Sha256, &cert, receiver_cert.thumbprint().as_byte_string());
+    serialize_test(security_header.clone());
+    serialize_test(AsymmetricSecurityHeader::none());
+
+    // The thumbprint must be for the receiver's cert
+    assert!(security_header.validate_receiver_thumbprint(&receiver_cert).is_ok());
+    assert_eq!(security_header.validate_receiver_thumbprint(&other_cert).unwrap_err(), StatusCode::BadSecurityChecksFailed);
+    assert_eq!(AsymmetricSecurityHeader::none().validate_receiver_thumbprint(&receiver_cert).unwrap_err(), StatusCode::BadSecurityChecksFailed);
+}
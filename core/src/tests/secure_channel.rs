@@ -5,10 +5,13 @@
 use opcua_crypto::SecurityPolicy;
 
 use crate::comms::chunker::*;
+use crate::comms::tcp_types::MIN_CHUNK_SIZE;
 use crate::comms::secure_channel::*;
 
 use crate::tests::*;
 
+use opcua_types::service_types::ChannelSecurityToken;
+
 fn test_symmetric_encrypt_decrypt(message: SupportedMessage, security_mode: MessageSecurityMode, security_policy: SecurityPolicy) {
     let (secure_channel1, mut secure_channel2) = make_secure_channels(security_mode, security_policy);
 
@@ -143,3 +146,130 @@ fn symmetric_sign_and_encrypt_message_chunk_basic256sha256() {
     error!("symmetric_sign_and_encrypt_message_chunk_basic256sha256");
     test_symmetric_encrypt_decrypt(make_sample_message(), MessageSecurityMode::SignAndEncrypt, SecurityPolicy::Basic256Sha256);
 }
+
+/// Encrypt a message that spans several chunks and decrypt the chunks independently, in reverse
+/// order. Each chunk starts from the same derived IV so no chunk depends on the one before it.
+#[test]
+fn symmetric_sign_and_encrypt_chunks_independently() {
+    let _ = Test::setup();
+    let (secure_channel1, mut secure_channel2) = make_secure_channels(MessageSecurityMode::SignAndEncrypt, SecurityPolicy::Basic256Sha256);
+
+    let results = (0..600).map(|i| DataValue::new_now(i as u32)).collect();
+    let message: SupportedMessage = ReadResponse {
+        response_header: ResponseHeader::null(),
+        results: Some(results),
+        diagnostic_infos: None,
+    }.into();
+
+    let chunks = Chunker::encode(1, 1, 0, MIN_CHUNK_SIZE, &secure_channel1, &message).unwrap();
+    assert!(chunks.len() > 1);
+
+    let encrypted_chunks = chunks.iter().map(|chunk| {
+        let mut encrypted_data = vec![0u8; chunk.data.len() + 4096];
+        let encrypted_size = secure_channel1.apply_security(chunk, &mut encrypted_data[..]).unwrap();
+        encrypted_data.truncate(encrypted_size);
+        encrypted_data
+    }).collect::<Vec<_>>();
+
+    // Decrypt the last chunk first
+    encrypted_chunks.iter().zip(chunks.iter()).rev().for_each(|(encrypted_data, chunk)| {
+        let chunk2 = secure_channel2.verify_and_remove_security(&encrypted_data[..]).unwrap();
+        assert_eq!(&chunk.data[12..], &chunk2.data[12..chunk.data.len()]);
+    });
+
+    let message2 = Chunker::decode(&chunks, &secure_channel2, None).unwrap();
+    assert_eq!(message, message2);
+}
+
+#[test]
+fn compute_padding_block_boundaries() {
+    // 16 byte blocks with a 20 byte signature. Plain text + signature + padding byte is exactly two
+    // blocks so only the padding byte itself is needed
+    assert_eq!(compute_padding(11, 20, 16), (0, 1));
+    // One more byte needs almost a whole block of padding
+    assert_eq!(compute_padding(12, 20, 16), (15, 16));
+    // One byte fewer needs a single extra byte of padding
+    assert_eq!(compute_padding(10, 20, 16), (1, 2));
+    // Padding always makes the total a multiple of the block size
+    (0..64).for_each(|plaintext_len| {
+        let (padding_byte, padding_len) = compute_padding(plaintext_len, 32, 16);
+        assert_eq!((plaintext_len + 32 + padding_len) % 16, 0);
+        assert_eq!(padding_byte as usize, padding_len - 1);
+    });
+}
+
+#[test]
+fn compute_padding_extra_padding() {
+    // A 4096 bit key with OAEP padding has a plain text block size of 512 - 42 = 470 bytes and a
+    // 512 byte signature. The padding is over 255 bytes so it needs the extra padding byte.
+    let (padding_byte, padding_len) = compute_padding(100, 512, 470);
+    assert_eq!(padding_len, 328);
+    assert_eq!((100 + 512 + padding_len) % 470, 0);
+    // The padding byte is the low byte of the padding size, the extra padding byte holds the rest
+    let padding_size = padding_len - 2;
+    assert_eq!(padding_byte, (padding_size & 0xff) as u8);
+    assert_eq!(padding_size >> 8, 1);
+
+    // Exactly on a boundary there are still two bytes, the padding size and extra padding
+    assert_eq!(compute_padding(470 * 3 - 514, 512, 470), (0, 2));
+}
+
+fn encrypt_chunk(secure_channel: &SecureChannel, message: &SupportedMessage) -> Vec<u8> {
+    let chunks = Chunker::encode(1, 1, 0, 0, secure_channel, message).unwrap();
+    let mut encrypted_data = vec![0u8; chunks[0].data.len() + 4096];
+    let encrypted_size = secure_channel.apply_security(&chunks[0], &mut encrypted_data[..]).unwrap();
+    encrypted_data.truncate(encrypted_size);
+    encrypted_data
+}
+
+/// Renew the token on a client / server pair, where the old token was created the specified
+/// number of milliseconds ago with a 60 second lifetime. Returns the pair and a message the client
+/// secured with the old token.
+fn renew_token(old_token_age: i64) -> (SecureChannel, SecureChannel, Vec<u8>) {
+    let (mut client, mut server) = make_secure_channels(MessageSecurityMode::SignAndEncrypt, SecurityPolicy::Basic256Sha256);
+    client.set_role(Role::Client);
+    server.set_role(Role::Server);
+    let token = |token_id: u32, created_at: DateTime| ChannelSecurityToken {
+        channel_id: 1,
+        token_id,
+        created_at,
+        revised_lifetime: 60000,
+    };
+    let created_at = DateTime::from(chrono::Utc::now() - chrono::Duration::milliseconds(old_token_age));
+    client.set_security_token(token(1, created_at.clone()));
+    server.set_security_token(token(1, created_at));
+
+    let old_message = encrypt_chunk(&client, &make_sample_message());
+
+    let client_nonce = (32..64).collect::<Vec<u8>>();
+    let server_nonce = (64..96).collect::<Vec<u8>>();
+    client.renew_token(&client_nonce, &server_nonce);
+    server.renew_token(&client_nonce, &server_nonce);
+    client.set_security_token(token(2, DateTime::now()));
+    server.set_security_token(token(2, DateTime::now()));
+    (client, server, old_message)
+}
+
+#[test]
+fn renew_token_grace_period() {
+    let _ = Test::setup();
+
+    // The old token has not expired so a message secured with it still decrypts
+    let (client, mut server, old_message) = renew_token(1000);
+    assert!(server.verify_and_remove_security(&old_message).is_ok());
+
+    // Messages secured with the new token decrypt too
+    let new_message = encrypt_chunk(&client, &make_sample_message());
+    assert!(server.verify_and_remove_security(&new_message).is_ok());
+
+    // The new keys are not the old ones. Pretending the old message used the new token fails
+    let mut forged_message = old_message.clone();
+    forged_message[12..16].copy_from_slice(&2u32.to_le_bytes());
+    assert!(server.verify_and_remove_security(&forged_message).is_err());
+
+    // Once the old token expires its messages are rejected
+    let (client, mut server, old_message) = renew_token(61000);
+    assert_eq!(server.verify_and_remove_security(&old_message).unwrap_err(), StatusCode::BadSecureChannelTokenUnknown);
+    let new_message = encrypt_chunk(&client, &make_sample_message());
+    assert!(server.verify_and_remove_security(&new_message).is_ok());
+}
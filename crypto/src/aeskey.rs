@@ -4,20 +4,24 @@
 
 //! Symmetric encryption / decryption wrapper.
 use opcua_types::status_code::StatusCode;
-use openssl::symm::{Cipher, Crypter, Mode};
+use openssl::symm::{Cipher, Mode};
 use std::result::Result;
 
-use crate::SecurityPolicy;
+use crate::{
+    SecurityPolicy,
+    secret::SecretBytes,
+    backend::{CryptoBackend, DefaultBackend},
+};
 
 #[derive(Debug)]
 pub struct AesKey {
-    value: Vec<u8>,
+    value: SecretBytes,
     security_policy: SecurityPolicy,
 }
 
 impl AesKey {
     pub fn new(security_policy: SecurityPolicy, value: &[u8]) -> AesKey {
-        AesKey { value: value.to_vec(), security_policy }
+        AesKey { value: SecretBytes::from(value), security_policy }
     }
 
     pub fn value(&self) -> &[u8] {
@@ -61,31 +65,18 @@ impl AesKey {
         let cipher = self.cipher();
 
         Self::validate_aes_args(&cipher, src, iv, dst)?;
+        if self.value.len() != cipher.key_len() {
+            error!("Key length {} does not match the cipher key length {}", self.value.len(), cipher.key_len());
+            return Err(StatusCode::BadUnexpectedError);
+        }
 
         trace!("Encrypting block of size {}", src.len());
 
-        let crypter = Crypter::new(cipher, mode, &self.value, Some(iv));
-        if let Ok(mut crypter) = crypter {
-            crypter.pad(false);
-            let result = crypter.update(src, dst);
-            if let Ok(count) = result {
-                crypter.finalize(&mut dst[count..])
-                    .map(|rest| {
-                        trace!("do cipher size {}", count + rest);
-                        count + rest
-                    })
-                    .map_err(|e| {
-                        error!("Encryption error during finalize {:?}", e);
-                        StatusCode::BadUnexpectedError
-                    })
-            } else {
-                error!("Encryption error during update {:?}", result.unwrap_err());
-                Err(StatusCode::BadUnexpectedError)
-            }
-        } else {
-            error!("Encryption Error");
-            Err(StatusCode::BadUnexpectedError)
-        }
+        let result = match mode {
+            Mode::Encrypt => DefaultBackend::aes_cbc_encrypt(&self.value, iv, src, dst),
+            Mode::Decrypt => DefaultBackend::aes_cbc_decrypt(&self.value, iv, src, dst),
+        };
+        result.map_err(|err| err.into())
     }
 
     pub fn block_size(&self) -> usize {
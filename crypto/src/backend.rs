@@ -0,0 +1,271 @@
+// OPCUA for Rust
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (C) 2017-2020 Adam Lock
+
+//! Abstraction over the library that supplies the cryptographic primitives.
+//!
+//! The security policies are built from a small set of primitives - AES-CBC, RSA, HMAC, hashing
+//! and random number generation. The `CryptoBackend` trait describes the symmetric primitives,
+//! hashing and random numbers so that a library other than OpenSSL may provide them. The
+//! `RsaBackend` trait describes the RSA operations.
+//!
+//! `DefaultBackend` is OpenSSL unless the crate is built with the `rustcrypto` feature, in which
+//! case the symmetric primitives, hashing and random numbers come from the pure Rust RustCrypto
+//! crates. Certificates and the RSA keys held in `PrivateKey` and `PublicKey` are OpenSSL's either
+//! way, so RSA goes through `KeyBackend`, which is always OpenSSL. The crate therefore still needs
+//! OpenSSL with the `rustcrypto` feature.
+use openssl::{hash, pkey, rand, rsa, sign, symm::{Cipher, Crypter, Mode}};
+
+use crate::{
+    algorithms,
+    error::CryptoError,
+    pkey::{PrivateKey, PublicKey, RsaPadding},
+};
+
+/// Hashing algorithms used by the security policies
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum HashAlgorithm {
+    Sha1,
+    Sha256,
+}
+
+/// The primitive crypto operations required by the security policies, apart from RSA.
+pub trait CryptoBackend {
+    /// Tests if the backend implements the algorithm identified by the URI. A security policy can
+    /// only be used if all of its algorithms are supported.
+    fn is_algorithm_supported(algorithm_uri: &str) -> bool;
+
+    /// Fills the slice with cryptographically strong pseudo-random bytes
+    fn random_bytes(dst: &mut [u8]);
+
+    /// Returns the digest of the data
+    fn hash(algorithm: HashAlgorithm, data: &[u8]) -> Result<Vec<u8>, CryptoError>;
+
+    /// Returns the HMAC of the data using the key
+    fn hmac(algorithm: HashAlgorithm, key: &[u8], data: &[u8]) -> Result<Vec<u8>, CryptoError>;
+
+    /// Encrypts src to dst with AES-CBC without padding. The AES variant is chosen from the key
+    /// length - 16 bytes for AES-128 or 32 bytes for AES-256, any other length is
+    /// `CryptoError::Unsupported`. Returns the number of bytes written.
+    fn aes_cbc_encrypt(key: &[u8], iv: &[u8], src: &[u8], dst: &mut [u8]) -> Result<usize, CryptoError>;
+
+    /// Decrypts src to dst with AES-CBC without padding. Returns the number of bytes written.
+    fn aes_cbc_decrypt(key: &[u8], iv: &[u8], src: &[u8], dst: &mut [u8]) -> Result<usize, CryptoError>;
+}
+
+/// The RSA operations required by the security policies.
+pub trait RsaBackend {
+    /// An RSA private key
+    type PrivateKey;
+    /// An RSA public key
+    type PublicKey;
+
+    /// Generates a new RSA private key of the specified length in bits
+    fn rsa_generate_key(bit_length: u32) -> Result<Self::PrivateKey, CryptoError>;
+
+    /// Returns the public half of an RSA private key
+    fn rsa_public_key(private_key: &Self::PrivateKey) -> Result<Self::PublicKey, CryptoError>;
+
+    /// Encrypts src to dst with the public key. Returns the number of bytes written.
+    fn rsa_public_encrypt(public_key: &Self::PublicKey, src: &[u8], dst: &mut [u8], padding: RsaPadding) -> Result<usize, CryptoError>;
+
+    /// Decrypts src to dst with the private key. Returns the number of bytes written.
+    fn rsa_private_decrypt(private_key: &Self::PrivateKey, src: &[u8], dst: &mut [u8], padding: RsaPadding) -> Result<usize, CryptoError>;
+}
+
+/// The backend compiled into this crate
+#[cfg(not(feature = "rustcrypto"))]
+pub type DefaultBackend = OpenSslBackend;
+
+/// The backend compiled into this crate
+#[cfg(feature = "rustcrypto")]
+pub type DefaultBackend = RustCryptoBackend;
+
+/// The RSA backend whose key types are this crate's `PrivateKey` and `PublicKey`
+pub type KeyBackend = OpenSslBackend;
+
+/// Crypto backend implemented with OpenSSL
+pub struct OpenSslBackend;
+
+impl OpenSslBackend {
+    fn message_digest(algorithm: HashAlgorithm) -> hash::MessageDigest {
+        match algorithm {
+            HashAlgorithm::Sha1 => hash::MessageDigest::sha1(),
+            HashAlgorithm::Sha256 => hash::MessageDigest::sha256(),
+        }
+    }
+
+    fn aes_cbc(mode: Mode, key: &[u8], iv: &[u8], src: &[u8], dst: &mut [u8]) -> Result<usize, CryptoError> {
+        let cipher = match key.len() {
+            16 => Cipher::aes_128_cbc(),
+            32 => Cipher::aes_256_cbc(),
+            key_len => {
+                error!("AES key length {} is not supported, only 16 or 32", key_len);
+                return Err(CryptoError::Unsupported);
+            }
+        };
+        let mut crypter = Crypter::new(cipher, mode, key, Some(iv))
+            .map_err(|err| CryptoError::Backend(err.to_string()))?;
+        crypter.pad(false);
+        let count = crypter.update(src, dst)
+            .map_err(|err| {
+                error!("Encryption error during update {:?}", err);
+                CryptoError::Backend(err.to_string())
+            })?;
+        let rest = crypter.finalize(&mut dst[count..])
+            .map_err(|err| {
+                error!("Encryption error during finalize {:?}", err);
+                CryptoError::Backend(err.to_string())
+            })?;
+        trace!("do cipher size {}", count + rest);
+        Ok(count + rest)
+    }
+}
+
+impl CryptoBackend for OpenSslBackend {
+    fn is_algorithm_supported(algorithm_uri: &str) -> bool {
+        match algorithm_uri {
+            algorithms::ENC_AES128_CBC | algorithms::ENC_AES256_CBC |
+            algorithms::ENC_RSA_15 | algorithms::ENC_RSA_OAEP |
+            algorithms::DSIG_HMAC_SHA1 | algorithms::DSIG_HMAC_SHA256 |
+            algorithms::DSIG_RSA_SHA1 | algorithms::DSIG_RSA_SHA256 |
+            algorithms::KEY_P_SHA1 | algorithms::KEY_P_SHA256 => true,
+            // RSA-PSS signatures are not implemented
+            _ => false
+        }
+    }
+
+    fn random_bytes(dst: &mut [u8]) {
+        let _ = rand::rand_bytes(dst);
+    }
+
+    fn hash(algorithm: HashAlgorithm, data: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        hash::hash(Self::message_digest(algorithm), data)
+            .map(|digest| digest.to_vec())
+            .map_err(|err| CryptoError::Backend(err.to_string()))
+    }
+
+    fn hmac(algorithm: HashAlgorithm, key: &[u8], data: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        let pkey = pkey::PKey::hmac(key).map_err(|err| CryptoError::Backend(err.to_string()))?;
+        let mut signer = sign::Signer::new(Self::message_digest(algorithm), &pkey)
+            .map_err(|err| CryptoError::Backend(err.to_string()))?;
+        signer.update(data).map_err(|err| CryptoError::Backend(err.to_string()))?;
+        signer.sign_to_vec().map_err(|err| CryptoError::Backend(err.to_string()))
+    }
+
+    fn aes_cbc_encrypt(key: &[u8], iv: &[u8], src: &[u8], dst: &mut [u8]) -> Result<usize, CryptoError> {
+        Self::aes_cbc(Mode::Encrypt, key, iv, src, dst)
+    }
+
+    fn aes_cbc_decrypt(key: &[u8], iv: &[u8], src: &[u8], dst: &mut [u8]) -> Result<usize, CryptoError> {
+        Self::aes_cbc(Mode::Decrypt, key, iv, src, dst)
+    }
+}
+
+impl RsaBackend for OpenSslBackend {
+    type PrivateKey = PrivateKey;
+    type PublicKey = PublicKey;
+
+    fn rsa_generate_key(bit_length: u32) -> Result<PrivateKey, CryptoError> {
+        rsa::Rsa::generate(bit_length)
+            .and_then(pkey::PKey::from_rsa)
+            .map(PrivateKey::wrap_private_key)
+            .map_err(|err| CryptoError::Backend(err.to_string()))
+    }
+
+    fn rsa_public_key(private_key: &PrivateKey) -> Result<PublicKey, CryptoError> {
+        private_key.value.public_key_to_der()
+            .and_then(|der| pkey::PKey::public_key_from_der(&der))
+            .map(PublicKey::wrap_public_key)
+            .map_err(|err| CryptoError::Backend(err.to_string()))
+    }
+
+    fn rsa_public_encrypt(public_key: &PublicKey, src: &[u8], dst: &mut [u8], padding: RsaPadding) -> Result<usize, CryptoError> {
+        public_key.public_encrypt(src, dst, padding)
+    }
+
+    fn rsa_private_decrypt(private_key: &PrivateKey, src: &[u8], dst: &mut [u8], padding: RsaPadding) -> Result<usize, CryptoError> {
+        private_key.private_decrypt(src, dst, padding)
+    }
+}
+
+/// Crypto backend implemented with the pure Rust RustCrypto crates
+#[cfg(feature = "rustcrypto")]
+pub struct RustCryptoBackend;
+
+#[cfg(feature = "rustcrypto")]
+impl RustCryptoBackend {
+    fn aes_cbc(encrypt: bool, key: &[u8], iv: &[u8], src: &[u8], dst: &mut [u8]) -> Result<usize, CryptoError> {
+        use aes::{Aes128, Aes256};
+        use block_modes::{BlockMode, Cbc, block_padding::NoPadding};
+
+        if dst.len() < src.len() {
+            error!("Destination buffer of {} bytes is too small for {} bytes", dst.len(), src.len());
+            return Err(CryptoError::Backend("destination buffer too small".to_string()));
+        }
+        let buffer = &mut dst[..src.len()];
+        buffer.copy_from_slice(src);
+        let result = match key.len() {
+            16 => {
+                let cipher = Cbc::<Aes128, NoPadding>::new_from_slices(key, iv).map_err(|err| CryptoError::Backend(err.to_string()))?;
+                if encrypt { cipher.encrypt(buffer, src.len()).map(|b| b.len()) } else { cipher.decrypt(buffer).map(|b| b.len()) }
+            }
+            32 => {
+                let cipher = Cbc::<Aes256, NoPadding>::new_from_slices(key, iv).map_err(|err| CryptoError::Backend(err.to_string()))?;
+                if encrypt { cipher.encrypt(buffer, src.len()).map(|b| b.len()) } else { cipher.decrypt(buffer).map(|b| b.len()) }
+            }
+            key_len => {
+                error!("AES key length {} is not supported, only 16 or 32", key_len);
+                return Err(CryptoError::Unsupported);
+            }
+        };
+        result.map_err(|err| {
+            error!("Encryption error {:?}", err);
+            CryptoError::Backend(err.to_string())
+        })
+    }
+}
+
+#[cfg(feature = "rustcrypto")]
+impl CryptoBackend for RustCryptoBackend {
+    fn is_algorithm_supported(algorithm_uri: &str) -> bool {
+        OpenSslBackend::is_algorithm_supported(algorithm_uri)
+    }
+
+    fn random_bytes(dst: &mut [u8]) {
+        use ::rand::RngCore;
+        ::rand::rngs::OsRng.fill_bytes(dst);
+    }
+
+    fn hash(algorithm: HashAlgorithm, data: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        use sha2::Digest;
+        Ok(match algorithm {
+            HashAlgorithm::Sha1 => sha1::Sha1::digest(data).to_vec(),
+            HashAlgorithm::Sha256 => sha2::Sha256::digest(data).to_vec(),
+        })
+    }
+
+    fn hmac(algorithm: HashAlgorithm, key: &[u8], data: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        use hmac::{Hmac, Mac, NewMac};
+        match algorithm {
+            HashAlgorithm::Sha1 => {
+                let mut mac = Hmac::<sha1::Sha1>::new_from_slice(key).map_err(|err| CryptoError::Backend(err.to_string()))?;
+                mac.update(data);
+                Ok(mac.finalize().into_bytes().to_vec())
+            }
+            HashAlgorithm::Sha256 => {
+                let mut mac = Hmac::<sha2::Sha256>::new_from_slice(key).map_err(|err| CryptoError::Backend(err.to_string()))?;
+                mac.update(data);
+                Ok(mac.finalize().into_bytes().to_vec())
+            }
+        }
+    }
+
+    fn aes_cbc_encrypt(key: &[u8], iv: &[u8], src: &[u8], dst: &mut [u8]) -> Result<usize, CryptoError> {
+        Self::aes_cbc(true, key, iv, src, dst)
+    }
+
+    fn aes_cbc_decrypt(key: &[u8], iv: &[u8], src: &[u8], dst: &mut [u8]) -> Result<usize, CryptoError> {
+        Self::aes_cbc(false, key, iv, src, dst)
+    }
+}
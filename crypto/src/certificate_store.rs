@@ -17,11 +17,13 @@ use std::path::{Path, PathBuf};
 use crate::{
     pkey::PrivateKey,
     security_policy::SecurityPolicy,
-    x509::{X509, X509Data},
+    x509::{CertificateRequest, X509, X509Data},
 };
 
 /// The name that the server/client's application instance certificate is expected to be
 const OWN_CERTIFICATE_NAME: &str = "cert.der";
+/// The name that the server/client's certificate signing request is written to
+const OWN_CERTIFICATE_REQUEST_NAME: &str = "cert.csr";
 /// The name that the server/client's application instance private key is expected to be
 const OWN_PRIVATE_KEY_NAME: &str = "private.pem";
 
@@ -34,20 +36,91 @@ const TRUSTED_CERTS_DIR: &str = "trusted";
 /// The directory holding rejected certificates
 const REJECTED_CERTS_DIR: &str = "rejected";
 
-/// The certificate store manages the storage of a server/client's own certificate & private key
-/// and the trust / rejection of certificates from the other end.
-pub struct CertificateStore {
-    /// Path to the certificate store on disk.
-    pub pki_path: PathBuf,
+/// The check that a certificate failed during validation
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CertificateCheck {
+    /// The certificate store could not be used, e.g. a directory is missing or the trusted copy
+    /// of the cert on disk does not match
+    Store,
+    /// The certificate is in the rejected directory
+    Rejected,
+    /// The certificate is unknown and unknown certificates are not trusted
+    Untrusted,
+    /// The key length is not valid for the security policy
+    KeyLength,
+    /// The certificate is not yet valid or has expired
+    Time,
+    /// The certificate does not contain the expected hostname
+    Hostname,
+    /// The certificate does not contain the expected application uri
+    ApplicationUri,
+}
+
+/// The outcome of validating a certificate - the check that failed, if any, and the status code
+/// that results.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CertificateValidationResult {
+    /// The check that failed, or None if the certificate is valid
+    pub failed_check: Option<CertificateCheck>,
+    /// The status code of the validation
+    pub status_code: StatusCode,
+}
+
+impl CertificateValidationResult {
+    fn good() -> CertificateValidationResult {
+        CertificateValidationResult { failed_check: None, status_code: StatusCode::Good }
+    }
+
+    fn failed(failed_check: CertificateCheck, status_code: StatusCode) -> CertificateValidationResult {
+        CertificateValidationResult { failed_check: Some(failed_check), status_code }
+    }
+
+    /// Tests if the certificate passed validation
+    pub fn is_good(&self) -> bool {
+        self.failed_check.is_none()
+    }
+}
+
+/// The checks made when validating a certificate. The default is the strictest set of options.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CertificateValidationOptions {
     /// Timestamps of the cert are normally checked on the cert to ensure it cannot be used before
     /// or after its limits, but this check can be disabled.
     pub check_time: bool,
+    /// Check the cert contains the hostname of the other end, when the hostname is supplied
+    pub check_hostname: bool,
+    /// Check the cert contains the application uri of the other end, when the uri is supplied
+    pub check_application_uri: bool,
+    /// The minimum key length in bits. The key length must also be in the range allowed by the
+    /// security policy, so the default of 0 leaves the policy to decide.
+    pub min_key_length: usize,
     /// Ordinarily an unknown cert will be dropped into the rejected folder, but it can be dropped
     /// into the trusted folder if this flag is set. Certs in the trusted folder must still pass
     /// validity checks.
     pub trust_unknown_certs: bool,
 }
 
+impl Default for CertificateValidationOptions {
+    fn default() -> Self {
+        CertificateValidationOptions {
+            check_time: true,
+            check_hostname: true,
+            check_application_uri: true,
+            min_key_length: 0,
+            trust_unknown_certs: false,
+        }
+    }
+}
+
+/// The certificate store manages the storage of a server/client's own certificate & private key
+/// and the trust / rejection of certificates from the other end.
+pub struct CertificateStore {
+    /// Path to the certificate store on disk.
+    pub pki_path: PathBuf,
+    /// The checks made when validating the certificate of the other end
+    pub validation_options: CertificateValidationOptions,
+}
+
 impl CertificateStore {
     /// Sets up the certificate store to the specified PKI directory.
     /// It is a bad idea to have more than one running instance pointing to the same path
@@ -55,8 +128,7 @@ impl CertificateStore {
     pub fn new(pki_path: &Path) -> CertificateStore {
         CertificateStore {
             pki_path: pki_path.to_path_buf(),
-            check_time: true,
-            trust_unknown_certs: false,
+            validation_options: CertificateValidationOptions::default(),
         }
     }
 
@@ -152,6 +224,32 @@ impl CertificateStore {
         Ok((cert, pkey))
     }
 
+    /// This function will use the supplied arguments to create a certificate signing request
+    /// and public/private key pair for a certificate authority to issue the Application Instance
+    /// Certificate from. The request is written PEM encoded under own/ and the private key under
+    /// private/. The issued cert should be written to the own cert path.
+    pub fn create_and_store_certificate_request(&self, args: &X509Data, overwrite: bool) -> Result<(CertificateRequest, PrivateKey), String> {
+        // Create the request and corresponding private key
+        let (request, pkey) = X509::create_certificate_request(args)?;
+
+        // Request goes under own/
+        let request_path = CertificateStore::make_and_ensure_file_path(&self.own_cert_dir(), OWN_CERTIFICATE_REQUEST_NAME)?;
+        // Private key goes under private/
+        let private_key_path = CertificateStore::make_and_ensure_file_path(&self.private_key_dir(), OWN_PRIVATE_KEY_NAME)?;
+
+        // Write the request
+        let pem = request.to_pem().map_err(|err| err.to_string())?;
+        info!("Writing certificate request to {}", request_path.display());
+        let _ = CertificateStore::write_to_file(&pem, &request_path, overwrite)?;
+
+        // Write the private key
+        let pem = pkey.private_key_to_pem().unwrap();
+        info!("Writing private key to {}", private_key_path.display());
+        let _ = CertificateStore::write_to_file(&pem, &private_key_path, overwrite)?;
+
+        Ok((request, pkey))
+    }
+
     /// Validates the cert as trusted and valid. If the cert is unknown, it will be written to
     /// the rejected folder so that the administrator can manually move it to the trusted folder.
     ///
@@ -209,24 +307,38 @@ impl CertificateStore {
     /// # Errors
     ///
     /// A non `Good` status code indicates a failure in the cert or in some action required in
-    /// order to validate it.
+    /// order to validate it. Use `validate_certificate()` to find out which check failed.
     ///
     pub fn validate_application_instance_cert(&self, cert: &X509, security_policy: SecurityPolicy, hostname: Option<&str>, application_uri: Option<&str>) -> StatusCode {
+        self.validate_certificate(cert, security_policy, hostname, application_uri).status_code
+    }
+
+    /// Validates the certificate in the same way as `validate_application_instance_cert()` but
+    /// returns which check failed along with the status code.
+    pub fn validate_certificate(&self, cert: &X509, security_policy: SecurityPolicy, hostname: Option<&str>, application_uri: Option<&str>) -> CertificateValidationResult {
+        self.validate_certificate_with_options(cert, security_policy, hostname, application_uri, &self.validation_options)
+    }
+
+    /// Validates the certificate with the supplied options instead of the store's own.
+    pub fn validate_certificate_with_options(&self, cert: &X509, security_policy: SecurityPolicy, hostname: Option<&str>, application_uri: Option<&str>, options: &CertificateValidationOptions) -> CertificateValidationResult {
         let cert_file_name = CertificateStore::cert_file_name(&cert);
         debug!("Validating cert with name on disk {}", cert_file_name);
+        self.check_certificate(cert, &cert_file_name, security_policy, hostname, application_uri, options)
+    }
 
+    fn check_certificate(&self, cert: &X509, cert_file_name: &str, security_policy: SecurityPolicy, hostname: Option<&str>, application_uri: Option<&str>, options: &CertificateValidationOptions) -> CertificateValidationResult {
         // Look for the cert in the rejected folder. If it's rejected there is no purpose going
         // any further
         {
             let mut cert_path = self.rejected_certs_dir();
             if !cert_path.exists() {
                 error!("Path for rejected certificates {} does not exist", cert_path.display());
-                return StatusCode::BadUnexpectedError;
+                return CertificateValidationResult::failed(CertificateCheck::Store, StatusCode::BadUnexpectedError);
             }
             cert_path.push(&cert_file_name);
             if cert_path.exists() {
                 warn!("Certificate {} is untrusted because it resides in the rejected directory", cert_file_name);
-                return StatusCode::BadSecurityChecksFailed;
+                return CertificateValidationResult::failed(CertificateCheck::Rejected, StatusCode::BadSecurityChecksFailed);
             }
         }
 
@@ -237,14 +349,14 @@ impl CertificateStore {
             let mut cert_path = self.trusted_certs_dir();
             if !cert_path.exists() {
                 error!("Path for rejected certificates {} does not exist", cert_path.display());
-                return StatusCode::BadUnexpectedError;
+                return CertificateValidationResult::failed(CertificateCheck::Store, StatusCode::BadUnexpectedError);
             }
             cert_path.push(&cert_file_name);
 
             // Check if cert is in the trusted folder
             if !cert_path.exists() {
                 // ... trust checks based on ca could be added here to add cert straight to trust folder
-                if self.trust_unknown_certs {
+                if options.trust_unknown_certs {
                     // Put the unknown cert into the trusted folder
                     warn!("Certificate {} is unknown but policy will store it into the trusted directory", cert_file_name);
                     let _ = self.store_trusted_cert(cert);
@@ -252,56 +364,60 @@ impl CertificateStore {
                 } else {
                     warn!("Certificate {} is unknown and untrusted so it will be stored in rejected directory", cert_file_name);
                     let _ = self.store_rejected_cert(cert);
-                    return StatusCode::BadCertificateUntrusted;
+                    return CertificateValidationResult::failed(CertificateCheck::Untrusted, StatusCode::BadCertificateUntrusted);
                 }
             }
 
             // Read the cert from the trusted folder to make sure it matches the one supplied
             if !CertificateStore::ensure_cert_and_file_are_the_same(cert, &cert_path) {
                 error!("Certificate in memory does not match the one on disk {} so cert will automatically be treated as untrusted", cert_path.display());
-                return StatusCode::BadUnexpectedError;
+                return CertificateValidationResult::failed(CertificateCheck::Store, StatusCode::BadUnexpectedError);
             }
 
             // Check that the certificate is the right length for the security policy
             match cert.key_length() {
                 Err(_) => {
                     error!("Cannot read key length from certificate {}", cert_file_name);
-                    return StatusCode::BadSecurityChecksFailed;
+                    return CertificateValidationResult::failed(CertificateCheck::KeyLength, StatusCode::BadSecurityChecksFailed);
                 }
                 Ok(key_length) => {
                     if !security_policy.is_valid_keylength(key_length) {
                         warn!("Certificate {} has an invalid key length {} for the policy {}", cert_file_name, key_length, security_policy);
-                        return StatusCode::BadSecurityChecksFailed;
+                        return CertificateValidationResult::failed(CertificateCheck::KeyLength, StatusCode::BadSecurityChecksFailed);
+                    }
+                    if key_length < options.min_key_length {
+                        warn!("Certificate {} has a key length {} below the minimum {}", cert_file_name, key_length, options.min_key_length);
+                        return CertificateValidationResult::failed(CertificateCheck::KeyLength, StatusCode::BadSecurityChecksFailed);
                     }
                 }
             }
 
             // Now inspect the cert not before / after values to ensure its validity
-            if self.check_time {
+            if options.check_time {
                 use chrono::Utc;
                 let now = Utc::now();
                 let status_code = cert.is_time_valid(&now);
                 if status_code.is_bad() {
                     warn!("Certificate {} is not valid for now, check start/end timestamps", cert_file_name);
-                    return status_code;
+                    return CertificateValidationResult::failed(CertificateCheck::Time, status_code);
                 }
             }
 
             // Compare the hostname of the cert against the cert supplied
-            if let Some(hostname) = hostname {
+            if let Some(hostname) = hostname.filter(|_| options.check_hostname) {
                 let status_code = cert.is_hostname_valid(hostname);
                 if status_code.is_bad() {
                     warn!("Certificate {} does not have a valid hostname", cert_file_name);
-                    return status_code;
+                    return CertificateValidationResult::failed(CertificateCheck::Hostname, status_code);
                 }
             }
 
             // Compare the application / product uri to the supplied application description
-            if let Some(application_uri) = application_uri {
+            if let Some(application_uri) = application_uri.filter(|_| options.check_application_uri) {
                 let status_code = cert.is_application_uri_valid(application_uri);
                 if status_code.is_bad() {
                     warn!("Certificate {} does not have a valid application uri", cert_file_name);
-                    return status_code;
+                    return CertificateValidationResult::failed(CertificateCheck::ApplicationUri, status_code);
                 }
             }
 
@@ -310,7 +426,7 @@ impl CertificateStore {
             // ... trust (self-signed, ca etc.)
             // ... revocation
         }
-        StatusCode::Good
+        CertificateValidationResult::good()
     }
 
     /// Returns a certificate file name from the cert's issuer and thumbprint fields.
@@ -375,6 +491,13 @@ impl CertificateStore {
         path
     }
 
+    /// Get path to the certificate signing request
+    pub fn own_certificate_request_path(&self) -> PathBuf {
+        let mut path = self.own_cert_dir();
+        path.push(OWN_CERTIFICATE_REQUEST_NAME);
+        path
+    }
+
     /// Get path to application instance private key
     pub fn own_private_key_path(&self) -> PathBuf {
         let mut path = self.private_key_dir();
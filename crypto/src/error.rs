@@ -0,0 +1,55 @@
+// OPCUA for Rust
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (C) 2017-2020 Adam Lock
+
+//! Errors raised by the asymmetric and symmetric crypto functions.
+use std::fmt;
+
+use opcua_types::status_code::StatusCode;
+
+/// Describes why a crypto operation failed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CryptoError {
+    /// The key is the wrong length. Holds the expected and actual length.
+    KeyLength(usize, usize),
+    /// The data is the wrong length for the operation, e.g. cipher text that is not a whole number
+    /// of blocks. Holds the expected and actual length.
+    DataLength(usize, usize),
+    /// The initialization vector is not the block size of the cipher. Holds the expected and
+    /// actual length.
+    IvLength(usize, usize),
+    /// The data could not be padded or the padding did not check out on decryption.
+    Padding,
+    /// The signature does not match the data.
+    SignatureMismatch,
+    /// The underlying crypto library returned an error.
+    Backend(String),
+    /// The security policy does not have the operation, e.g. the None policy does not sign, or
+    /// the crypto backend does not implement one of the policy's algorithms.
+    Unsupported,
+}
+
+impl fmt::Display for CryptoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CryptoError::KeyLength(expected, actual) => write!(f, "Invalid key length, expected {}, got {}", expected, actual),
+            CryptoError::DataLength(expected, actual) => write!(f, "Invalid data length, expected {}, got {}", expected, actual),
+            CryptoError::IvLength(expected, actual) => write!(f, "Invalid initialization vector length, expected {}, got {}", expected, actual),
+            CryptoError::Padding => write!(f, "Padding error"),
+            CryptoError::SignatureMismatch => write!(f, "Signature mismatch"),
+            CryptoError::Backend(err) => write!(f, "Crypto backend error - {}", err),
+            CryptoError::Unsupported => write!(f, "Unsupported by the security policy"),
+        }
+    }
+}
+
+impl From<CryptoError> for StatusCode {
+    fn from(err: CryptoError) -> Self {
+        match err {
+            CryptoError::KeyLength(_, _) | CryptoError::Unsupported => StatusCode::BadSecurityPolicyRejected,
+            CryptoError::DataLength(_, _) | CryptoError::Padding | CryptoError::SignatureMismatch => StatusCode::BadSecurityChecksFailed,
+            CryptoError::IvLength(_, _) => StatusCode::BadConfigurationError,
+            CryptoError::Backend(_) => StatusCode::BadUnexpectedError,
+        }
+    }
+}
@@ -6,11 +6,13 @@
 
 use std::result::Result;
 
-use openssl::{hash, pkey, sign};
-
 use opcua_types::status_code::StatusCode;
 
-use crate::{SHA1_SIZE, SHA256_SIZE};
+use crate::{
+    SHA1_SIZE, SHA256_SIZE,
+    backend::{CryptoBackend, DefaultBackend, HashAlgorithm},
+    secret::SecretBytes,
+};
 
 /// Pseudo random `P_SHA` implementation for creating pseudo random range of bytes from an input
 ///
@@ -25,45 +27,43 @@ use crate::{SHA1_SIZE, SHA256_SIZE};
 ///   A(0) = seed
 ///   A(n) = HMAC_SHA1(secret, A(n-1))
 /// + indicates that the results are appended to previous results.
-pub fn p_sha(message_digest: hash::MessageDigest, secret: &[u8], seed: &[u8], length: usize) -> Vec<u8> {
-    let mut result = Vec::with_capacity(length);
-
-    let mut hmac = Vec::with_capacity(seed.len() * 2);
+///
+/// The output is key material, so it and every intermediate buffer are held in `SecretBytes` and
+/// wiped when they are dropped.
+pub fn p_sha(hash_algorithm: HashAlgorithm, secret: &[u8], seed: &[u8], length: usize) -> SecretBytes {
+    let mut result = SecretBytes::zeroed(length);
+    let mut result_len = 0;
 
-    let mut a_last = Vec::with_capacity(seed.len());
-    a_last.extend_from_slice(seed); // A(0) = seed
+    let mut a_last = SecretBytes::from(seed); // A(0) = seed
 
-    while result.len() < length {
+    while result_len < length {
         // A(n) = HMAC_SHA1(secret, A(n-1))
-        let a_next = hmac_vec(message_digest, secret, &a_last);
+        let a_next = hmac_secret(hash_algorithm, secret, &a_last);
 
         // Append a slice of random data
         let bytes = {
-            hmac.clear();
-            hmac.extend(&a_next);
-            hmac.extend_from_slice(seed);
-            hmac_vec(message_digest, secret, &hmac)
+            let mut hmac = SecretBytes::zeroed(a_next.len() + seed.len());
+            hmac[..a_next.len()].copy_from_slice(&a_next);
+            hmac[a_next.len()..].copy_from_slice(seed);
+            hmac_secret(hash_algorithm, secret, &hmac)
         };
-        result.extend(&bytes);
+        let count = bytes.len().min(length - result_len);
+        result[result_len..(result_len + count)].copy_from_slice(&bytes[..count]);
+        result_len += count;
 
-        a_last.clear();
-        a_last.extend(&a_next);
+        a_last = a_next;
     }
 
-    result.truncate(length);
     result
 }
 
-fn hmac_vec(digest: hash::MessageDigest, key: &[u8], data: &[u8]) -> Vec<u8> {
+fn hmac_secret(hash_algorithm: HashAlgorithm, key: &[u8], data: &[u8]) -> SecretBytes {
     // Compute a signature
-    let pkey = pkey::PKey::hmac(key).unwrap();
-    let mut signer = sign::Signer::new(digest, &pkey).unwrap();
-    signer.update(data).unwrap();
-    signer.sign_to_vec().unwrap()
+    SecretBytes::from(DefaultBackend::hmac(hash_algorithm, key, data).unwrap())
 }
 
-fn hmac(digest: hash::MessageDigest, key: &[u8], data: &[u8], signature: &mut [u8]) -> Result<(), StatusCode> {
-    let hmac = hmac_vec(digest, key, data);
+fn hmac(hash_algorithm: HashAlgorithm, key: &[u8], data: &[u8], signature: &mut [u8]) -> Result<(), StatusCode> {
+    let hmac = DefaultBackend::hmac(hash_algorithm, key, data)?;
     trace!("hmac length = {}", hmac.len());
     signature.copy_from_slice(&hmac);
     Ok(())
@@ -71,7 +71,7 @@ fn hmac(digest: hash::MessageDigest, key: &[u8], data: &[u8], signature: &mut [u
 
 pub fn hmac_sha1(key: &[u8], data: &[u8], signature: &mut [u8]) -> Result<(), StatusCode> {
     if signature.len() == SHA1_SIZE {
-        hmac(hash::MessageDigest::sha1(), key, data, signature)
+        hmac(HashAlgorithm::Sha1, key, data, signature)
     } else {
         error!("Signature buffer length must be exactly {} bytes to receive hmac_sha1 signature", SHA1_SIZE);
         Err(StatusCode::BadInvalidArgument)
@@ -96,7 +96,7 @@ pub fn verify_hmac_sha1(key: &[u8], data: &[u8], signature: &[u8]) -> bool {
 
 pub fn hmac_sha256(key: &[u8], data: &[u8], signature: &mut [u8]) -> Result<(), StatusCode> {
     if signature.len() == SHA256_SIZE {
-        hmac(hash::MessageDigest::sha256(), key, data, signature)
+        hmac(HashAlgorithm::Sha256, key, data, signature)
     } else {
         error!("Signature buffer length must be exactly {} bytes to receive hmac_sha256 signature", SHA256_SIZE);
         Err(StatusCode::BadInvalidArgument)
@@ -13,9 +13,12 @@ extern crate serde_derive;
 
 pub use {
     aeskey::*,
+    backend::*,
     certificate_store::*,
+    error::*,
     hash::*,
     pkey::*,
+    secret::*,
     security_policy::*,
     thumbprint::*,
     user_identity::*,
@@ -32,9 +35,12 @@ mod tests;
 
 pub mod x509;
 pub mod aeskey;
+pub mod backend;
 pub mod pkey;
+pub mod secret;
 pub mod thumbprint;
 pub mod certificate_store;
+pub mod error;
 pub mod hash;
 pub mod security_policy;
 pub mod user_identity;
@@ -47,11 +53,11 @@ pub const SHA256_SIZE: usize = 32;
 
 /// These are algorithms that are used by various policies or external to this file
 pub(crate) mod algorithms {
-    // Symmetric encryption algorithm AES128-CBC
-    //pub const ENC_AES128_CBC: &str = "http://www.w3.org/2001/04/xmlenc#aes128-cbc";
+    /// Symmetric encryption algorithm AES128-CBC
+    pub const ENC_AES128_CBC: &str = "http://www.w3.org/2001/04/xmlenc#aes128-cbc";
 
-    // Symmetric encryption algorithm AES256-CBC
-    //pub const ENC_AES256_CBC: &str = "http://www.w3.org/2001/04/xmlenc#aes256-cbc";
+    /// Symmetric encryption algorithm AES256-CBC
+    pub const ENC_AES256_CBC: &str = "http://www.w3.org/2001/04/xmlenc#aes256-cbc";
 
     /// Asymmetric encryption algorithm RSA15
     pub const ENC_RSA_15: &str = "http://www.w3.org/2001/04/xmlenc#rsa-1_5";
@@ -77,11 +83,17 @@ pub(crate) mod algorithms {
     /// Asymmetric digital signature algorithm using RSA-PSS_SHA2-256
     pub const DSIG_RSA_PSS_SHA2_256: &str = "http://opcfoundation.org/UA/security/rsa-pss-sha2-256";
 
-    // Key derivation algorithm P_SHA1
-    //pub const KEY_P_SHA1: &str = "http://docs.oasis-open.org/ws-sx/ws-secureconversation/200512/dk/p_sha1";
+    /// Key derivation algorithm P_SHA1
+    pub const KEY_P_SHA1: &str = "http://docs.oasis-open.org/ws-sx/ws-secureconversation/200512/dk/p_sha1";
 
-    // Key derivation algorithm P_SHA256
-    //pub const KEY_P_SHA256: &str = "http://docs.oasis-open.org/ws-sx/ws-secureconversation/200512/dk/p_sha256";
+    /// Key derivation algorithm P_SHA256
+    pub const KEY_P_SHA256: &str = "http://docs.oasis-open.org/ws-sx/ws-secureconversation/200512/dk/p_sha256";
+}
+
+/// Tests if the compiled in crypto backend implements the algorithm identified by the URI, e.g.
+/// `http://www.w3.org/2001/04/xmlenc#rsa-oaep`. Unrecognized URIs are unsupported.
+pub fn is_algorithm_supported(algorithm_uri: &str) -> bool {
+    DefaultBackend::is_algorithm_supported(algorithm_uri)
 }
 
 fn concat_data_and_nonce(data: &[u8], nonce: &[u8]) -> Vec<u8> {
@@ -150,6 +162,43 @@ pub fn verify_signature_data(signature: &SignatureData, security_policy: Securit
     }
 }
 
+/// Verifies the signature a peer produced during the handshake to prove possession of its private
+/// key. The signed blob is our certificate followed by the nonce we sent, so a signature replayed
+/// from another handshake with a different nonce will not verify. The signature is checked with the
+/// algorithm of the negotiated security policy, so a peer cannot downgrade it by naming a weaker
+/// algorithm alongside the signature.
+///
+/// # Errors
+///
+/// `BadSecurityChecksFailed` if the policy does not sign, the algorithm supplied with the signature
+/// is not the policy's, or the signature does not match.
+pub fn verify_handshake_signature(security_policy: SecurityPolicy, peer_cert: &X509, our_cert: &X509, our_nonce: &[u8], signature: &SignatureData) -> Result<(), StatusCode> {
+    match security_policy {
+        SecurityPolicy::None | SecurityPolicy::Unknown => {
+            error!("Handshake signature cannot be verified with security policy {:?}", security_policy);
+            return Err(StatusCode::BadSecurityChecksFailed);
+        }
+        _ => {}
+    }
+    let algorithm: &str = signature.algorithm.as_ref();
+    if algorithm != security_policy.asymmetric_signature_algorithm() {
+        error!("Handshake signature algorithm \"{}\" does not match the security policy's algorithm \"{}\"", algorithm, security_policy.asymmetric_signature_algorithm());
+        return Err(StatusCode::BadSecurityChecksFailed);
+    }
+    if signature.signature.is_null() {
+        error!("Handshake signature is empty");
+        return Err(StatusCode::BadSecurityChecksFailed);
+    }
+    let verification_key = peer_cert.public_key()?;
+    let our_cert = our_cert.as_byte_string();
+    let data = concat_data_and_nonce(our_cert.as_ref(), our_nonce);
+    security_policy.asymmetric_verify_signature(&verification_key, &data, signature.signature.as_ref(), None)
+        .map_err(|_| {
+            error!("Handshake signature verification failed");
+            StatusCode::BadSecurityChecksFailed
+        })
+}
+
 /// Returns this computer's hostname
 pub fn hostname() -> Result<String, ()> {
     use gethostname::gethostname;
@@ -13,6 +13,8 @@ use openssl::{hash, pkey, rsa, sign};
 
 use opcua_types::status_code::StatusCode;
 
+use crate::error::CryptoError;
+
 #[derive(Copy, Clone, Debug)]
 pub enum RsaPadding {
     PKCS1,
@@ -49,6 +51,13 @@ impl<T> Debug for PKey<T> {
     }
 }
 
+impl<T> Clone for PKey<T> {
+    fn clone(&self) -> Self {
+        // The underlying key is reference counted so this does not copy it
+        PKey { value: self.value.clone() }
+    }
+}
+
 pub trait KeySize {
     fn bit_length(&self) -> usize;
 
@@ -109,6 +118,14 @@ impl PrivateKey {
             })
     }
 
+    /// Returns the public key of the key pair
+    pub fn public_key(&self) -> Result<PublicKey, CryptoError> {
+        self.value.public_key_to_der()
+            .and_then(|der| pkey::PKey::public_key_from_der(&der))
+            .map(|value| PKey { value })
+            .map_err(|err| CryptoError::Backend(err.to_string()))
+    }
+
     pub fn private_key_to_pem(&self) -> Result<Vec<u8>, ()> {
         self.value.private_key_to_pem_pkcs8()
             .map_err(|_| {
@@ -154,10 +171,20 @@ impl PrivateKey {
 
     /// Decrypts data in src to dst using the specified padding and returning the size of the decrypted
     /// data in bytes or an error.
-    pub fn private_decrypt(&self, src: &[u8], dst: &mut [u8], padding: RsaPadding) -> Result<usize, ()> {
+    ///
+    /// # Errors
+    ///
+    /// `CryptoError::DataLength` if the source is not a multiple of the key's cipher text block size,
+    /// `CryptoError::Padding` if a block does not decrypt with the padding.
+    pub fn private_decrypt(&self, src: &[u8], dst: &mut [u8], padding: RsaPadding) -> Result<usize, CryptoError> {
         // decrypt data using our private key
         let cipher_text_block_size = self.cipher_text_block_size();
-        let rsa = self.value.rsa().unwrap();
+        if src.len() % cipher_text_block_size != 0 {
+            error!("Decryption failed, src len {} is not a multiple of the key size {}", src.len(), cipher_text_block_size);
+            let expected_len = (src.len() / cipher_text_block_size + 1) * cipher_text_block_size;
+            return Err(CryptoError::DataLength(expected_len, src.len()));
+        }
+        let rsa = self.value.rsa().map_err(|err| CryptoError::Backend(err.to_string()))?;
         let rsa_padding: rsa::Padding = padding.into();
 
         // Decrypt the data
@@ -173,6 +200,7 @@ impl PrivateKey {
                 rsa.private_decrypt(src, dst, rsa_padding)
                     .map_err(|err| {
                         error!("Decryption failed for key size {}, src idx {}, dst idx {}, padding {:?}, error - {:?}", cipher_text_block_size, src_idx, dst_idx, padding, err);
+                        CryptoError::Padding
                     })?
             };
             src_idx += cipher_text_block_size;
@@ -230,14 +258,14 @@ impl PublicKey {
 
     /// Encrypts data from src to dst using the specified padding and returns the size of encrypted
     /// data in bytes or an error.
-    pub fn public_encrypt(&self, src: &[u8], dst: &mut [u8], padding: RsaPadding) -> Result<usize, ()> {
+    pub fn public_encrypt(&self, src: &[u8], dst: &mut [u8], padding: RsaPadding) -> Result<usize, CryptoError> {
         let cipher_text_block_size = self.cipher_text_block_size();
         let plain_text_block_size = self.plain_text_block_size(padding);
 
         // For reference:
         //
         // https://www.openssl.org/docs/man1.0.2/crypto/RSA_public_encrypt.html
-        let rsa = self.value.rsa().unwrap();
+        let rsa = self.value.rsa().map_err(|err| CryptoError::Backend(err.to_string()))?;
         let padding: rsa::Padding = padding.into();
 
         // Encrypt the data in chunks no larger than the key size less padding
@@ -261,6 +289,7 @@ impl PublicKey {
                 rsa.public_encrypt(src, dst, padding)
                     .map_err(|err| {
                         error!("Encryption failed for bytes_to_encrypt {}, key_size {}, src_idx {}, dst_idx {} error - {:?}", bytes_to_encrypt, cipher_text_block_size, src_idx, dst_idx, err);
+                        CryptoError::Backend(err.to_string())
                     })?
             };
 
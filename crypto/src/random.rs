@@ -4,13 +4,13 @@
 
 //! Module contains functions for creating cryptographically strong random bytes.
 
-use openssl::rand;
-
 use opcua_types::byte_string::ByteString;
 
+use crate::backend::{CryptoBackend, DefaultBackend};
+
 /// Fills the slice with cryptographically strong pseudo-random bytes
 pub fn bytes(bytes: &mut [u8]) {
-    let _ = rand::rand_bytes(bytes);
+    DefaultBackend::random_bytes(bytes);
 }
 
 /// Create a byte string with a number of random characters. Can be used to create a nonce or
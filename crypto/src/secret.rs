@@ -0,0 +1,81 @@
+// OPCUA for Rust
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (C) 2017-2020 Adam Lock
+
+//! Container for key material that is wiped from memory when it is dropped.
+use std::{
+    fmt,
+    ops::{Deref, DerefMut},
+    ptr,
+    sync::atomic::{self, Ordering},
+};
+
+/// Holds sensitive bytes such as derived keys and nonces. The buffer is overwritten with zeroes
+/// when the value is dropped and the `Debug` implementation only reveals the length.
+///
+/// The buffer cannot grow, so the bytes are never copied into a reallocation that would be
+/// freed without being wiped. To change the value, replace the whole `SecretBytes`.
+#[derive(Clone, Default)]
+pub struct SecretBytes {
+    value: Vec<u8>,
+}
+
+impl Drop for SecretBytes {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl fmt::Debug for SecretBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretBytes([REDACTED; {}])", self.value.len())
+    }
+}
+
+impl Deref for SecretBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.value
+    }
+}
+
+impl DerefMut for SecretBytes {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.value
+    }
+}
+
+impl AsRef<[u8]> for SecretBytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.value
+    }
+}
+
+impl From<Vec<u8>> for SecretBytes {
+    fn from(value: Vec<u8>) -> Self {
+        SecretBytes { value }
+    }
+}
+
+impl<'a> From<&'a [u8]> for SecretBytes {
+    fn from(value: &'a [u8]) -> Self {
+        SecretBytes { value: value.to_vec() }
+    }
+}
+
+impl SecretBytes {
+    /// Creates a zero filled buffer of the specified length
+    pub fn zeroed(len: usize) -> SecretBytes {
+        SecretBytes { value: vec![0u8; len] }
+    }
+
+    /// Overwrites every byte of the buffer with zero. Volatile writes are used so the compiler
+    /// cannot elide the stores on a value that is about to be freed.
+    pub fn zeroize(&mut self) {
+        for b in self.value.iter_mut() {
+            unsafe { ptr::write_volatile(b, 0) };
+        }
+        atomic::compiler_fence(Ordering::SeqCst);
+    }
+}
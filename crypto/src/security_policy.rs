@@ -7,21 +7,29 @@
 use opcua_types::{
     ByteString,
     constants,
+    MessageSecurityMode,
     status_code::StatusCode,
 };
-use openssl::hash as openssl_hash;
 use std::fmt;
 use std::str::FromStr;
 
 use crate::{
     aeskey::AesKey,
+    backend::{HashAlgorithm, KeyBackend, RsaBackend},
+    error::CryptoError,
     hash,
+    is_algorithm_supported,
     pkey::{KeySize, PrivateKey, PublicKey, RsaPadding},
     random,
+    secret::SecretBytes,
     SHA1_SIZE,
     SHA256_SIZE,
 };
 
+/// Keys derived from the nonces for one direction of a secure channel - the signing key, the
+/// encrypting key and the initialization vector.
+pub type DerivedKeys = (SecretBytes, AesKey, SecretBytes);
+
 // These are constants that govern the different encryption / signing modes for OPC UA. In some
 // cases these algorithm string constants will be passed over the wire and code needs to test the
 // string to see if the algorithm is supported.
@@ -49,6 +57,8 @@ mod aes_128_sha_256_rsa_oaep {
     pub const SYMMETRIC_SIGNATURE_ALGORITHM: &str = DSIG_HMAC_SHA256;
     pub const ASYMMETRIC_SIGNATURE_ALGORITHM: &str = DSIG_RSA_SHA256;
     pub const ASYMMETRIC_ENCRYPTION_ALGORITHM: &str = ENC_RSA_15;
+    pub const SYMMETRIC_ENCRYPTION_ALGORITHM: &str = ENC_AES128_CBC;
+    pub const KEY_DERIVATION_ALGORITHM: &str = KEY_P_SHA256;
     pub const DERIVED_SIGNATURE_KEY_LENGTH: usize = 256;
     pub const ASYMMETRIC_KEY_LENGTH: (usize, usize) = (2048, 4096);
 }
@@ -76,6 +86,8 @@ mod aes_256_sha_256_rsa_pss {
     pub const SYMMETRIC_SIGNATURE_ALGORITHM: &str = DSIG_HMAC_SHA256;
     pub const ASYMMETRIC_SIGNATURE_ALGORITHM: &str = DSIG_RSA_PSS_SHA2_256;
     pub const ASYMMETRIC_ENCRYPTION_ALGORITHM: &str = ENC_RSA_OAEP;
+    pub const SYMMETRIC_ENCRYPTION_ALGORITHM: &str = ENC_AES256_CBC;
+    pub const KEY_DERIVATION_ALGORITHM: &str = KEY_P_SHA256;
     pub const DERIVED_SIGNATURE_KEY_LENGTH: usize = 256;
     pub const ASYMMETRIC_KEY_LENGTH: (usize, usize) = (2048, 4096);
 }
@@ -103,6 +115,8 @@ mod basic_256_sha_256 {
     pub const SYMMETRIC_SIGNATURE_ALGORITHM: &str = DSIG_HMAC_SHA256;
     pub const ASYMMETRIC_SIGNATURE_ALGORITHM: &str = DSIG_RSA_SHA256;
     pub const ASYMMETRIC_ENCRYPTION_ALGORITHM: &str = ENC_RSA_OAEP;
+    pub const SYMMETRIC_ENCRYPTION_ALGORITHM: &str = ENC_AES256_CBC;
+    pub const KEY_DERIVATION_ALGORITHM: &str = KEY_P_SHA256;
     pub const DERIVED_SIGNATURE_KEY_LENGTH: usize = 256;
     pub const ASYMMETRIC_KEY_LENGTH: (usize, usize) = (2048, 4096);
 }
@@ -129,6 +143,8 @@ mod basic_128_rsa_15 {
     pub const SYMMETRIC_SIGNATURE_ALGORITHM: &str = DSIG_HMAC_SHA1;
     pub const ASYMMETRIC_SIGNATURE_ALGORITHM: &str = DSIG_RSA_SHA1;
     pub const ASYMMETRIC_ENCRYPTION_ALGORITHM: &str = ENC_RSA_15;
+    pub const SYMMETRIC_ENCRYPTION_ALGORITHM: &str = ENC_AES128_CBC;
+    pub const KEY_DERIVATION_ALGORITHM: &str = KEY_P_SHA1;
     pub const DERIVED_SIGNATURE_KEY_LENGTH: usize = 128;
     pub const ASYMMETRIC_KEY_LENGTH: (usize, usize) = (1024, 2048);
 }
@@ -155,6 +171,8 @@ mod basic_256 {
     pub const SYMMETRIC_SIGNATURE_ALGORITHM: &str = DSIG_HMAC_SHA1;
     pub const ASYMMETRIC_SIGNATURE_ALGORITHM: &str = DSIG_RSA_SHA1;
     pub const ASYMMETRIC_ENCRYPTION_ALGORITHM: &str = ENC_RSA_OAEP;
+    pub const SYMMETRIC_ENCRYPTION_ALGORITHM: &str = ENC_AES256_CBC;
+    pub const KEY_DERIVATION_ALGORITHM: &str = KEY_P_SHA1;
     pub const DERIVED_SIGNATURE_KEY_LENGTH: usize = 192;
     pub const ASYMMETRIC_KEY_LENGTH: (usize, usize) = (1024, 2048);
 }
@@ -172,6 +190,19 @@ pub enum SecurityPolicy {
     Basic256,
 }
 
+/// Returns the security policies that this build can actually use, i.e. that are implemented by
+/// the compiled in crypto backend. Endpoints using any other policy should not be advertised.
+pub fn available_security_policies() -> Vec<SecurityPolicy> {
+    [
+        SecurityPolicy::None,
+        SecurityPolicy::Basic128Rsa15,
+        SecurityPolicy::Basic256,
+        SecurityPolicy::Basic256Sha256,
+        SecurityPolicy::Aes128Sha256RsaOaep,
+        SecurityPolicy::Aes256Sha256RsaPss,
+    ].iter().filter(|p| p.is_supported()).cloned().collect()
+}
+
 impl fmt::Display for SecurityPolicy {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.to_str())
@@ -218,13 +249,19 @@ impl SecurityPolicy {
         }
     }
 
-    /// Returns true if the security policy is supported. It might be recognized but be unsupported by the implementation
+    /// Returns true if the security policy is supported. It might be recognized but be unsupported
+    /// by the implementation, i.e. the crypto backend does not implement one of its algorithms.
     pub fn is_supported(&self) -> bool {
         match self {
-            SecurityPolicy::None | SecurityPolicy::Basic128Rsa15 | SecurityPolicy::Basic256 |
-            SecurityPolicy::Basic256Sha256 | SecurityPolicy::Aes128Sha256RsaOaep => true,
-            // | SecurityPolicy::Aes256Sha256RsaPss
-            _ => false
+            SecurityPolicy::None => true,
+            SecurityPolicy::Unknown => false,
+            _ => {
+                [
+                    self.asymmetric_encryption_algorithm(), self.asymmetric_signature_algorithm(),
+                    self.symmetric_encryption_algorithm(), self.symmetric_signature_algorithm(),
+                    self.key_derivation_algorithm(),
+                ].iter().all(|algorithm_uri| is_algorithm_supported(algorithm_uri))
+            }
         }
     }
 
@@ -237,6 +274,26 @@ impl SecurityPolicy {
         }
     }
 
+    /// Returns a relative security level for an endpoint using this policy and the security mode,
+    /// higher meaning more secure. Sign and encrypt always ranks above sign only, and within a mode
+    /// the stronger policy ranks higher, with every SHA-256 policy above the deprecated SHA-1
+    /// policies. No security is 0.
+    pub fn security_level(&self, security_mode: MessageSecurityMode) -> u8 {
+        let security_level = match self {
+            SecurityPolicy::Basic128Rsa15 => 1,
+            SecurityPolicy::Basic256 => 2,
+            SecurityPolicy::Aes128Sha256RsaOaep => 3,
+            SecurityPolicy::Basic256Sha256 => 4,
+            SecurityPolicy::Aes256Sha256RsaPss => 5,
+            _ => 0
+        };
+        match security_mode {
+            MessageSecurityMode::SignAndEncrypt if security_level > 0 => security_level + 10,
+            MessageSecurityMode::Sign => security_level,
+            _ => 0
+        }
+    }
+
     pub fn to_str(&self) -> &'static str {
         match self {
             SecurityPolicy::None => constants::SECURITY_POLICY_NONE,
@@ -290,6 +347,32 @@ impl SecurityPolicy {
         }
     }
 
+    pub fn symmetric_encryption_algorithm(&self) -> &'static str {
+        match self {
+            SecurityPolicy::Basic128Rsa15 => basic_128_rsa_15::SYMMETRIC_ENCRYPTION_ALGORITHM,
+            SecurityPolicy::Basic256 => basic_256::SYMMETRIC_ENCRYPTION_ALGORITHM,
+            SecurityPolicy::Basic256Sha256 => basic_256_sha_256::SYMMETRIC_ENCRYPTION_ALGORITHM,
+            SecurityPolicy::Aes128Sha256RsaOaep => aes_128_sha_256_rsa_oaep::SYMMETRIC_ENCRYPTION_ALGORITHM,
+            SecurityPolicy::Aes256Sha256RsaPss => aes_256_sha_256_rsa_pss::SYMMETRIC_ENCRYPTION_ALGORITHM,
+            _ => {
+                panic!("Invalid policy");
+            }
+        }
+    }
+
+    pub fn key_derivation_algorithm(&self) -> &'static str {
+        match self {
+            SecurityPolicy::Basic128Rsa15 => basic_128_rsa_15::KEY_DERIVATION_ALGORITHM,
+            SecurityPolicy::Basic256 => basic_256::KEY_DERIVATION_ALGORITHM,
+            SecurityPolicy::Basic256Sha256 => basic_256_sha_256::KEY_DERIVATION_ALGORITHM,
+            SecurityPolicy::Aes128Sha256RsaOaep => aes_128_sha_256_rsa_oaep::KEY_DERIVATION_ALGORITHM,
+            SecurityPolicy::Aes256Sha256RsaPss => aes_256_sha_256_rsa_pss::KEY_DERIVATION_ALGORITHM,
+            _ => {
+                panic!("Invalid policy");
+            }
+        }
+    }
+
     // Plaintext block size in bytes
     pub fn plain_block_size(&self) -> usize {
         match self {
@@ -383,18 +466,18 @@ impl SecurityPolicy {
 
     /// Pseudo random function is used as a key derivation algorithm. It creates pseudo random bytes
     /// from a secret and seed specified by the parameters.
-    fn prf(&self, secret: &[u8], seed: &[u8], length: usize, offset: usize) -> Vec<u8> {
+    fn prf(&self, secret: &[u8], seed: &[u8], length: usize, offset: usize) -> SecretBytes {
         // P_SHA1 or P_SHA256
-        let message_digest = match self {
-            SecurityPolicy::Basic128Rsa15 | SecurityPolicy::Basic256 => openssl_hash::MessageDigest::sha1(),
+        let hash_algorithm = match self {
+            SecurityPolicy::Basic128Rsa15 | SecurityPolicy::Basic256 => HashAlgorithm::Sha1,
             SecurityPolicy::Basic256Sha256 | SecurityPolicy::Aes128Sha256RsaOaep |
-            SecurityPolicy::Aes256Sha256RsaPss => openssl_hash::MessageDigest::sha256(),
+            SecurityPolicy::Aes256Sha256RsaPss => HashAlgorithm::Sha256,
             _ => {
                 panic!("Invalid policy");
             }
         };
-        let result = hash::p_sha(message_digest, secret, seed, offset + length);
-        result[offset..(offset + length)].to_vec()
+        let result = hash::p_sha(hash_algorithm, secret, seed, offset + length);
+        SecretBytes::from(&result[offset..(offset + length)])
     }
 
     /// Part 6
@@ -432,7 +515,7 @@ impl SecurityPolicy {
     /// The Client keys are used to secure Messages sent by the Client. The Server keys
     /// are used to secure Messages sent by the Server.
     ///
-    pub fn make_secure_channel_keys(&self, secret: &[u8], seed: &[u8]) -> (Vec<u8>, AesKey, Vec<u8>) {
+    pub fn make_secure_channel_keys(&self, secret: &[u8], seed: &[u8]) -> DerivedKeys {
         // Work out the length of stuff
         let signing_key_length = self.derived_signature_key_size();
         let (encrypting_key_length, encrypting_block_size) = match self {
@@ -442,13 +525,34 @@ impl SecurityPolicy {
                 panic!("Invalid policy");
             }
         };
+        self.derive_secure_channel_keys(secret, seed, signing_key_length, encrypting_key_length, encrypting_block_size)
+            .unwrap_or_else(|err| panic!("Policy {} has invalid key lengths, {}", self, err))
+    }
 
-        let signing_key = self.prf(secret, seed, signing_key_length, 0);
+    /// Derives the keys as `make_secure_channel_keys()` does but with the lengths supplied by the
+    /// caller. The encrypting key must be the key length of the policy's cipher and the
+    /// initialization vector must be exactly its block size, otherwise decryption with them would
+    /// silently produce garbage.
+    ///
+    /// # Errors
+    ///
+    /// `CryptoError::KeyLength` holding the expected and actual length if the encrypting key is the
+    /// wrong length, `CryptoError::IvLength` likewise if the initialization vector is.
+    pub fn derive_secure_channel_keys(&self, secret: &[u8], seed: &[u8], signing_key_length: usize, encrypting_key_length: usize, iv_length: usize) -> Result<DerivedKeys, CryptoError> {
         let encrypting_key = self.prf(secret, seed, encrypting_key_length, signing_key_length);
         let encrypting_key = AesKey::new(*self, &encrypting_key);
-        let iv = self.prf(secret, seed, encrypting_block_size, signing_key_length + encrypting_key_length);
+        if encrypting_key_length != encrypting_key.key_length() {
+            error!("Derived encrypting key length {} does not match the cipher key length {}", encrypting_key_length, encrypting_key.key_length());
+            return Err(CryptoError::KeyLength(encrypting_key.key_length(), encrypting_key_length));
+        }
+        if iv_length != encrypting_key.block_size() {
+            error!("Derived initialization vector length {} does not match the cipher block size {}", iv_length, encrypting_key.block_size());
+            return Err(CryptoError::IvLength(encrypting_key.block_size(), iv_length));
+        }
+        let signing_key = self.prf(secret, seed, signing_key_length, 0);
+        let iv = self.prf(secret, seed, iv_length, signing_key_length + encrypting_key_length);
 
-        (signing_key, encrypting_key, iv)
+        Ok((signing_key, encrypting_key, iv))
     }
 
     /// Produce a signature of the data using an asymmetric key. Stores the signature in the supplied
@@ -507,12 +611,29 @@ impl SecurityPolicy {
         }
     }
 
+    /// Returns the largest block of plain text that can be encrypted in one go with the key under
+    /// this policy's padding scheme, i.e. the key size less 11 bytes for PKCS#1 v1.5 or 42 bytes
+    /// for OAEP-SHA1.
+    pub fn asymmetric_plaintext_block_size<T>(&self, key: &T) -> usize where T: KeySize {
+        key.plain_text_block_size(self.asymmetric_encryption_padding())
+    }
+
+    /// Returns the size of each block of cipher text produced by encrypting with the key, which is
+    /// the key size regardless of padding.
+    pub fn asymmetric_ciphertext_block_size<T>(&self, key: &T) -> usize where T: KeySize {
+        key.cipher_text_block_size()
+    }
+
     /// Encrypts a message using the supplied encryption key, returns the encrypted size. Destination
     /// buffer must be large enough to hold encrypted bytes including any padding.
     pub fn asymmetric_encrypt(&self, encryption_key: &PublicKey, src: &[u8], dst: &mut [u8]) -> Result<usize, StatusCode> {
         let padding = self.asymmetric_encryption_padding();
-        encryption_key.public_encrypt(src, dst, padding)
-            .map_err(|_| StatusCode::BadUnexpectedError)
+        KeyBackend::rsa_public_encrypt(encryption_key, src, dst, padding)
+            .map_err(|err| {
+                let status_code = StatusCode::from(err.clone());
+                error!("Asymmetric encryption failed - {}, status code = {}", err, status_code);
+                status_code
+            })
     }
 
     /// Decrypts a message whose thumbprint matches the x509 cert and private key pair.
@@ -520,10 +641,11 @@ impl SecurityPolicy {
     /// Returns the number of decrypted bytes
     pub fn asymmetric_decrypt(&self, decryption_key: &PrivateKey, src: &[u8], dst: &mut [u8]) -> Result<usize, StatusCode> {
         let padding = self.asymmetric_encryption_padding();
-        decryption_key.private_decrypt(src, dst, padding)
-            .map_err(|_| {
-                error!("Asymmetric decryption failed");
-                StatusCode::BadSecurityChecksFailed
+        KeyBackend::rsa_private_decrypt(decryption_key, src, dst, padding)
+            .map_err(|err| {
+                let status_code = StatusCode::from(err.clone());
+                error!("Asymmetric decryption failed - {}, status code = {}", err, status_code);
+                status_code
             })
     }
 
@@ -567,4 +689,69 @@ impl SecurityPolicy {
     pub fn symmetric_decrypt(&self, key: &AesKey, iv: &[u8], src: &[u8], dst: &mut [u8]) -> Result<usize, StatusCode> {
         key.decrypt(src, iv, dst)
     }
+
+    /// Tests that the crypto backend works for this policy by putting random data through
+    /// asymmetric encryption / decryption and signing / verification with a freshly generated key
+    /// pair, and then through symmetric encryption / decryption and signing / verification with
+    /// keys derived from random nonces. A server can call this at startup so that a misconfigured
+    /// backend fails before the policy is advertised on an endpoint, rather than on the first
+    /// secure channel that uses it.
+    ///
+    /// The key pair is the smallest that the policy allows, so the test is not quick.
+    ///
+    /// # Errors
+    ///
+    /// `CryptoError::Unsupported` for the None policy, which has nothing to test, or a policy the
+    /// backend does not support. `CryptoError::SignatureMismatch` if a signature does not verify
+    /// and `CryptoError::Backend` if an operation fails or the data does not survive the round trip.
+    pub fn self_test(&self) -> Result<(), CryptoError> {
+        if *self == SecurityPolicy::None || !self.is_supported() {
+            error!("Security policy {:?} cannot be tested", self);
+            return Err(CryptoError::Unsupported);
+        }
+        let backend_error = |status_code: StatusCode| CryptoError::Backend(status_code.to_string());
+        let verify_error = |status_code: StatusCode| if status_code == StatusCode::BadSecurityChecksFailed {
+            CryptoError::SignatureMismatch
+        } else {
+            CryptoError::Backend(status_code.to_string())
+        };
+        let round_trip = |kind: &str, expected: &[u8], actual: &[u8]| if expected == actual {
+            Ok(())
+        } else {
+            error!("Security policy {} {} decryption does not match the data that was encrypted", self, kind);
+            Err(CryptoError::Backend(format!("{} round trip mismatch", kind)))
+        };
+
+        // Asymmetric
+        let private_key = KeyBackend::rsa_generate_key(self.min_max_asymmetric_keylength().0 as u32)?;
+        let public_key = KeyBackend::rsa_public_key(&private_key)?;
+        let padding = self.asymmetric_encryption_padding();
+        let mut plaintext = vec![0u8; self.asymmetric_plaintext_block_size(&public_key)];
+        random::bytes(&mut plaintext);
+        let mut ciphertext = vec![0u8; self.asymmetric_ciphertext_block_size(&public_key)];
+        let encrypted_size = KeyBackend::rsa_public_encrypt(&public_key, &plaintext, &mut ciphertext, padding)?;
+        let mut decrypted = vec![0u8; ciphertext.len()];
+        let decrypted_size = KeyBackend::rsa_private_decrypt(&private_key, &ciphertext[..encrypted_size], &mut decrypted, padding)?;
+        round_trip("asymmetric", &plaintext, &decrypted[..decrypted_size])?;
+
+        let mut signature = vec![0u8; private_key.size()];
+        self.asymmetric_sign(&private_key, &plaintext, &mut signature).map_err(backend_error)?;
+        self.asymmetric_verify_signature(&public_key, &plaintext, &signature, None).map_err(verify_error)?;
+
+        // Symmetric
+        let (signing_key, encrypting_key, iv) = self.make_secure_channel_keys(self.random_nonce().as_ref(), self.random_nonce().as_ref());
+        let mut plaintext = vec![0u8; encrypting_key.block_size() * 4];
+        random::bytes(&mut plaintext);
+        let mut ciphertext = vec![0u8; plaintext.len() + encrypting_key.block_size()];
+        let encrypted_size = self.symmetric_encrypt(&encrypting_key, &iv, &plaintext, &mut ciphertext).map_err(backend_error)?;
+        let mut decrypted = vec![0u8; encrypted_size + encrypting_key.block_size()];
+        let decrypted_size = self.symmetric_decrypt(&encrypting_key, &iv, &ciphertext[..encrypted_size], &mut decrypted).map_err(backend_error)?;
+        round_trip("symmetric", &plaintext, &decrypted[..decrypted_size])?;
+
+        let mut signature = vec![0u8; self.symmetric_signature_size()];
+        self.symmetric_sign(&signing_key, &plaintext, &mut signature).map_err(backend_error)?;
+        self.symmetric_verify_signature(&signing_key, &plaintext, &signature).map_err(verify_error)?;
+
+        Ok(())
+    }
 }
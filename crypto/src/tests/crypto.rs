@@ -1,17 +1,19 @@
 extern crate rustc_serialize as serialize;
 
-use opcua_types::status_code::StatusCode;
+use opcua_types::{status_code::StatusCode, UAString};
 use std::fs::File;
-use std::io::Write;
+use std::io::{Read, Write};
 
 use crate::{
-    aeskey::AesKey, certificate_store::*, pkey::{KeySize, PrivateKey, RsaPadding},
+    aeskey::AesKey, backend::{CryptoBackend, DefaultBackend, HashAlgorithm, KeyBackend, RsaBackend}, certificate_store::*, create_signature_data, error::CryptoError, pkey::{KeySize, PrivateKey, RsaPadding},
+    available_security_policies,
     random,
     SecurityPolicy,
     SHA1_SIZE,
     SHA256_SIZE,
     user_identity::{legacy_password_decrypt, legacy_password_encrypt},
-    x509::{X509, X509Data},
+    verify_handshake_signature,
+    x509::{CertificateInfo, X509, X509Data},
 };
 use crate::tests::{APPLICATION_HOSTNAME, APPLICATION_URI, make_certificate_store, make_test_cert_1024, make_test_cert_2048};
 
@@ -100,6 +102,39 @@ fn create_own_cert_in_pki() {
     drop(tmp_dir)
 }
 
+#[test]
+fn create_own_certificate_request_in_pki() {
+    let args = X509Data {
+        key_size: 2048,
+        common_name: "x".to_string(),
+        organization: "x.org".to_string(),
+        organizational_unit: "x.org ops".to_string(),
+        country: "EN".to_string(),
+        state: "London".to_string(),
+        alt_host_names: vec!["urn:testapplication".to_string(), "host2".to_string()],
+        certificate_duration_days: 60,
+    };
+
+    let (tmp_dir, cert_store) = make_certificate_store();
+    let (request, _) = cert_store.create_and_store_certificate_request(&args, false).unwrap();
+
+    // The request is written PEM encoded alongside the private key
+    let request_path = cert_store.own_certificate_request_path();
+    let mut pem = Vec::new();
+    File::open(&request_path).unwrap().read_to_end(&mut pem).unwrap();
+    assert_eq!(pem, request.to_pem().unwrap());
+    assert!(String::from_utf8(pem).unwrap().starts_with("-----BEGIN CERTIFICATE REQUEST-----"));
+    assert!(cert_store.own_private_key_path().exists());
+    assert!(CertificateStore::read_pkey(&cert_store.own_private_key_path()).is_ok());
+
+    // Create again with no overwrite
+    assert!(cert_store.create_and_store_certificate_request(&args, false).is_err());
+
+    // Create again with overwrite
+    assert!(cert_store.create_and_store_certificate_request(&args, true).is_ok());
+    drop(tmp_dir)
+}
+
 #[test]
 fn create_rejected_cert_in_pki() {
     let (tmp_dir, cert_store) = make_certificate_store();
@@ -174,6 +209,134 @@ fn test_and_reject_thumbprint_mismatch() {
     drop(tmp_dir);
 }
 
+fn trust_cert(cert_store: &CertificateStore, cert: &X509) {
+    let mut cert_trusted_path = cert_store.trusted_certs_dir();
+    cert_trusted_path.push(CertificateStore::cert_file_name(cert));
+    let mut file = File::create(cert_trusted_path).unwrap();
+    assert!(file.write(&cert.to_der().unwrap()).is_ok());
+}
+
+#[test]
+fn validate_certificate_reports_failed_check() {
+    let (tmp_dir, cert_store) = make_certificate_store();
+
+    // Unknown cert is untrusted and ends up in the rejected dir
+    let (cert, _) = make_test_cert_1024();
+    let result = cert_store.validate_certificate(&cert, SecurityPolicy::Basic128Rsa15, None, None);
+    assert_eq!(result.failed_check, Some(CertificateCheck::Untrusted));
+    assert_eq!(result.status_code, StatusCode::BadCertificateUntrusted);
+
+    // Validating it again finds it in the rejected dir
+    let result = cert_store.validate_certificate(&cert, SecurityPolicy::Basic128Rsa15, None, None);
+    assert_eq!(result.failed_check, Some(CertificateCheck::Rejected));
+    assert_eq!(result.status_code, StatusCode::BadSecurityChecksFailed);
+
+    // A trusted cert passes, or fails on key length, hostname and application uri
+    let (cert, _) = make_test_cert_1024();
+    trust_cert(&cert_store, &cert);
+
+    let result = cert_store.validate_certificate(&cert, SecurityPolicy::Basic128Rsa15, Some(APPLICATION_HOSTNAME), Some(APPLICATION_URI));
+    assert!(result.is_good());
+    assert_eq!(result.status_code, StatusCode::Good);
+
+    let result = cert_store.validate_certificate(&cert, SecurityPolicy::Basic256Sha256, None, None);
+    assert_eq!(result.failed_check, Some(CertificateCheck::KeyLength));
+    assert_eq!(result.status_code, StatusCode::BadSecurityChecksFailed);
+
+    let result = cert_store.validate_certificate(&cert, SecurityPolicy::Basic128Rsa15, Some("notthehost"), None);
+    assert_eq!(result.failed_check, Some(CertificateCheck::Hostname));
+    assert_eq!(result.status_code, StatusCode::BadCertificateHostNameInvalid);
+
+    let result = cert_store.validate_certificate(&cert, SecurityPolicy::Basic128Rsa15, None, Some("urn:notthisapp"));
+    assert_eq!(result.failed_check, Some(CertificateCheck::ApplicationUri));
+    assert_eq!(result.status_code, StatusCode::BadCertificateUriInvalid);
+
+    // A cert whose trusted copy on disk does not match
+    let (cert2, _) = make_test_cert_1024();
+    let mut cert_trusted_path = cert_store.trusted_certs_dir();
+    cert_trusted_path.push(CertificateStore::cert_file_name(&cert2));
+    {
+        let mut file = File::create(cert_trusted_path).unwrap();
+        assert!(file.write(&cert.to_der().unwrap()).is_ok());
+    }
+    let result = cert_store.validate_certificate(&cert2, SecurityPolicy::Basic128Rsa15, None, None);
+    assert_eq!(result.failed_check, Some(CertificateCheck::Store));
+    assert_eq!(result.status_code, StatusCode::BadUnexpectedError);
+
+    // A trusted cert that has expired
+    let cert = make_expired_cert();
+    trust_cert(&cert_store, &cert);
+    let result = cert_store.validate_certificate(&cert, SecurityPolicy::Basic128Rsa15, None, None);
+    assert_eq!(result.failed_check, Some(CertificateCheck::Time));
+    assert_eq!(result.status_code, StatusCode::BadCertificateTimeInvalid);
+
+    drop(tmp_dir);
+}
+
+/// Makes a self-signed cert that was valid for a day, a week ago
+fn make_expired_cert() -> X509 {
+    use openssl::{asn1::Asn1Time, hash::MessageDigest, pkey::PKey, rsa::Rsa, x509};
+
+    let pkey = PKey::from_rsa(Rsa::generate(1024).unwrap()).unwrap();
+    let mut name = x509::X509NameBuilder::new().unwrap();
+    name.append_entry_by_text("CN", "expired").unwrap();
+    let name = name.build();
+
+    let now = chrono::Utc::now().timestamp();
+    let mut builder = x509::X509Builder::new().unwrap();
+    builder.set_version(2).unwrap();
+    builder.set_subject_name(&name).unwrap();
+    builder.set_issuer_name(&name).unwrap();
+    builder.set_not_before(&Asn1Time::from_unix(now - 7 * 86400).unwrap()).unwrap();
+    builder.set_not_after(&Asn1Time::from_unix(now - 6 * 86400).unwrap()).unwrap();
+    builder.set_pubkey(&pkey).unwrap();
+    builder.sign(&pkey, MessageDigest::sha256()).unwrap();
+    X509::from(builder.build())
+}
+
+#[test]
+fn validate_certificate_with_options() {
+    let (tmp_dir, cert_store) = make_certificate_store();
+
+    // A trusted cert with a short key that is presented by the wrong host
+    let (cert, _) = make_test_cert_1024();
+    trust_cert(&cert_store, &cert);
+
+    let strict = CertificateValidationOptions {
+        min_key_length: 2048,
+        ..Default::default()
+    };
+    let lenient = CertificateValidationOptions {
+        check_time: false,
+        check_hostname: false,
+        check_application_uri: false,
+        min_key_length: 0,
+        trust_unknown_certs: true,
+    };
+
+    // The store's own options are the defaults, which check the hostname
+    assert_eq!(cert_store.validation_options, CertificateValidationOptions::default());
+    let result = cert_store.validate_certificate(&cert, SecurityPolicy::Basic128Rsa15, Some("notthehost"), Some(APPLICATION_URI));
+    assert_eq!(result.failed_check, Some(CertificateCheck::Hostname));
+
+    let result = cert_store.validate_certificate_with_options(&cert, SecurityPolicy::Basic128Rsa15, Some("notthehost"), Some("urn:notthisapp"), &lenient);
+    assert!(result.is_good());
+
+    let result = cert_store.validate_certificate_with_options(&cert, SecurityPolicy::Basic128Rsa15, Some(APPLICATION_HOSTNAME), Some(APPLICATION_URI), &strict);
+    assert_eq!(result.failed_check, Some(CertificateCheck::KeyLength));
+    assert_eq!(result.status_code, StatusCode::BadSecurityChecksFailed);
+
+    // An unknown cert is trusted by the lenient options and still rejected by the strict ones
+    let (cert, _) = make_test_cert_2048();
+    let result = cert_store.validate_certificate_with_options(&cert, SecurityPolicy::Basic256Sha256, None, None, &lenient);
+    assert!(result.is_good());
+    let (cert, _) = make_test_cert_2048();
+    let result = cert_store.validate_certificate_with_options(&cert, SecurityPolicy::Basic256Sha256, None, None, &strict);
+    assert_eq!(result.failed_check, Some(CertificateCheck::Untrusted));
+
+    drop(tmp_dir);
+}
+
 fn test_asymmetric_encrypt_and_decrypt(cert: &X509, key: &PrivateKey, security_policy: SecurityPolicy, plaintext_size: usize) {
     let plaintext = (0..plaintext_size).map(|i| (i % 256) as u8).collect::<Vec<u8>>();
 
@@ -255,6 +418,44 @@ fn calculate_cipher_text_size2() {
     }
 }
 
+#[test]
+fn private_decrypt_wrong_length() {
+    let (_, private_key) = make_test_cert_1024();
+    // Cipher text must be a multiple of the key size
+    let src = vec![0u8; private_key.cipher_text_block_size() + 1];
+    let mut dst = vec![0u8; src.len()];
+    let err = private_key.private_decrypt(&src, &mut dst, RsaPadding::OAEP).unwrap_err();
+    assert_eq!(err, CryptoError::DataLength(256, 129));
+    assert_eq!(StatusCode::from(err), StatusCode::BadSecurityChecksFailed);
+}
+
+#[test]
+fn asymmetric_decrypt_truncated() {
+    let (cert, key) = make_test_cert_2048();
+    let plaintext = vec![1u8; 100];
+    let mut ciphertext = vec![0u8; 4096];
+    let mut plaintext2 = vec![0u8; 4096];
+    for security_policy in &[SecurityPolicy::Basic128Rsa15, SecurityPolicy::Basic256, SecurityPolicy::Basic256Sha256] {
+        let encrypted_size = security_policy.asymmetric_encrypt(&cert.public_key().unwrap(), &plaintext, &mut ciphertext).unwrap();
+        // A truncated message from the peer fails the security checks, it is not a policy problem
+        let result = security_policy.asymmetric_decrypt(&key, &ciphertext[..encrypted_size - 1], &mut plaintext2);
+        assert_eq!(result.unwrap_err(), StatusCode::BadSecurityChecksFailed);
+    }
+}
+
+#[test]
+fn private_decrypt_bad_padding() {
+    let (_, private_key) = make_test_cert_1024();
+    // A block of zeroes can never have valid padding
+    let src = vec![0u8; private_key.cipher_text_block_size()];
+    let mut dst = vec![0u8; src.len()];
+    for padding in &[RsaPadding::PKCS1, RsaPadding::OAEP] {
+        let err = private_key.private_decrypt(&src, &mut dst, *padding).unwrap_err();
+        assert_eq!(err, CryptoError::Padding);
+        assert_eq!(StatusCode::from(err), StatusCode::BadSecurityChecksFailed);
+    }
+}
+
 #[test]
 fn sign_verify_sha1() {
     let (cert, private_key) = make_test_cert_2048();
@@ -449,13 +650,43 @@ fn derive_keys_from_nonce_basic128rsa15() {
     let remote_keys = security_policy.make_secure_channel_keys(&local_nonce, &remote_nonce);
 
     // Compare the keys we received against the expected
-    assert_eq!(local_keys.0, local_signing_key);
+    assert_eq!(&local_keys.0[..], &local_signing_key[..]);
     assert_eq!(local_keys.1.value().to_vec(), local_encrypting_key);
-    assert_eq!(local_keys.2, local_iv);
+    assert_eq!(&local_keys.2[..], &local_iv[..]);
 
-    assert_eq!(remote_keys.0, remote_signing_key);
+    assert_eq!(&remote_keys.0[..], &remote_signing_key[..]);
     assert_eq!(remote_keys.1.value().to_vec(), remote_encrypting_key);
-    assert_eq!(remote_keys.2, remote_iv);
+    assert_eq!(&remote_keys.2[..], &remote_iv[..]);
+}
+
+#[test]
+fn derive_keys_rejects_short_iv() {
+    let mut nonce1 = [0u8; 32];
+    let mut nonce2 = [0u8; 32];
+    random::bytes(&mut nonce1);
+    random::bytes(&mut nonce2);
+    let (nonce1, nonce2) = (&nonce1[..], &nonce2[..]);
+
+    // A deliberately short initialization vector is rejected
+    let security_policy = SecurityPolicy::Basic256Sha256;
+    let err = security_policy.derive_secure_channel_keys(nonce1, nonce2, 32, 32, 8).unwrap_err();
+    assert_eq!(err, CryptoError::IvLength(16, 8));
+    assert_eq!(StatusCode::from(err), StatusCode::BadConfigurationError);
+
+    // As is an encrypting key that doesn't fit the cipher, which is told apart from a bad IV
+    let err = security_policy.derive_secure_channel_keys(nonce1, nonce2, 32, 16, 16).unwrap_err();
+    assert_eq!(err, CryptoError::KeyLength(32, 16));
+    assert_eq!(StatusCode::from(err), StatusCode::BadSecurityPolicyRejected);
+
+    // The policy's own lengths are accepted
+    let (signing_key, encryption_key, iv) = security_policy.derive_secure_channel_keys(nonce1, nonce2, 32, 32, 16).unwrap();
+    assert_eq!(signing_key.len(), 32);
+    assert_eq!(encryption_key.value().len(), 32);
+    assert_eq!(iv.len(), 16);
+
+    let security_policy = SecurityPolicy::Basic128Rsa15;
+    let err = security_policy.derive_secure_channel_keys(nonce1, nonce2, 16, 16, 12).unwrap_err();
+    assert_eq!(err, CryptoError::IvLength(16, 12));
 }
 
 #[test]
@@ -506,4 +737,148 @@ fn encrypt_decrypt_password() {
     let password2 = legacy_password_decrypt(&secret, nonce.as_ref(), &pkey, padding).unwrap();
 
     assert_eq!(password, password2);
-}
\ No newline at end of file
+}
+
+#[test]
+fn verify_handshake_signature_good() {
+    let (our_cert, _) = make_test_cert_2048();
+    let (peer_cert, peer_pkey) = make_test_cert_2048();
+    let our_nonce = random::byte_string(32);
+
+    for security_policy in &[SecurityPolicy::Basic128Rsa15, SecurityPolicy::Basic256, SecurityPolicy::Basic256Sha256, SecurityPolicy::Aes128Sha256RsaOaep, SecurityPolicy::Aes256Sha256RsaPss] {
+        // Peer signs our cert and nonce
+        let signature = create_signature_data(&peer_pkey, *security_policy, &our_cert.as_byte_string(), &our_nonce).unwrap();
+        assert!(verify_handshake_signature(*security_policy, &peer_cert, &our_cert, our_nonce.as_ref(), &signature).is_ok());
+    }
+}
+
+#[test]
+fn verify_handshake_signature_replayed_nonce() {
+    let (our_cert, _) = make_test_cert_2048();
+    let (peer_cert, peer_pkey) = make_test_cert_2048();
+
+    // Signature is from an earlier handshake using a different nonce
+    let old_nonce = random::byte_string(32);
+    let our_nonce = random::byte_string(32);
+    let signature = create_signature_data(&peer_pkey, SecurityPolicy::Basic256Sha256, &our_cert.as_byte_string(), &old_nonce).unwrap();
+    assert_eq!(verify_handshake_signature(SecurityPolicy::Basic256Sha256, &peer_cert, &our_cert, our_nonce.as_ref(), &signature).unwrap_err(), StatusCode::BadSecurityChecksFailed);
+
+    // Signature made by a key other than the peer's
+    let (_, other_pkey) = make_test_cert_2048();
+    let signature = create_signature_data(&other_pkey, SecurityPolicy::Basic256Sha256, &our_cert.as_byte_string(), &our_nonce).unwrap();
+    assert_eq!(verify_handshake_signature(SecurityPolicy::Basic256Sha256, &peer_cert, &our_cert, our_nonce.as_ref(), &signature).unwrap_err(), StatusCode::BadSecurityChecksFailed);
+}
+
+#[test]
+fn verify_handshake_signature_wrong_algorithm() {
+    let (our_cert, _) = make_test_cert_2048();
+    let (peer_cert, peer_pkey) = make_test_cert_2048();
+    let our_nonce = random::byte_string(32);
+
+    // Peer signs with a weaker algorithm than the negotiated policy's
+    let signature = create_signature_data(&peer_pkey, SecurityPolicy::Basic128Rsa15, &our_cert.as_byte_string(), &our_nonce).unwrap();
+    assert!(verify_handshake_signature(SecurityPolicy::Basic128Rsa15, &peer_cert, &our_cert, our_nonce.as_ref(), &signature).is_ok());
+    assert_eq!(verify_handshake_signature(SecurityPolicy::Basic256Sha256, &peer_cert, &our_cert, our_nonce.as_ref(), &signature).unwrap_err(), StatusCode::BadSecurityChecksFailed);
+
+    // Peer claims the policy's algorithm but signed with the weaker one
+    let mut signature = signature;
+    signature.algorithm = UAString::from(SecurityPolicy::Basic256Sha256.asymmetric_signature_algorithm());
+    assert_eq!(verify_handshake_signature(SecurityPolicy::Basic256Sha256, &peer_cert, &our_cert, our_nonce.as_ref(), &signature).unwrap_err(), StatusCode::BadSecurityChecksFailed);
+
+    // Nothing is verified without a signing policy
+    assert_eq!(verify_handshake_signature(SecurityPolicy::None, &peer_cert, &our_cert, our_nonce.as_ref(), &signature).unwrap_err(), StatusCode::BadSecurityChecksFailed);
+}
+
+#[test]
+fn certificate_info() {
+    let (cert, _) = make_test_cert_1024();
+    let info = CertificateInfo::from_x509(&cert);
+    assert_eq!(info.common_name.as_ref().unwrap(), "x");
+    assert_eq!(info.organization.as_ref().unwrap(), "x.org");
+    assert_eq!(info.organizational_unit.as_ref().unwrap(), "x.org ops");
+    assert_eq!(info.country.as_ref().unwrap(), "EN");
+    assert_eq!(info.state.as_ref().unwrap(), "London");
+    assert_eq!(info.subject_alt_names, vec![APPLICATION_URI.to_string(), "foo".to_string(), "foo2".to_string(), APPLICATION_HOSTNAME.to_string(), "foo3".to_string()]);
+}
+
+/// Round trips every primitive of a crypto backend apart from RSA. Known answers come from RFC 3174, RFC 4634,
+/// RFC 2202 and RFC 4231.
+fn backend_round_trip<B: CryptoBackend>() {
+    use crate::tests::crypto::serialize::hex::FromHex;
+
+    // Random
+    let mut r1 = [0u8; 32];
+    let mut r2 = [0u8; 32];
+    B::random_bytes(&mut r1);
+    B::random_bytes(&mut r2);
+    assert_ne!(r1, r2);
+
+    // Hash
+    assert_eq!(B::hash(HashAlgorithm::Sha1, b"abc").unwrap(), "a9993e364706816aba3e25717850c26c9cd0d89d".from_hex().unwrap());
+    assert_eq!(B::hash(HashAlgorithm::Sha256, b"abc").unwrap(), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad".from_hex().unwrap());
+
+    // HMAC
+    let data = b"what do ya want for nothing?";
+    assert_eq!(B::hmac(HashAlgorithm::Sha1, b"Jefe", data).unwrap(), "effcdf6ae5eb2fa2d27416d5f184df9c259a7c79".from_hex().unwrap());
+    assert_eq!(B::hmac(HashAlgorithm::Sha256, b"Jefe", data).unwrap(), "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843".from_hex().unwrap());
+
+    // AES-128 and AES-256
+    let plaintext = (0..64u8).collect::<Vec<u8>>();
+    let mut iv = [0u8; 16];
+    B::random_bytes(&mut iv);
+    for key_len in &[16usize, 32usize] {
+        let mut key = vec![0u8; *key_len];
+        B::random_bytes(&mut key);
+
+        let mut ciphertext = vec![0u8; plaintext.len() + 16];
+        let encrypted_len = B::aes_cbc_encrypt(&key, &iv, &plaintext, &mut ciphertext).unwrap();
+        assert_eq!(encrypted_len, plaintext.len());
+        assert_ne!(&ciphertext[..encrypted_len], &plaintext[..]);
+
+        let mut decrypted = vec![0u8; encrypted_len + 16];
+        let decrypted_len = B::aes_cbc_decrypt(&key, &iv, &ciphertext[..encrypted_len], &mut decrypted).unwrap();
+        assert_eq!(&decrypted[..decrypted_len], &plaintext[..]);
+    }
+    let mut dst = vec![0u8; plaintext.len() + 16];
+    assert_eq!(B::aes_cbc_encrypt(&[0u8; 5], &iv, &plaintext, &mut dst).unwrap_err(), CryptoError::Unsupported);
+    assert_eq!(B::aes_cbc_encrypt(&[0u8; 24], &iv, &plaintext, &mut dst).unwrap_err(), CryptoError::Unsupported);
+}
+
+/// Round trips the RSA operations of a backend
+fn rsa_backend_round_trip<B: RsaBackend>() {
+    let private_key = B::rsa_generate_key(2048).unwrap();
+    let public_key = B::rsa_public_key(&private_key).unwrap();
+    let plaintext = b"The quick brown fox jumps over the lazy dog";
+    for padding in &[RsaPadding::PKCS1, RsaPadding::OAEP] {
+        let mut ciphertext = vec![0u8; 256];
+        let encrypted_len = B::rsa_public_encrypt(&public_key, plaintext, &mut ciphertext, *padding).unwrap();
+        assert_eq!(encrypted_len, 256);
+
+        let mut decrypted = vec![0u8; 256];
+        let decrypted_len = B::rsa_private_decrypt(&private_key, &ciphertext, &mut decrypted, *padding).unwrap();
+        assert_eq!(&decrypted[..decrypted_len], &plaintext[..]);
+    }
+}
+
+#[test]
+fn default_backend_round_trip() {
+    backend_round_trip::<DefaultBackend>();
+}
+
+#[test]
+fn key_backend_round_trip() {
+    backend_round_trip::<KeyBackend>();
+    rsa_backend_round_trip::<KeyBackend>();
+}
+
+#[test]
+fn security_policy_self_test() {
+    // Every supported policy that secures anything passes
+    available_security_policies().iter().filter(|p| **p != SecurityPolicy::None).for_each(|security_policy| {
+        assert!(security_policy.self_test().is_ok(), "Self test failed for {}", security_policy);
+    });
+
+    // None has nothing to sign or encrypt with
+    assert_eq!(SecurityPolicy::None.self_test().unwrap_err(), CryptoError::Unsupported);
+    assert_eq!(SecurityPolicy::Unknown.self_test().unwrap_err(), CryptoError::Unsupported);
+}
@@ -37,4 +37,5 @@ fn make_test_cert_2048() -> (X509, PrivateKey) { make_test_cert(2048) }
 
 mod authentication;
 mod crypto;
+mod secret;
 mod security_policy;
\ No newline at end of file
@@ -0,0 +1,9 @@
+use crate::secret::SecretBytes;
+
+#[test]
+fn secret_bytes_debug_is_redacted() {
+    let secret = SecretBytes::from(&[1u8, 2, 3, 4][..]);
+    let debug = format!("{:?}", secret);
+    assert_eq!(debug, "SecretBytes([REDACTED; 4])");
+    assert_eq!(&secret[..], &[1u8, 2, 3, 4]);
+}
@@ -1,6 +1,9 @@
 use std::str::FromStr;
 
-use crate::SecurityPolicy;
+use opcua_types::MessageSecurityMode;
+
+use crate::{available_security_policies, is_algorithm_supported, SecurityPolicy};
+use crate::tests::make_test_cert_2048;
 
 #[test]
 fn is_deprecated() {
@@ -74,4 +77,89 @@ fn is_valid_keylength() {
     assert!(SecurityPolicy::Aes256Sha256RsaPss.is_valid_keylength(4096));
     assert!(!SecurityPolicy::Aes256Sha256RsaPss.is_valid_keylength(1024));
     assert!(!SecurityPolicy::Aes256Sha256RsaPss.is_valid_keylength(8192));
-}
\ No newline at end of file
+}
+
+#[test]
+fn security_level() {
+    let level = |security_policy: SecurityPolicy, security_mode| security_policy.security_level(security_mode);
+
+    // No security is the lowest
+    assert_eq!(level(SecurityPolicy::None, MessageSecurityMode::None), 0);
+    assert!(level(SecurityPolicy::None, MessageSecurityMode::None) < level(SecurityPolicy::Basic128Rsa15, MessageSecurityMode::Sign));
+
+    // Sign and encrypt beats sign for the same policy
+    assert!(level(SecurityPolicy::Basic256Sha256, MessageSecurityMode::Sign) < level(SecurityPolicy::Basic256Sha256, MessageSecurityMode::SignAndEncrypt));
+
+    // Sign and encrypt with any policy beats sign with the strongest policy
+    assert!(level(SecurityPolicy::Aes256Sha256RsaPss, MessageSecurityMode::Sign) < level(SecurityPolicy::Basic128Rsa15, MessageSecurityMode::SignAndEncrypt));
+
+    // Stronger policies beat weaker ones in the same mode
+    assert!(level(SecurityPolicy::Basic128Rsa15, MessageSecurityMode::SignAndEncrypt) < level(SecurityPolicy::Basic256, MessageSecurityMode::SignAndEncrypt));
+    assert!(level(SecurityPolicy::Basic256, MessageSecurityMode::SignAndEncrypt) < level(SecurityPolicy::Basic256Sha256, MessageSecurityMode::SignAndEncrypt));
+    assert!(level(SecurityPolicy::Basic256Sha256, MessageSecurityMode::SignAndEncrypt) < level(SecurityPolicy::Aes256Sha256RsaPss, MessageSecurityMode::SignAndEncrypt));
+
+    // SHA-256 policies beat the deprecated SHA-1 policies
+    assert!(level(SecurityPolicy::Basic256, MessageSecurityMode::SignAndEncrypt) < level(SecurityPolicy::Aes128Sha256RsaOaep, MessageSecurityMode::SignAndEncrypt));
+    assert!(level(SecurityPolicy::Basic256, MessageSecurityMode::Sign) < level(SecurityPolicy::Aes128Sha256RsaOaep, MessageSecurityMode::Sign));
+    assert!(level(SecurityPolicy::Aes128Sha256RsaOaep, MessageSecurityMode::Sign) < level(SecurityPolicy::Basic256Sha256, MessageSecurityMode::Sign));
+
+    // Mismatched or invalid combinations have no security
+    assert_eq!(level(SecurityPolicy::Basic256Sha256, MessageSecurityMode::None), 0);
+    assert_eq!(level(SecurityPolicy::Basic256Sha256, MessageSecurityMode::Invalid), 0);
+    assert_eq!(level(SecurityPolicy::Unknown, MessageSecurityMode::SignAndEncrypt), 0);
+}
+
+#[test]
+fn available_security_policies_are_supported() {
+    let security_policies = available_security_policies();
+    assert_eq!(security_policies, vec![
+        SecurityPolicy::None,
+        SecurityPolicy::Basic128Rsa15,
+        SecurityPolicy::Basic256,
+        SecurityPolicy::Basic256Sha256,
+        SecurityPolicy::Aes128Sha256RsaOaep,
+    ]);
+    assert!(security_policies.iter().all(|p| p.is_supported()));
+    assert!(!security_policies.contains(&SecurityPolicy::Unknown));
+}
+
+#[test]
+fn algorithm_supported() {
+    // Every algorithm of a supported policy is implemented by the default backend
+    assert!(is_algorithm_supported("http://www.w3.org/2001/04/xmlenc#rsa-oaep"));
+    assert!(is_algorithm_supported("http://www.w3.org/2000/09/xmldsig#hmac-sha256"));
+    assert!(is_algorithm_supported(SecurityPolicy::Basic256Sha256.asymmetric_signature_algorithm()));
+    assert!(is_algorithm_supported(SecurityPolicy::Basic256Sha256.symmetric_encryption_algorithm()));
+    assert!(is_algorithm_supported(SecurityPolicy::Basic128Rsa15.key_derivation_algorithm()));
+    assert!(is_algorithm_supported("http://www.w3.org/2001/04/xmlenc#aes128-cbc"));
+    assert!(is_algorithm_supported("http://docs.oasis-open.org/ws-sx/ws-secureconversation/200512/dk/p_sha256"));
+
+    // RSA-PSS is not implemented so its policy is not supported
+    assert!(!is_algorithm_supported("http://opcfoundation.org/UA/security/rsa-pss-sha2-256"));
+    assert!(!SecurityPolicy::Aes256Sha256RsaPss.is_supported());
+    assert!(!is_algorithm_supported("http://example.org/unknown-algorithm"));
+    assert!(!is_algorithm_supported(""));
+}
+
+#[test]
+fn asymmetric_block_sizes() {
+    let (cert, private_key) = make_test_cert_2048();
+    let public_key = cert.public_key().unwrap();
+
+    // PKCS#1 v1.5 padding has 11 bytes of overhead
+    let security_policy = SecurityPolicy::Basic128Rsa15;
+    assert_eq!(security_policy.asymmetric_plaintext_block_size(&public_key), 256 - 11);
+    assert_eq!(security_policy.asymmetric_plaintext_block_size(&private_key), 256 - 11);
+    assert_eq!(security_policy.asymmetric_ciphertext_block_size(&public_key), 256);
+
+    // OAEP-SHA1 padding has 42 bytes of overhead
+    let security_policy = SecurityPolicy::Basic256Sha256;
+    assert_eq!(security_policy.asymmetric_plaintext_block_size(&public_key), 256 - 42);
+    assert_eq!(security_policy.asymmetric_plaintext_block_size(&private_key), 256 - 42);
+    assert_eq!(security_policy.asymmetric_ciphertext_block_size(&public_key), 256);
+
+    // A plain text block encrypts to exactly one cipher text block
+    let src = vec![0u8; security_policy.asymmetric_plaintext_block_size(&public_key)];
+    let mut dst = vec![0u8; security_policy.asymmetric_ciphertext_block_size(&public_key)];
+    assert_eq!(security_policy.asymmetric_encrypt(&public_key, &src, &mut dst).unwrap(), 256);
+}
@@ -17,7 +17,7 @@ use opcua_types::{
     UAString,
 };
 
-use super::{KeySize, PrivateKey, RsaPadding, SecurityPolicy, X509};
+use super::{KeyBackend, KeySize, PrivateKey, RsaBackend, RsaPadding, SecurityPolicy, X509};
 
 /// Create a filled in UserNameIdentityToken by using the supplied channel security policy, user token policy, nonce, cert, user name and password.
 pub fn make_user_name_identity_token(channel_security_policy: SecurityPolicy, user_token_policy: &UserTokenPolicy, nonce: &[u8], cert: &Option<X509>, user: &str, pass: &str) -> Result<UserNameIdentityToken, StatusCode> {
@@ -119,8 +119,11 @@ pub fn legacy_password_encrypt(password: &str, server_nonce: &[u8], server_cert:
 
     let cipher_size = public_key.calculate_cipher_text_size(plaintext_size, padding);
     let mut dst = vec![0u8; cipher_size];
-    let actual_size = public_key.public_encrypt(&src.into_inner(), &mut dst, padding)
-        .map_err(|_| StatusCode::BadEncodingError)?;
+    let actual_size = KeyBackend::rsa_public_encrypt(&public_key, &src.into_inner(), &mut dst, padding)
+        .map_err(|err| {
+            error!("Password encryption failed - {}", err);
+            StatusCode::BadEncodingError
+        })?;
 
     assert_eq!(actual_size, cipher_size);
 
@@ -136,8 +139,11 @@ pub fn legacy_password_decrypt(secret: &ByteString, server_nonce: &[u8], server_
         // Decrypt the message
         let src = secret.value.as_ref().unwrap();
         let mut dst = vec![0u8; src.len()];
-        let actual_size = server_key.private_decrypt(&src, &mut dst, padding)
-            .map_err(|_| StatusCode::BadEncodingError)?;
+        let actual_size = KeyBackend::rsa_private_decrypt(server_key, &src, &mut dst, padding)
+            .map_err(|err| {
+                error!("Password decryption failed - {}", err);
+                StatusCode::BadEncodingError
+            })?;
 
         let mut dst = Cursor::new(dst);
         let plaintext_size = read_u32(&mut dst)? as usize;
@@ -18,12 +18,15 @@ use openssl::{
     nid::Nid,
     pkey,
     rsa::*,
+    stack::Stack,
     x509::{self, extension::*},
 };
 
 use opcua_types::{ByteString, service_types::ApplicationDescription, status_code::StatusCode};
 
 use crate::{
+    backend::{CryptoBackend, DefaultBackend, HashAlgorithm},
+    error::CryptoError,
     hostname,
     pkey::{PrivateKey, PublicKey},
     thumbprint::Thumbprint,
@@ -53,6 +56,38 @@ pub struct X509Data {
     pub certificate_duration_days: u32,
 }
 
+/// The subject fields read back from an X509 cert. This mirrors the fields in `X509Data` that are
+/// used to create a cert. A subject field is `None` if the cert doesn't have it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CertificateInfo {
+    pub common_name: Option<String>,
+    pub organization: Option<String>,
+    pub organizational_unit: Option<String>,
+    pub country: Option<String>,
+    pub state: Option<String>,
+    /// The subject alternative names as text, i.e. the application uri followed by IP addresses or
+    /// DNS names.
+    pub subject_alt_names: Vec<String>,
+}
+
+impl CertificateInfo {
+    /// Reads the subject fields from the supplied cert
+    pub fn from_x509(cert: &X509) -> CertificateInfo {
+        CertificateInfo {
+            common_name: cert.get_subject_entry(Nid::COMMONNAME).ok(),
+            organization: cert.get_subject_entry(Nid::ORGANIZATIONNAME).ok(),
+            organizational_unit: cert.get_subject_entry(Nid::ORGANIZATIONALUNITNAME).ok(),
+            country: cert.get_subject_entry(Nid::COUNTRYNAME).ok(),
+            state: cert.get_subject_entry(Nid::STATEORPROVINCENAME).ok(),
+            subject_alt_names: if let Some(ref alt_names) = cert.value.subject_alt_names() {
+                alt_names.iter().map(|n| X509::general_name_to_string(n)).collect()
+            } else {
+                Vec::new()
+            },
+        }
+    }
+}
+
 impl From<(ApplicationDescription, Option<Vec<String>>)> for X509Data {
     fn from(v: (ApplicationDescription, Option<Vec<String>>)) -> Self {
         let (application_description, addresses) = v;
@@ -203,20 +238,7 @@ impl X509 {
         let mut builder = x509::X509Builder::new().unwrap();
         // value 2 == version 3 (go figure)
         let _ = builder.set_version(2);
-        let issuer_name = {
-            let mut name = x509::X509NameBuilder::new().unwrap();
-            // Common name
-            name.append_entry_by_text("CN", &x509_data.common_name).unwrap();
-            // Organization
-            name.append_entry_by_text("O", &x509_data.organization).unwrap();
-            // Organizational Unit
-            name.append_entry_by_text("OU", &x509_data.organizational_unit).unwrap();
-            // Country
-            name.append_entry_by_text("C", &x509_data.country).unwrap();
-            // State
-            name.append_entry_by_text("ST", &x509_data.state).unwrap();
-            name.build()
-        };
+        let issuer_name = Self::build_name(x509_data);
         // Issuer and subject shall be the same for self-signed cert
         let _ = builder.set_subject_name(&issuer_name);
         let _ = builder.set_issuer_name(&issuer_name);
@@ -251,28 +273,8 @@ impl X509 {
 
         // Subject alt names - The first is assumed to be the application uri. The remainder
         // are either IP or DNS entries.
-        if !x509_data.alt_host_names.is_empty() {
-            let subject_alternative_name = {
-                let mut subject_alternative_name = SubjectAlternativeName::new();
-                x509_data.alt_host_names.iter().enumerate().for_each(|(i, alt_host_name)| {
-                    if !alt_host_name.is_empty() {
-                        if i == 0 {
-                            // The first entry is the application uri
-                            subject_alternative_name.uri(alt_host_name);
-                        } else if let Ok(_) = alt_host_name.parse::<Ipv4Addr>() {
-                            // Treat this as an IPv4 address
-                            subject_alternative_name.ip(alt_host_name);
-                        } else if let Ok(_) = alt_host_name.parse::<Ipv6Addr>() {
-                            // Treat this as an IPv6 address
-                            subject_alternative_name.ip(alt_host_name);
-                        } else {
-                            // Treat this as a DNS entries
-                            subject_alternative_name.dns(alt_host_name);
-                        }
-                    }
-                });
-                subject_alternative_name.build(&builder.x509v3_context(None, None)).unwrap()
-            };
+        if let Some(mut subject_alternative_name) = Self::build_subject_alternative_name(x509_data) {
+            let subject_alternative_name = subject_alternative_name.build(&builder.x509v3_context(None, None)).unwrap();
             builder.append_extension(subject_alternative_name).unwrap();
         }
 
@@ -282,6 +284,84 @@ impl X509 {
         Ok(X509::from(builder.build()))
     }
 
+    /// Creates a PKCS#10 certificate signing request and public/private key from the supplied
+    /// creation args. The request has the same subject and subject alt names as a self-signed
+    /// cert created from the args. It is sent to a certificate authority which issues the
+    /// application instance cert for the private key.
+    pub fn create_certificate_request(x509_data: &X509Data) -> Result<(CertificateRequest, PrivateKey), String> {
+        let rsa = Rsa::generate(x509_data.key_size)
+            .map_err(|err| {
+                format!("Cannot create key pair check error {} and key size {}", err.to_string(), x509_data.key_size)
+            })?;
+        let pkey = pkey::PKey::from_rsa(rsa)
+            .map_err(|err| {
+                format!("Cannot create key pair check error {}", err.to_string())
+            })?;
+        let pkey = PrivateKey::wrap_private_key(pkey);
+        let request = CertificateRequest::from_pkey(&pkey, x509_data)?;
+        Ok((request, pkey))
+    }
+
+    /// Builds the subject name from the creation args. A self-signed cert is also its own issuer.
+    fn build_name(x509_data: &X509Data) -> x509::X509Name {
+        let mut name = x509::X509NameBuilder::new().unwrap();
+        // Common name
+        name.append_entry_by_text("CN", &x509_data.common_name).unwrap();
+        // Organization
+        name.append_entry_by_text("O", &x509_data.organization).unwrap();
+        // Organizational Unit
+        name.append_entry_by_text("OU", &x509_data.organizational_unit).unwrap();
+        // Country
+        name.append_entry_by_text("C", &x509_data.country).unwrap();
+        // State
+        name.append_entry_by_text("ST", &x509_data.state).unwrap();
+        name.build()
+    }
+
+    /// Builds the subject alt names from the creation args, or None if there are none. The first
+    /// is the application uri and the remainder are IP or DNS entries.
+    fn build_subject_alternative_name(x509_data: &X509Data) -> Option<SubjectAlternativeName> {
+        if x509_data.alt_host_names.is_empty() {
+            None
+        } else {
+            let mut subject_alternative_name = SubjectAlternativeName::new();
+            x509_data.alt_host_names.iter().enumerate().for_each(|(i, alt_host_name)| {
+                if !alt_host_name.is_empty() {
+                    if i == 0 {
+                        // The first entry is the application uri
+                        subject_alternative_name.uri(alt_host_name);
+                    } else if let Ok(_) = alt_host_name.parse::<Ipv4Addr>() {
+                        // Treat this as an IPv4 address
+                        subject_alternative_name.ip(alt_host_name);
+                    } else if let Ok(_) = alt_host_name.parse::<Ipv6Addr>() {
+                        // Treat this as an IPv6 address
+                        subject_alternative_name.ip(alt_host_name);
+                    } else {
+                        // Treat this as a DNS entries
+                        subject_alternative_name.dns(alt_host_name);
+                    }
+                }
+            });
+            Some(subject_alternative_name)
+        }
+    }
+
+    // Produces a string such as "CN=foo/C=IE" from a name
+    fn name_to_string(name: &x509::X509NameRef) -> String {
+        use std::ops::Deref;
+        name.entries()
+            .map(|e| {
+                let v = if let Ok(v) = e.data().as_utf8() {
+                    v.deref().to_string()
+                } else {
+                    "?".into()
+                };
+                format!("{}={}", e.object(), v)
+            })
+            .collect::<Vec<String>>()
+            .join("/")
+    }
+
     pub fn from_byte_string(data: &ByteString) -> Result<X509, StatusCode> {
         if data.is_null() {
             error!("Cannot make certificate from null bytestring");
@@ -334,18 +414,7 @@ impl X509 {
 
     // Produces a string such as "CN=foo/C=IE"
     pub fn subject_name(&self) -> String {
-        use std::ops::Deref;
-        self.value.subject_name().entries()
-            .map(|e| {
-                let v = if let Ok(v) = e.data().as_utf8() {
-                    v.deref().to_string()
-                } else {
-                    "?".into()
-                };
-                format!("{}={}", e.object(), v)
-            })
-            .collect::<Vec<String>>()
-            .join("/")
+        Self::name_to_string(self.value.subject_name())
     }
 
     /// Gets the common name out of the cert
@@ -389,31 +458,36 @@ impl X509 {
     fn subject_alt_names(&self) -> Option<Vec<String>> {
         if let Some(ref alt_names) = self.value.subject_alt_names() {
             // Skip the application uri
-            let subject_alt_names = alt_names.iter().skip(1).map(|n| {
-                if let Some(dnsname) = n.dnsname() {
-                    dnsname.to_string()
-                } else if let Some(ip) = n.ipaddress() {
-                    if ip.len() == 4 {
-                        let mut addr = [0u8; 4];
-                        addr[..].clone_from_slice(&ip);
-                        Ipv4Addr::from(addr).to_string()
-                    } else if ip.len() == 16 {
-                        let mut addr = [0u8; 16];
-                        addr[..].clone_from_slice(&ip);
-                        Ipv6Addr::from(addr).to_string()
-                    } else {
-                        "".to_string()
-                    }
-                } else {
-                    "".to_string()
-                }
-            }).collect();
+            let subject_alt_names = alt_names.iter().skip(1).map(|n| Self::general_name_to_string(n)).collect();
             Some(subject_alt_names)
         } else {
             None
         }
     }
 
+    /// Turns a subject alt name into text. IP addresses are returned in their canonical form.
+    fn general_name_to_string(name: &x509::GeneralNameRef) -> String {
+        if let Some(uri) = name.uri() {
+            uri.to_string()
+        } else if let Some(dnsname) = name.dnsname() {
+            dnsname.to_string()
+        } else if let Some(ip) = name.ipaddress() {
+            if ip.len() == 4 {
+                let mut addr = [0u8; 4];
+                addr[..].clone_from_slice(&ip);
+                Ipv4Addr::from(addr).to_string()
+            } else if ip.len() == 16 {
+                let mut addr = [0u8; 16];
+                addr[..].clone_from_slice(&ip);
+                Ipv6Addr::from(addr).to_string()
+            } else {
+                "".to_string()
+            }
+        } else {
+            "".to_string()
+        }
+    }
+
     /// Tests if the supplied hostname matches any of the dns alt subject name entries on the cert
     pub fn is_hostname_valid(&self, hostname: &str) -> StatusCode {
         trace!("is_hostname_valid against {} on cert", hostname);
@@ -477,9 +551,8 @@ impl X509 {
     ///
     /// The thumbprint might be used by the server / client for look-up purposes.
     pub fn thumbprint(&self) -> Thumbprint {
-        use openssl::hash::{MessageDigest, hash};
         let der = self.value.to_der().unwrap();
-        let digest = hash(MessageDigest::sha1(), &der).unwrap();
+        let digest = DefaultBackend::hash(HashAlgorithm::Sha1, &der).unwrap();
         Thumbprint::new(&digest)
     }
 
@@ -516,6 +589,80 @@ impl X509 {
     }
 }
 
+/// This is a wrapper around the `OpenSSL` `X509Req` certificate signing request
+pub struct CertificateRequest {
+    value: x509::X509Req,
+}
+
+impl Debug for CertificateRequest {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[x509 request]")
+    }
+}
+
+impl CertificateRequest {
+    /// Creates a certificate signing request for the public key of the private key, signed by
+    /// the private key.
+    pub fn from_pkey(pkey: &PrivateKey, x509_data: &X509Data) -> Result<Self, String> {
+        let mut builder = x509::X509ReqBuilder::new().unwrap();
+        // value 0 == version 1, the only version of PKCS#10
+        let _ = builder.set_version(0);
+        let _ = builder.set_subject_name(&X509::build_name(x509_data));
+        builder.set_pubkey(&pkey.value).unwrap();
+
+        // The key usages and subject alt names are requested as extensions. The certificate
+        // authority decides which of them appear in the issued cert.
+        let mut extensions = Stack::new().unwrap();
+        let key_usage = KeyUsage::new().
+            digital_signature().
+            non_repudiation().
+            key_encipherment().
+            data_encipherment().
+            build().unwrap();
+        extensions.push(key_usage).unwrap();
+        let extended_key_usage = ExtendedKeyUsage::new().
+            client_auth().
+            server_auth().build().unwrap();
+        extensions.push(extended_key_usage).unwrap();
+        if let Some(mut subject_alternative_name) = X509::build_subject_alternative_name(x509_data) {
+            let subject_alternative_name = subject_alternative_name.build(&builder.x509v3_context(None)).unwrap();
+            extensions.push(subject_alternative_name).unwrap();
+        }
+        builder.add_extensions(&extensions)
+            .map_err(|err| format!("Cannot add extensions to certificate request, error {}", err))?;
+
+        builder.sign(&pkey.value, hash::MessageDigest::sha256())
+            .map_err(|err| format!("Cannot sign certificate request, error {}", err))?;
+
+        Ok(CertificateRequest { value: builder.build() })
+    }
+
+    /// Returns the request PEM encoded, i.e. the form usually submitted to a certificate authority
+    pub fn to_pem(&self) -> Result<Vec<u8>, CryptoError> {
+        self.value.to_pem().map_err(|e| {
+            CryptoError::Backend(format!("Cannot turn certificate request to PEM, err = {}", e))
+        })
+    }
+
+    pub fn to_der(&self) -> Result<Vec<u8>, CryptoError> {
+        self.value.to_der().map_err(|e| {
+            CryptoError::Backend(format!("Cannot turn certificate request to DER, err = {}", e))
+        })
+    }
+
+    // Produces a string such as "CN=foo/C=IE"
+    pub fn subject_name(&self) -> String {
+        X509::name_to_string(self.value.subject_name())
+    }
+
+    /// Tests if the request is signed by the private key of the public key it holds
+    pub fn is_signature_valid(&self) -> bool {
+        self.value.public_key()
+            .and_then(|public_key| self.value.verify(&public_key))
+            .unwrap_or(false)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -570,5 +717,57 @@ mod tests {
             assert!(x509.is_hostname_valid(n).is_good());
         })
     }
-}
 
+    /// This test checks that a certificate request holds the subject and subject alt names of
+    /// the args, and that a cert issued from it is valid for the application uri and hostnames.
+    #[test]
+    fn certificate_request() {
+        let alt_host_names = ["urn:testapplication", "host2", "192.168.1.1"];
+        let args = X509Data {
+            key_size: 2048,
+            common_name: "x".to_string(),
+            organization: "x.org".to_string(),
+            organizational_unit: "x.org ops".to_string(),
+            country: "EN".to_string(),
+            state: "London".to_string(),
+            alt_host_names: alt_host_names.iter().map(|h| h.to_string()).collect(),
+            certificate_duration_days: 60,
+        };
+
+        let (request, pkey) = X509::create_certificate_request(&args).unwrap();
+        assert!(request.is_signature_valid());
+        // The subject is the same as a self signed cert made from the args
+        let (self_signed, _) = X509::cert_and_pkey(&args).unwrap();
+        assert_eq!(request.subject_name(), self_signed.subject_name());
+        let pem = String::from_utf8(request.to_pem().unwrap()).unwrap();
+        assert!(pem.starts_with("-----BEGIN CERTIFICATE REQUEST-----"));
+
+        // The request holds the public key of the private key
+        let public_key = request.value.public_key().unwrap();
+        assert!(public_key.public_eq(&pkey.value));
+
+        // Issue a cert from the request as a certificate authority would, copying the requested
+        // extensions, to check the subject alt names
+        let ca_key = pkey::PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let mut builder = x509::X509Builder::new().unwrap();
+        builder.set_version(2).unwrap();
+        builder.set_subject_name(request.value.subject_name()).unwrap();
+        let mut issuer_name = x509::X509NameBuilder::new().unwrap();
+        issuer_name.append_entry_by_text("CN", "Test CA").unwrap();
+        builder.set_issuer_name(&issuer_name.build()).unwrap();
+        builder.set_pubkey(&public_key).unwrap();
+        builder.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+        builder.set_not_after(&Asn1Time::days_from_now(60).unwrap()).unwrap();
+        request.value.extensions().unwrap().into_iter().for_each(|extension| {
+            builder.append_extension(extension).unwrap();
+        });
+        builder.sign(&ca_key, hash::MessageDigest::sha256()).unwrap();
+        let x509 = X509::from(builder.build());
+
+        assert_eq!(x509.subject_name(), request.subject_name());
+        assert!(x509.is_application_uri_valid("urn:testapplication").is_good());
+        assert!(x509.is_hostname_valid("host2").is_good());
+        assert!(x509.is_hostname_valid("192.168.1.1").is_good());
+        assert!(!x509.is_hostname_valid("host1").is_good());
+    }
+}
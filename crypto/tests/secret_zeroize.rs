@@ -0,0 +1,52 @@
+//! Checks that secret bytes are zeroed when they are dropped. The check installs a global
+//! allocator, so it is an integration test with a binary of its own rather than a unit test that
+//! would change the allocator of every crypto test.
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    ptr,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+use opcua_crypto::secret::SecretBytes;
+
+/// Allocator that inspects one watched buffer as it is freed so the test can see what was left
+/// behind in memory by the drop.
+struct WatchingAllocator;
+
+static WATCHED_PTR: AtomicUsize = AtomicUsize::new(0);
+static WATCHED_LEN: AtomicUsize = AtomicUsize::new(0);
+static WATCHED_FREED: AtomicBool = AtomicBool::new(false);
+static WATCHED_WAS_ZERO: AtomicBool = AtomicBool::new(false);
+
+unsafe impl GlobalAlloc for WatchingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, p: *mut u8, layout: Layout) {
+        if p as usize == WATCHED_PTR.load(Ordering::SeqCst) {
+            let len = WATCHED_LEN.load(Ordering::SeqCst);
+            let was_zero = (0..len).all(|i| ptr::read_volatile(p.add(i)) == 0);
+            WATCHED_WAS_ZERO.store(was_zero, Ordering::SeqCst);
+            WATCHED_FREED.store(true, Ordering::SeqCst);
+            WATCHED_PTR.store(0, Ordering::SeqCst);
+        }
+        System.dealloc(p, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: WatchingAllocator = WatchingAllocator;
+
+#[test]
+fn secret_bytes_zeroed_on_drop() {
+    let secret = SecretBytes::from(vec![0xa5u8; 64]);
+    assert!(secret.iter().all(|b| *b == 0xa5));
+
+    WATCHED_LEN.store(secret.len(), Ordering::SeqCst);
+    WATCHED_PTR.store(secret.as_ptr() as usize, Ordering::SeqCst);
+    drop(secret);
+
+    assert!(WATCHED_FREED.load(Ordering::SeqCst));
+    assert!(WATCHED_WAS_ZERO.load(Ordering::SeqCst));
+}
@@ -3,7 +3,7 @@
 // Copyright (C) 2017-2020 Adam Lock
 
 //! Implementation of `AddressSpace`.
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::sync::{Arc, Mutex, RwLock};
 
 use chrono::Utc;
@@ -31,6 +31,17 @@ use crate::{
     state::ServerState,
 };
 
+/// A write made to an attribute of a node. The address space remembers recent writes so the
+/// monitored items in push mode of every session can sample the written node.
+#[derive(Debug, Clone)]
+pub struct AttributeWrite {
+    /// Increases by one with each write
+    pub sequence_number: u64,
+    pub node_id: NodeId,
+    pub attribute_id: AttributeId,
+    pub index_range: NumericRange,
+}
+
 /// Finds a node in the address space and coerces it into a reference of the expected node type.
 macro_rules! find_node {
     ($a: expr, $id: expr, $node_type: ident) =>  {
@@ -164,6 +175,10 @@ pub struct AddressSpace {
     internal_namespace: u16,
     /// The list of all registered namespaces.
     namespaces: Vec<String>,
+    /// Recent writes, oldest first
+    writes: VecDeque<AttributeWrite>,
+    /// The sequence number of the last write
+    last_write_sequence_number: u64,
 }
 
 impl Default for AddressSpace {
@@ -180,6 +195,8 @@ impl Default for AddressSpace {
             // OPC UA namespace for its standard nodes. The second is the internal namespace used
             // by this implementation.
             namespaces: vec!["http://opcfoundation.org/UA/".to_string()],
+            writes: VecDeque::new(),
+            last_write_sequence_number: 0,
         }
     }
 }
@@ -199,6 +216,32 @@ impl AddressSpace {
         self.last_modified.clone()
     }
 
+    /// Records a write to an attribute of a node so that monitored items in push mode sample it.
+    /// The Write service and `set_variable_value()` call this. Call it after changing a node
+    /// through some other means if push mode items should see the change.
+    pub fn notify_write(&mut self, node_id: &NodeId, attribute_id: AttributeId, index_range: NumericRange) {
+        self.last_write_sequence_number += 1;
+        if self.writes.len() == constants::MAX_RECORDED_WRITES {
+            let _ = self.writes.pop_front();
+        }
+        self.writes.push_back(AttributeWrite {
+            sequence_number: self.last_write_sequence_number,
+            node_id: node_id.clone(),
+            attribute_id,
+            index_range,
+        });
+    }
+
+    /// Returns the sequence number of the last write
+    pub(crate) fn last_write_sequence_number(&self) -> u64 {
+        self.last_write_sequence_number
+    }
+
+    /// Returns the remembered writes made after the write with the sequence number, oldest first
+    pub(crate) fn writes_since(&self, sequence_number: u64) -> impl Iterator<Item=&AttributeWrite> {
+        self.writes.iter().filter(move |w| w.sequence_number > sequence_number)
+    }
+
     /// Registers a namespace described by a uri with address space. The return code is the index
     /// of the newly added namespace / index. The index is used with `NodeId`. Registering a
     /// namespace that is already registered will return the index to the previous instance.
@@ -691,6 +734,7 @@ impl AddressSpace {
         where V: Into<Variant> {
         if let Some(ref mut variable) = self.find_variable_mut_by_ref(node_id) {
             let _ = variable.set_value_direct(value, StatusCode::Good, source_timestamp, server_timestamp);
+            self.notify_write(node_id, AttributeId::Value, NumericRange::None);
             true
         } else {
             false
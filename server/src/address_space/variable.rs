@@ -349,7 +349,15 @@ impl Variable {
         !self.data_type.is_null() && self.base.is_valid()
     }
 
+    /// Returns the variable's value. If a `max_age` is specified the server timestamp of the
+    /// value is set to the current wall clock time.
     pub fn value(&self, index_range: NumericRange, data_encoding: &QualifiedName, max_age: f64) -> DataValue {
+        self.value_at(index_range, data_encoding, max_age, &DateTime::now())
+    }
+
+    /// Returns the variable's value. If a `max_age` is specified the server timestamp of the
+    /// value is set to `now`, the current time of the server's clock.
+    pub fn value_at(&self, index_range: NumericRange, data_encoding: &QualifiedName, max_age: f64, now: &DateTime) -> DataValue {
         use std::i32;
 
         if let Some(ref value_getter) = self.value_getter {
@@ -380,7 +388,7 @@ impl Variable {
             }
             if max_age > 0.0 && max_age <= i32::MAX as f64 {
                 // Update the server timestamp to now as a "best effort" attempt to get the latest value
-                result.server_timestamp = Some(DateTime::now());
+                result.server_timestamp = Some(now.clone());
             }
             result
         }
@@ -388,6 +396,12 @@ impl Variable {
 
     /// Sets the variable's `Variant` value. The timestamps for the change are updated to now.
     pub fn set_value<V>(&mut self, index_range: NumericRange, value: V) -> Result<(), StatusCode> where V: Into<Variant> {
+        self.set_value_at(index_range, value, &DateTime::now())
+    }
+
+    /// Sets the variable's `Variant` value. The timestamps for the change are updated to `now`,
+    /// the current time of the server's clock.
+    pub fn set_value_at<V>(&mut self, index_range: NumericRange, value: V, now: &DateTime) -> Result<(), StatusCode> where V: Into<Variant> {
         let mut value = value.into();
 
         // A special case is required here for when the variable is a single dimension
@@ -408,13 +422,10 @@ impl Variable {
         if let Some(ref value_setter) = self.value_setter {
             let mut value_setter = value_setter.lock().unwrap();
             value_setter.set(&self.node_id(), AttributeId::Value, index_range, value.into())
+        } else if index_range.has_range() {
+            self.set_value_range(value, index_range, StatusCode::Good, now, now)
         } else {
-            let now = DateTime::now();
-            if index_range.has_range() {
-                self.set_value_range(value, index_range, StatusCode::Good, &now, &now)
-            } else {
-                self.set_value_direct(value, StatusCode::Good, &now, &now)
-            }
+            self.set_value_direct(value, StatusCode::Good, now, now)
         }
     }
 
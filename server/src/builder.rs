@@ -5,10 +5,11 @@
 use std::path::PathBuf;
 
 use opcua_core::config::Config;
+use opcua_crypto::SecurityPolicy;
 
 use crate::{
     constants,
-    config::{ServerConfig, ServerEndpoint, ServerUserToken, ANONYMOUS_USER_TOKEN_ID},
+    config::{ServerConfig, ServerEndpoint, ServerPolicyCertificate, ServerUserToken, ANONYMOUS_USER_TOKEN_ID},
     server::Server,
 };
 
@@ -155,6 +156,16 @@ impl ServerBuilder {
         self
     }
 
+    /// Sets the certificate and private key that endpoints with the security policy use instead of
+    /// the application instance certificate. Relative paths are relative to the pki directory.
+    pub fn policy_certificate<T>(mut self, security_policy: SecurityPolicy, certificate_path: T, private_key_path: T) -> Self where T: Into<PathBuf> {
+        self.config.policy_certificates.insert(security_policy.to_str().to_string(), ServerPolicyCertificate {
+            certificate_path: certificate_path.into(),
+            private_key_path: private_key_path.into(),
+        });
+        self
+    }
+
     /// Adds an endpoint to the list of endpoints the client knows of.
     pub fn endpoint<T>(mut self, endpoint_id: T, endpoint: ServerEndpoint) -> Self where T: Into<String> {
         self.config.endpoints.insert(endpoint_id.into(), endpoint);
@@ -215,6 +226,26 @@ impl ServerBuilder {
         self
     }
 
+    /// Set the maximum number of monitored items per subscription that are ticked in one cycle
+    pub fn max_monitored_items_per_tick(mut self, max_monitored_items_per_tick: u32) -> Self {
+        self.config.limits.max_monitored_items_per_tick = max_monitored_items_per_tick;
+        self
+    }
+
+    /// Set the maximum number of notifications queued across the monitored items of a session
+    pub fn max_session_queued_notifications(mut self, max_session_queued_notifications: u32) -> Self {
+        self.config.limits.max_session_queued_notifications = max_session_queued_notifications;
+        self
+    }
+
+    /// Set the queue size and discard policy given to monitored items when the client requests a
+    /// queue size of 0. These are reported back to the client in the revised parameters.
+    pub fn default_queue_size(mut self, default_queue_size: u32, default_discard_oldest: bool) -> Self {
+        self.config.limits.default_queue_size = default_queue_size;
+        self.config.limits.default_discard_oldest = default_discard_oldest;
+        self
+    }
+
     /// Set the max array length in elements
     pub fn max_array_length(mut self, max_array_length: u32) -> Self {
         self.config.limits.max_array_length = max_array_length;
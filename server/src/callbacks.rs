@@ -65,6 +65,16 @@ pub trait UnregisterNodes {
     fn unregister_nodes(&mut self, session: Arc<RwLock<Session>>, nodes_to_unregister: &[NodeId]) -> Result<(), StatusCode>;
 }
 
+/// Called when the notification queue of a monitored item overflows
+pub trait QueueOverflow {
+    /// Called when a monitored item's queue goes into overflow, i.e. the first time it drops
+    /// notifications since its queue was last emptied by a publish. `dropped` is the number of
+    /// notifications that were dropped to make way for the newest one, or 1 if the newest one
+    /// was itself dropped because the session has no room for more. The call is made by the
+    /// subscription timer after it ticks the session's subscriptions and unlocks the session.
+    fn on_queue_overflow(&mut self, monitored_item_id: u32, dropped: usize);
+}
+
 /// Called by the Method service when it invokes a method
 pub trait Method {
     /// A method is registered via the address space to a method id and optionally an object id.
@@ -0,0 +1,63 @@
+// OPCUA for Rust
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (C) 2017-2020 Adam Lock
+
+//! The clock that supplies the server's notion of the current time.
+//!
+//! Subscriptions and monitored items are ticked with the time from the server's clock and the
+//! server timestamp of any sampled value that lacks one is stamped from it. By default this is the
+//! system clock but a server implementation may substitute its own, e.g. to run a simulation or to
+//! test time dependent behaviour.
+
+use std::sync::Mutex;
+
+use chrono::Utc;
+
+use opcua_types::DateTimeUtc;
+
+/// A source of the current time
+pub trait Clock {
+    /// Returns the current time
+    fn now(&self) -> DateTimeUtc;
+}
+
+/// A clock that returns the wall clock time. This is the default.
+#[derive(Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTimeUtc {
+        Utc::now()
+    }
+}
+
+/// A clock whose time only changes when it is set or advanced by the caller.
+pub struct SimulationClock {
+    now: Mutex<DateTimeUtc>,
+}
+
+impl Clock for SimulationClock {
+    fn now(&self) -> DateTimeUtc {
+        *self.now.lock().unwrap()
+    }
+}
+
+impl SimulationClock {
+    /// Creates a clock that starts at the specified time
+    pub fn new(now: DateTimeUtc) -> SimulationClock {
+        SimulationClock {
+            now: Mutex::new(now)
+        }
+    }
+
+    /// Sets the current time of the clock
+    pub fn set(&self, now: DateTimeUtc) {
+        *self.now.lock().unwrap() = now;
+    }
+
+    /// Moves the time of the clock forward by the duration
+    pub fn advance(&self, duration: chrono::Duration) {
+        let mut now = self.now.lock().unwrap();
+        *now = *now + duration;
+    }
+}
@@ -133,7 +133,6 @@ impl SecureChannelService {
         // Create a new secure channel info
         let security_mode = request.security_mode;
         secure_channel.set_security_mode(security_mode);
-        secure_channel.set_token_id(self.secure_channel_state.create_token_id());
         secure_channel.set_secure_channel_id(secure_channel_id);
         secure_channel.set_remote_cert_from_byte_string(&security_header.sender_certificate)?;
 
@@ -147,18 +146,28 @@ impl SecureChannelService {
 
         let security_policy = secure_channel.security_policy();
         if security_policy != SecurityPolicy::None && (security_mode == MessageSecurityMode::Sign || security_mode == MessageSecurityMode::SignAndEncrypt) {
-            secure_channel.derive_keys();
+            if request.request_type == SecurityTokenRequestType::Renew {
+                // Messages the client secured with the old token are accepted until it expires
+                let client_nonce = secure_channel.remote_nonce().to_vec();
+                let server_nonce = secure_channel.local_nonce().to_vec();
+                secure_channel.renew_token(&client_nonce, &server_nonce);
+            } else {
+                secure_channel.derive_keys();
+            }
         }
+        // The new token is set after a renew so the old one is remembered with its keys
+        let security_token = ChannelSecurityToken {
+            channel_id: secure_channel.secure_channel_id(),
+            token_id: self.secure_channel_state.create_token_id(),
+            created_at: DateTime::now(),
+            revised_lifetime: request.requested_lifetime,
+        };
+        secure_channel.set_security_token(security_token.clone());
 
         let response = OpenSecureChannelResponse {
             response_header: ResponseHeader::new_good(&request.request_header),
             server_protocol_version: 0,
-            security_token: ChannelSecurityToken {
-                channel_id: secure_channel.secure_channel_id(),
-                token_id: secure_channel.token_id(),
-                created_at: DateTime::now(),
-                revised_lifetime: request.requested_lifetime,
-            },
+            security_token,
             server_nonce: secure_channel.local_nonce_as_byte_string(),
         };
         Ok(response.into())
@@ -72,7 +72,7 @@ macro_rules! connection_finished_test {
 }
 
 /// Messages that may be sent to the writer.
-enum Message {
+pub(crate) enum Message {
     // Message for writer to quit right now.
     Quit,
     // A supported message with a request id
@@ -124,6 +124,8 @@ pub struct TcpTransport {
     message_handler: MessageHandler,
     /// Client protocol version set during HELLO
     client_protocol_version: u32,
+    /// The maximum size of a request, as sent to the client in the ACKNOWLEDGE, or 0 for no limit
+    max_message_size: usize,
     /// Last decoded sequence number
     last_received_sequence_number: u32,
     /// A message may consist of one or more chunks which are stored here until complete.
@@ -183,6 +185,7 @@ impl TcpTransport {
             secure_channel,
             secure_channel_service,
             client_protocol_version: 0,
+            max_message_size: 0,
             last_received_sequence_number: 0,
             pending_chunks: Vec::with_capacity(2),
         }
@@ -706,21 +709,31 @@ impl TcpTransport {
                 })
                 .for_each(move |_| {
                     let transport = trace_read_lock_unwrap!(state.transport);
-                    let mut session = trace_write_lock_unwrap!(transport.session);
+                    let now = {
+                        let server_state = trace_read_lock_unwrap!(transport.server_state);
+                        server_state.now()
+                    };
+                    let (publish_responses, queue_overflow_events) = {
+                        let mut session = trace_write_lock_unwrap!(transport.session);
 
-                    let now = Utc::now();
+                        // Request queue might contain stale publish requests
+                        session.expire_stale_publish_requests(&now);
+
+                        // Process subscriptions
+                        {
+                            let address_space = trace_read_lock_unwrap!(transport.address_space);
+                            let _ = session.tick_subscriptions(&now, &address_space, TickReason::TickTimerFired);
+                        }
 
-                    // Request queue might contain stale publish requests
-                    session.expire_stale_publish_requests(&now);
+                        let subscriptions = session.subscriptions_mut();
+                        (subscriptions.take_publish_responses(), subscriptions.take_queue_overflow_events())
+                    };
 
-                    // Process subscriptions
-                    {
-                        let address_space = trace_read_lock_unwrap!(transport.address_space);
-                        let _ = session.tick_subscriptions(&now, &address_space, TickReason::TickTimerFired);
-                    }
+                    // Queue overflow callbacks are made with the session unlocked
+                    queue_overflow_events.into_iter().for_each(|e| e.notify());
 
                     // Check if there are publish responses to send for transmission
-                    if let Some(publish_responses) = session.subscriptions_mut().take_publish_responses() {
+                    if let Some(publish_responses) = publish_responses {
                         match subscription_tx.unbounded_send(SubscriptionEvent::PublishResponses(publish_responses)) {
                             Err(error) => {
                                 error!("Cannot send publish responses, err = {}", error);
@@ -797,7 +810,7 @@ impl TcpTransport {
         server_state.is_abort()
     }
 
-    fn process_hello(&mut self, hello: HelloMessage, sender: &mut UnboundedSender<Message>) -> std::result::Result<(), StatusCode> {
+    pub(crate) fn process_hello(&mut self, hello: HelloMessage, sender: &mut UnboundedSender<Message>) -> std::result::Result<(), StatusCode> {
         let server_protocol_version = 0;
         let endpoints = {
             let server_state = trace_read_lock_unwrap!(self.server_state);
@@ -820,6 +833,7 @@ impl TcpTransport {
         }
 
         let client_protocol_version = hello.protocol_version;
+        let max_message_size = MAX_MESSAGE_SIZE;
 
         // Send acknowledge
         let mut acknowledge = AcknowledgeMessage {
@@ -827,7 +841,7 @@ impl TcpTransport {
             protocol_version: server_protocol_version,
             receive_buffer_size: RECEIVE_BUFFER_SIZE as u32,
             send_buffer_size: SEND_BUFFER_SIZE as u32,
-            max_message_size: MAX_MESSAGE_SIZE as u32,
+            max_message_size: max_message_size as u32,
             max_chunk_count: MAX_CHUNK_COUNT as u32,
         };
         acknowledge.message_header.message_size = acknowledge.byte_len() as u32;
@@ -836,6 +850,7 @@ impl TcpTransport {
         // New state
         self.transport_state = TransportState::ProcessMessages;
         self.client_protocol_version = client_protocol_version;
+        self.max_message_size = max_message_size;
 
         debug!("Sending ACK");
         let _ = sender.unbounded_send(Message::Message(0, acknowledge));
@@ -847,10 +862,10 @@ impl TcpTransport {
         let secure_channel = trace_read_lock_unwrap!(self.secure_channel);
         self.last_received_sequence_number = Chunker::validate_chunks(self.last_received_sequence_number + 1, &secure_channel, chunks)?;
         // Now decode
-        Chunker::decode(&chunks, &secure_channel, None)
+        Chunker::decode_with_max_message_size(&chunks, &secure_channel, self.max_message_size, None)
     }
 
-    fn process_chunk(&mut self, chunk: MessageChunk, sender: &mut UnboundedSender<Message>) -> std::result::Result<(), StatusCode> {
+    pub(crate) fn process_chunk(&mut self, chunk: MessageChunk, sender: &mut UnboundedSender<Message>) -> std::result::Result<(), StatusCode> {
         let decoding_limits = {
             let secure_channel = trace_read_lock_unwrap!(self.secure_channel);
             secure_channel.decoding_limits()
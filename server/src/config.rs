@@ -13,7 +13,7 @@ use opcua_core::{
 };
 use opcua_crypto::{CertificateStore, SecurityPolicy, Thumbprint};
 use opcua_types::{
-    constants as opcua_types_constants, DecodingLimits, MessageSecurityMode,
+    constants as opcua_types_constants, DecodingLimits, Duration, MessageSecurityMode,
     service_types::ApplicationType,
     UAString,
 };
@@ -32,6 +32,22 @@ pub struct TcpConfig {
     pub port: u16,
 }
 
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct SamplingConfig {
+    /// The quantum in milliseconds at which subscriptions and their monitored items are ticked.
+    /// No monitored item samples faster than this, so an item with a sampling interval of 0, the
+    /// fastest practical rate, samples once per quantum.
+    pub tick_interval: Duration,
+}
+
+impl Default for SamplingConfig {
+    fn default() -> Self {
+        Self {
+            tick_interval: constants::SUBSCRIPTION_TIMER_RATE_MS as Duration,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub struct ServerUserToken {
     /// User name
@@ -122,6 +138,15 @@ pub struct ServerLimits {
     pub max_subscriptions: u32,
     /// Maximum number of monitored items per subscription, 0 for no limit
     pub max_monitored_items_per_sub: u32,
+    /// Maximum number of monitored items in a subscription that are ticked in one cycle, 0 for no
+    /// limit. Items beyond the limit are ticked on subsequent cycles in round-robin order.
+    #[serde(default = "ServerLimits::default_max_monitored_items_per_tick")]
+    pub max_monitored_items_per_tick: u32,
+    /// Maximum number of notifications queued across all the monitored items of a session, 0 for
+    /// no limit. Once reached, items treat their queues as full and overflow until the client
+    /// publishes.
+    #[serde(default = "ServerLimits::default_max_session_queued_notifications")]
+    pub max_session_queued_notifications: u32,
     /// Max array length in elements
     pub max_array_length: u32,
     /// Max string length in characters
@@ -132,6 +157,25 @@ pub struct ServerLimits {
     pub min_sampling_interval: f64,
     /// Specifies the minimum publishing interval for this server in seconds.
     pub min_publishing_interval: f64,
+    /// Queue size given to a monitored item when the client requests a queue size of 0. This is
+    /// reported back to the client as the revised queue size.
+    #[serde(default = "ServerLimits::default_queue_size")]
+    pub default_queue_size: u32,
+    /// Discard policy given to a monitored item when the client requests a queue size of 0.
+    #[serde(default = "ServerLimits::default_discard_oldest")]
+    pub default_discard_oldest: bool,
+    /// Smallest absolute change that a percent deadband reports, whatever the size of the
+    /// EURange. Stops floating point noise being reported on variables with a tiny range.
+    #[serde(default = "ServerLimits::default_percent_deadband_floor")]
+    pub percent_deadband_floor: f64,
+    /// Offsets the first sample of each monitored item by a fraction of its sampling interval
+    /// derived from the item id, so items created together do not all sample on the same tick.
+    #[serde(default = "ServerLimits::default_stagger_first_samples")]
+    pub stagger_first_samples: bool,
+    /// Only reports the value sampled when a monitored item is created if its status is Good.
+    /// The specification says the initial value is reported whatever its status.
+    #[serde(default = "ServerLimits::default_initial_value_good_only")]
+    pub initial_value_good_only: bool,
 }
 
 impl Default for ServerLimits {
@@ -142,13 +186,60 @@ impl Default for ServerLimits {
             max_byte_string_length: opcua_types_constants::MAX_BYTE_STRING_LENGTH as u32,
             max_subscriptions: constants::DEFAULT_MAX_SUBSCRIPTIONS,
             max_monitored_items_per_sub: constants::DEFAULT_MAX_MONITORED_ITEMS_PER_SUB,
+            max_monitored_items_per_tick: constants::DEFAULT_MAX_MONITORED_ITEMS_PER_TICK,
+            max_session_queued_notifications: constants::DEFAULT_MAX_SESSION_QUEUED_NOTIFICATIONS,
             clients_can_modify_address_space: false,
             min_sampling_interval: constants::MIN_SAMPLING_INTERVAL,
             min_publishing_interval: constants::MIN_PUBLISHING_INTERVAL,
+            default_queue_size: constants::DEFAULT_DATA_CHANGE_QUEUE_SIZE,
+            default_discard_oldest: constants::DEFAULT_DISCARD_OLDEST,
+            percent_deadband_floor: constants::DEFAULT_PERCENT_DEADBAND_FLOOR,
+            stagger_first_samples: constants::DEFAULT_STAGGER_FIRST_SAMPLES,
+            initial_value_good_only: constants::DEFAULT_INITIAL_VALUE_GOOD_ONLY,
         }
     }
 }
 
+impl ServerLimits {
+    fn default_queue_size() -> u32 {
+        constants::DEFAULT_DATA_CHANGE_QUEUE_SIZE
+    }
+
+    fn default_discard_oldest() -> bool {
+        constants::DEFAULT_DISCARD_OLDEST
+    }
+
+    fn default_max_monitored_items_per_tick() -> u32 {
+        constants::DEFAULT_MAX_MONITORED_ITEMS_PER_TICK
+    }
+
+    fn default_percent_deadband_floor() -> f64 {
+        constants::DEFAULT_PERCENT_DEADBAND_FLOOR
+    }
+
+    fn default_max_session_queued_notifications() -> u32 {
+        constants::DEFAULT_MAX_SESSION_QUEUED_NOTIFICATIONS
+    }
+
+    fn default_stagger_first_samples() -> bool {
+        constants::DEFAULT_STAGGER_FIRST_SAMPLES
+    }
+
+    fn default_initial_value_good_only() -> bool {
+        constants::DEFAULT_INITIAL_VALUE_GOOD_ONLY
+    }
+}
+
+/// A certificate and private key that endpoints with a particular security policy use instead of
+/// the application instance certificate.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct ServerPolicyCertificate {
+    /// Path to the DER encoded certificate, either absolute or relative to the pki folder
+    pub certificate_path: PathBuf,
+    /// Path to the PEM encoded private key, either absolute or relative to the pki folder
+    pub private_key_path: PathBuf,
+}
+
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub struct ServerEndpoint {
     /// Endpoint path
@@ -193,19 +284,7 @@ impl ServerEndpoint {
 
     /// Recommends a security level for the supplied security policy
     fn security_level(security_policy: SecurityPolicy, security_mode: MessageSecurityMode) -> u8 {
-        let security_level = match security_policy {
-            SecurityPolicy::Basic128Rsa15 => 1,
-            SecurityPolicy::Aes128Sha256RsaOaep => 2,
-            SecurityPolicy::Basic256 => 3,
-            SecurityPolicy::Basic256Sha256 => 4,
-            SecurityPolicy::Aes256Sha256RsaPss => 5,
-            _ => 0
-        };
-        if security_mode == MessageSecurityMode::SignAndEncrypt {
-            security_level + 10
-        } else {
-            security_level
-        }
+        security_policy.security_level(security_mode)
     }
 
     pub fn new_none<T>(path: T, user_token_ids: &[String]) -> Self where T: Into<String> {
@@ -377,6 +456,10 @@ pub struct ServerConfig {
     /// Autocreates public / private keypair if they don't exist. For testing/samples only
     /// since you do not have control of the values
     pub create_sample_keypair: bool,
+    /// Certificates used by the endpoints of a security policy instead of the application instance
+    /// certificate, keyed by the name of the policy, e.g. "Basic256Sha256"
+    #[serde(default)]
+    pub policy_certificates: BTreeMap<String, ServerPolicyCertificate>,
     /// Auto trusts client certificates. For testing/samples only unless you're sure what you're
     /// doing.
     pub trust_client_certs: bool,
@@ -387,6 +470,9 @@ pub struct ServerConfig {
     pub tcp_config: TcpConfig,
     /// Server limits
     pub limits: ServerLimits,
+    /// Sampling configuration
+    #[serde(default)]
+    pub sampling: SamplingConfig,
     /// Supported locale ids
     pub locale_ids: Vec<String>,
     /// User tokens
@@ -430,6 +516,15 @@ impl Config for ServerConfig {
                 valid = false;
             }
         }
+        for security_policy in self.policy_certificates.keys() {
+            match SecurityPolicy::from_str(security_policy).unwrap() {
+                SecurityPolicy::Unknown | SecurityPolicy::None => {
+                    error!("Server configuration is invalid. Policy certificate for \"{}\" does not name a security policy that uses certificates", security_policy);
+                    valid = false;
+                }
+                _ => {}
+            }
+        }
         if self.limits.max_array_length == 0 {
             error!("Server configuration is invalid. Max array length is invalid");
             valid = false;
@@ -442,6 +537,10 @@ impl Config for ServerConfig {
             error!("Server configuration is invalid. Max byte string length is invalid");
             valid = false;
         }
+        if self.sampling.tick_interval <= 0f64 {
+            error!("Server configuration is invalid. Sampling tick interval must be greater than 0");
+            valid = false;
+        }
         if self.discovery_urls.is_empty() {
             error!("Server configuration is invalid. Discovery urls not set");
             valid = false;
@@ -472,6 +571,7 @@ impl Default for ServerConfig {
             product_uri: String::new(),
             pki_dir,
             create_sample_keypair: false,
+            policy_certificates: BTreeMap::new(),
             trust_client_certs: false,
             discovery_server_url: None,
             tcp_config: TcpConfig {
@@ -480,6 +580,7 @@ impl Default for ServerConfig {
                 hello_timeout: constants::DEFAULT_HELLO_TIMEOUT_SECONDS,
             },
             limits: ServerLimits::default(),
+            sampling: SamplingConfig::default(),
             user_tokens: BTreeMap::new(),
             locale_ids: vec!["en".to_string()],
             discovery_urls: Vec::new(),
@@ -508,6 +609,7 @@ impl ServerConfig {
             product_uri,
             pki_dir,
             create_sample_keypair: false,
+            policy_certificates: BTreeMap::new(),
             trust_client_certs: false,
             discovery_server_url,
             tcp_config: TcpConfig {
@@ -516,6 +618,7 @@ impl ServerConfig {
                 hello_timeout: constants::DEFAULT_HELLO_TIMEOUT_SECONDS,
             },
             limits: ServerLimits::default(),
+            sampling: SamplingConfig::default(),
             locale_ids,
             user_tokens,
             discovery_urls,
@@ -530,6 +633,7 @@ impl ServerConfig {
             max_string_length: self.limits.max_string_length as usize,
             max_byte_string_length: self.limits.max_byte_string_length as usize,
             max_array_length: self.limits.max_array_length as usize,
+            ..DecodingLimits::default()
         }
     }
 
@@ -74,6 +74,7 @@ pub mod http;
 pub mod address_space;
 pub mod builder;
 pub mod callbacks;
+pub mod clock;
 pub mod comms;
 pub mod config;
 pub mod continuation_point;
@@ -101,6 +102,7 @@ pub mod prelude {
         address_space::{AccessLevel, EventNotifier, UserAccessLevel},
         builder::*,
         callbacks::*,
+        clock::*,
         config::*,
         events::event::*,
         historical::*,
@@ -120,23 +122,41 @@ pub mod constants {
     pub const DEFAULT_MAX_SUBSCRIPTIONS: u32 = 100;
     /// Default maximum number of monitored items per subscription
     pub const DEFAULT_MAX_MONITORED_ITEMS_PER_SUB: u32 = 1000;
+    /// Default maximum number of monitored items ticked per subscription per cycle, 0 for no limit
+    pub const DEFAULT_MAX_MONITORED_ITEMS_PER_TICK: u32 = 0;
+    /// Default maximum number of notifications queued on the monitored items of a session, 0 for no limit
+    pub const DEFAULT_MAX_SESSION_QUEUED_NOTIFICATIONS: u32 = 0;
     /// Default, well known address for TCP discovery server
     pub const DEFAULT_DISCOVERY_SERVER_URL: &str = "opc.tcp://localhost:4840/UADiscovery";
 
     // Internally controlled values
 
-    /// The polling interval in millis on subscriptions and monitored items. The more
-    /// fine-grained this is, the more often subscriptions will be checked for changes. The minimum
-    /// publish interval cannot be less than this.
+    /// The default polling interval in millis on subscriptions and monitored items, configurable as
+    /// the sampling tick interval. The more fine-grained this is, the more often subscriptions will
+    /// be checked for changes. The minimum publish interval cannot be less than this.
     pub const SUBSCRIPTION_TIMER_RATE_MS: u64 = 100;
     /// Minimum publishing interval for subscriptions
     pub const MIN_PUBLISHING_INTERVAL: f64 = (SUBSCRIPTION_TIMER_RATE_MS as f64) / 1000.0;
     /// Minimum sampling interval on monitored items
     pub const MIN_SAMPLING_INTERVAL: f64 = (SUBSCRIPTION_TIMER_RATE_MS as f64) / 1000.0;
+    /// Sampling interval in millis given to a monitored item that requests a negative interval other than -1
+    pub const DEFAULT_SAMPLING_INTERVAL: f64 = 1000.0;
     /// Maximum data change queue allowed by clients on monitored items
     pub const MAX_DATA_CHANGE_QUEUE_SIZE: usize = 10;
+    /// Default data change queue size used when a client requests a queue size of 0
+    pub const DEFAULT_DATA_CHANGE_QUEUE_SIZE: u32 = 1;
+    /// Default discard policy used when a client requests a queue size of 0
+    pub const DEFAULT_DISCARD_OLDEST: bool = true;
+    /// Default smallest absolute change reported by a percent deadband, however small the EURange
+    pub const DEFAULT_PERCENT_DEADBAND_FLOOR: f64 = 1e-9;
+    /// Default for staggering the first samples of monitored items across their sampling interval
+    pub const DEFAULT_STAGGER_FIRST_SAMPLES: bool = false;
+    /// Default for only reporting the value sampled when a monitored item is created if it is Good
+    pub const DEFAULT_INITIAL_VALUE_GOOD_ONLY: bool = false;
     /// The default size of preallocated vecs of monitored items per subscription
     pub const DEFAULT_MONITORED_ITEM_CAPACITY: usize = 100;
+    /// Number of recent writes the address space remembers for monitored items in push mode
+    pub const MAX_RECORDED_WRITES: usize = 1000;
     /// Interval to check for HELLO timeout in millis. This can be fairly coarse because it's not
     /// something that requires huge accuracy.
     pub const HELLO_TIMEOUT_POLL_MS: u64 = 500;
@@ -7,6 +7,7 @@
 use std::{
     marker::Sync,
     net::SocketAddr,
+    str::FromStr,
     sync::{Arc, RwLock},
     time::{Duration, Instant},
 };
@@ -27,6 +28,7 @@ use crate::{
     address_space::types::AddressSpace,
     comms::tcp_transport::*,
     comms::transport::Transport,
+    clock::SystemClock,
     config::ServerConfig,
     constants,
     events::audit::AuditLog,
@@ -35,6 +37,7 @@ use crate::{
     services::message_handler::MessageHandler,
     session::Session,
     state::{OperationalLimits, ServerState},
+    subscriptions::monitored_item::MonitoredItemDefaults,
     util::PollingAction,
 };
 
@@ -108,6 +111,16 @@ impl Server {
         let base_endpoint = format!("opc.tcp://{}:{}", config.tcp_config.host, config.tcp_config.port);
         let max_subscriptions = config.limits.max_subscriptions as usize;
         let max_monitored_items_per_sub = config.limits.max_monitored_items_per_sub as usize;
+        let max_monitored_items_per_tick = config.limits.max_monitored_items_per_tick as usize;
+        let max_session_queued_notifications = config.limits.max_session_queued_notifications as usize;
+        let monitored_item_defaults = MonitoredItemDefaults {
+            queue_size: config.limits.default_queue_size as usize,
+            discard_oldest: config.limits.default_discard_oldest,
+            percent_deadband_floor: config.limits.percent_deadband_floor,
+            stagger_first_samples: config.limits.stagger_first_samples,
+            initial_value_good_only: config.limits.initial_value_good_only,
+            sampling_quantum: config.sampling.tick_interval,
+        };
         let diagnostics = Arc::new(RwLock::new(ServerDiagnostics::default()));
         let min_publishing_interval_ms = config.limits.min_publishing_interval * 1000.0;
         let min_sampling_interval_ms = config.limits.min_sampling_interval * 1000.0;
@@ -121,6 +134,19 @@ impl Server {
             error!("Server is missing its application instance certificate and/or its private key. Encrypted endpoints will not function correctly.")
         }
 
+        // Certificates that endpoints with a particular security policy use instead
+        let policy_certificates = config.policy_certificates.iter().filter_map(|(security_policy, policy_certificate)| {
+            let certificate_path = config.pki_dir.join(&policy_certificate.certificate_path);
+            let private_key_path = config.pki_dir.join(&policy_certificate.private_key_path);
+            match (CertificateStore::read_cert(&certificate_path), CertificateStore::read_pkey(&private_key_path)) {
+                (Ok(cert), Ok(pkey)) => Some((SecurityPolicy::from_str(security_policy).unwrap(), cert, pkey)),
+                (Err(err), _) | (_, Err(err)) => {
+                    error!("Cannot read the certificate for security policy {}, the server certificate will be used instead - {}", security_policy, err);
+                    None
+                }
+            }
+        }).collect();
+
         // Load thumbprints of every user token
         config.read_x509_thumbprints();
 
@@ -128,7 +154,7 @@ impl Server {
         // This is strongly not advised in production.
         if config.trust_client_certs {
             info!("Server has chosen to auto trust client certificates. You do not want to do this in production code.");
-            certificate_store.trust_unknown_certs = true;
+            certificate_store.validation_options.trust_unknown_certs = true;
         }
 
         let config = Arc::new(RwLock::new(config.clone()));
@@ -152,9 +178,13 @@ impl Server {
             config,
             server_certificate,
             server_pkey,
+            policy_certificates,
             last_subscription_id: 0,
             max_subscriptions,
             max_monitored_items_per_sub,
+            max_monitored_items_per_tick,
+            max_session_queued_notifications,
+            monitored_item_defaults,
             min_publishing_interval_ms,
             min_sampling_interval_ms,
             default_keep_alive_count: constants::DEFAULT_KEEP_ALIVE_COUNT,
@@ -167,6 +197,7 @@ impl Server {
             unregister_nodes_callback: None,
             historical_data_provider: None,
             historical_event_provider: None,
+            clock: Arc::new(SystemClock),
             operational_limits: OperationalLimits::default()
         };
         let server_state = Arc::new(RwLock::new(server_state));
@@ -571,11 +602,12 @@ impl Server {
             connections.push(connection.clone());
         }
 
-        // Looping interval has to cope with whatever sampling rate server needs
+        // Subscriptions are ticked at the configured quantum, which is also the fastest that any
+        // monitored item samples
         let looping_interval_ms = {
             let server_state = trace_read_lock_unwrap!(self.server_state);
-            // Get the minimum interval in ms
-            f64::min(server_state.min_publishing_interval_ms, server_state.min_sampling_interval_ms)
+            let config = trace_read_lock_unwrap!(server_state.config);
+            config.sampling.tick_interval
         };
 
         // Run adds a session task to the tokio session
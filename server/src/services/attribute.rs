@@ -71,8 +71,9 @@ impl AttributeService {
                 let session = trace_read_lock_unwrap!(session);
                 let address_space = trace_read_lock_unwrap!(address_space);
                 let timestamps_to_return = request.timestamps_to_return;
+                let now = DateTime::from(server_state.now());
                 let results = nodes_to_read.iter().map(|node_to_read| {
-                    Self::read_node_value(&session, &address_space, node_to_read, request.max_age, timestamps_to_return)
+                    Self::read_node_value(&session, &address_space, node_to_read, request.max_age, timestamps_to_return, &now)
                 }).collect();
 
                 let diagnostic_infos = None;
@@ -126,13 +127,21 @@ impl AttributeService {
         } else {
             // TODO audit - generate AuditWriteUpdateEventType event
             let server_state = trace_read_lock_unwrap!(server_state);
-            let session = trace_read_lock_unwrap!(session);
+            let mut session = trace_write_lock_unwrap!(session);
             let mut address_space = trace_write_lock_unwrap!(address_space);
 
             let nodes_to_write = request.nodes_to_write.as_ref().unwrap();
             if nodes_to_write.len() <= server_state.operational_limits.max_nodes_per_write {
+                let now = server_state.now();
+                let timestamp = DateTime::from(now);
                 let results = nodes_to_write.iter().map(|node_to_write| {
-                    Self::write_node_value(&session, &mut address_space, node_to_write)
+                    let status_code = Self::write_node_value(&session, &mut address_space, node_to_write, &timestamp);
+                    if status_code.is_good() {
+                        // Monitored items in push mode sample the write, those of this session now
+                        // and those of other sessions when their subscriptions next tick
+                        Self::notify_write(&mut session, &mut address_space, &now, node_to_write);
+                    }
+                    status_code
                 }).collect();
 
                 let diagnostic_infos = None;
@@ -327,7 +336,7 @@ impl AttributeService {
         }
     }
 
-    fn read_node_value(session: &Session, address_space: &AddressSpace, node_to_read: &ReadValueId, max_age: f64, timestamps_to_return: TimestampsToReturn) -> DataValue {
+    fn read_node_value(session: &Session, address_space: &AddressSpace, node_to_read: &ReadValueId, max_age: f64, timestamps_to_return: TimestampsToReturn, now: &DateTime) -> DataValue {
         // Node node found
         // debug!("read_node_value asked to read node id {}, attribute {}", node_to_read.node_id, node_to_read.attribute_id);
         let mut result_value = DataValue::null();
@@ -360,7 +369,7 @@ impl AttributeService {
                     // Caller must request binary
                     debug!("read_node_value result for read node id {}, attribute {} is invalid data encoding", node_to_read.node_id, node_to_read.attribute_id);
                     result_value.status = Some(StatusCode::BadDataEncodingInvalid);
-                } else if let Some(attribute) = node.as_node().get_attribute_max_age(attribute_id, index_range, &node_to_read.data_encoding, max_age) {
+                } else if let Some(attribute) = Self::read_attribute(node, attribute_id, index_range, &node_to_read.data_encoding, max_age, now) {
                     // If caller was reading the user access level, this needs to be modified to
                     // take account of the effective level based on who is logged in.
                     let value = if attribute_id == AttributeId::UserAccessLevel {
@@ -438,6 +447,17 @@ impl AttributeService {
         session.effective_user_access_level(user_access_level, &node.node_id(), attribute_id)
     }
 
+    /// Reads an attribute of the node. A variable's value is read with the time of the server's
+    /// clock so that a value read with a max age is stamped with it.
+    fn read_attribute(node: &NodeType, attribute_id: AttributeId, index_range: NumericRange, data_encoding: &QualifiedName, max_age: f64, now: &DateTime) -> Option<DataValue> {
+        match node {
+            NodeType::Variable(ref variable) if attribute_id == AttributeId::Value => {
+                Some(variable.value_at(index_range, data_encoding, max_age, now))
+            }
+            _ => node.as_node().get_attribute_max_age(attribute_id, index_range, data_encoding, max_age)
+        }
+    }
+
     fn is_readable(session: &Session, node: &NodeType, attribute_id: AttributeId) -> bool {
         // TODO session for current user
         // Check for access level, user access level
@@ -548,7 +568,15 @@ impl AttributeService {
         valid
     }
 
-    fn write_node_value(session: &Session, address_space: &mut AddressSpace, node_to_write: &WriteValue) -> StatusCode {
+    fn notify_write(session: &mut Session, address_space: &mut AddressSpace, now: &DateTimeUtc, node_to_write: &WriteValue) {
+        if let Ok(attribute_id) = AttributeId::from_u32(node_to_write.attribute_id) {
+            let index_range = node_to_write.index_range.as_ref().parse::<NumericRange>().unwrap_or(NumericRange::None);
+            address_space.notify_write(&node_to_write.node_id, attribute_id, index_range);
+            session.subscriptions_mut().process_writes(address_space, now);
+        }
+    }
+
+    fn write_node_value(session: &Session, address_space: &mut AddressSpace, node_to_write: &WriteValue, now: &DateTime) -> StatusCode {
         if let Some(node) = address_space.find_node(&node_to_write.node_id) {
             if let Ok(attribute_id) = AttributeId::from_u32(node_to_write.attribute_id) {
                 let index_range = node_to_write.index_range.as_ref().parse::<NumericRange>();
@@ -591,7 +619,7 @@ impl AttributeService {
                         let result = if attribute_id == AttributeId::Value {
                             match node {
                                 NodeType::Variable(ref mut variable) => {
-                                    variable.set_value(index_range, value.clone())
+                                    variable.set_value_at(index_range, value.clone(), now)
                                         .map_err(|err| {
                                             error!("Value could not be set to node {} Value, error = {}", node_to_write.node_id, err);
                                             err
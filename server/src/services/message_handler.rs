@@ -4,8 +4,6 @@
 
 use std::sync::{Arc, RwLock};
 
-use chrono::Utc;
-
 use opcua_core::supported_message::SupportedMessage;
 use opcua_crypto::{CertificateStore, SecurityPolicy};
 use opcua_types::{*, status_code::StatusCode};
@@ -242,7 +240,7 @@ impl MessageHandler {
             }
             SupportedMessage::ModifyMonitoredItemsRequest(request) => {
                 Self::validate_security_and_active_session(&message, session.clone(), MODIFY_MONITORED_ITEMS_COUNT, move || {
-                    self.monitored_item_service.modify_monitored_items(session, address_space, request)
+                    self.monitored_item_service.modify_monitored_items(server_state, session, address_space, request)
                 })
             }
             SupportedMessage::SetMonitoringModeRequest(request) => {
@@ -297,7 +295,11 @@ impl MessageHandler {
                     // Unlike other calls which return immediately, this one is asynchronous - the
                     // request is queued and the response will come back out of sequence some time in
                     // the future.
-                    self.subscription_service.async_publish(&Utc::now(), session, address_space, request_id, &request)
+                    let now = {
+                        let server_state = trace_read_lock_unwrap!(server_state);
+                        server_state.now()
+                    };
+                    self.subscription_service.async_publish(&now, session, address_space, request_id, &request)
                 }
             }
             SupportedMessage::RepublishRequest(request) => {
@@ -37,8 +37,8 @@ impl MonitoredItemService {
             let items_to_create = request.items_to_create.as_ref().unwrap();
             // Find subscription and add items to it
             if let Some(subscription) = session.subscriptions_mut().get_mut(request.subscription_id) {
-                let now = chrono::Utc::now();
-                let results = Some(subscription.create_monitored_items(&address_space, &now, request.timestamps_to_return, items_to_create, server_state.max_monitored_items_per_sub));
+                let now = server_state.now();
+                let results = Some(subscription.create_monitored_items(&address_space, &now, request.timestamps_to_return, items_to_create, server_state.max_monitored_items_per_sub, &server_state.monitored_item_defaults));
                 let response = CreateMonitoredItemsResponse {
                     response_header: ResponseHeader::new_good(&request.request_header),
                     results,
@@ -53,17 +53,18 @@ impl MonitoredItemService {
     }
 
     /// Implementation of ModifyMonitoredItems service. See OPC Unified Architecture, Part 4 5.12.3
-    pub fn modify_monitored_items(&self, session: Arc<RwLock<Session>>, address_space: Arc<RwLock<AddressSpace>>, request: &ModifyMonitoredItemsRequest) -> SupportedMessage {
+    pub fn modify_monitored_items(&self, server_state: Arc<RwLock<ServerState>>, session: Arc<RwLock<Session>>, address_space: Arc<RwLock<AddressSpace>>, request: &ModifyMonitoredItemsRequest) -> SupportedMessage {
         if is_empty_option_vec!(request.items_to_modify) {
             self.service_fault(&request.request_header, StatusCode::BadNothingToDo)
         } else {
+            let server_state = trace_read_lock_unwrap!(server_state);
             let mut session = trace_write_lock_unwrap!(session);
             let address_space = trace_read_lock_unwrap!(address_space);
             let items_to_modify = request.items_to_modify.as_ref().unwrap();
             // Find subscription and modify items in it
             let subscription_id = request.subscription_id;
             if let Some(subscription) = session.subscriptions_mut().get_mut(subscription_id) {
-                let results = Some(subscription.modify_monitored_items(&address_space, request.timestamps_to_return, items_to_modify));
+                let results = Some(subscription.modify_monitored_items(&address_space, request.timestamps_to_return, items_to_modify, &server_state.monitored_item_defaults));
                 ModifyMonitoredItemsResponse {
                     response_header: ResponseHeader::new_good(&request.request_header),
                     results,
@@ -85,10 +86,7 @@ impl MonitoredItemService {
             let monitored_item_ids = request.monitored_item_ids.as_ref().unwrap();
             let subscription_id = request.subscription_id;
             if let Some(subscription) = session.subscriptions_mut().get_mut(subscription_id) {
-                let monitoring_mode = request.monitoring_mode;
-                let results = monitored_item_ids.iter().map(|i| {
-                    subscription.set_monitoring_mode(*i, monitoring_mode)
-                }).collect();
+                let results = subscription.set_monitoring_modes(request.monitoring_mode, monitored_item_ids);
                 SetMonitoringModeResponse {
                     response_header: ResponseHeader::new_good(&request.request_header),
                     results: Some(results),
@@ -106,7 +106,7 @@ impl SessionService {
                 let max_request_message_size = constants::MAX_REQUEST_MESSAGE_SIZE;
 
                 // Calculate a signature (assuming there is a pkey)
-                let server_signature = if let Some(ref pkey) = server_state.server_pkey {
+                let server_signature = if let Some(pkey) = server_state.server_pkey_for_policy(security_policy) {
                     crypto::create_signature_data(pkey, security_policy, &request.client_certificate, &request.client_nonce)
                         .unwrap_or_else(|err| {
                             error!("Cannot create signature data from private key, check log and error {:?}", err);
@@ -117,7 +117,9 @@ impl SessionService {
                 };
                 let authentication_token = NodeId::new(0, random::byte_string(32));
                 let server_nonce = security_policy.random_nonce();
-                let server_certificate = server_state.server_certificate_as_byte_string();
+                let server_certificate = server_state.server_certificate_for_policy(security_policy)
+                    .map(|server_certificate| server_certificate.as_byte_string())
+                    .unwrap_or_else(ByteString::null);
                 let server_endpoints = Some(endpoints);
 
                 session.set_authentication_token(authentication_token.clone());
@@ -216,6 +218,18 @@ impl SessionService {
 
         audit::log_close_session(&server_state, &session, address_space, true, request);
 
+        if request.delete_subscriptions {
+            // Subscriptions are deleted along with their monitored items and queued notifications
+            let deleted_subscriptions = session.delete_subscriptions();
+            if !deleted_subscriptions.is_empty() {
+                debug!("Closing session {} deleted subscriptions {:?}", session.session_id(), deleted_subscriptions);
+            }
+        } else {
+            // Subscriptions are kept so they can be transferred to another session. Without
+            // publish requests they expire when their lifetime count runs out.
+            debug!("Closing session {} kept its subscriptions", session.session_id());
+        }
+
         CloseSessionResponse {
             response_header: ResponseHeader::new_good(&request.request_header),
         }.into()
@@ -233,12 +247,12 @@ impl SessionService {
     /// from the server's certificate and nonce.
     fn verify_client_signature(server_state: &ServerState, session: &Session, client_signature: &SignatureData) -> StatusCode {
         if let Some(ref client_certificate) = session.client_certificate() {
-            if let Some(ref server_certificate) = server_state.server_certificate {
-                let security_policy = {
-                    let secure_channel = session.secure_channel();
-                    let secure_channel = trace_read_lock_unwrap!(secure_channel);
-                    secure_channel.security_policy()
-                };
+            let security_policy = {
+                let secure_channel = session.secure_channel();
+                let secure_channel = trace_read_lock_unwrap!(secure_channel);
+                secure_channel.security_policy()
+            };
+            if let Some(server_certificate) = server_state.server_certificate_for_policy(security_policy) {
                 crypto::verify_signature_data(client_signature, security_policy, client_certificate, server_certificate, session.session_nonce().as_ref())
             } else {
                 error!("Client signature verification failed, server has no server certificate");
@@ -33,6 +33,7 @@ impl SubscriptionService {
         let mut server_state = trace_write_lock_unwrap!(server_state);
         let mut session = trace_write_lock_unwrap!(session);
 
+        let session_id = session.session_id().clone();
         let subscriptions = session.subscriptions_mut();
 
         if server_state.max_subscriptions > 0 && subscriptions.len() >= server_state.max_subscriptions {
@@ -46,7 +47,7 @@ impl SubscriptionService {
 
             // Create a new subscription
             let publishing_enabled = request.publishing_enabled;
-            let subscription = Subscription::new(
+            let mut subscription = Subscription::new(
                 server_state.diagnostics.clone(),
                 subscription_id,
                 publishing_enabled,
@@ -54,6 +55,9 @@ impl SubscriptionService {
                 revised_lifetime_count,
                 revised_max_keep_alive_count,
                 request.priority);
+            subscription.set_session_id(session_id);
+            subscription.set_max_monitored_items_per_tick(server_state.max_monitored_items_per_tick);
+            subscription.set_publishing_interval_start(&server_state.now());
             subscriptions.insert(subscription_id, subscription);
 
             // Create the response
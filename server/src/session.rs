@@ -163,8 +163,14 @@ impl Session {
             (config.decoding_limits(), config.limits.clients_can_modify_address_space)
         };
 
+        let mut secure_channel = SecureChannel::new(server.certificate_store(), Role::Server, decoding_limits);
+        secure_channel.set_policy_certs(server_state.policy_certificates.clone());
+
+        let mut subscriptions = Subscriptions::new(max_subscriptions, PUBLISH_REQUEST_TIMEOUT);
+        subscriptions.set_max_queued_notifications(server_state.max_session_queued_notifications);
+
         let session = Session {
-            subscriptions: Subscriptions::new(max_subscriptions, PUBLISH_REQUEST_TIMEOUT),
+            subscriptions,
             session_id: next_session_id(),
             activated: false,
             terminate_session: false,
@@ -173,7 +179,7 @@ impl Session {
             client_certificate: None,
             security_policy_uri: String::new(),
             authentication_token: NodeId::null(),
-            secure_channel: Arc::new(RwLock::new(SecureChannel::new(server.certificate_store(), Role::Server, decoding_limits))),
+            secure_channel: Arc::new(RwLock::new(secure_channel)),
             session_nonce: ByteString::null(),
             session_name: UAString::null(),
             session_timeout: 0f64,
@@ -292,6 +298,25 @@ impl Session {
         &mut self.subscriptions
     }
 
+    /// Deletes the subscriptions owned by this session together with their monitored items and
+    /// queued notifications. Returns the ids of the deleted subscriptions.
+    pub(crate) fn delete_subscriptions(&mut self) -> Vec<u32> {
+        self.subscriptions.delete_session_subscriptions(&self.session_id)
+    }
+
+    /// Transfers a subscription from this session to another. The subscription keeps its monitored
+    /// items, the notifications queued on them and any notifications awaiting acknowledgement, so
+    /// the new session can carry on publishing where this one left off.
+    pub(crate) fn transfer_subscription(&mut self, subscription_id: u32, to: &mut Session) -> StatusCode {
+        if let Some(detached) = self.subscriptions.detach(subscription_id) {
+            debug!("Transferring subscription {} from session {} to session {}", subscription_id, self.session_id, to.session_id);
+            to.subscriptions.attach(&to.session_id, detached);
+            StatusCode::Good
+        } else {
+            StatusCode::BadSubscriptionIdInvalid
+        }
+    }
+
     pub(crate) fn enqueue_publish_request(&mut self, now: &DateTimeUtc, request_id: u32, request: PublishRequest, address_space: &AddressSpace) -> Result<(), StatusCode> {
         self.subscriptions.enqueue_publish_request(now, request_id, request, address_space)
     }
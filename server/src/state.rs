@@ -7,7 +7,7 @@
 use std::sync::{Arc, RwLock};
 
 use opcua_core::prelude::*;
-use opcua_crypto::{PrivateKey, SecurityPolicy, user_identity, X509};
+use opcua_crypto::{available_security_policies, PrivateKey, SecurityPolicy, user_identity, X509};
 use opcua_types::{
     profiles,
     service_types::{
@@ -20,6 +20,7 @@ use opcua_types::{
 
 use crate::{
     callbacks::{RegisterNodes, UnregisterNodes},
+    clock::Clock,
     config::{ServerConfig, ServerEndpoint},
     constants,
     diagnostics::ServerDiagnostics,
@@ -29,6 +30,7 @@ use crate::{
     },
     historical::{HistoricalDataProvider, HistoricalEventProvider},
     identity_token::{IdentityToken, POLICY_ID_ANONYMOUS, POLICY_ID_USER_PASS_NONE, POLICY_ID_USER_PASS_RSA_15, POLICY_ID_USER_PASS_RSA_OAEP, POLICY_ID_X509},
+    subscriptions::monitored_item::MonitoredItemDefaults,
 };
 
 pub(crate) struct OperationalLimits {
@@ -86,6 +88,9 @@ pub struct ServerState {
     pub server_certificate: Option<X509>,
     /// Server private key
     pub server_pkey: Option<PrivateKey>,
+    /// Certificates and private keys that endpoints with a particular security policy use instead
+    /// of the server certificate and private key
+    pub policy_certificates: Vec<(SecurityPolicy, X509, PrivateKey)>,
     /// The next subscription id - subscriptions are shared across the whole server. Initial value
     /// is a random u32.
     pub last_subscription_id: u32,
@@ -93,6 +98,12 @@ pub struct ServerState {
     pub max_subscriptions: usize,
     /// Maximum number of monitored items per subscription, 0 means no limit (danger)
     pub max_monitored_items_per_sub: usize,
+    /// Maximum number of monitored items ticked per subscription per cycle, 0 means no limit
+    pub max_monitored_items_per_tick: usize,
+    /// Maximum number of notifications queued on the monitored items of a session, 0 means no limit
+    pub max_session_queued_notifications: usize,
+    /// Defaults for monitored items when the client requests a queue size of 0
+    pub(crate) monitored_item_defaults: MonitoredItemDefaults,
     /// Minimum publishing interval (in millis)
     pub min_publishing_interval_ms: Duration,
     /// Minimum sampling interval (in millis)
@@ -121,6 +132,8 @@ pub struct ServerState {
     pub(crate) historical_data_provider: Option<Box<dyn HistoricalDataProvider + Send + Sync>>,
     /// Callback for historical events
     pub(crate) historical_event_provider: Option<Box<dyn HistoricalEventProvider + Send + Sync>>,
+    /// The clock that subscriptions are ticked with and sampled values are stamped from
+    pub(crate) clock: Arc<dyn Clock + Send + Sync>,
 }
 
 impl ServerState {
@@ -147,6 +160,7 @@ impl ServerState {
                 debug!("Endpoint url \"{}\" hostname supplied by caller does not match server's hostname \"{}\"", endpoint_url, &config.tcp_config.host);
             }
             let endpoints = config.endpoints.iter()
+                .filter(|(_, e)| Self::is_available_endpoint(e))
                 .map(|(_, e)| {
                     self.new_endpoint_description(&config, e, true)
                 })
@@ -177,11 +191,23 @@ impl ServerState {
         let base_endpoint_url = config.base_endpoint_url();
         let endpoints: Vec<EndpointDescription> = config.endpoints.iter().filter(|&(_, e)| {
             // Test end point's security_policy_uri and matching url
-            url_matches_except_host(&e.endpoint_url(&base_endpoint_url), endpoint_url)
+            Self::is_available_endpoint(e) && url_matches_except_host(&e.endpoint_url(&base_endpoint_url), endpoint_url)
         }).map(|(_, e)| self.new_endpoint_description(&config, e, false)).collect();
         if endpoints.is_empty() { None } else { Some(endpoints) }
     }
 
+    /// Tests if the endpoint's security policy is available in this build. Endpoints that are not
+    /// are left out of the descriptions given to clients.
+    fn is_available_endpoint(endpoint: &ServerEndpoint) -> bool {
+        let security_policy = endpoint.security_policy();
+        if available_security_policies().contains(&security_policy) {
+            true
+        } else {
+            debug!("Endpoint {} is not advertised because security policy {} is not available", endpoint.path, security_policy);
+            false
+        }
+    }
+
     /// Determine what user/pass encryption to use depending on the security policy.
     fn user_pass_security_policy_id(endpoint: &ServerEndpoint) -> UAString {
         match endpoint.password_security_policy() {
@@ -260,7 +286,7 @@ impl ServerState {
                 gateway_server_uri: self.gateway_server_uri(),
                 discovery_profile_uri: UAString::null(),
                 discovery_urls: self.discovery_urls(),
-            }, self.server_certificate_as_byte_string())
+            }, self.endpoint_server_certificate(endpoint))
         } else {
             (ApplicationDescription {
                 application_uri: UAString::null(),
@@ -285,6 +311,19 @@ impl ServerState {
         }
     }
 
+    /// Returns the DER encoded certificate that a client uses to authenticate the endpoint and to
+    /// secure the channel to it. That is the certificate configured for the endpoint's security
+    /// policy, or else the server certificate, which is also used by the None policy where a
+    /// client still needs it to encrypt user identity tokens.
+    fn endpoint_server_certificate(&self, endpoint: &ServerEndpoint) -> ByteString {
+        if let Some(server_certificate) = self.server_certificate_for_policy(endpoint.security_policy()) {
+            server_certificate.as_byte_string()
+        } else {
+            warn!("Endpoint {} / {} has no server certificate to describe it", endpoint.path, endpoint.security_policy);
+            ByteString::null()
+        }
+    }
+
     pub fn discovery_urls(&self) -> Option<Vec<UAString>> {
         let config = trace_read_lock_unwrap!(self.config);
         if config.discovery_urls.is_empty() {
@@ -322,6 +361,22 @@ impl ServerState {
         }
     }
 
+    /// Returns the certificate configured for the security policy, or else the server certificate
+    pub fn server_certificate_for_policy(&self, security_policy: SecurityPolicy) -> Option<&X509> {
+        match self.policy_certificates.iter().find(|(p, _, _)| *p == security_policy) {
+            Some((_, cert, _)) => Some(cert),
+            None => self.server_certificate.as_ref()
+        }
+    }
+
+    /// Returns the private key configured for the security policy, or else the server private key
+    pub fn server_pkey_for_policy(&self, security_policy: SecurityPolicy) -> Option<&PrivateKey> {
+        match self.policy_certificates.iter().find(|(p, _, _)| *p == security_policy) {
+            Some((_, _, pkey)) => Some(pkey),
+            None => self.server_pkey.as_ref()
+        }
+    }
+
     pub fn registered_server(&self) -> RegisteredServer {
         let server_uri = self.application_uri.clone();
         let product_uri = self.product_uri.clone();
@@ -369,10 +424,10 @@ impl ServerState {
                     Self::authenticate_anonymous_token(endpoint, &token)
                 }
                 IdentityToken::UserNameIdentityToken(token) => {
-                    self.authenticate_username_identity_token(&config, endpoint, &token, &self.server_pkey, server_nonce)
+                    self.authenticate_username_identity_token(&config, endpoint, &token, self.server_pkey_for_policy(security_policy), server_nonce)
                 }
                 IdentityToken::X509IdentityToken(token) => {
-                    self.authenticate_x509_identity_token(&config, endpoint, &token, &request.user_token_signature, &self.server_certificate, server_nonce)
+                    self.authenticate_x509_identity_token(&config, endpoint, &token, &request.user_token_signature, self.server_certificate_for_policy(security_policy), server_nonce)
                 }
                 IdentityToken::Invalid(o) => {
                     error!("User identity token type {:?} is unsupported", o.node_id);
@@ -412,7 +467,7 @@ impl ServerState {
 
     /// Authenticates the username identity token with the supplied endpoint. The function returns the user token identifier
     /// that matches the identity token.
-    fn authenticate_username_identity_token(&self, config: &ServerConfig, endpoint: &ServerEndpoint, token: &UserNameIdentityToken, server_key: &Option<PrivateKey>, server_nonce: &ByteString) -> Result<String, StatusCode> {
+    fn authenticate_username_identity_token(&self, config: &ServerConfig, endpoint: &ServerEndpoint, token: &UserNameIdentityToken, server_key: Option<&PrivateKey>, server_nonce: &ByteString) -> Result<String, StatusCode> {
         if !endpoint.supports_user_pass(&config.user_tokens) {
             error!("Endpoint doesn't support username password tokens");
             Err(StatusCode::BadIdentityTokenRejected)
@@ -425,7 +480,7 @@ impl ServerState {
         } else {
             debug!("policy id = {}, encryption algorithm = {}", token.policy_id.as_ref(), token.encryption_algorithm.as_ref());
             let token_password = if !token.encryption_algorithm.is_null() {
-                if let Some(ref server_key) = server_key {
+                if let Some(server_key) = server_key {
                     user_identity::decrypt_user_identity_token_password(&token, server_nonce.as_ref(), server_key)?
                 } else {
                     error!("Identity token password is encrypted but no server private key was supplied");
@@ -464,7 +519,7 @@ impl ServerState {
 
     /// Authenticate the x509 token against the endpoint. The function returns the user token identifier
     /// that matches the identity token.
-    fn authenticate_x509_identity_token(&self, config: &ServerConfig, endpoint: &ServerEndpoint, token: &X509IdentityToken, user_token_signature: &SignatureData, server_certificate: &Option<X509>, server_nonce: &ByteString) -> Result<String, StatusCode> {
+    fn authenticate_x509_identity_token(&self, config: &ServerConfig, endpoint: &ServerEndpoint, token: &X509IdentityToken, user_token_signature: &SignatureData, server_certificate: Option<&X509>, server_nonce: &ByteString) -> Result<String, StatusCode> {
         if !endpoint.supports_x509(&config.user_tokens) {
             error!("Endpoint doesn't support x509 tokens");
             Err(StatusCode::BadIdentityTokenRejected)
@@ -473,7 +528,7 @@ impl ServerState {
             Err(StatusCode::BadIdentityTokenRejected)
         } else {
             let result = match server_certificate {
-                Some(ref server_certificate) => {
+                Some(server_certificate) => {
 
                     // Find the security policy used for verifying tokens
                     let user_identity_tokens = self.user_identity_tokens(config, endpoint);
@@ -522,6 +577,18 @@ impl ServerState {
         self.historical_event_provider = Some(historical_event_provider);
     }
 
+    /// Replaces the clock of the server, e.g. with a `SimulationClock` to run the server in
+    /// simulated time. Subscriptions are ticked with the time of this clock and it supplies the
+    /// server timestamp of sampled values that do not have one.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock + Send + Sync>) {
+        self.clock = clock;
+    }
+
+    /// Returns the current time according to the server's clock
+    pub fn now(&self) -> DateTimeUtc {
+        self.clock.now()
+    }
+
     pub(crate) fn raise_and_log<T>(&self, event: T) -> Result<NodeId, ()> where T: AuditEvent + Event {
         let audit_log = trace_write_lock_unwrap!(self.audit_log);
         audit_log.raise_and_log(event)
@@ -2,15 +2,18 @@
 // SPDX-License-Identifier: MPL-2.0
 // Copyright (C) 2017-2020 Adam Lock
 
-use std::collections::{BTreeSet, VecDeque};
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::fmt;
+use std::io::Cursor;
 use std::result::Result;
+use std::sync::{Arc, Mutex};
 
 use opcua_types::{
     *,
     node_ids::ObjectId,
     service_types::{
-        DataChangeFilter, EventFieldList, EventFilter, MonitoredItemCreateRequest, MonitoredItemModifyRequest,
-        MonitoredItemNotification, ReadValueId, TimestampsToReturn,
+        AggregateFilter, AggregateFilterResult, DataChangeFilter, DataChangeTrigger, DeadbandType, EventFieldList, EventFilter, MonitoredItemCreateRequest, MonitoredItemModifyRequest,
+        MonitoredItemNotification, MonitoringParameters, Range, ReadValueId, TimestampsToReturn,
     },
     status_code::StatusCode,
 };
@@ -19,8 +22,10 @@ use crate::{
     address_space::{
         AddressSpace,
         EventNotifier,
-        node::Node,
+        node::{Node, NodeBase, NodeType},
+        variable::Variable,
     },
+    callbacks::QueueOverflow,
     constants,
     events::event_filter,
 };
@@ -43,15 +48,108 @@ impl From<EventFieldList> for Notification {
     }
 }
 
+/// Nodes looked up and values read during a single tick quantum by the items that sample in it.
+/// Items monitoring the same node share one address space lookup, and items monitoring the same
+/// attribute of it for data changes share one read of the value, each applying its own filter.
+#[derive(Default)]
+pub struct SampledNodes<'a> {
+    nodes: HashMap<NodeId, Option<&'a NodeType>>,
+    values: HashMap<(NodeId, AttributeId), Option<DataValue>>,
+}
+
+impl<'a> SampledNodes<'a> {
+    /// Finds the node, looking it up in the address space only the first time it is asked for
+    fn find_node(&mut self, address_space: &'a AddressSpace, node_id: &NodeId) -> Option<&'a NodeType> {
+        *self.nodes.entry(node_id.clone()).or_insert_with(|| address_space.find_node(node_id))
+    }
+
+    /// Reads the attribute of the node, calling the node only the first time it is asked for
+    fn read_value(&mut self, node: &dyn Node, attribute_id: AttributeId) -> Option<DataValue> {
+        self.values.entry((node.node_id(), attribute_id))
+            .or_insert_with(|| node.get_attribute(attribute_id, NumericRange::None, &QualifiedName::null()))
+            .clone()
+    }
+
+    /// Returns the number of address space lookups that have been made
+    pub fn lookups(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns the number of values that have been read
+    pub fn reads(&self) -> usize {
+        self.values.len()
+    }
+}
+
+/// What happens to the queued notifications of a monitored item when a new one arrives and the
+/// queue is full. The spec only defines discarding the oldest or the newest notification, which is
+/// what the client chooses with `discard_oldest`, but a server may override that for an item.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum OverflowStrategy {
+    /// The oldest notification is discarded to make room for the new one
+    DiscardOldest,
+    /// The newest notification is replaced by the new one, keeping the older notifications
+    DiscardNewest,
+    /// Every queued notification is discarded and only the new one, marked with the overflow
+    /// bit, remains
+    DropAllMarkOverflow,
+}
+
+/// The callback a monitored item makes when its queue goes into overflow. Items holding the same
+/// callback are equal.
+#[derive(Clone)]
+pub(crate) struct QueueOverflowCallback(Arc<Mutex<dyn QueueOverflow + Send>>);
+
+impl fmt::Debug for QueueOverflowCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[queue overflow callback]")
+    }
+}
+
+impl PartialEq for QueueOverflowCallback {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::as_ptr(&self.0) as *const u8 == Arc::as_ptr(&other.0) as *const u8
+    }
+}
+
+/// A queue overflow waiting to be passed to the item's callback. Overflows happen while the
+/// session is locked so they are collected and the callbacks made once it is unlocked.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct QueueOverflowEvent {
+    callback: QueueOverflowCallback,
+    monitored_item_id: u32,
+    dropped: usize,
+}
+
+impl QueueOverflowEvent {
+    /// Makes the callback. A callback that panicked in an earlier call is not called again.
+    pub fn notify(self) {
+        match self.callback.0.lock() {
+            Ok(mut callback) => callback.on_queue_overflow(self.monitored_item_id, self.dropped),
+            Err(_) => error!("Queue overflow callback for monitored item {} panicked previously and will not be called", self.monitored_item_id)
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize)]
 pub(crate) enum FilterType {
     None,
     DataChangeFilter(DataChangeFilter),
     EventFilter(EventFilter),
+    AggregateFilter(AggregateFilter),
 }
 
 impl FilterType {
-    pub fn from_filter(filter: &ExtensionObject) -> Result<FilterType, StatusCode> {
+    /// Decodes the monitoring filter held in the extension object, choosing the concrete filter
+    /// from the extension object's type id. A null extension object means there is no filter.
+    ///
+    /// # Errors
+    ///
+    /// BadMonitoredItemFilterUnsupported indicates the type id is not a monitoring filter that the
+    /// server supports. BadDeadbandFilterInvalid indicates a data change filter that cannot be
+    /// decoded or has an unknown trigger. A decoding error indicates the body is not a valid filter
+    /// of another type.
+    pub fn from_extension_object(filter: &ExtensionObject) -> Result<FilterType, StatusCode> {
         // Check if the filter is a supported filter type
         let filter_type_id = &filter.node_id;
         if filter_type_id.is_null() {
@@ -61,21 +159,160 @@ impl FilterType {
             match filter_type_id {
                 ObjectId::DataChangeFilter_Encoding_DefaultBinary => {
                     let decoding_limits = DecodingLimits::minimal();
-                    Ok(FilterType::DataChangeFilter(filter.decode_inner::<DataChangeFilter>(&decoding_limits)?))
+                    let filter = filter.decode_inner::<DataChangeFilter>(&decoding_limits).map_err(|status_code| {
+                        error!("Data change filter could not be decoded, status code = {}", status_code);
+                        StatusCode::BadDeadbandFilterInvalid
+                    })?;
+                    let _ = DataChangeTrigger::from_u32(filter.trigger as u32)?;
+                    Ok(FilterType::DataChangeFilter(filter))
                 }
                 ObjectId::EventFilter_Encoding_DefaultBinary => {
                     let decoding_limits = DecodingLimits::default();
                     Ok(FilterType::EventFilter(filter.decode_inner::<EventFilter>(&decoding_limits)?))
                 }
+                ObjectId::AggregateFilter_Encoding_DefaultBinary => {
+                    let decoding_limits = DecodingLimits::default();
+                    Ok(FilterType::AggregateFilter(filter.decode_inner::<AggregateFilter>(&decoding_limits)?))
+                }
                 _ => {
-                    error!("Requested data filter type is not supported, {:?}", filter_type_id);
-                    Err(StatusCode::BadFilterNotAllowed)
+                    let status_code = StatusCode::BadMonitoredItemFilterUnsupported;
+                    error!("Requested data filter type is not supported, {:?}, status code = {}", filter_type_id, status_code);
+                    Err(status_code)
                 }
             }
         } else {
-            error!("Requested data filter type is not an object id, {:?}", filter_type_id);
-            Err(StatusCode::BadFilterNotAllowed)
+            let status_code = StatusCode::BadMonitoredItemFilterUnsupported;
+            error!("Requested data filter type is not an object id, {:?}, status code = {}", filter_type_id, status_code);
+            Err(status_code)
+        }
+    }
+}
+
+/// Values given to a monitored item when the client requests a queue size of 0. They affect the
+/// revised parameters reported back to the client.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MonitoredItemDefaults {
+    pub queue_size: usize,
+    pub discard_oldest: bool,
+    /// The smallest absolute change reported by a percent deadband
+    pub percent_deadband_floor: f64,
+    /// Offsets the first sample of each item by a fraction of its sampling interval
+    pub stagger_first_samples: bool,
+    /// Only reports the initial value of an item if its status is Good
+    pub initial_value_good_only: bool,
+    /// The quantum in milliseconds at which items are ticked. No item samples faster than this,
+    /// 0 for items to sample on every tick however often that is
+    pub sampling_quantum: Duration,
+}
+
+impl Default for MonitoredItemDefaults {
+    fn default() -> Self {
+        MonitoredItemDefaults {
+            queue_size: constants::DEFAULT_DATA_CHANGE_QUEUE_SIZE as usize,
+            discard_oldest: constants::DEFAULT_DISCARD_OLDEST,
+            percent_deadband_floor: constants::DEFAULT_PERCENT_DEADBAND_FLOOR,
+            stagger_first_samples: constants::DEFAULT_STAGGER_FIRST_SAMPLES,
+            initial_value_good_only: constants::DEFAULT_INITIAL_VALUE_GOOD_ONLY,
+            sampling_quantum: 0f64,
+        }
+    }
+}
+
+/// The configuration of a monitored item, without its queued notifications or sampled values, in
+/// a form that can be persisted and used to restore the item.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MonitoredItemSnapshot {
+    pub monitored_item_id: u32,
+    pub node_id: NodeId,
+    pub attribute_id: u32,
+    pub index_range: UAString,
+    pub data_encoding: QualifiedName,
+    /// The numeric value of the `MonitoringMode`
+    pub monitoring_mode: i32,
+    pub client_handle: u32,
+    pub sampling_interval: Duration,
+    pub filter: ExtensionObject,
+    pub queue_size: u32,
+    pub discard_oldest: bool,
+    /// The numeric value of the `TimestampsToReturn`
+    pub timestamps_to_return: i32,
+    pub triggered_items: Vec<u32>,
+    pub coalesce: bool,
+    pub min_notification_interval: Duration,
+    pub source_timestamp_hold_off: bool,
+    pub report_uncertain: bool,
+    #[serde(default)]
+    pub report_heartbeats: bool,
+    #[serde(default)]
+    pub push_on_write: bool,
+    #[serde(default)]
+    pub ignore_deadband: bool,
+    #[serde(default)]
+    pub strict_type_compare: bool,
+    #[serde(default)]
+    pub return_diagnostics: bool,
+    #[serde(default)]
+    pub overflow_strategy: Option<OverflowStrategy>,
+}
+
+/// The numeric samples collected during the processing interval of an aggregate filter
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct AggregateInterval {
+    /// The start of the processing interval
+    start: DateTimeUtc,
+    samples: Vec<f64>,
+}
+
+/// The intervals actually achieved between consecutive samples of a monitored item, in
+/// milliseconds. Comparing them to the requested sampling interval shows if the sampling loop is
+/// keeping up.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SamplingIntervalStats {
+    /// The time of the most recent sample
+    last_sample: Option<DateTimeUtc>,
+    /// The number of intervals measured, one less than the number of samples
+    count: u64,
+    min: Duration,
+    max: Duration,
+    total: Duration,
+}
+
+impl SamplingIntervalStats {
+    /// Records a sample taken at the time, measuring the interval since the previous one
+    pub fn record(&mut self, now: &DateTimeUtc) {
+        if let Some(last_sample) = self.last_sample {
+            let interval = now.signed_duration_since(last_sample).num_microseconds().unwrap_or(i64::MAX) as f64 / 1000f64;
+            if self.count == 0 {
+                self.min = interval;
+                self.max = interval;
+            } else {
+                self.min = f64::min(self.min, interval);
+                self.max = f64::max(self.max, interval);
+            }
+            self.total += interval;
+            self.count += 1;
         }
+        self.last_sample = Some(*now);
+    }
+
+    /// The number of intervals measured
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// The shortest interval between two samples, or None if fewer than two have been taken
+    pub fn min(&self) -> Option<Duration> {
+        if self.count > 0 { Some(self.min) } else { None }
+    }
+
+    /// The longest interval between two samples, or None if fewer than two have been taken
+    pub fn max(&self) -> Option<Duration> {
+        if self.count > 0 { Some(self.max) } else { None }
+    }
+
+    /// The mean interval between samples, or None if fewer than two have been taken
+    pub fn average(&self) -> Option<Duration> {
+        if self.count > 0 { Some(self.total / self.count as f64) } else { None }
     }
 }
 
@@ -96,9 +333,73 @@ pub(crate) struct MonitoredItem {
     /// message, pop back gets the most recent.
     notification_queue: VecDeque<Notification>,
     queue_overflow: bool,
+    /// Called when the queue goes into overflow. It is not part of a snapshot.
+    #[serde(skip)]
+    queue_overflow_callback: Option<QueueOverflowCallback>,
+    /// Overflows waiting to be passed to the callback
+    #[serde(skip)]
+    queue_overflow_events: Vec<QueueOverflowEvent>,
+    /// Set when the session has no room for more notifications, in which case the queue is
+    /// treated as full at its current length.
+    session_queue_full: bool,
+    /// When coalescing, a data change replaces any data change already queued so that only the
+    /// most recent sample is reported per publishing interval, regardless of the queue size.
+    coalesce: bool,
+    /// The minimum time in milliseconds between data change notifications, or 0 for no limit. A
+    /// change inside the interval is held back as the pending notification.
+    min_notification_interval: Duration,
+    last_notification_time: Option<DateTimeUtc>,
+    pending_notification: Option<MonitoredItemNotification>,
+    /// When set, a sample whose source timestamp is the same as the last sample's is treated as no
+    /// new sample, so a slow source polled quickly does not produce duplicate notifications.
+    source_timestamp_hold_off: bool,
+    /// When set, every sample with an uncertain status is reported whether or not it changed.
+    report_uncertain: bool,
+    /// When set, a sample whose source timestamp has moved on while its value and status stay the
+    /// same, i.e. a heartbeat, is reported whatever the trigger. Otherwise heartbeats are only
+    /// reported by the status/value/timestamp trigger.
+    report_heartbeats: bool,
+    /// When set, a write to the monitored node samples the item immediately instead of waiting for
+    /// the next tick. The item is still polled as normal.
+    push_on_write: bool,
+    /// When set, the deadband of a data change filter is ignored and values are compared exactly,
+    /// so any change in value is reported.
+    ignore_deadband: bool,
+    /// When set, a sample whose value is of a different type to the last one is reported as a
+    /// change. Otherwise numbers are widened to a common type so only a change in magnitude is.
+    strict_type_compare: bool,
+    /// When set, a diagnostic info accompanies each data change notification of the item and
+    /// describes a bad status. Otherwise no diagnostics are produced.
+    return_diagnostics: bool,
+    /// When set, overrides the discard policy requested by the client for a full queue
+    overflow_strategy: Option<OverflowStrategy>,
     timestamps_to_return: TimestampsToReturn,
+    /// The cached EURange property of the monitored variable used by percent deadband filters. The
+    /// outer option is None until the range has been looked up.
+    eu_range: Option<Option<(f64, f64)>>,
+    /// The EURange property the cached range was read from. A write to its value invalidates
+    /// the cache.
+    eu_range_node_id: Option<NodeId>,
+    /// The smallest absolute change reported by a percent deadband, however small the EURange
+    percent_deadband_floor: f64,
+    /// The tick quantum of the server. The item samples no faster than this.
+    sampling_quantum: Duration,
     last_sample_time: DateTimeUtc,
+    /// Set when the first sample is offset by a fraction of the sampling interval
+    stagger_first_sample: bool,
+    /// Set when the initial value is only reported if its status is Good
+    initial_value_good_only: bool,
+    /// The last sampled value, or `None` before the first sample. A null value is sampled like
+    /// any other, so it is reported once and later samples are compared against it.
     last_data_value: Option<DataValue>,
+    /// Set when the monitored node has gone from the address space and the bad status has been
+    /// reported. Cleared when the node reappears.
+    node_missing: bool,
+    /// The processing interval being aggregated when the item has an aggregate filter. None until
+    /// the first sample.
+    aggregate_interval: Option<AggregateInterval>,
+    /// The achieved intervals between samples, reported in the server metrics
+    sampling_interval_stats: SamplingIntervalStats,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -113,10 +414,17 @@ pub(crate) enum TickResult {
 
 impl MonitoredItem {
     pub fn new(now: &DateTimeUtc, monitored_item_id: u32, timestamps_to_return: TimestampsToReturn, request: &MonitoredItemCreateRequest) -> Result<MonitoredItem, StatusCode> {
-        let filter = FilterType::from_filter(&request.requested_parameters.filter)?;
+        Self::new_with_defaults(now, monitored_item_id, timestamps_to_return, request, &MonitoredItemDefaults::default())
+    }
+
+    /// Creates a monitored item, applying the defaults for any parameters the client left unspecified.
+    /// The request is checked with `validate_create_request()` first.
+    pub fn new_with_defaults(now: &DateTimeUtc, monitored_item_id: u32, timestamps_to_return: TimestampsToReturn, request: &MonitoredItemCreateRequest, defaults: &MonitoredItemDefaults) -> Result<MonitoredItem, StatusCode> {
+        let filter = Self::validated_filter(request)?;
         let sampling_interval = Self::sanitize_sampling_interval(request.requested_parameters.sampling_interval);
-        let queue_size = Self::sanitize_queue_size(request.requested_parameters.queue_size as usize);
-        Ok(MonitoredItem {
+        let (queue_size, discard_oldest) = Self::sanitize_queue_size(request.requested_parameters.queue_size as usize, request.requested_parameters.discard_oldest, defaults);
+        let timestamps_to_return = Self::sanitize_timestamps_to_return(timestamps_to_return, request.item_to_monitor.attribute_id);
+        let mut monitored_item = MonitoredItem {
             monitored_item_id,
             item_to_monitor: request.item_to_monitor.clone(),
             monitoring_mode: request.monitoring_mode,
@@ -124,25 +432,220 @@ impl MonitoredItem {
             client_handle: request.requested_parameters.client_handle,
             sampling_interval,
             filter,
-            discard_oldest: request.requested_parameters.discard_oldest,
+            discard_oldest,
             timestamps_to_return,
+            eu_range: None,
+            eu_range_node_id: None,
+            percent_deadband_floor: defaults.percent_deadband_floor,
+            sampling_quantum: defaults.sampling_quantum,
             last_sample_time: now.clone(),
+            stagger_first_sample: defaults.stagger_first_samples,
+            initial_value_good_only: defaults.initial_value_good_only,
             last_data_value: None,
+            node_missing: false,
+            aggregate_interval: None,
+            sampling_interval_stats: SamplingIntervalStats::default(),
             queue_size,
             notification_queue: VecDeque::with_capacity(queue_size),
             queue_overflow: false,
-        })
+            queue_overflow_callback: None,
+            queue_overflow_events: Vec::new(),
+            session_queue_full: false,
+            coalesce: false,
+            min_notification_interval: 0f64,
+            last_notification_time: None,
+            pending_notification: None,
+            source_timestamp_hold_off: false,
+            report_uncertain: false,
+            report_heartbeats: false,
+            push_on_write: false,
+            ignore_deadband: false,
+            strict_type_compare: false,
+            return_diagnostics: false,
+            overflow_strategy: None,
+        };
+        monitored_item.stagger_last_sample_time(now);
+        Ok(monitored_item)
+    }
+
+    /// Returns the phase offset of the item's first sample, a fraction of the sampling interval
+    /// derived from the item id so that it is the same every time the item is created. Items
+    /// with sequential ids are spread evenly across the interval. Items that sample every tick,
+    /// on the publishing interval or for events are not offset.
+    fn sampling_offset(&self) -> Duration {
+        if !self.stagger_first_sample || self.samples_every_tick() || self.sampling_interval < 0f64 || self.is_event_filter() {
+            0f64
+        } else {
+            // Fibonacci hashing of the id to a fraction in the range [0, 1)
+            let fraction = self.monitored_item_id.wrapping_mul(0x9E37_79B9) as f64 / (u32::MAX as f64 + 1f64);
+            self.sampling_interval * fraction
+        }
+    }
+
+    /// Moves the last sample time back by the sampling offset so the next sample is due sooner
+    /// than a full interval from now.
+    fn stagger_last_sample_time(&mut self, now: &DateTimeUtc) {
+        let sampling_offset = self.sampling_offset();
+        if sampling_offset > 0f64 {
+            self.last_sample_time = *now - super::duration_from_ms(sampling_offset);
+        }
+    }
+
+    /// Takes a snapshot of the item's configuration
+    pub fn snapshot(&self) -> MonitoredItemSnapshot {
+        let filter = match self.filter {
+            FilterType::None => ExtensionObject::null(),
+            FilterType::DataChangeFilter(ref filter) => ExtensionObject::from_encodable(ObjectId::DataChangeFilter_Encoding_DefaultBinary, filter),
+            FilterType::EventFilter(ref filter) => ExtensionObject::from_encodable(ObjectId::EventFilter_Encoding_DefaultBinary, filter),
+            FilterType::AggregateFilter(ref filter) => ExtensionObject::from_encodable(ObjectId::AggregateFilter_Encoding_DefaultBinary, filter),
+        };
+        MonitoredItemSnapshot {
+            monitored_item_id: self.monitored_item_id,
+            node_id: self.item_to_monitor.node_id.clone(),
+            attribute_id: self.item_to_monitor.attribute_id,
+            index_range: self.item_to_monitor.index_range.clone(),
+            data_encoding: self.item_to_monitor.data_encoding.clone(),
+            monitoring_mode: self.monitoring_mode as i32,
+            client_handle: self.client_handle,
+            sampling_interval: self.sampling_interval,
+            filter,
+            queue_size: self.queue_size as u32,
+            discard_oldest: self.discard_oldest,
+            timestamps_to_return: self.timestamps_to_return as i32,
+            triggered_items: self.triggered_items.iter().cloned().collect(),
+            coalesce: self.coalesce,
+            min_notification_interval: self.min_notification_interval,
+            source_timestamp_hold_off: self.source_timestamp_hold_off,
+            report_uncertain: self.report_uncertain,
+            report_heartbeats: self.report_heartbeats,
+            push_on_write: self.push_on_write,
+            ignore_deadband: self.ignore_deadband,
+            strict_type_compare: self.strict_type_compare,
+            return_diagnostics: self.return_diagnostics,
+            overflow_strategy: self.overflow_strategy,
+        }
+    }
+
+    /// Restores an item from a snapshot. The item has no sampled value so its current value is
+    /// reported as if it had just been created. The defaults should be the server's configured
+    /// defaults, the same as items are created with.
+    pub fn restore(now: &DateTimeUtc, snapshot: &MonitoredItemSnapshot, defaults: &MonitoredItemDefaults) -> Result<MonitoredItem, StatusCode> {
+        let request = MonitoredItemCreateRequest {
+            item_to_monitor: ReadValueId {
+                node_id: snapshot.node_id.clone(),
+                attribute_id: snapshot.attribute_id,
+                index_range: snapshot.index_range.clone(),
+                data_encoding: snapshot.data_encoding.clone(),
+            },
+            monitoring_mode: Self::decode_enum(snapshot.monitoring_mode)?,
+            requested_parameters: MonitoringParameters {
+                client_handle: snapshot.client_handle,
+                sampling_interval: snapshot.sampling_interval,
+                filter: snapshot.filter.clone(),
+                queue_size: snapshot.queue_size,
+                discard_oldest: snapshot.discard_oldest,
+            },
+        };
+        let timestamps_to_return = Self::decode_enum(snapshot.timestamps_to_return)?;
+        let mut monitored_item = Self::new_with_defaults(now, snapshot.monitored_item_id, timestamps_to_return, &request, defaults)?;
+        monitored_item.set_triggering(&snapshot.triggered_items, &[]);
+        monitored_item.set_coalesce(snapshot.coalesce);
+        monitored_item.set_min_notification_interval(snapshot.min_notification_interval);
+        monitored_item.set_source_timestamp_hold_off(snapshot.source_timestamp_hold_off);
+        monitored_item.set_report_uncertain(snapshot.report_uncertain);
+        monitored_item.set_report_heartbeats(snapshot.report_heartbeats);
+        monitored_item.set_push_on_write(snapshot.push_on_write);
+        monitored_item.set_ignore_deadband(snapshot.ignore_deadband);
+        monitored_item.set_strict_type_compare(snapshot.strict_type_compare);
+        monitored_item.set_return_diagnostics(snapshot.return_diagnostics);
+        monitored_item.set_overflow_strategy(snapshot.overflow_strategy);
+        Ok(monitored_item)
+    }
+
+    /// Decodes a create request from its binary encoding and validates it with
+    /// `validate_create_request()`, so a request that decodes is safe to create an item from.
+    ///
+    /// # Errors
+    ///
+    /// BadDecodingError indicates the bytes are not exactly one encoded request. Other errors are
+    /// those of `validate_create_request()`.
+    pub fn decode_create_request(data: &[u8], decoding_limits: &DecodingLimits) -> Result<MonitoredItemCreateRequest, StatusCode> {
+        let mut stream = Cursor::new(data);
+        let request = MonitoredItemCreateRequest::decode(&mut stream, decoding_limits)?;
+        if stream.position() as usize != data.len() {
+            error!("Monitored item create request has {} bytes left over after decoding", data.len() - stream.position() as usize);
+            return Err(StatusCode::BadDecodingError);
+        }
+        Self::validate_create_request(&request)?;
+        Ok(request)
+    }
+
+    /// Validates the parts of a create request that do not depend on the address space.
+    ///
+    /// # Errors
+    ///
+    /// * BadNodeIdInvalid - the node id is null
+    /// * BadAttributeIdInvalid - the attribute id is unknown
+    /// * BadIndexRangeInvalid - the index range cannot be parsed
+    /// * BadInvalidArgument - the sampling interval is not a number
+    /// * BadMonitoredItemFilterUnsupported - the filter is not one the server supports
+    /// * BadFilterNotAllowed - the filter cannot be used on the attribute
+    /// * a decoding error - the filter cannot be decoded
+    pub fn validate_create_request(request: &MonitoredItemCreateRequest) -> Result<(), StatusCode> {
+        Self::validated_filter(request).map(|_| ())
+    }
+
+    /// Validates a create request as `validate_create_request()` does and returns its decoded filter
+    fn validated_filter(request: &MonitoredItemCreateRequest) -> Result<FilterType, StatusCode> {
+        let item_to_monitor = &request.item_to_monitor;
+        if item_to_monitor.node_id.is_null() {
+            error!("Monitored item create request has a null node id");
+            return Err(StatusCode::BadNodeIdInvalid);
+        }
+        if AttributeId::from_u32(item_to_monitor.attribute_id).is_err() {
+            error!("Monitored item create request has an invalid attribute id {}", item_to_monitor.attribute_id);
+            return Err(StatusCode::BadAttributeIdInvalid);
+        }
+        if item_to_monitor.index_range.as_ref().parse::<NumericRange>().is_err() {
+            error!("Monitored item create request has an invalid index range {}", item_to_monitor.index_range);
+            return Err(StatusCode::BadIndexRangeInvalid);
+        }
+        if request.requested_parameters.sampling_interval.is_nan() {
+            error!("Monitored item create request has a sampling interval that is not a number");
+            return Err(StatusCode::BadInvalidArgument);
+        }
+        let filter = FilterType::from_extension_object(&request.requested_parameters.filter)?;
+        Self::validate_filter_attribute(&filter, item_to_monitor.attribute_id)?;
+        Ok(filter)
+    }
+
+    /// Decodes an enum from its numeric value, using its binary decoder to validate the value
+    fn decode_enum<T: BinaryEncoder<T>>(value: i32) -> Result<T, StatusCode> {
+        let mut stream = Cursor::new(value.to_le_bytes());
+        T::decode(&mut stream, &DecodingLimits::default())
     }
 
     /// Modifies the existing item with the values of the modify request. On success, the result
-    /// holds the filter result.
-    pub fn modify(&mut self, address_space: &AddressSpace, timestamps_to_return: TimestampsToReturn, request: &MonitoredItemModifyRequest) -> Result<ExtensionObject, StatusCode> {
-        self.timestamps_to_return = timestamps_to_return;
-        self.filter = FilterType::from_filter(&request.requested_parameters.filter)?;
+    /// holds the filter result. On failure the item is left unmodified.
+    pub fn modify(&mut self, address_space: &AddressSpace, timestamps_to_return: TimestampsToReturn, request: &MonitoredItemModifyRequest, defaults: &MonitoredItemDefaults) -> Result<ExtensionObject, StatusCode> {
+        let filter = FilterType::from_extension_object(&request.requested_parameters.filter)?;
+        Self::validate_filter_attribute(&filter, self.item_to_monitor.attribute_id)?;
+        // Validate the new filter before anything is changed, so a rejected filter does not
+        // discard the queue or replace the filter of the item
+        let filter_result = self.validate_filter_type(&filter, address_space)?;
+        self.timestamps_to_return = Self::sanitize_timestamps_to_return(timestamps_to_return, self.item_to_monitor.attribute_id);
+        self.rekey_filter(filter);
         self.sampling_interval = Self::sanitize_sampling_interval(request.requested_parameters.sampling_interval);
-        self.queue_size = Self::sanitize_queue_size(request.requested_parameters.queue_size as usize);
+        let (queue_size, discard_oldest) = Self::sanitize_queue_size(request.requested_parameters.queue_size as usize, request.requested_parameters.discard_oldest, defaults);
+        self.queue_size = queue_size;
         self.client_handle = request.requested_parameters.client_handle;
-        self.discard_oldest = request.requested_parameters.discard_oldest;
+        self.discard_oldest = discard_oldest;
+        self.percent_deadband_floor = defaults.percent_deadband_floor;
+        self.sampling_quantum = defaults.sampling_quantum;
+        // The filter may have changed so look up the EURange again when it's next needed
+        self.eu_range = None;
+        // Samples collected under the old filter do not belong to an interval of the new one
+        self.aggregate_interval = None;
 
         // Shrink / grow the notification queue to the new threshold
         if self.notification_queue.len() > self.queue_size {
@@ -157,8 +660,33 @@ impl MonitoredItem {
             let extra_capacity = self.queue_size - self.notification_queue.capacity();
             self.notification_queue.reserve(extra_capacity);
         }
-        // Validate the filter, return that from this function
-        self.validate_filter(address_space)
+        Ok(filter_result)
+    }
+
+    /// Replaces the filter of the item. When the new filter changes the kind of notification the
+    /// item produces, e.g. from data changes to events, the queued notifications and the state
+    /// they were compared against are discarded together so the two kinds are never mixed.
+    fn rekey_filter(&mut self, filter: FilterType) {
+        let was_event_filter = self.is_event_filter();
+        self.filter = filter;
+        if was_event_filter != self.is_event_filter() {
+            debug!("Monitored item {} has changed notification kind, discarding its queue and last value", self.monitored_item_id);
+            self.notification_queue.clear();
+            self.queue_overflow = false;
+            self.pending_notification = None;
+            self.last_notification_time = None;
+            self.last_data_value = None;
+        }
+    }
+
+    /// Returns the encoding node ids of the monitoring filters that the server implements, i.e.
+    /// the filter types that a create or modify request may carry, for capability reporting.
+    pub fn supported_filter_types() -> Vec<NodeId> {
+        vec![
+            ObjectId::DataChangeFilter_Encoding_DefaultBinary.into(),
+            ObjectId::EventFilter_Encoding_DefaultBinary.into(),
+            ObjectId::AggregateFilter_Encoding_DefaultBinary.into(),
+        ]
     }
 
     /// Adds or removes other monitored items which will be triggered when this monitored item changes
@@ -168,18 +696,113 @@ impl MonitoredItem {
         items_to_add.iter().for_each(|i| { self.triggered_items.insert(*i); });
     }
 
+    /// Tests if the attribute can be monitored on a node of the node class, i.e. if nodes of that
+    /// class have the attribute. The attributes common to all nodes can be monitored on any node.
+    pub fn is_monitorable_attribute(node_class: NodeClass, attribute_id: AttributeId) -> bool {
+        match attribute_id {
+            AttributeId::NodeId | AttributeId::NodeClass | AttributeId::BrowseName | AttributeId::DisplayName |
+            AttributeId::Description | AttributeId::WriteMask | AttributeId::UserWriteMask |
+            AttributeId::RolePermissions | AttributeId::UserRolePermissions | AttributeId::AccessRestrictions => true,
+            AttributeId::IsAbstract => matches!(node_class, NodeClass::ObjectType | NodeClass::VariableType | NodeClass::ReferenceType | NodeClass::DataType),
+            AttributeId::Symmetric | AttributeId::InverseName => node_class == NodeClass::ReferenceType,
+            AttributeId::ContainsNoLoops => node_class == NodeClass::View,
+            AttributeId::EventNotifier => matches!(node_class, NodeClass::Object | NodeClass::View),
+            AttributeId::Value | AttributeId::DataType | AttributeId::ValueRank | AttributeId::ArrayDimensions => {
+                matches!(node_class, NodeClass::Variable | NodeClass::VariableType)
+            }
+            AttributeId::AccessLevel | AttributeId::UserAccessLevel | AttributeId::MinimumSamplingInterval |
+            AttributeId::Historizing | AttributeId::AccessLevelEx => node_class == NodeClass::Variable,
+            AttributeId::Executable | AttributeId::UserExecutable => node_class == NodeClass::Method,
+            AttributeId::DataTypeDefinition => node_class == NodeClass::DataType,
+        }
+    }
+
+    /// Validates that the monitored attribute exists on the class of the monitored node.
+    /// BadAttributeIdInvalid is returned when it does not. The value of a variable can only be
+    /// monitored if its access level allows the current value to be read, otherwise BadNotReadable
+    /// is returned.
+    pub fn validate_attribute(&self, address_space: &AddressSpace) -> Result<(), StatusCode> {
+        let attribute_id = AttributeId::from_u32(self.item_to_monitor.attribute_id)
+            .map_err(|_| StatusCode::BadAttributeIdInvalid)?;
+        if let Some(node) = address_space.find_node(&self.item_to_monitor.node_id) {
+            let node_class = node.node_class();
+            if Self::is_monitorable_attribute(node_class, attribute_id) {
+                match node {
+                    NodeType::Variable(ref variable) if attribute_id == AttributeId::Value && !variable.is_readable() => {
+                        error!("Value of node {:?} cannot be monitored because it is not readable", self.item_to_monitor.node_id);
+                        Err(StatusCode::BadNotReadable)
+                    }
+                    _ => Ok(())
+                }
+            } else {
+                error!("Attribute {:?} cannot be monitored on node {:?} of class {:?}", attribute_id, self.item_to_monitor.node_id, node_class);
+                Err(StatusCode::BadAttributeIdInvalid)
+            }
+        } else {
+            Err(StatusCode::BadNodeIdUnknown)
+        }
+    }
+
+    /// Tests that the filter can be applied to the attribute. Data change and aggregate filters
+    /// only apply to the Value attribute and event filters only to the EventNotifier attribute,
+    /// so BadFilterNotAllowed is returned for them on any other.
+    fn validate_filter_attribute(filter: &FilterType, attribute_id: u32) -> Result<(), StatusCode> {
+        match filter {
+            FilterType::DataChangeFilter(_) if attribute_id != AttributeId::Value as u32 => {
+                error!("A data change filter is not allowed on attribute {}", attribute_id);
+                Err(StatusCode::BadFilterNotAllowed)
+            }
+            FilterType::AggregateFilter(_) if attribute_id != AttributeId::Value as u32 => {
+                error!("An aggregate filter is not allowed on attribute {}", attribute_id);
+                Err(StatusCode::BadFilterNotAllowed)
+            }
+            FilterType::EventFilter(_) if attribute_id != AttributeId::EventNotifier as u32 => {
+                error!("An event filter is not allowed on attribute {}", attribute_id);
+                Err(StatusCode::BadFilterNotAllowed)
+            }
+            _ => Ok(())
+        }
+    }
+
     /// Validates the filter associated with the monitored item and returns the filter result
     /// encoded in an extension object.
     pub fn validate_filter(&self, address_space: &AddressSpace) -> Result<ExtensionObject, StatusCode> {
-        // Event filter must be validated
-        let filter_result = if let FilterType::EventFilter(ref event_filter) = self.filter {
-            let filter_result = event_filter::validate(event_filter, address_space)?;
-            ExtensionObject::from_encodable(ObjectId::EventFilterResult_Encoding_DefaultBinary, &filter_result)
-        } else {
+        self.validate_filter_type(&self.filter, address_space)
+    }
+
+    /// Validates a filter for the item and returns the filter result encoded in an extension
+    /// object, without making it the filter of the item.
+    fn validate_filter_type(&self, filter: &FilterType, address_space: &AddressSpace) -> Result<ExtensionObject, StatusCode> {
+        match *filter {
+            FilterType::EventFilter(ref event_filter) => {
+                // Event filter must be validated
+                let filter_result = event_filter::validate(event_filter, address_space)?;
+                Ok(ExtensionObject::from_encodable(ObjectId::EventFilterResult_Encoding_DefaultBinary, &filter_result))
+            }
+            FilterType::AggregateFilter(ref aggregate_filter) => {
+                match aggregate_filter.aggregate_type.as_object_id() {
+                    Ok(ObjectId::AggregateFunction_Average) |
+                    Ok(ObjectId::AggregateFunction_Minimum) |
+                    Ok(ObjectId::AggregateFunction_Maximum) => {}
+                    _ => {
+                        error!("Aggregate {:?} is not supported, node {:?}", aggregate_filter.aggregate_type, self.item_to_monitor.node_id);
+                        return Err(StatusCode::BadAggregateNotSupported);
+                    }
+                }
+                if aggregate_filter.processing_interval.is_nan() || aggregate_filter.processing_interval <= 0f64 {
+                    error!("Aggregate processing interval {} is invalid, node {:?}", aggregate_filter.processing_interval, self.item_to_monitor.node_id);
+                    return Err(StatusCode::BadMonitoredItemFilterInvalid);
+                }
+                let filter_result = AggregateFilterResult {
+                    revised_start_time: aggregate_filter.start_time.clone(),
+                    revised_processing_interval: aggregate_filter.processing_interval,
+                    revised_aggregate_configuration: aggregate_filter.aggregate_configuration.clone(),
+                };
+                Ok(ExtensionObject::from_encodable(ObjectId::AggregateFilterResult_Encoding_DefaultBinary, &filter_result))
+            }
             // DataChangeFilter has no result
-            ExtensionObject::null()
-        };
-        Ok(filter_result)
+            _ => Ok(ExtensionObject::null())
+        }
     }
 
     /// Called repeatedly on the monitored item.
@@ -191,9 +814,18 @@ impl MonitoredItem {
     /// Function returns a `TickResult` denoting if the value changed or not, and whether it should
     /// be reported.
     pub fn tick(&mut self, now: &DateTimeUtc, address_space: &AddressSpace, publishing_interval_elapsed: bool, resend_data: bool) -> TickResult {
+        self.tick_with_nodes(now, address_space, &mut SampledNodes::default(), publishing_interval_elapsed, resend_data)
+    }
+
+    /// Does the same as `tick()` except that the item finds its node and reads its value through
+    /// `sampled_nodes`, sharing them with other items ticked in the same quantum.
+    pub fn tick_with_nodes<'a>(&mut self, now: &DateTimeUtc, address_space: &'a AddressSpace, sampled_nodes: &mut SampledNodes<'a>, publishing_interval_elapsed: bool, resend_data: bool) -> TickResult {
         if self.monitoring_mode == MonitoringMode::Disabled {
             TickResult::NoChange
         } else {
+            // A held back notification is released once the notification interval has passed
+            let released = self.release_pending_notification(now);
+
             let check_value = if resend_data {
                 // Always check for resend_data flag
                 true
@@ -201,26 +833,35 @@ impl MonitoredItem {
                 // -1 means use the subscription publishing interval so if the publishing interval elapsed,
                 // then this monitored item is evaluated otherwise it won't be.
                 publishing_interval_elapsed
-            } else if self.sampling_interval == 0f64 {
-                // 0 means fastest practical rate, i.e. the tick quantum itself
-                // 0 is also used for clients subscribing for events.
-                true
             } else {
-                // Compare sample interval to the time elapsed
-                let sampling_interval = super::duration_from_ms(self.sampling_interval);
-                let elapsed = now.signed_duration_since(self.last_sample_time);
-                elapsed >= sampling_interval
+                // 0 means fastest practical rate, i.e. the tick quantum itself. 0 is also used for
+                // clients subscribing for events. No item samples faster than the quantum, however
+                // often it is ticked.
+                let sampling_interval = f64::max(self.sampling_interval, self.sampling_quantum);
+                if sampling_interval == 0f64 {
+                    true
+                } else {
+                    // Compare sample interval to the time elapsed
+                    let sampling_interval = super::duration_from_ms(sampling_interval);
+                    let elapsed = now.signed_duration_since(self.last_sample_time);
+                    elapsed >= sampling_interval
+                }
             };
 
             // Test the value (or don't)
             let value_changed = check_value && {
                 // Indicate a change if reporting is enabled
                 let first_tick = !self.is_event_filter() && self.last_data_value.is_none();
-                let value_changed = self.check_value(address_space, now, resend_data);
+                let node = sampled_nodes.find_node(address_space, &self.item_to_monitor.node_id);
+                let value_changed = self.check_node_value(address_space, node, now, resend_data, Some(sampled_nodes));
                 first_tick || value_changed || !self.notification_queue.is_empty()
             };
 
-            if value_changed {
+            // Notifications queued outside of a tick, e.g. the initial sample, are reported when
+            // the publishing interval elapses
+            let queued = publishing_interval_elapsed && self.monitoring_mode == MonitoringMode::Reporting && !self.notification_queue.is_empty();
+
+            if value_changed || released || queued {
                 if self.monitoring_mode == MonitoringMode::Reporting {
                     TickResult::ReportValueChanged
                 } else {
@@ -232,6 +873,32 @@ impl MonitoredItem {
         }
     }
 
+    /// Samples the current value of a newly created item in reporting mode so that the first
+    /// publish response contains it without waiting for the item's sampling interval to elapse.
+    /// If the item only reports a Good initial value, any other value is sampled but not queued,
+    /// and is reported later only if it changes. Returns true if a notification was queued.
+    pub fn initial_sample(&mut self, address_space: &AddressSpace, now: &DateTimeUtc) -> bool {
+        if self.monitoring_mode == MonitoringMode::Reporting && !self.is_event_filter() {
+            let mut changed = self.check_value(address_space, now, true);
+            if changed && self.initial_value_good_only {
+                let good = match self.notification_queue.back() {
+                    Some(Notification::MonitoredItemNotification(notification)) => notification.value.status().is_good(),
+                    _ => true
+                };
+                if !good {
+                    debug!("Initial value is not Good so it is not reported, node {:?}", self.item_to_monitor.node_id);
+                    let _ = self.notification_queue.pop_back();
+                    changed = false;
+                }
+            }
+            // The initial sample does not count as the first sample of the sampling interval
+            self.stagger_last_sample_time(now);
+            changed
+        } else {
+            false
+        }
+    }
+
     /// Gets the event notifier bits for a node, or empty if there are no bits
     fn get_event_notifier(node: &dyn Node) -> EventNotifier {
         if let Some(v) = node.get_attribute(AttributeId::EventNotifier, NumericRange::None, &QualifiedName::null()) {
@@ -266,74 +933,301 @@ impl MonitoredItem {
         }
     }
 
-    fn check_for_data_change(&mut self, _address_space: &AddressSpace, resend_data: bool, attribute_id: AttributeId, node: &dyn Node) -> bool {
-        let data_value = node.get_attribute(attribute_id, NumericRange::None, &QualifiedName::null());
-        if let Some(mut data_value) = data_value {
-            // Test for data change
-            let data_change = if resend_data {
-                true
-            } else if let Some(ref last_data_value) = self.last_data_value {
-                // If there is a filter on the monitored item then the filter determines
-                // if the value is considered to have changed, otherwise it is a straight
-                // equality test.
-                match self.filter {
-                    FilterType::None => {
-                        data_value.value != last_data_value.value
-                    }
-                    FilterType::DataChangeFilter(ref filter) => {
-                        !filter.compare(&data_value, last_data_value, None)
-                    }
-                    _ => {
-                        // Unrecognized filter
-                        false
+    fn check_for_data_change(&mut self, address_space: &AddressSpace, now: &DateTimeUtc, resend_data: bool, attribute_id: AttributeId, node: &dyn Node, sampled_nodes: Option<&mut SampledNodes>) -> bool {
+        let data_value = if let Some(sampled_nodes) = sampled_nodes {
+            sampled_nodes.read_value(node, attribute_id)
+        } else {
+            node.get_attribute(attribute_id, NumericRange::None, &QualifiedName::null())
+        };
+        // A node that has no value for the attribute is sampled as a null value whose status says
+        // whether the node's class supports the attribute at all
+        let (mut data_value, unavailable) = match data_value {
+            Some(data_value) => (data_value, false),
+            None => (Self::unavailable_attribute_value(node, attribute_id), true)
+        };
+        let eu_range = if self.is_percent_deadband() { self.eu_range(address_space) } else { None };
+        // Test for data change
+        let data_change = if resend_data {
+            true
+        } else if unavailable {
+            // There is nothing to compare so the null value is only reported when it or its
+            // status is different to the last sample
+            self.last_data_value.as_ref().map_or(true, |last_data_value| {
+                last_data_value.value.is_some() || last_data_value.status() != data_value.status()
+            })
+        } else if self.report_uncertain && data_value.status().is_uncertain() {
+            trace!("Sample has an uncertain status so it is reported, node {:?}", self.item_to_monitor.node_id);
+            true
+        } else if self.is_same_source_sample(&data_value) {
+            trace!("Source timestamp has not moved on so there is no new sample, node {:?}", self.item_to_monitor.node_id);
+            false
+        } else if self.is_heartbeat(&data_value) {
+            trace!("Sample is a heartbeat so it is reported, node {:?}", self.item_to_monitor.node_id);
+            true
+        } else if self.is_type_change(&data_value) {
+            trace!("Sample is of a different type to the last so it is reported, node {:?}", self.item_to_monitor.node_id);
+            true
+        } else if let Some(ref last_data_value) = self.last_data_value {
+            // If there is a filter on the monitored item then the filter determines
+            // if the value is considered to have changed, otherwise it is a straight
+            // equality test.
+            match self.filter {
+                FilterType::None => {
+                    match (&data_value.value, &last_data_value.value) {
+                        (Some(v1), Some(v2)) => !v1.numerically_eq(v2),
+                        (v1, v2) => v1.is_some() != v2.is_some()
                     }
                 }
-            } else {
-                // There is no previous data value so yes consider it changed
-                trace!("No last data value so item has changed, node {:?}", self.item_to_monitor.node_id);
-                true
-            };
-            if data_change {
-                trace!("Data change on item -, node {:?}, data_value = {:?}", self.item_to_monitor.node_id, data_value);
-
-                // Store current data value to compare against on the next tick
-                self.last_data_value = Some(data_value.clone());
-
-                // Strip out timestamps that subscriber is not interested in
-                match self.timestamps_to_return {
-                    TimestampsToReturn::Neither | TimestampsToReturn::Invalid => {
-                        data_value.source_timestamp = None;
-                        data_value.source_picoseconds = None;
-                        data_value.server_timestamp = None;
-                        data_value.server_picoseconds = None
-                    }
-                    TimestampsToReturn::Server => {
-                        data_value.source_timestamp = None;
-                        data_value.source_picoseconds = None;
-                    }
-                    TimestampsToReturn::Source => {
-                        data_value.server_timestamp = None;
-                        data_value.server_picoseconds = None
-                    }
-                    TimestampsToReturn::Both => {
-                        // DO NOTHING
-                    }
+                FilterType::DataChangeFilter(ref filter) => {
+                    let filter = if self.ignore_deadband {
+                        DataChangeFilter {
+                            trigger: filter.trigger,
+                            deadband_type: DeadbandType::None as u32,
+                            deadband_value: 0f64,
+                        }
+                    } else {
+                        filter.with_percent_floor(eu_range, self.percent_deadband_floor)
+                    };
+                    !filter.compare(&data_value, last_data_value, eu_range)
                 }
+                _ => {
+                    // Unrecognized filter
+                    false
+                }
+            }
+        } else {
+            // There is no previous data value so yes consider it changed
+            trace!("No last data value so item has changed, node {:?}", self.item_to_monitor.node_id);
+            true
+        };
+        if data_change {
+            trace!("Data change on item -, node {:?}, data_value = {:?}", self.item_to_monitor.node_id, data_value);
 
-                // Enqueue notification message
-                let client_handle = self.client_handle;
-                self.enqueue_notification_message(MonitoredItemNotification {
-                    client_handle,
-                    value: data_value,
-                });
+            // The source timestamp belongs to the source and is passed through untouched. A value
+            // without a server timestamp, e.g. from a getter, is stamped with the sample time.
+            if data_value.server_timestamp.is_none() {
+                data_value.server_timestamp = Some(DateTime::from_chrono(now));
+                data_value.server_picoseconds = None;
+            }
+
+            // Strip out timestamps that subscriber is not interested in
+            let value = data_value.trimmed_for(self.timestamps_to_return);
 
-                trace!("Monitored item state = {:?}", self);
+            // Store current data value to compare against on the next tick
+            self.last_data_value = Some(data_value);
+
+            // Enqueue notification message
+            let client_handle = self.client_handle;
+            self.enqueue_data_change(now, resend_data, MonitoredItemNotification {
+                client_handle,
+                value,
+            });
+
+            trace!("Monitored item state = {:?}", self);
+        } else {
+            trace!("No data change on item, node {:?}", self.item_to_monitor.node_id);
+        }
+        data_change
+    }
+
+    /// Returns the null value sampled when a node has no value for the attribute. If the node's
+    /// class does not have the attribute the status is BadAttributeIdInvalid, otherwise the
+    /// attribute is optional and currently unset so the status is Good.
+    fn unavailable_attribute_value(node: &dyn Node, attribute_id: AttributeId) -> DataValue {
+        let status = if Self::is_monitorable_attribute(node.node_class(), attribute_id) {
+            StatusCode::Good
+        } else {
+            debug!("Attribute {:?} is not supported by node {:?}", attribute_id, node.node_id());
+            StatusCode::BadAttributeIdInvalid
+        };
+        DataValue {
+            value: None,
+            status: Some(status),
+            source_timestamp: None,
+            source_picoseconds: None,
+            server_timestamp: None,
+            server_picoseconds: None,
+        }
+    }
+
+    /// Samples the value for an aggregate filter. The numeric samples are collected until the
+    /// processing interval has elapsed and then the aggregate of the interval is reported with the
+    /// start of the interval as its source timestamp. An interval without numeric samples is
+    /// reported with a BadNoData status, as is each interval that elapsed between two samples, up
+    /// to the queue size. Intervals are aligned to the filter's start time, or begin with the first
+    /// sample if the start time is null.
+    ///
+    /// Returns true if the aggregate of an interval was enqueued.
+    fn check_for_aggregate(&mut self, now: &DateTimeUtc, attribute_id: AttributeId, node: &dyn Node) -> bool {
+        let (aggregate_type, processing_interval, start_time) = match self.filter {
+            FilterType::AggregateFilter(ref filter) => (filter.aggregate_type.clone(), filter.processing_interval, filter.start_time.clone()),
+            _ => {
+                error!("Monitored item {} does not have an aggregate filter", self.monitored_item_id);
+                return false;
+            }
+        };
+        let processing_interval = super::duration_from_ms(processing_interval);
+        if processing_interval <= chrono::Duration::zero() {
+            return false;
+        }
+
+        // Report the interval if it has elapsed and move on to the interval holding this sample
+        let mut reported = false;
+        if let Some(interval) = self.aggregate_interval.take() {
+            let mut next_start = interval.start + processing_interval;
+            if *now >= next_start {
+                self.enqueue_aggregate(now, &aggregate_type, &interval.start, &interval.samples);
+                // Intervals that elapsed without a sample are reported as having no data. Only as
+                // many as the queue holds are reported since older ones would be discarded anyway.
+                let skipped = (now.signed_duration_since(next_start).num_milliseconds() / processing_interval.num_milliseconds().max(1)) as usize;
+                let max_skipped = self.queue_size.saturating_sub(1);
+                if skipped > max_skipped {
+                    next_start = next_start + processing_interval * (skipped - max_skipped) as i32;
+                }
+                while *now >= next_start + processing_interval {
+                    self.enqueue_aggregate(now, &aggregate_type, &next_start, &[]);
+                    next_start = next_start + processing_interval;
+                }
+                self.aggregate_interval = Some(AggregateInterval { start: next_start, samples: Vec::new() });
+                reported = true;
             } else {
-                trace!("No data change on item, node {:?}", self.item_to_monitor.node_id);
+                self.aggregate_interval = Some(interval);
+            }
+        }
+        if self.aggregate_interval.is_none() {
+            let start = Self::aggregate_interval_start(now, &start_time, processing_interval);
+            self.aggregate_interval = Some(AggregateInterval { start, samples: Vec::new() });
+        }
+
+        if let Some(data_value) = node.get_attribute(attribute_id, NumericRange::None, &QualifiedName::null()) {
+            // Only good numeric samples contribute to the aggregate
+            let good = data_value.status.map_or(true, |status| status.is_good());
+            if let (true, Some(sample)) = (good, data_value.value.as_ref().and_then(|v| v.as_f64())) {
+                self.aggregate_interval.as_mut().unwrap().samples.push(sample);
             }
-            data_change
+            self.last_data_value = Some(data_value);
+        }
+        reported
+    }
+
+    /// Enqueues the aggregate of the samples of the interval starting at `start`
+    fn enqueue_aggregate(&mut self, now: &DateTimeUtc, aggregate_type: &NodeId, start: &DateTimeUtc, samples: &[f64]) {
+        let status = if samples.is_empty() { StatusCode::BadNoData } else { StatusCode::Good };
+        let data_value = DataValue {
+            value: Self::compute_aggregate(aggregate_type, samples).map(Variant::from),
+            status: Some(status),
+            source_timestamp: Some(DateTime::from_chrono(start)),
+            source_picoseconds: None,
+            server_timestamp: Some(DateTime::from_chrono(now)),
+            server_picoseconds: None,
+        };
+        let client_handle = self.client_handle;
+        self.enqueue_data_change(now, false, MonitoredItemNotification {
+            client_handle,
+            value: data_value.trimmed_for(self.timestamps_to_return),
+        });
+    }
+
+    /// Returns the start of the processing interval that holds `now`. Intervals are counted from
+    /// the start time, or begin at `now` when the start time is null.
+    fn aggregate_interval_start(now: &DateTimeUtc, start_time: &DateTime, processing_interval: chrono::Duration) -> DateTimeUtc {
+        if start_time.is_null() {
+            *now
         } else {
-            false
+            let start_time = start_time.as_chrono();
+            let interval_ms = processing_interval.num_milliseconds().max(1);
+            let elapsed_ms = now.signed_duration_since(start_time).num_milliseconds();
+            start_time + chrono::Duration::milliseconds(elapsed_ms.div_euclid(interval_ms) * interval_ms)
+        }
+    }
+
+    /// Computes the aggregate of the samples, or None if there are no samples
+    fn compute_aggregate(aggregate_type: &NodeId, samples: &[f64]) -> Option<f64> {
+        if samples.is_empty() {
+            return None;
+        }
+        match aggregate_type.as_object_id() {
+            Ok(ObjectId::AggregateFunction_Average) => Some(samples.iter().sum::<f64>() / samples.len() as f64),
+            Ok(ObjectId::AggregateFunction_Minimum) => Some(samples.iter().cloned().fold(f64::INFINITY, f64::min)),
+            Ok(ObjectId::AggregateFunction_Maximum) => Some(samples.iter().cloned().fold(f64::NEG_INFINITY, f64::max)),
+            _ => None
+        }
+    }
+
+    /// Tests if the item reports heartbeats and the sample has the same value and status as the
+    /// last sample but a newer source timestamp
+    fn is_heartbeat(&self, data_value: &DataValue) -> bool {
+        if !self.report_heartbeats {
+            return false;
+        }
+        match self.last_data_value {
+            Some(ref last_data_value) => {
+                last_data_value.value == data_value.value && last_data_value.status() == data_value.status() &&
+                    last_data_value.source_timestamp != data_value.source_timestamp
+            }
+            None => false
+        }
+    }
+
+    /// Tests if the item compares types strictly and the sampled value is of a different type to
+    /// the last value
+    fn is_type_change(&self, data_value: &DataValue) -> bool {
+        if !self.strict_type_compare {
+            return false;
+        }
+        match (&data_value.value, self.last_data_value.as_ref().and_then(|v| v.value.as_ref())) {
+            (Some(v1), Some(v2)) => v1.type_id() != v2.type_id(),
+            _ => false
+        }
+    }
+
+    fn is_percent_deadband(&self) -> bool {
+        match self.filter {
+            FilterType::DataChangeFilter(ref filter) => filter.deadband_type == DeadbandType::Percent as u32,
+            _ => false
+        }
+    }
+
+    /// Returns the EURange of the monitored variable, reading it from the address space the first
+    /// time it's asked for and from the cache thereafter. The cache is invalidated when the value
+    /// of the EURange property is written.
+    fn eu_range(&mut self, address_space: &AddressSpace) -> Option<(f64, f64)> {
+        if self.eu_range.is_none() {
+            self.refresh_eu_range(address_space);
+        }
+        self.eu_range.unwrap()
+    }
+
+    /// Forces the cached EURange to be read again from the address space. This should be called
+    /// if the EURange property of the monitored variable changes without a write being recorded,
+    /// e.g. the property is added or replaced, or its value is set directly on the variable.
+    pub fn refresh_eu_range(&mut self, address_space: &AddressSpace) {
+        let eu_range_property = Self::find_eu_range_property(address_space, &self.item_to_monitor.node_id);
+        self.eu_range = Some(eu_range_property.and_then(Self::eu_range_of));
+        self.eu_range_node_id = eu_range_property.map(|v| v.node_id());
+    }
+
+    /// Finds the EURange property of a variable.
+    ///
+    /// A percent deadband is a percentage of the EURange, the range the value is expected to
+    /// operate in (Part 8 5.3.2). An analog item may also have an InstrumentRange property, the
+    /// range the instrument itself can measure, which is not used even when both are present.
+    /// The property is found by its browse name, so only a property named `EURange` in namespace 0
+    /// is used.
+    fn find_eu_range_property<'a>(address_space: &'a AddressSpace, node_id: &NodeId) -> Option<&'a Variable> {
+        let references = address_space.find_references(node_id, Some((ReferenceTypeId::HasProperty, false)))?;
+        let eu_range_name = QualifiedName::from("EURange");
+        references.iter()
+            .filter_map(|r| address_space.find_variable_by_ref(&r.target_node))
+            .find(|v| v.browse_name() == eu_range_name)
+    }
+
+    /// Reads the value of an EURange property as a (low, high) pair
+    fn eu_range_of(eu_range_property: &Variable) -> Option<(f64, f64)> {
+        if let Some(Variant::ExtensionObject(ref range)) = eu_range_property.value(NumericRange::None, &QualifiedName::null(), 0f64).value {
+            range.decode_inner::<Range>(&DecodingLimits::minimal()).ok().map(|range| (range.low, range.high))
+        } else {
+            None
         }
     }
 
@@ -350,10 +1244,27 @@ impl MonitoredItem {
     ///
     /// The function will return true if the value was changed, false otherwise.
     pub fn check_value(&mut self, address_space: &AddressSpace, now: &DateTimeUtc, resend_data: bool) -> bool {
+        let node = address_space.find_node(&self.item_to_monitor.node_id);
+        self.check_node_value(address_space, node, now, resend_data, None)
+    }
+
+    /// Tests if the item samples on every tick. A requested interval of 0 is revised to the
+    /// minimum sampling interval which is shorter than the tick rate.
+    fn samples_every_tick(&self) -> bool {
+        self.sampling_interval >= 0f64 && self.sampling_interval <= constants::MIN_SAMPLING_INTERVAL
+    }
+
+    /// Checks the value of a node that has already been looked up, or is missing if `None`. A data
+    /// change is read through `sampled_nodes` when supplied.
+    fn check_node_value(&mut self, address_space: &AddressSpace, node: Option<&NodeType>, now: &DateTimeUtc, resend_data: bool, sampled_nodes: Option<&mut SampledNodes>) -> bool {
         if self.monitoring_mode == MonitoringMode::Disabled {
             panic!("Should not check value while monitoring mode is disabled");
         }
-        let changed = if let Some(node) = address_space.find_node(&self.item_to_monitor.node_id) {
+        let changed = if let Some(node) = node {
+            if self.node_missing {
+                debug!("Item to monitor has reappeared, node {:?}", self.item_to_monitor.node_id);
+                self.node_missing = false;
+            }
             match AttributeId::from_u32(self.item_to_monitor.attribute_id) {
                 Ok(attribute_id) => {
                     let node = node.as_node();
@@ -367,8 +1278,11 @@ impl MonitoredItem {
                                 false
                             }
                         }
+                        FilterType::AggregateFilter(_) => {
+                            self.check_for_aggregate(now, attribute_id, node)
+                        }
                         _ => {
-                            self.check_for_data_change(address_space, resend_data, attribute_id, node)
+                            self.check_for_data_change(address_space, now, resend_data, attribute_id, node, sampled_nodes)
                         }
                     }
                 }
@@ -377,43 +1291,181 @@ impl MonitoredItem {
                     false
                 }
             }
+        } else if !self.node_missing {
+            debug!("Item to monitor has disappeared, node {:?}", self.item_to_monitor.node_id);
+            self.node_missing = true;
+            self.enqueue_missing_node(now)
         } else {
             trace!("Cannot find item to monitor, node {:?}", self.item_to_monitor.node_id);
             false
         };
+        self.sampling_interval_stats.record(now);
         self.last_sample_time = *now;
         changed
     }
 
+    /// Tests if source timestamp hold off is enabled and the value carries the same source
+    /// timestamp as the last sample. Values without a source timestamp are never held off.
+    fn is_same_source_sample(&self, data_value: &DataValue) -> bool {
+        if !self.source_timestamp_hold_off {
+            return false;
+        }
+        match (&data_value.source_timestamp, &self.last_data_value) {
+            (Some(source_timestamp), Some(last_data_value)) => {
+                last_data_value.source_timestamp.as_ref() == Some(source_timestamp)
+            }
+            _ => false
+        }
+    }
+
+    /// Reports once that the monitored node is no longer in the address space by enqueuing a data
+    /// change with a BadNodeIdUnknown status. The bad value becomes the last value so the node's
+    /// value is reported again if it reappears. Returns true if a notification was enqueued.
+    fn enqueue_missing_node(&mut self, now: &DateTimeUtc) -> bool {
+        if self.is_event_filter() {
+            false
+        } else {
+            let server_timestamp = match self.timestamps_to_return {
+                TimestampsToReturn::Server | TimestampsToReturn::Both => Some(DateTime::from_chrono(now)),
+                _ => None
+            };
+            let data_value = DataValue {
+                value: None,
+                status: Some(StatusCode::BadNodeIdUnknown),
+                source_timestamp: None,
+                source_picoseconds: None,
+                server_timestamp,
+                server_picoseconds: None,
+            };
+            self.last_data_value = Some(data_value.clone());
+            let client_handle = self.client_handle;
+            self.enqueue_data_change(now, true, MonitoredItemNotification {
+                client_handle,
+                value: data_value,
+            });
+            true
+        }
+    }
+
+    /// Enqueues a data change notification unless one was enqueued less than the minimum
+    /// notification interval ago, in which case it replaces the pending notification. Resending data
+    /// ignores the interval.
+    fn enqueue_data_change(&mut self, now: &DateTimeUtc, resend_data: bool, notification: MonitoredItemNotification) {
+        if self.min_notification_interval > 0f64 && !resend_data {
+            if let Some(ref last_notification_time) = self.last_notification_time {
+                let min_notification_interval = super::duration_from_ms(self.min_notification_interval);
+                if now.signed_duration_since(*last_notification_time) < min_notification_interval {
+                    trace!("Holding back data change, node {:?}", self.item_to_monitor.node_id);
+                    self.pending_notification = Some(notification);
+                    return;
+                }
+            }
+        }
+        self.last_notification_time = Some(*now);
+        self.pending_notification = None;
+        self.enqueue_notification_message(notification);
+    }
+
+    /// Enqueues the pending notification if the minimum notification interval has elapsed. Returns
+    /// true if a notification was released.
+    fn release_pending_notification(&mut self, now: &DateTimeUtc) -> bool {
+        if self.pending_notification.is_some() {
+            let due = if let Some(ref last_notification_time) = self.last_notification_time {
+                let min_notification_interval = super::duration_from_ms(self.min_notification_interval);
+                now.signed_duration_since(*last_notification_time) >= min_notification_interval
+            } else {
+                true
+            };
+            if due {
+                let notification = self.pending_notification.take().unwrap();
+                self.last_notification_time = Some(*now);
+                self.enqueue_notification_message(notification);
+            }
+            due
+        } else {
+            false
+        }
+    }
+
     /// Enqueues a notification message for the monitored item
     pub fn enqueue_notification_message<T>(&mut self, notification: T) where T: Into<Notification> {
-        // test for overflow
-        let overflow = if self.notification_queue.len() == self.queue_size {
-            trace!("Data change overflow, node {:?}", self.item_to_monitor.node_id);
-            // Overflow behaviour
-            if self.discard_oldest {
-                // Throw away oldest item (the one at the start) to make space at the end
-                let _ = self.notification_queue.pop_front();
-            } else {
-                // Remove the latest notification
-                self.notification_queue.pop_back();
+        let mut notification = notification.into();
+        if self.coalesce {
+            if let Notification::MonitoredItemNotification(ref mut notification) = notification {
+                // Replace the pending data change with the latest sample rather than accumulating
+                if let Some(Notification::MonitoredItemNotification(_)) = self.notification_queue.back() {
+                    trace!("Coalescing data change, node {:?}", self.item_to_monitor.node_id);
+                    if let Some(Notification::MonitoredItemNotification(older)) = self.notification_queue.pop_back() {
+                        let newer = std::mem::take(&mut notification.value);
+                        notification.value = older.value.merge_latest(newer);
+                    }
+                }
             }
+        }
+        // test for overflow. A session without room for more notifications caps the queue where it is
+        let queue_limit = if self.session_queue_full { self.notification_queue.len().min(self.queue_size) } else { self.queue_size };
+        if queue_limit == 0 {
+            trace!("Session notification limit reached, dropping notification, node {:?}", self.item_to_monitor.node_id);
+            self.set_queue_overflow(1);
+            return;
+        }
+        let (overflow, dropped) = if self.notification_queue.len() >= queue_limit {
+            trace!("Data change overflow, node {:?}", self.item_to_monitor.node_id);
+            // Overflow behaviour. Notifications are queued at the back so the oldest is at the front
+            let dropped = match self.overflow_strategy() {
+                OverflowStrategy::DiscardOldest => {
+                    // Throw away oldest item (the one at the start) to make space at the end
+                    let _ = self.notification_queue.pop_front();
+                    1
+                }
+                OverflowStrategy::DiscardNewest => {
+                    // The new notification replaces the newest one (the one at the end), so the
+                    // older notifications are kept and the queue always ends with the latest value
+                    let _ = self.notification_queue.pop_back();
+                    1
+                }
+                OverflowStrategy::DropAllMarkOverflow => {
+                    // Throw everything away so only the latest value is reported
+                    let dropped = self.notification_queue.len();
+                    self.notification_queue.clear();
+                    dropped
+                }
+            };
             // Overflow only affects queues > 1 element
-            self.queue_size > 1
+            (self.queue_size > 1, dropped)
         } else {
-            false
+            (false, 0)
         };
-        let mut notification = notification.into();
         if overflow {
             if let Notification::MonitoredItemNotification(ref mut notification) = notification {
                 // Set the overflow bit on the data value's status
-                notification.value.status = Some(notification.value.status() | StatusCode::OVERFLOW);
+                notification.value.status = Some(notification.value.status().with_overflow(true));
             }
-            self.queue_overflow = true;
+            self.set_queue_overflow(dropped);
         }
         self.notification_queue.push_back(notification);
     }
 
+    /// Marks the queue as in overflow, queueing an event for the queue overflow callback if it
+    /// was not already
+    fn set_queue_overflow(&mut self, dropped: usize) {
+        if !self.queue_overflow {
+            if let Some(ref callback) = self.queue_overflow_callback {
+                self.queue_overflow_events.push(QueueOverflowEvent {
+                    callback: callback.clone(),
+                    monitored_item_id: self.monitored_item_id,
+                    dropped,
+                });
+            }
+        }
+        self.queue_overflow = true;
+    }
+
+    /// Takes the queue overflows waiting to be passed to the callback
+    pub(crate) fn take_queue_overflow_events(&mut self) -> Vec<QueueOverflowEvent> {
+        std::mem::take(&mut self.queue_overflow_events)
+    }
+
     /// Gets the oldest notification message from the notification queue
     #[cfg(test)]
     pub fn oldest_notification_message(&mut self) -> Option<Notification> {
@@ -436,13 +1488,29 @@ impl MonitoredItem {
         }
     }
 
+    /// Takes the requested timestamps to return and ensures they make sense for the monitored
+    /// attribute. Only the Value attribute has a source timestamp, so for any other attribute a
+    /// request for `Source` or `Both` is downgraded to `Server`.
+    pub fn sanitize_timestamps_to_return(timestamps_to_return: TimestampsToReturn, attribute_id: u32) -> TimestampsToReturn {
+        match timestamps_to_return {
+            TimestampsToReturn::Source | TimestampsToReturn::Both if attribute_id != AttributeId::Value as u32 => {
+                debug!("Attribute {} has no source timestamp, so only the server timestamp will be returned", attribute_id);
+                TimestampsToReturn::Server
+            }
+            timestamps_to_return => timestamps_to_return
+        }
+    }
+
     /// Takes the requested sampling interval value supplied by client and ensures it is within
     /// the range supported by the server
     fn sanitize_sampling_interval(requested_sampling_interval: f64) -> f64 {
-        if requested_sampling_interval < 0.0 {
-            // From spec "any negative number is interpreted as -1"
+        if requested_sampling_interval == -1.0 {
             // -1 means monitored item's sampling interval defaults to the subscription's publishing interval
             -1.0
+        } else if requested_sampling_interval < 0.0 {
+            // Other negative values have no defined meaning so the item is given the default
+            // interval. Tick only ever sees -1 or a non-negative interval.
+            constants::DEFAULT_SAMPLING_INTERVAL
         } else if requested_sampling_interval == 0.0 || requested_sampling_interval < constants::MIN_SAMPLING_INTERVAL {
             constants::MIN_SAMPLING_INTERVAL
         } else {
@@ -450,21 +1518,69 @@ impl MonitoredItem {
         }
     }
 
-    /// Takes the requested queue size and ensures it is within the range supported by the server
-    fn sanitize_queue_size(requested_queue_size: usize) -> usize {
-        if requested_queue_size == 0 {
-            // For data monitored items 0 -> 1
-            1
+    /// Takes the requested queue size and discard policy and ensures they are within the range
+    /// supported by the server. A queue size of 0 means the client has no preference so the
+    /// server's defaults are used instead.
+    fn sanitize_queue_size(requested_queue_size: usize, requested_discard_oldest: bool, defaults: &MonitoredItemDefaults) -> (usize, bool) {
+        let (queue_size, discard_oldest) = if requested_queue_size == 0 {
+            // For data monitored items 0 -> default
+            (defaults.queue_size, defaults.discard_oldest)
             // Future - for event monitored items, queue size should be the default queue size for event notifications
-        } else if requested_queue_size == 1 {
+        } else {
+            (requested_queue_size, requested_discard_oldest)
+        };
+        let queue_size = if queue_size <= 1 {
             1
             // Future - for event monitored items, the minimum queue size the server requires for event notifications
-        } else if requested_queue_size > constants::MAX_DATA_CHANGE_QUEUE_SIZE {
+        } else if queue_size > constants::MAX_DATA_CHANGE_QUEUE_SIZE {
             constants::MAX_DATA_CHANGE_QUEUE_SIZE
             // Future - for event monitored items MaxUInt32 returns the maximum queue size the server support
             // for event notifications
         } else {
-            requested_queue_size
+            queue_size
+        };
+        (queue_size, discard_oldest)
+    }
+
+    /// Tests if the item was created from an identical request - the same item to monitor, filter
+    /// and sampling interval. The sampling interval is compared after it is revised, so requests
+    /// that are revised to the same interval are duplicates. Other parameters, e.g. the queue size
+    /// or client handle, are not compared.
+    pub fn is_duplicate(&self, request: &MonitoredItemCreateRequest) -> bool {
+        self.item_to_monitor == request.item_to_monitor &&
+            self.sampling_interval == Self::sanitize_sampling_interval(request.requested_parameters.sampling_interval) &&
+            FilterType::from_extension_object(&request.requested_parameters.filter).map_or(false, |filter| filter == self.filter)
+    }
+
+    /// Tests if the item monitors the node and attribute, and if its index range overlaps the
+    /// supplied range. A write to the node can use this to push a change to the items interested
+    /// in it instead of waiting for them to poll.
+    pub fn matches(&self, node_id: &NodeId, attribute_id: AttributeId, index_range: &NumericRange) -> bool {
+        if self.item_to_monitor.node_id != *node_id || self.item_to_monitor.attribute_id != attribute_id as u32 {
+            false
+        } else if self.item_to_monitor.index_range.is_empty() {
+            true
+        } else {
+            let item_range: &str = self.item_to_monitor.index_range.as_ref();
+            match NumericRange::new(item_range) {
+                Ok(item_range) => item_range.overlaps(index_range),
+                Err(_) => false
+            }
+        }
+    }
+
+    /// Called after a write to the address space. If the item is in push mode, enabled and
+    /// interested in what was written, it samples its value now rather than on the next tick.
+    /// Returns true if a notification was queued.
+    pub fn on_write(&mut self, address_space: &AddressSpace, now: &DateTimeUtc, node_id: &NodeId, attribute_id: AttributeId, index_range: &NumericRange) -> bool {
+        if attribute_id == AttributeId::Value && self.eu_range_node_id.as_ref() == Some(node_id) {
+            // The EURange has been written so read it again when it is next needed
+            self.eu_range = None;
+        }
+        if self.push_on_write && self.monitoring_mode != MonitoringMode::Disabled && self.matches(node_id, attribute_id, index_range) {
+            self.check_value(address_space, now, false)
+        } else {
+            false
         }
     }
 
@@ -480,11 +1596,43 @@ impl MonitoredItem {
         self.sampling_interval
     }
 
+    /// Returns the interval in millis that the item actually samples at once the requested
+    /// interval has been revised. An interval of -1 means the item is sampled each time the
+    /// subscription's publishing interval elapses, so that is what is returned for it. Otherwise
+    /// it is the revised interval, but no shorter than the interval the item is ticked at, i.e.
+    /// the server's tick quantum or the subscription timer rate when there is no quantum.
+    pub fn effective_sampling_interval(&self, publishing_interval: Duration) -> Duration {
+        if self.sampling_interval < 0f64 {
+            publishing_interval
+        } else {
+            let tick_interval = if self.sampling_quantum > 0f64 {
+                self.sampling_quantum
+            } else {
+                constants::SUBSCRIPTION_TIMER_RATE_MS as Duration
+            };
+            f64::max(self.sampling_interval, tick_interval)
+        }
+    }
+
+    pub fn timestamps_to_return(&self) -> TimestampsToReturn {
+        self.timestamps_to_return
+    }
+
+    pub fn sampling_interval_stats(&self) -> &SamplingIntervalStats {
+        &self.sampling_interval_stats
+    }
+
     pub fn triggered_items(&self) -> &BTreeSet<u32> {
         &self.triggered_items
     }
 
+    /// Sets the monitoring mode. An item that is enabled again after being disabled forgets its
+    /// last value so the next tick samples it afresh and reports the latest value, including any
+    /// change that happened while it was disabled.
     pub fn set_monitoring_mode(&mut self, monitoring_mode: MonitoringMode) {
+        if self.monitoring_mode == MonitoringMode::Disabled && monitoring_mode != MonitoringMode::Disabled && !self.is_event_filter() {
+            self.last_data_value = None;
+        }
         self.monitoring_mode = monitoring_mode;
     }
 
@@ -492,10 +1640,147 @@ impl MonitoredItem {
         self.monitoring_mode
     }
 
+    /// Forgets the last value so it is no longer a baseline for comparison. The next tick that
+    /// samples the item reports the value it samples, whether or not it has changed, as on the
+    /// item's first tick. Ticks before the sampling interval elapses are unaffected.
+    pub fn reset_baseline(&mut self) {
+        self.last_data_value = None;
+    }
+
     pub fn queue_size(&self) -> usize {
         self.queue_size
     }
 
+    /// Returns the number of notifications waiting in the queue
+    pub fn queue_len(&self) -> usize {
+        self.notification_queue.len()
+    }
+
+    /// Returns how many more notifications can be queued before the queue overflows
+    pub fn queue_remaining(&self) -> usize {
+        self.queue_size.saturating_sub(self.notification_queue.len())
+    }
+
+    #[cfg(test)]
+    pub fn discard_oldest(&self) -> bool {
+        self.discard_oldest
+    }
+
+    #[cfg(test)]
+    pub fn coalesce(&self) -> bool {
+        self.coalesce
+    }
+
+    #[cfg(test)]
+    pub fn min_notification_interval(&self) -> Duration {
+        self.min_notification_interval
+    }
+
+    /// Sets the minimum interval in milliseconds between data change notifications. Changes that
+    /// happen faster are held back and only the latest is reported when the interval elapses. This
+    /// is independent of the sampling interval. A value of 0 or less removes the limit.
+    pub fn set_min_notification_interval(&mut self, min_notification_interval: Duration) {
+        self.min_notification_interval = if min_notification_interval > 0f64 { min_notification_interval } else { 0f64 };
+        if self.min_notification_interval == 0f64 {
+            if let Some(notification) = self.pending_notification.take() {
+                self.enqueue_notification_message(notification);
+            }
+        }
+    }
+
+    /// Sets the coalescing mode. When enabled, data changes that happen within one publishing
+    /// interval collapse into a single notification holding the most recent sample.
+    pub fn set_coalesce(&mut self, coalesce: bool) {
+        self.coalesce = coalesce;
+    }
+
+    /// Sets source timestamp hold off. When enabled, a sample is only considered for a data change
+    /// if its source timestamp differs from the previous sample's, so the item never reports
+    /// faster than the source itself updates.
+    pub fn set_source_timestamp_hold_off(&mut self, source_timestamp_hold_off: bool) {
+        self.source_timestamp_hold_off = source_timestamp_hold_off;
+    }
+
+    /// Sets whether every sample with an uncertain status is reported, regardless of whether the
+    /// value or status changed. Samples with a good or bad status follow the normal rules.
+    pub fn set_report_uncertain(&mut self, report_uncertain: bool) {
+        self.report_uncertain = report_uncertain;
+    }
+
+    /// Sets whether heartbeats are reported. A heartbeat is a sample from a source that updates
+    /// its source timestamp while its value and status stay the same. By default heartbeats are
+    /// suppressed unless the trigger of the item's filter is status/value/timestamp. When set,
+    /// they are reported whatever the trigger.
+    pub fn set_report_heartbeats(&mut self, report_heartbeats: bool) {
+        self.report_heartbeats = report_heartbeats;
+    }
+
+    /// Sets whether a write to the monitored node samples the item immediately. The change is
+    /// queued subject to the queue size and discard policy and reported on the next publish.
+    pub fn set_push_on_write(&mut self, push_on_write: bool) {
+        self.push_on_write = push_on_write;
+    }
+
+    /// Sets whether the deadband of the item's data change filter is ignored. When set, values
+    /// are compared exactly and any change is reported, while the filter's trigger still applies.
+    pub fn set_ignore_deadband(&mut self, ignore_deadband: bool) {
+        self.ignore_deadband = ignore_deadband;
+    }
+
+    /// Sets whether a change in the type of the item's value is reported as a change. By default
+    /// numbers of differing types are compared by magnitude, so a value read as a `UInt16` one tick
+    /// and an `Int32` the next is unchanged if they are equal.
+    pub fn set_strict_type_compare(&mut self, strict_type_compare: bool) {
+        self.strict_type_compare = strict_type_compare;
+    }
+
+    /// Sets whether the item's data change notifications are accompanied by diagnostic infos
+    pub fn set_return_diagnostics(&mut self, return_diagnostics: bool) {
+        self.return_diagnostics = return_diagnostics;
+    }
+
+    /// Sets the callback made when the queue goes into overflow, or `None` for no callback. See
+    /// `QueueOverflow` for when it is called. The callback is not made by the item itself but
+    /// once the session's subscriptions have been ticked and the session is unlocked.
+    pub fn set_queue_overflow_callback(&mut self, queue_overflow_callback: Option<Arc<Mutex<dyn QueueOverflow + Send>>>) {
+        self.queue_overflow_callback = queue_overflow_callback.map(QueueOverflowCallback);
+    }
+
+    /// Sets the strategy for a full queue, overriding the discard policy requested by the client.
+    /// `None` restores the client's policy.
+    pub fn set_overflow_strategy(&mut self, overflow_strategy: Option<OverflowStrategy>) {
+        self.overflow_strategy = overflow_strategy;
+    }
+
+    /// Returns the strategy the item uses when its queue is full
+    pub fn overflow_strategy(&self) -> OverflowStrategy {
+        match self.overflow_strategy {
+            Some(overflow_strategy) => overflow_strategy,
+            None if self.discard_oldest => OverflowStrategy::DiscardOldest,
+            None => OverflowStrategy::DiscardNewest
+        }
+    }
+
+    /// Returns the diagnostic info to accompany a data change notification of this item, or `None`
+    /// if the item does not return diagnostics. A notification with a bad status is described
+    /// by its status and the node and attribute that were sampled, any other has an empty one.
+    pub fn diagnostic_info(&self, notification: &MonitoredItemNotification) -> Option<DiagnosticInfo> {
+        if !self.return_diagnostics {
+            None
+        } else {
+            let status = notification.value.status();
+            if status.is_bad() {
+                Some(DiagnosticInfo {
+                    additional_info: Some(UAString::from(format!("Node {} attribute {} has a bad status", self.item_to_monitor.node_id, self.item_to_monitor.attribute_id))),
+                    inner_status_code: Some(status),
+                    ..DiagnosticInfo::null()
+                })
+            } else {
+                Some(DiagnosticInfo::null())
+            }
+        }
+    }
+
     #[cfg(test)]
     pub fn queue_overflow(&self) -> bool {
         self.queue_overflow
@@ -506,7 +1791,10 @@ impl MonitoredItem {
         &self.notification_queue
     }
 
-    #[cfg(test)]
+    pub(crate) fn set_session_queue_full(&mut self, session_queue_full: bool) {
+        self.session_queue_full = session_queue_full;
+    }
+
     pub(crate) fn set_discard_oldest(&mut self, discard_oldest: bool) {
         self.discard_oldest = discard_oldest;
     }
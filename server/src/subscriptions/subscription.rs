@@ -3,7 +3,7 @@
 // Copyright (C) 2017-2020 Adam Lock
 
 use std::collections::{HashMap, BTreeSet, VecDeque};
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
 
 use chrono;
 
@@ -18,8 +18,9 @@ use opcua_types::{
 use opcua_core::handle::Handle;
 
 use crate::{
+    callbacks::QueueOverflow,
     constants,
-    subscriptions::monitored_item::{MonitoredItem, TickResult, Notification},
+    subscriptions::monitored_item::{MonitoredItem, MonitoredItemDefaults, MonitoredItemSnapshot, OverflowStrategy, QueueOverflowEvent, SampledNodes, TickResult, Notification},
     address_space::AddressSpace,
     diagnostics::ServerDiagnostics,
 };
@@ -102,10 +103,26 @@ pub(crate) enum TickReason {
     TickTimerFired,
 }
 
+/// The configuration of a subscription and its monitored items, without any queued notifications,
+/// in a form that can be persisted. A server can use it to restore subscriptions after a restart.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubscriptionSnapshot {
+    pub subscription_id: u32,
+    pub publishing_interval: Duration,
+    pub lifetime_count: u32,
+    pub max_keep_alive_count: u32,
+    pub priority: u8,
+    pub publishing_enabled: bool,
+    pub max_monitored_items_per_tick: usize,
+    pub monitored_items: Vec<MonitoredItemSnapshot>,
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct Subscription {
     /// Subscription id
     subscription_id: u32,
+    /// The id of the session that owns the subscription
+    session_id: NodeId,
     /// Publishing interval in milliseconds
     publishing_interval: Duration,
     /// The lifetime count reset value
@@ -143,7 +160,14 @@ pub struct Subscription {
     /// wrong somewhere.
     last_sequence_number: u32,
     // The last monitored item id
-    next_monitored_item_id: u32,
+    monitored_item_id: Handle,
+    /// The maximum number of monitored items to tick in one cycle, or 0 for no limit. When there
+    /// are more items than this, each cycle resumes from where the last one stopped.
+    max_monitored_items_per_tick: usize,
+    // The monitored item id that the next budgeted tick resumes from
+    next_monitored_item_to_tick: u32,
+    // How many more notifications the session allows monitored items to queue, None for no limit
+    notification_budget: Option<usize>,
     // The time that the subscription interval last fired
     last_time_publishing_interval_elapsed: DateTimeUtc,
     // Currently outstanding notifications to send
@@ -170,6 +194,7 @@ impl Subscription {
     pub fn new(diagnostics: Arc<RwLock<ServerDiagnostics>>, subscription_id: u32, publishing_enabled: bool, publishing_interval: Duration, lifetime_counter: u32, keep_alive_counter: u32, priority: u8) -> Subscription {
         let subscription = Subscription {
             subscription_id,
+            session_id: NodeId::null(),
             publishing_interval,
             priority,
             monitored_items: HashMap::with_capacity(constants::DEFAULT_MONITORED_ITEM_CAPACITY),
@@ -185,7 +210,10 @@ impl Subscription {
             // Counters for new items
             sequence_number: Handle::new(1),
             last_sequence_number: 0,
-            next_monitored_item_id: 1,
+            monitored_item_id: Handle::new(1),
+            max_monitored_items_per_tick: 0,
+            notification_budget: None,
+            next_monitored_item_to_tick: 0,
             last_time_publishing_interval_elapsed: chrono::Utc::now(),
             notifications: VecDeque::with_capacity(100),
             diagnostics,
@@ -198,12 +226,49 @@ impl Subscription {
         subscription
     }
 
+    /// Takes a snapshot of the subscription's configuration and its monitored items
+    pub fn snapshot(&self) -> SubscriptionSnapshot {
+        let mut monitored_items = self.monitored_items.values().map(|m| m.snapshot()).collect::<Vec<_>>();
+        monitored_items.sort_by_key(|m| m.monitored_item_id);
+        SubscriptionSnapshot {
+            subscription_id: self.subscription_id,
+            publishing_interval: self.publishing_interval,
+            lifetime_count: self.max_lifetime_counter,
+            max_keep_alive_count: self.max_keep_alive_counter,
+            priority: self.priority,
+            publishing_enabled: self.publishing_enabled,
+            max_monitored_items_per_tick: self.max_monitored_items_per_tick,
+            monitored_items,
+        }
+    }
+
+    /// Restores a subscription from a snapshot. The subscription starts in the same state as a
+    /// newly created one, so the current value of each monitored item is reported on the first
+    /// publish. The subscription must still be added to a session. The time `now` should come from
+    /// the server's clock and the defaults should be the server's configured monitored item
+    /// defaults.
+    pub fn restore(diagnostics: Arc<RwLock<ServerDiagnostics>>, now: &DateTimeUtc, snapshot: &SubscriptionSnapshot, defaults: &MonitoredItemDefaults) -> Result<Subscription, StatusCode> {
+        let mut subscription = Subscription::new(diagnostics, snapshot.subscription_id, snapshot.publishing_enabled, snapshot.publishing_interval, snapshot.lifetime_count, snapshot.max_keep_alive_count, snapshot.priority);
+        subscription.max_monitored_items_per_tick = snapshot.max_monitored_items_per_tick;
+        subscription.set_publishing_interval_start(now);
+        for monitored_item in &snapshot.monitored_items {
+            let monitored_item = MonitoredItem::restore(now, monitored_item, defaults)?;
+            subscription.monitored_items.insert(monitored_item.monitored_item_id(), monitored_item);
+        }
+        // New items are given ids after the restored ones
+        if let Some(next_id) = subscription.monitored_items.keys().max().and_then(|id| id.checked_add(1)) {
+            subscription.monitored_item_id.set_next(next_id);
+        }
+        Ok(subscription)
+    }
+
     pub(crate) fn ready_to_remove(&self) -> bool {
         self.state == SubscriptionState::Closed && self.notifications.is_empty()
     }
 
     /// Creates a MonitoredItemCreateResult containing an error code
     fn monitored_item_create_error(status_code: StatusCode) -> MonitoredItemCreateResult {
+        debug!("Monitored item cannot be created, status code = {}", status_code);
         MonitoredItemCreateResult {
             status_code,
             monitored_item_id: 0,
@@ -217,8 +282,63 @@ impl Subscription {
         self.monitored_items.len()
     }
 
+    /// Returns the number of notifications queued on all the monitored items
+    pub(crate) fn queued_notifications_len(&self) -> usize {
+        self.monitored_items.values().map(|m| m.queue_len()).sum()
+    }
+
+    /// Sets how many more notifications the session allows the monitored items to queue, or
+    /// `None` for no limit. Items overflow instead of queueing once the budget is spent.
+    pub(crate) fn set_notification_budget(&mut self, notification_budget: Option<usize>) {
+        self.notification_budget = notification_budget;
+    }
+
+    /// Tells the monitored item whether the session has room for more notifications. Called
+    /// before something that may queue notifications on the item.
+    fn apply_notification_budget(notification_budget: Option<usize>, monitored_item: &mut MonitoredItem) -> usize {
+        monitored_item.set_session_queue_full(notification_budget == Some(0));
+        monitored_item.queue_len()
+    }
+
+    /// Deducts any growth in the monitored item's queue from the session's notification budget
+    fn spend_notification_budget(notification_budget: &mut Option<usize>, queued_before: usize, monitored_item: &MonitoredItem) {
+        if let Some(budget) = notification_budget {
+            let queued = monitored_item.queue_len().saturating_sub(queued_before);
+            *budget = budget.saturating_sub(queued);
+        }
+    }
+
+    /// Returns the number of notifications queued on a monitored item, or None if there is no such item
+    #[cfg(test)]
+    pub(crate) fn monitored_item_notifications_len(&self, monitored_item_id: u32) -> Option<usize> {
+        self.monitored_items.get(&monitored_item_id).map(|m| m.queue_len())
+    }
+
+    /// Returns true if any monitored item has overflowed its queue since it was last emptied
+    #[cfg(test)]
+    pub(crate) fn any_queue_overflow(&self) -> bool {
+        self.monitored_items.values().any(|m| m.queue_overflow())
+    }
+
+    /// Finds a monitored item that duplicates the create request, i.e. that monitors the same node,
+    /// attribute and index range with the same filter and sampling interval. Returns the id of the
+    /// item, the lowest if there is more than one, so a caller may choose to share it rather than
+    /// create another item. See `MonitoredItem::is_duplicate()`.
+    pub fn find_duplicate(&self, request: &MonitoredItemCreateRequest) -> Option<u32> {
+        self.monitored_items.iter()
+            .filter(|(_, monitored_item)| monitored_item.is_duplicate(request))
+            .map(|(monitored_item_id, _)| *monitored_item_id)
+            .min()
+    }
+
+    /// Sets the maximum number of monitored items that are ticked in a single cycle, or 0 for
+    /// no limit. Items are ticked round-robin so none are starved by the limit.
+    pub fn set_max_monitored_items_per_tick(&mut self, max_monitored_items_per_tick: usize) {
+        self.max_monitored_items_per_tick = max_monitored_items_per_tick;
+    }
+
     /// Creates monitored items on the specified subscription, returning the creation results
-    pub fn create_monitored_items(&mut self, address_space: &AddressSpace, now: &DateTimeUtc, timestamps_to_return: TimestampsToReturn, items_to_create: &[MonitoredItemCreateRequest], max_monitored_items_per_sub: usize) -> Vec<MonitoredItemCreateResult> {
+    pub fn create_monitored_items(&mut self, address_space: &AddressSpace, now: &DateTimeUtc, timestamps_to_return: TimestampsToReturn, items_to_create: &[MonitoredItemCreateRequest], max_monitored_items_per_sub: usize, defaults: &MonitoredItemDefaults) -> Vec<MonitoredItemCreateResult> {
         self.reset_lifetime_counter();
 
         // Add items to the subscription if they're not already in its
@@ -227,22 +347,26 @@ impl Subscription {
                 Self::monitored_item_create_error(StatusCode::BadNodeIdUnknown)
             } else {
 
-                // TODO validate the attribute id for the type of node
                 // TODO validate the index range for the node
 
-                // Create a monitored item, if possible
-                let monitored_item_id = self.next_monitored_item_id;
-                match MonitoredItem::new(now, monitored_item_id, timestamps_to_return, item_to_create) {
-                    Ok(monitored_item) => {
+                // Create a monitored item, if possible. Ids still in use are skipped when the id wraps
+                let monitored_items = &self.monitored_items;
+                let monitored_item_id = match self.monitored_item_id.next_unused(|id| monitored_items.contains_key(&id)) {
+                    Some(monitored_item_id) => monitored_item_id,
+                    None => return Self::monitored_item_create_error(StatusCode::BadTooManyMonitoredItems)
+                };
+                let result = match MonitoredItem::new_with_defaults(now, monitored_item_id, timestamps_to_return, item_to_create, defaults) {
+                    Ok(mut monitored_item) => {
                         if max_monitored_items_per_sub == 0 || self.monitored_items.len() <= max_monitored_items_per_sub {
                             let revised_sampling_interval = monitored_item.sampling_interval();
                             let revised_queue_size = monitored_item.queue_size() as u32;
-                            // Validate the filter before registering the item
-                            match monitored_item.validate_filter(address_space) {
+                            // Validate the attribute and filter before registering the item
+                            match monitored_item.validate_attribute(address_space).and_then(|_| monitored_item.validate_filter(address_space)) {
                                 Ok(filter_result) => {
+                                    // Queue the current value so it is reported on the first publish
+                                    let _ = monitored_item.initial_sample(address_space, now);
                                     // Register the item with the subscription
                                     self.monitored_items.insert(monitored_item_id, monitored_item);
-                                    self.next_monitored_item_id += 1;
                                     MonitoredItemCreateResult {
                                         status_code: StatusCode::Good,
                                         monitored_item_id,
@@ -259,19 +383,24 @@ impl Subscription {
                         }
                     }
                     Err(status_code) => Self::monitored_item_create_error(status_code)
+                };
+                if result.status_code.is_bad() {
+                    // Nothing was created so the id can be issued to the next item
+                    self.monitored_item_id.set_next(monitored_item_id);
                 }
+                result
             }
         }).collect()
     }
 
     /// Modify the specified monitored items, returning a result for each
-    pub fn modify_monitored_items(&mut self, address_space: &AddressSpace, timestamps_to_return: TimestampsToReturn, items_to_modify: &[MonitoredItemModifyRequest]) -> Vec<MonitoredItemModifyResult> {
+    pub fn modify_monitored_items(&mut self, address_space: &AddressSpace, timestamps_to_return: TimestampsToReturn, items_to_modify: &[MonitoredItemModifyRequest], defaults: &MonitoredItemDefaults) -> Vec<MonitoredItemModifyResult> {
         self.reset_lifetime_counter();
         items_to_modify.iter().map(|item_to_modify| {
             match self.monitored_items.get_mut(&item_to_modify.monitored_item_id) {
                 Some(monitored_item) => {
                     // Try to change the monitored item according to the modify request
-                    let modify_result = monitored_item.modify(address_space, timestamps_to_return, item_to_modify);
+                    let modify_result = monitored_item.modify(address_space, timestamps_to_return, item_to_modify, defaults);
                     match modify_result {
                         Ok(filter_result) => MonitoredItemModifyResult {
                             status_code: StatusCode::Good,
@@ -308,6 +437,120 @@ impl Subscription {
         }
     }
 
+    /// Sets the monitoring mode on several monitored items, returning a status code for each id in
+    /// the order supplied. Unknown ids are BadMonitoredItemIdInvalid and do not affect the others.
+    pub fn set_monitoring_modes(&mut self, monitoring_mode: MonitoringMode, monitored_item_ids: &[u32]) -> Vec<StatusCode> {
+        monitored_item_ids.iter().map(|monitored_item_id| {
+            self.set_monitoring_mode(*monitored_item_id, monitoring_mode)
+        }).collect()
+    }
+
+    /// Calls the function with one monitored item, for setting its options. Returns
+    /// BadMonitoredItemIdInvalid without calling it if there is no item with the id.
+    pub(crate) fn with_monitored_item<F>(&mut self, monitored_item_id: u32, f: F) -> StatusCode where F: FnOnce(&mut MonitoredItem) {
+        if let Some(monitored_item) = self.monitored_items.get_mut(&monitored_item_id) {
+            f(monitored_item);
+            StatusCode::Good
+        } else {
+            StatusCode::BadMonitoredItemIdInvalid
+        }
+    }
+
+    /// Sets the coalescing mode on one monitored item. See `MonitoredItem::set_coalesce()`.
+    pub fn set_coalesce(&mut self, monitored_item_id: u32, coalesce: bool) -> StatusCode {
+        self.with_monitored_item(monitored_item_id, |m| m.set_coalesce(coalesce))
+    }
+
+    /// Sets the minimum interval in milliseconds between notifications on one monitored item. See
+    /// `MonitoredItem::set_min_notification_interval()`.
+    pub fn set_min_notification_interval(&mut self, monitored_item_id: u32, min_notification_interval: Duration) -> StatusCode {
+        self.with_monitored_item(monitored_item_id, |m| m.set_min_notification_interval(min_notification_interval))
+    }
+
+    /// Sets source timestamp hold off on one monitored item. See
+    /// `MonitoredItem::set_source_timestamp_hold_off()`.
+    pub fn set_source_timestamp_hold_off(&mut self, monitored_item_id: u32, source_timestamp_hold_off: bool) -> StatusCode {
+        self.with_monitored_item(monitored_item_id, |m| m.set_source_timestamp_hold_off(source_timestamp_hold_off))
+    }
+
+    /// Sets whether one monitored item reports every uncertain sample. See
+    /// `MonitoredItem::set_report_uncertain()`.
+    pub fn set_report_uncertain(&mut self, monitored_item_id: u32, report_uncertain: bool) -> StatusCode {
+        self.with_monitored_item(monitored_item_id, |m| m.set_report_uncertain(report_uncertain))
+    }
+
+    /// Sets whether one monitored item reports heartbeats. See
+    /// `MonitoredItem::set_report_heartbeats()`.
+    pub fn set_report_heartbeats(&mut self, monitored_item_id: u32, report_heartbeats: bool) -> StatusCode {
+        self.with_monitored_item(monitored_item_id, |m| m.set_report_heartbeats(report_heartbeats))
+    }
+
+    /// Sets whether a write to the node of one monitored item samples it immediately. See
+    /// `MonitoredItem::set_push_on_write()`.
+    pub fn set_push_on_write(&mut self, monitored_item_id: u32, push_on_write: bool) -> StatusCode {
+        self.with_monitored_item(monitored_item_id, |m| m.set_push_on_write(push_on_write))
+    }
+
+    /// Sets whether one monitored item ignores the deadband of its filter. See
+    /// `MonitoredItem::set_ignore_deadband()`.
+    pub fn set_ignore_deadband(&mut self, monitored_item_id: u32, ignore_deadband: bool) -> StatusCode {
+        self.with_monitored_item(monitored_item_id, |m| m.set_ignore_deadband(ignore_deadband))
+    }
+
+    /// Sets whether one monitored item reports a change in the type of its value. See
+    /// `MonitoredItem::set_strict_type_compare()`.
+    pub fn set_strict_type_compare(&mut self, monitored_item_id: u32, strict_type_compare: bool) -> StatusCode {
+        self.with_monitored_item(monitored_item_id, |m| m.set_strict_type_compare(strict_type_compare))
+    }
+
+    /// Sets whether one monitored item returns diagnostic infos with its data change notifications.
+    /// See `MonitoredItem::set_return_diagnostics()`.
+    pub fn set_return_diagnostics(&mut self, monitored_item_id: u32, return_diagnostics: bool) -> StatusCode {
+        self.with_monitored_item(monitored_item_id, |m| m.set_return_diagnostics(return_diagnostics))
+    }
+
+    /// Sets the strategy one monitored item uses when its queue is full, or `None` for the discard
+    /// policy the client requested. See `MonitoredItem::set_overflow_strategy()`.
+    pub fn set_overflow_strategy(&mut self, monitored_item_id: u32, overflow_strategy: Option<OverflowStrategy>) -> StatusCode {
+        self.with_monitored_item(monitored_item_id, |m| m.set_overflow_strategy(overflow_strategy))
+    }
+
+    /// Sets the callback one monitored item makes when its queue goes into overflow, or `None` for
+    /// no callback. See `MonitoredItem::set_queue_overflow_callback()`.
+    pub fn set_queue_overflow_callback(&mut self, monitored_item_id: u32, queue_overflow_callback: Option<Arc<Mutex<dyn QueueOverflow + Send>>>) -> StatusCode {
+        self.with_monitored_item(monitored_item_id, |m| m.set_queue_overflow_callback(queue_overflow_callback))
+    }
+
+    /// Takes the queue overflows of the monitored items that are waiting to be passed to their
+    /// callbacks
+    pub(crate) fn take_queue_overflow_events(&mut self) -> Vec<QueueOverflowEvent> {
+        self.monitored_items.values_mut().flat_map(|m| m.take_queue_overflow_events()).collect()
+    }
+
+    /// Passes a write on to the monitored items so those in push mode can sample it immediately.
+    /// Returns the number of notifications queued.
+    pub fn on_write(&mut self, address_space: &AddressSpace, now: &DateTimeUtc, node_id: &NodeId, attribute_id: AttributeId, index_range: &NumericRange) -> usize {
+        if self.state == SubscriptionState::Closed {
+            0
+        } else {
+            let mut queued = 0;
+            for monitored_item in self.monitored_items.values_mut() {
+                let queued_before = Self::apply_notification_budget(self.notification_budget, monitored_item);
+                if monitored_item.on_write(address_space, now, node_id, attribute_id, index_range) {
+                    queued += 1;
+                }
+                Self::spend_notification_budget(&mut self.notification_budget, queued_before, monitored_item);
+            }
+            queued
+        }
+    }
+
+    /// Rereads the cached EURange of every monitored item. Call this after changing the EURange
+    /// property of a monitored variable.
+    pub fn refresh_eu_ranges(&mut self, address_space: &AddressSpace) {
+        self.monitored_items.values_mut().for_each(|monitored_item| monitored_item.refresh_eu_range(address_space));
+    }
+
     /// Delete the specified monitored items (by item id), returning a status code for each
     pub fn delete_monitored_items(&mut self, items_to_delete: &[u32]) -> Vec<StatusCode> {
         self.reset_lifetime_counter();
@@ -333,6 +576,12 @@ impl Subscription {
         self.resend_data = true;
     }
 
+    /// Sets the time from which the first publishing interval is measured. A new subscription
+    /// measures it from the wall clock time it was created.
+    pub(crate) fn set_publishing_interval_start(&mut self, now: &DateTimeUtc) {
+        self.last_time_publishing_interval_elapsed = *now;
+    }
+
     /// Tests if the publishing interval has elapsed since the last time this function in which case
     /// it returns `true` and updates its internal state.
     fn test_and_set_publishing_interval_elapsed(&mut self, now: &DateTimeUtc) -> bool {
@@ -546,6 +795,7 @@ impl Subscription {
             SubscriptionState::Normal => {
                 if tick_reason == TickReason::ReceivePublishRequest && (!self.publishing_enabled || (self.publishing_enabled && !p.more_notifications)) {
                     // State #4
+                    self.reset_lifetime_counter();
                     return UpdateStateResult::new(HandledState::Normal4, UpdateStateAction::None);
                 } else if tick_reason == TickReason::ReceivePublishRequest && self.publishing_enabled && p.more_notifications {
                     // State #5
@@ -599,13 +849,14 @@ impl Subscription {
             SubscriptionState::KeepAlive => {
                 if tick_reason == TickReason::ReceivePublishRequest {
                     // State #13
+                    self.reset_lifetime_counter();
                     return UpdateStateResult::new(HandledState::KeepAlive13, UpdateStateAction::None);
                 } else if p.publishing_timer_expired && self.publishing_enabled && p.notifications_available && p.publishing_req_queued {
                     // State #14
                     self.first_message_sent = true;
                     self.state = SubscriptionState::Normal;
                     return UpdateStateResult::new(HandledState::KeepAlive14, UpdateStateAction::ReturnNotifications);
-                } else if p.publishing_timer_expired && p.publishing_req_queued && self.keep_alive_counter == 1 && (!self.publishing_enabled || (self.publishing_enabled && p.notifications_available)) {
+                } else if p.publishing_timer_expired && p.publishing_req_queued && self.keep_alive_counter == 1 && (!self.publishing_enabled || (self.publishing_enabled && !p.notifications_available)) {
                     // State #15
                     self.start_publishing_timer();
                     self.reset_keep_alive_counter();
@@ -630,6 +881,40 @@ impl Subscription {
         UpdateStateResult::new(HandledState::None0, UpdateStateAction::None)
     }
 
+    /// Returns the ids of the monitored items to tick this cycle. If there is a budget on the number
+    /// of items per tick then the ids are taken in order starting from where the previous cycle
+    /// left off, wrapping around to the start. A resend of data always ticks every item.
+    ///
+    /// The ids are returned with the items nearest to overflowing their queues first, so they have
+    /// the first call on the session's notification budget.
+    pub(crate) fn monitored_items_to_tick(&mut self, resend_data: bool) -> Vec<u32> {
+        let mut monitored_item_ids = self.monitored_item_ids_to_tick(resend_data);
+        monitored_item_ids.sort_by_key(|id| (self.monitored_items[id].queue_remaining(), *id));
+        monitored_item_ids
+    }
+
+    fn monitored_item_ids_to_tick(&mut self, resend_data: bool) -> Vec<u32> {
+        let mut monitored_item_ids: Vec<u32> = self.monitored_items.keys().cloned().collect();
+        let max_items = self.max_monitored_items_per_tick;
+        if resend_data || max_items == 0 || monitored_item_ids.len() <= max_items {
+            monitored_item_ids
+        } else {
+            monitored_item_ids.sort_unstable();
+            let start = monitored_item_ids.iter()
+                .position(|id| *id >= self.next_monitored_item_to_tick)
+                .unwrap_or(0);
+            let monitored_item_ids: Vec<u32> = monitored_item_ids.iter()
+                .cycle()
+                .skip(start)
+                .take(max_items)
+                .cloned()
+                .collect();
+            // Resume after the last item ticked. If that is past the highest id, the next cycle wraps around.
+            self.next_monitored_item_to_tick = monitored_item_ids.last().unwrap() + 1;
+            monitored_item_ids
+        }
+    }
+
     /// Iterate through the monitored items belonging to the subscription, calling tick on each in turn.
     ///
     /// Items that are in a reporting state, or triggered to report will be have their pending notifications
@@ -640,11 +925,19 @@ impl Subscription {
     fn tick_monitored_items(&mut self, now: &DateTimeUtc, address_space: &AddressSpace, publishing_interval_elapsed: bool, resend_data: bool) -> Option<NotificationMessage> {
         let mut triggered_items: BTreeSet<u32> = BTreeSet::new();
         let mut monitored_item_notifications = Vec::with_capacity(self.monitored_items.len() * 2);
+        // The diagnostic info of each data change notification, if its item returns diagnostics
+        let mut diagnostic_infos = Vec::new();
+        // Items sampling on every tick share their node lookups for this quantum
+        let mut sampled_nodes = SampledNodes::default();
 
-        for (_, monitored_item) in &mut self.monitored_items {
+        for monitored_item_id in self.monitored_items_to_tick(resend_data) {
+            let monitored_item = self.monitored_items.get_mut(&monitored_item_id).unwrap();
             // If this returns true then the monitored item wants to report its notification
             let monitoring_mode = monitored_item.monitoring_mode();
-            match monitored_item.tick(now, address_space, publishing_interval_elapsed, resend_data) {
+            let queued_before = Self::apply_notification_budget(self.notification_budget, monitored_item);
+            let tick_result = monitored_item.tick_with_nodes(now, address_space, &mut sampled_nodes, publishing_interval_elapsed, resend_data);
+            Self::spend_notification_budget(&mut self.notification_budget, queued_before, monitored_item);
+            match tick_result {
                 TickResult::ReportValueChanged => {
                     if publishing_interval_elapsed {
                         // If this monitored item has triggered items, then they need to be handled
@@ -663,9 +956,7 @@ impl Subscription {
                             }
                         }
                         // Take some / all of the monitored item's pending notifications
-                        if let Some(mut item_notification_messages) = monitored_item.all_notifications() {
-                            monitored_item_notifications.append(&mut item_notification_messages);
-                        }
+                        Self::take_notifications(monitored_item, &mut monitored_item_notifications, &mut diagnostic_infos);
                     }
                 }
                 TickResult::ValueChanged => {
@@ -704,9 +995,7 @@ impl Subscription {
                         //
                         // Call with the resend_data flag as true to force the monitored item to
                         monitored_item.check_value(address_space, now, true);
-                        if let Some(mut notifications) = monitored_item.all_notifications() {
-                            monitored_item_notifications.append(&mut notifications);
-                        }
+                        Self::take_notifications(monitored_item, &mut monitored_item_notifications, &mut diagnostic_infos);
                     }
                     MonitoringMode::Reporting => {
                         // If the monitoring mode of the item to report is REPORTING, this effectively causes the
@@ -744,14 +1033,34 @@ impl Subscription {
                 .map(|v| if let Notification::Event(v) = v { v.clone() } else { panic!() })
                 .collect();
 
+            // Diagnostics are only sent if an item asked for them, empty ones standing in for the rest
+            let diagnostic_infos = if diagnostic_infos.iter().any(Option::is_some) {
+                Some(diagnostic_infos.into_iter().map(|d| d.unwrap_or_else(DiagnosticInfo::null)).collect())
+            } else {
+                None
+            };
+
             // Make a notification
-            let notification = NotificationMessage::data_change(next_sequence_number, DateTime::from(now.clone()), data_change_notifications, event_notifications);
+            let notification = NotificationMessage::data_change_with_diagnostics(next_sequence_number, DateTime::from(now.clone()), data_change_notifications, diagnostic_infos, event_notifications);
             Some(notification)
         } else {
             None
         }
     }
 
+    /// Takes the monitored item's pending notifications, along with the diagnostic info of each of
+    /// its data change notifications
+    fn take_notifications(monitored_item: &mut MonitoredItem, notifications: &mut Vec<Notification>, diagnostic_infos: &mut Vec<Option<DiagnosticInfo>>) {
+        if let Some(mut item_notifications) = monitored_item.all_notifications() {
+            item_notifications.iter().for_each(|n| {
+                if let Notification::MonitoredItemNotification(n) = n {
+                    diagnostic_infos.push(monitored_item.diagnostic_info(n));
+                }
+            });
+            notifications.append(&mut item_notifications);
+        }
+    }
+
     /// Reset the keep-alive counter to the maximum keep-alive count of the Subscription.
     /// The maximum keep-alive count is set by the Client when the Subscription is created
     /// and may be modified using the ModifySubscription Service
@@ -775,10 +1084,24 @@ impl Subscription {
         self.subscription_id
     }
 
+    /// Returns the id of the session that owns the subscription
+    pub fn session_id(&self) -> &NodeId {
+        &self.session_id
+    }
+
+    pub(crate) fn set_session_id(&mut self, session_id: NodeId) {
+        self.session_id = session_id;
+    }
+
     pub fn lifetime_counter(&self) -> u32 {
         self.lifetime_counter
     }
 
+    #[cfg(test)]
+    pub(crate) fn set_next_monitored_item_id(&mut self, next_monitored_item_id: u32) {
+        self.monitored_item_id.set_next(next_monitored_item_id);
+    }
+
     #[cfg(test)]
     pub(crate) fn set_current_lifetime_count(&mut self, current_lifetime_count: u32) {
         self.lifetime_counter = current_lifetime_count;
@@ -16,6 +16,7 @@ use crate::{
     address_space::types::AddressSpace,
     subscriptions::{
         PublishRequestEntry, PublishResponseEntry,
+        monitored_item::QueueOverflowEvent,
         subscription::{Subscription, TickReason},
     },
 };
@@ -43,6 +44,18 @@ pub(crate) struct Subscriptions {
     // Notifications that have been sent but have yet to be acknowledged (retransmission queue).
     // Key is (subscription_id, sequence_number). Value is notification message.
     retransmission_queue: BTreeMap<(u32, u32), NotificationMessage>,
+    // Maximum number of notifications queued on the monitored items of all subscriptions, 0 for no limit
+    max_queued_notifications: usize,
+    // Sequence number of the last address space write passed on to the subscriptions
+    last_write_sequence_number: u64,
+}
+
+/// A subscription that has been detached from its session so that it can be attached to another,
+/// e.g. for TransferSubscriptions. The subscription carries its monitored items and their queued
+/// notifications with it. Notifications that were sent but not yet acknowledged travel alongside.
+pub(crate) struct DetachedSubscription {
+    subscription: Subscription,
+    unacknowledged_notifications: Vec<NotificationMessage>,
 }
 
 #[derive(Serialize)]
@@ -64,6 +77,29 @@ impl Subscriptions {
             subscriptions: BTreeMap::new(),
             transmission_queue: VecDeque::with_capacity(max_publish_requests),
             retransmission_queue: BTreeMap::new(),
+            max_queued_notifications: 0,
+            last_write_sequence_number: 0,
+        }
+    }
+
+    /// Sets the maximum number of notifications that may be queued on the monitored items of all
+    /// the subscriptions, 0 for no limit. Items overflow rather than queue beyond the limit.
+    pub(crate) fn set_max_queued_notifications(&mut self, max_queued_notifications: usize) {
+        self.max_queued_notifications = max_queued_notifications;
+    }
+
+    /// Returns the number of notifications queued on the monitored items of all the subscriptions
+    pub(crate) fn queued_notifications_len(&self) -> usize {
+        self.subscriptions.values().map(|s| s.queued_notifications_len()).sum()
+    }
+
+    /// Returns how many more notifications may be queued before the limit is reached, or `None`
+    /// if there is no limit.
+    fn notification_budget(&self) -> Option<usize> {
+        if self.max_queued_notifications == 0 {
+            None
+        } else {
+            Some(self.max_queued_notifications.saturating_sub(self.queued_notifications_len()))
         }
     }
 
@@ -98,6 +134,13 @@ impl Subscriptions {
         &mut self.retransmission_queue
     }
 
+    /// Takes the queue overflows of every monitored item that are waiting to be passed to their
+    /// callbacks. The caller makes the callbacks with `QueueOverflowEvent::notify()` once the
+    /// session is unlocked.
+    pub(crate) fn take_queue_overflow_events(&mut self) -> Vec<QueueOverflowEvent> {
+        self.subscriptions.values_mut().flat_map(|s| s.take_queue_overflow_events()).collect()
+    }
+
     /// Takes the publish responses which are queued for the client and returns them to the caller,
     /// or returns None if there are none to process.
     pub fn take_publish_responses(&mut self) -> Option<VecDeque<PublishResponseEntry>> {
@@ -178,11 +221,68 @@ impl Subscriptions {
         self.subscriptions.get_mut(&subscription_id)
     }
 
+    /// Removes every subscription owned by the session, dropping their monitored items along with
+    /// any notifications that are waiting to be sent or acknowledged. Returns the ids of the
+    /// subscriptions that were removed.
+    pub(crate) fn delete_session_subscriptions(&mut self, session_id: &NodeId) -> Vec<u32> {
+        let subscription_ids = self.subscriptions.values()
+            .filter(|s| s.session_id() == session_id)
+            .map(|s| s.subscription_id())
+            .collect::<Vec<u32>>();
+        subscription_ids.iter().for_each(|subscription_id| {
+            debug!("Deleting subscription {} owned by session {}", subscription_id, session_id);
+            let _ = self.subscriptions.remove(subscription_id);
+        });
+        self.transmission_queue.retain(|(subscription_id, _, _)| !subscription_ids.contains(subscription_id));
+        let sequence_nrs_to_remove = self.retransmission_queue.keys()
+            .filter(|k| subscription_ids.contains(&k.0))
+            .cloned()
+            .collect::<Vec<_>>();
+        self.remove_notifications(&sequence_nrs_to_remove);
+        subscription_ids
+    }
+
+    /// Detaches a subscription so it can be attached to another session's subscriptions. Returns
+    /// None if there is no such subscription.
+    ///
+    /// Notifications only sit on the transmission queue during a tick so the subscription and the
+    /// retransmission queue hold everything that is still to be delivered or acknowledged.
+    pub(crate) fn detach(&mut self, subscription_id: u32) -> Option<DetachedSubscription> {
+        let subscription = self.subscriptions.remove(&subscription_id)?;
+        let sequence_nrs = self.retransmission_queue.keys()
+            .filter(|k| k.0 == subscription_id)
+            .cloned()
+            .collect::<Vec<_>>();
+        let unacknowledged_notifications = sequence_nrs.iter()
+            .filter_map(|k| self.retransmission_queue.remove(k))
+            .collect();
+        Some(DetachedSubscription {
+            subscription,
+            unacknowledged_notifications,
+        })
+    }
+
+    /// Attaches a subscription detached from another session, making this session its owner.
+    pub(crate) fn attach(&mut self, session_id: &NodeId, detached: DetachedSubscription) {
+        let DetachedSubscription { mut subscription, unacknowledged_notifications } = detached;
+        let subscription_id = subscription.subscription_id();
+        debug!("Attaching subscription {} to session {}", subscription_id, session_id);
+        subscription.set_session_id(session_id.clone());
+        unacknowledged_notifications.into_iter().for_each(|n| {
+            self.retransmission_queue.insert((subscription_id, n.sequence_number), n);
+        });
+        self.subscriptions.insert(subscription_id, subscription);
+    }
+
     /// The tick causes the subscription manager to iterate through individual subscriptions calling tick
     /// on each in order of priority. In each case this could generate data change notifications. Data change
     /// notifications will be attached to the next available publish response and queued for sending
     /// to the client.
     pub(crate) fn tick(&mut self, now: &DateTimeUtc, address_space: &AddressSpace, tick_reason: TickReason) -> Result<(), StatusCode> {
+        // Items in push mode sample what was written since the last tick, whichever session or
+        // code wrote it
+        let _ = self.process_writes(address_space, now);
+
         let subscription_ids = {
             // Sort subscriptions by priority
             let mut subscription_priority: Vec<(u32, u8)> = self.subscriptions.values().map(|v| (v.subscription_id(), v.priority())).collect();
@@ -196,7 +296,9 @@ impl Subscriptions {
         // Now tick over the subscriptions
         for subscription_id in subscription_ids {
             let publishing_req_queued = !self.publish_request_queue.is_empty();
+            let notification_budget = self.notification_budget();
             let subscription = self.subscriptions.get_mut(&subscription_id).unwrap();
+            subscription.set_notification_budget(notification_budget);
 
             // Now tick the subscription to see if it has any notifications. If there are
             // notifications then the publish response will be associated with his subscription
@@ -250,6 +352,29 @@ impl Subscriptions {
         Ok(())
     }
 
+    /// Passes the writes recorded by the address space since the last call on to every
+    /// subscription so monitored items in push mode sample the written nodes. Returns the number
+    /// of notifications queued.
+    pub(crate) fn process_writes(&mut self, address_space: &AddressSpace, now: &DateTimeUtc) -> usize {
+        let last_write_sequence_number = self.last_write_sequence_number;
+        self.last_write_sequence_number = address_space.last_write_sequence_number();
+        address_space.writes_since(last_write_sequence_number)
+            .map(|w| self.on_write(address_space, now, &w.node_id, w.attribute_id, &w.index_range))
+            .sum()
+    }
+
+    /// Passes a write on to every subscription so monitored items in push mode can sample the
+    /// written node immediately. Returns the number of notifications queued.
+    pub(crate) fn on_write(&mut self, address_space: &AddressSpace, now: &DateTimeUtc, node_id: &NodeId, attribute_id: AttributeId, index_range: &NumericRange) -> usize {
+        let subscription_ids = self.subscriptions.keys().cloned().collect::<Vec<u32>>();
+        subscription_ids.iter().map(|subscription_id| {
+            let notification_budget = self.notification_budget();
+            let subscription = self.subscriptions.get_mut(subscription_id).unwrap();
+            subscription.set_notification_budget(notification_budget);
+            subscription.on_write(address_space, now, node_id, attribute_id, index_range)
+        }).sum()
+    }
+
     /// Iterates through the existing queued publish requests and creates a timeout
     /// publish response any that have expired.
     pub fn expire_stale_publish_requests(&mut self, now: &DateTimeUtc) {
@@ -0,0 +1,57 @@
+use futures::{Stream, sync::mpsc::unbounded};
+
+use opcua_core::comms::{chunker::Chunker, tcp_types::{HelloMessage, MIN_CHUNK_SIZE}};
+
+use crate::comms::tcp_transport::Message;
+
+use super::*;
+
+/// Makes a request whose body is the specified size by padding its audit entry id
+fn make_request_of_size(message_size: usize) -> SupportedMessage {
+    let make_request = |audit_entry_id: String| -> SupportedMessage {
+        GetEndpointsRequest {
+            request_header: RequestHeader {
+                audit_entry_id: UAString::from(audit_entry_id),
+                ..RequestHeader::dummy()
+            },
+            endpoint_url: UAString::from("opc.tcp://localhost:4855/"),
+            locale_ids: None,
+            profile_uris: None,
+        }.into()
+    };
+    let padding = message_size - make_request(String::new()).byte_len();
+    let request = make_request("x".repeat(padding));
+    assert_eq!(request.byte_len(), message_size);
+    request
+}
+
+#[test]
+fn transport_max_message_size() {
+    let server = ServerBuilder::new_sample().server().unwrap();
+    let mut transport = server.new_transport();
+
+    // The server acknowledges the hello with the largest request it will receive
+    let (mut sender, receiver) = unbounded();
+    let hello = HelloMessage::new("opc.tcp://localhost:4855/", MIN_CHUNK_SIZE, MIN_CHUNK_SIZE, 0);
+    transport.process_hello(hello, &mut sender).unwrap();
+    let max_message_size = match receiver.wait().next() {
+        Some(Ok(Message::Message(_, SupportedMessage::AcknowledgeMessage(acknowledge)))) => acknowledge.max_message_size as usize,
+        _ => panic!("Expected an acknowledge"),
+    };
+    assert!(max_message_size > 0);
+
+    let secure_channel: SecureChannel = (SecurityPolicy::None, MessageSecurityMode::None).into();
+    let mut process_request = |sequence_number: u32, request: SupportedMessage| -> Result<(), StatusCode> {
+        let chunks = Chunker::encode(sequence_number, sequence_number, 0, MIN_CHUNK_SIZE, &secure_channel, &request).unwrap();
+        assert!(chunks.len() > 1);
+        chunks.into_iter().map(|chunk| transport.process_chunk(chunk, &mut sender)).collect()
+    };
+
+    // A request at the negotiated limit is processed
+    assert!(process_request(1, make_request_of_size(max_message_size)).is_ok());
+
+    // A request one byte over it is rejected
+    let chunk_count = Chunker::encode(1, 1, 0, MIN_CHUNK_SIZE, &secure_channel, &make_request_of_size(max_message_size)).unwrap().len() as u32;
+    let err = process_request(1 + chunk_count, make_request_of_size(max_message_size + 1)).unwrap_err();
+    assert_eq!(err, StatusCode::BadRequestTooLarge);
+}
@@ -28,6 +28,7 @@ use crate::{
 };
 
 mod address_space;
+mod comms;
 mod services;
 mod subscriptions;
 mod events;
@@ -86,6 +87,25 @@ pub fn server_config_save() {
     }
 }
 
+#[test]
+pub fn server_config_load_without_newer_fields() {
+    // A config written before the sampling section and the newer limits existed still loads with
+    // their defaults
+    let config = ServerBuilder::new_anonymous("foo").config();
+    let mut value = serde_json::to_value(&config).unwrap();
+    {
+        let value = value.as_object_mut().unwrap();
+        value.remove("sampling");
+        let limits = value.get_mut("limits").unwrap().as_object_mut().unwrap();
+        for field in &["max_monitored_items_per_tick", "max_session_queued_notifications", "default_queue_size", "default_discard_oldest",
+            "percent_deadband_floor", "stagger_first_samples", "initial_value_good_only"] {
+            assert!(limits.remove(*field).is_some());
+        }
+    }
+    let config2: ServerConfig = serde_json::from_value(value).unwrap();
+    assert_eq!(config, config2);
+}
+
 #[test]
 pub fn server_config_invalid() {
     // Remove the endpoint
@@ -1,4 +1,4 @@
-use chrono::Duration;
+use chrono::{Duration, TimeZone, Utc};
 
 use opcua_types::{Variant, WriteMask};
 
@@ -159,6 +159,54 @@ fn read_invalid_timestamps() {
     });
 }
 
+#[test]
+fn read_write_simulation_clock() {
+    // Values read with a max age and values written are stamped with the time of the server's
+    // clock, not the wall clock
+    do_attribute_service_test(|server_state, session, address_space, ats| {
+        let node_ids = node_ids(address_space.clone());
+        {
+            let mut address_space = trace_write_lock_unwrap!(address_space);
+            let node = address_space.find_node_mut(&node_ids[0]).unwrap();
+            let _ = node.as_mut_node().set_attribute(AttributeId::AccessLevel, Variant::from((AccessLevel::CURRENT_READ | AccessLevel::CURRENT_WRITE).bits())).unwrap();
+            let _ = node.as_mut_node().set_attribute(AttributeId::UserAccessLevel, Variant::from((UserAccessLevel::CURRENT_READ | UserAccessLevel::CURRENT_WRITE).bits())).unwrap();
+        }
+
+        let start = Utc.ymd(2001, 1, 1).and_hms(0, 0, 0);
+        let clock = Arc::new(SimulationClock::new(start));
+        trace_write_lock_unwrap!(server_state).set_clock(clock.clone());
+
+        let read = |max_age: f64| -> DataValue {
+            let request = ReadRequest {
+                request_header: make_request_header(),
+                max_age,
+                timestamps_to_return: TimestampsToReturn::Both,
+                nodes_to_read: Some(vec![read_value(&node_ids[0], AttributeId::Value)]),
+            };
+            let response = ats.read(server_state.clone(), session.clone(), address_space.clone(), &request);
+            let response: ReadResponse = supported_message_as!(response, ReadResponse);
+            response.results.unwrap().remove(0)
+        };
+
+        // A read with a max age is stamped with the simulated time
+        clock.advance(Duration::seconds(5));
+        let value = read(1000f64);
+        assert_eq!(value.server_timestamp, Some(DateTime::from(start + Duration::seconds(5))));
+
+        // A write is stamped with the simulated time and a read without a max age returns it
+        clock.advance(Duration::seconds(5));
+        let response = write_request(server_state.clone(), session.clone(), address_space.clone(), ats, vec![
+            write_value(&node_ids[0], AttributeId::Value, DataValue::new_now(100i32))
+        ]);
+        assert_eq!(response.results.unwrap()[0], StatusCode::Good);
+        clock.advance(Duration::seconds(5));
+        let value = read(0f64);
+        assert_eq!(value.value, Some(Variant::Int32(100)));
+        assert_eq!(value.server_timestamp, Some(DateTime::from(start + Duration::seconds(10))));
+        assert_eq!(value.source_timestamp, Some(DateTime::from(start + Duration::seconds(10))));
+    });
+}
+
 fn write_value(node_id: &NodeId, attribute_id: AttributeId, value: DataValue) -> WriteValue {
     WriteValue {
         node_id: node_id.clone(),
@@ -1,3 +1,4 @@
+use opcua_crypto::{available_security_policies, pkey::{KeySize, RsaPadding}, SecurityPolicy, X509, X509Data};
 use opcua_types::UAString;
 
 use crate::services::discovery::DiscoveryService;
@@ -32,10 +33,107 @@ fn get_endpoints() {
         let endpoints = result.endpoints.unwrap();
         assert!(!endpoints.is_empty());
 
+        // Endpoints with a policy this build cannot use are not advertised
+        let available_security_policies = available_security_policies();
+        endpoints.iter().for_each(|e| {
+            let security_policy = SecurityPolicy::from_uri(e.security_policy_uri.as_ref());
+            assert!(available_security_policies.contains(&security_policy));
+        });
+
         debug!("Endpoints = {:#?}", endpoints);
     });
 }
 
+#[test]
+fn get_endpoints_server_certificate() {
+    do_discovery_service_test(|server_state, _session, ds| {
+        let request = GetEndpointsRequest {
+            request_header: make_request_header(),
+            endpoint_url: UAString::from("opc.tcp://localhost:4855/"),
+            locale_ids: None,
+            profile_uris: None,
+        };
+        let result = ds.get_endpoints(server_state.clone(), &request);
+        let result = supported_message_as!(result, GetEndpointsResponse);
+
+        // Without policy certificates, every endpoint carries the server's own certificate
+        let server_certificate = trace_read_lock_unwrap!(server_state).server_certificate_as_byte_string();
+        assert!(!server_certificate.is_null());
+        let endpoints = result.endpoints.unwrap();
+        assert!(endpoints.iter().any(|e| e.security_policy_uri.as_ref() == SecurityPolicy::None.to_uri()));
+        assert!(endpoints.iter().any(|e| e.security_policy_uri.as_ref() != SecurityPolicy::None.to_uri()));
+        endpoints.iter().for_each(|e| {
+            assert_eq!(e.server_certificate, server_certificate, "Endpoint {} / {}", e.endpoint_url, e.security_policy_uri);
+        });
+
+        // The certificate is a valid DER encoded X509 certificate
+        assert!(X509::from_byte_string(&endpoints[0].server_certificate).is_ok());
+    });
+}
+
+#[test]
+fn get_endpoints_policy_certificate() {
+    // Write a certificate and private key for each of two policies
+    let make_policy_certificate = |name: &str| {
+        let x509_data = X509Data {
+            common_name: name.to_string(),
+            ..X509Data::sample_cert()
+        };
+        let (cert, pkey) = X509::cert_and_pkey(&x509_data).unwrap();
+        let certificate_path = make_test_file(&format!("{}.der", name));
+        let private_key_path = make_test_file(&format!("{}.pem", name));
+        std::fs::write(&certificate_path, cert.to_der().unwrap()).unwrap();
+        std::fs::write(&private_key_path, pkey.private_key_to_pem().unwrap()).unwrap();
+        (cert, certificate_path, private_key_path)
+    };
+    let (basic256sha256_cert, basic256sha256_cert_path, basic256sha256_pkey_path) = make_policy_certificate("policy_cert_basic256sha256");
+    let (aes128_cert, aes128_cert_path, aes128_pkey_path) = make_policy_certificate("policy_cert_aes128");
+    assert_ne!(basic256sha256_cert.as_byte_string(), aes128_cert.as_byte_string());
+
+    let server = ServerBuilder::new_sample()
+        .policy_certificate(SecurityPolicy::Basic256Sha256, basic256sha256_cert_path, basic256sha256_pkey_path)
+        .policy_certificate(SecurityPolicy::Aes128Sha256RsaOaep, aes128_cert_path, aes128_pkey_path)
+        .server().unwrap();
+    let server_state = server.server_state();
+
+    let request = GetEndpointsRequest {
+        request_header: make_request_header(),
+        endpoint_url: UAString::from("opc.tcp://localhost:4855/"),
+        locale_ids: None,
+        profile_uris: None,
+    };
+    let result = DiscoveryService::new().get_endpoints(server_state.clone(), &request);
+    let result = supported_message_as!(result, GetEndpointsResponse);
+
+    // Each policy with its own certificate is described by it, the others by the server certificate
+    let server_certificate = trace_read_lock_unwrap!(server_state).server_certificate_as_byte_string();
+    assert_ne!(server_certificate, basic256sha256_cert.as_byte_string());
+    assert_ne!(server_certificate, aes128_cert.as_byte_string());
+    let endpoints = result.endpoints.unwrap();
+    [SecurityPolicy::None, SecurityPolicy::Basic256Sha256, SecurityPolicy::Aes128Sha256RsaOaep].iter().for_each(|security_policy| {
+        assert!(endpoints.iter().any(|e| e.security_policy_uri.as_ref() == security_policy.to_uri()));
+    });
+    endpoints.iter().for_each(|e| {
+        let expected = match SecurityPolicy::from_uri(e.security_policy_uri.as_ref()) {
+            SecurityPolicy::Basic256Sha256 => basic256sha256_cert.as_byte_string(),
+            SecurityPolicy::Aes128Sha256RsaOaep => aes128_cert.as_byte_string(),
+            _ => server_certificate.clone()
+        };
+        assert_eq!(e.server_certificate, expected, "Endpoint {} / {}", e.endpoint_url, e.security_policy_uri);
+    });
+
+    // The private key for the policy decrypts what is encrypted with its certificate
+    let server_state = trace_read_lock_unwrap!(server_state);
+    let pkey = server_state.server_pkey_for_policy(SecurityPolicy::Aes128Sha256RsaOaep).unwrap();
+    let cert = server_state.server_certificate_for_policy(SecurityPolicy::Aes128Sha256RsaOaep).unwrap();
+    let public_key = cert.public_key().unwrap();
+    let mut encrypted = vec![0u8; public_key.size()];
+    let encrypted_size = public_key.public_encrypt(b"secret", &mut encrypted, RsaPadding::OAEP).unwrap();
+    let mut decrypted = vec![0u8; pkey.size()];
+    let decrypted_size = pkey.private_decrypt(&encrypted[..encrypted_size], &mut decrypted, RsaPadding::OAEP).unwrap();
+    assert_eq!(&decrypted[..decrypted_size], b"secret");
+}
+
 #[test]
 fn find_servers() {
     do_discovery_service_test(|server_state, _session, ds| {
@@ -84,7 +182,13 @@ fn discovery_test() {
             // Verify endpoints
             let endpoints = result.endpoints.unwrap();
             assert!(!endpoints.is_empty());
-            assert_eq!(endpoints.len(), 12);
+            // Only endpoints with a policy that this build supports are advertised
+            let available_endpoints = {
+                let server_state = trace_read_lock_unwrap!(server_state);
+                let config = trace_read_lock_unwrap!(server_state.config);
+                config.endpoints.values().filter(|e| e.security_policy().is_supported()).count()
+            };
+            assert_eq!(endpoints.len(), available_endpoints);
         }
 
         // specify profile ids in request
@@ -1,12 +1,15 @@
 use std::ops::Add;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
 
-use chrono::{self, Utc};
+use chrono::{self, TimeZone, Utc};
 
 use crate::{
     prelude::*,
+    diagnostics::ServerDiagnostics,
     subscriptions::{
-        subscription::{TickReason, SubscriptionState},
+        subscription::{Subscription, SubscriptionSnapshot, TickReason, SubscriptionState},
+        subscriptions::Subscriptions,
         monitored_item::*,
     },
     services::{
@@ -195,6 +198,15 @@ fn assert_first_notification_is_i32(monitored_item: &mut MonitoredItem, value: i
     }
 }
 
+fn assert_first_notification_is_u32(monitored_item: &mut MonitoredItem, value: u32) {
+    let notification = monitored_item.oldest_notification_message().unwrap();
+    if let Notification::MonitoredItemNotification(notification) = notification {
+        assert_eq!(notification.value.value.unwrap(), Variant::UInt32(value));
+    } else {
+        panic!();
+    }
+}
+
 #[test]
 fn data_change_filter_test() {
     let mut filter = DataChangeFilter {
@@ -249,10 +261,34 @@ fn data_change_filter_test() {
 
     // Change timestamps to differ
     let now = DateTime::now();
-    v1.server_timestamp = Some(now.clone());
+    v1.source_timestamp = Some(now.clone());
     assert_eq!(filter.compare(&v1, &v2, None), false);
 }
 
+#[test]
+fn data_change_filter_ignores_server_timestamp() {
+    let v1 = DataValue {
+        value: Some(Variant::Int32(100)),
+        status: Some(StatusCode::Good),
+        source_timestamp: None,
+        source_picoseconds: None,
+        server_timestamp: Some(DateTime::ymd(2020, 1, 1)),
+        server_picoseconds: None,
+    };
+    let mut v2 = v1.clone();
+    v2.server_timestamp = Some(DateTime::ymd(2020, 1, 2));
+
+    // Only the server timestamp differs so no trigger should report a change
+    [DataChangeTrigger::Status, DataChangeTrigger::StatusValue, DataChangeTrigger::StatusValueTimestamp].iter().for_each(|trigger| {
+        let filter = DataChangeFilter {
+            trigger: *trigger,
+            deadband_type: DeadbandType::None as u32,
+            deadband_value: 0f64,
+        };
+        assert!(filter.compare(&v1, &v2, None));
+    });
+}
+
 
 #[test]
 fn data_change_deadband_abs_test() {
@@ -297,6 +333,211 @@ fn data_change_deadband_abs_test() {
     assert_eq!(filter.compare(&v1, &v2, None), false);
 }
 
+#[test]
+fn deadband_compare_none() {
+    let filter = DataChangeFilter {
+        trigger: DataChangeTrigger::StatusValue,
+        deadband_type: DeadbandType::None as u32,
+        deadband_value: 1f64,
+    };
+    // Deadband value is ignored, values must be identical
+    assert_eq!(filter.deadband_compare(&Variant::Double(10f64), &Variant::Double(10f64), None), Ok(true));
+    assert_eq!(filter.deadband_compare(&Variant::Double(10f64), &Variant::Double(10.5f64), None), Ok(false));
+    // Non-numeric values are comparable without a deadband
+    assert_eq!(filter.deadband_compare(&Variant::from("abc"), &Variant::from("abc"), None), Ok(true));
+    assert_eq!(filter.deadband_compare(&Variant::from("abc"), &Variant::from("xyz"), None), Ok(false));
+}
+
+#[test]
+fn deadband_compare_absolute() {
+    let filter = DataChangeFilter {
+        trigger: DataChangeTrigger::StatusValue,
+        deadband_type: DeadbandType::Absolute as u32,
+        deadband_value: 1f64,
+    };
+    assert_eq!(filter.deadband_compare(&Variant::Double(10f64), &Variant::Double(11f64), None), Ok(true));
+    assert_eq!(filter.deadband_compare(&Variant::Int32(10), &Variant::Int32(9), None), Ok(true));
+    assert_eq!(filter.deadband_compare(&Variant::Double(10f64), &Variant::Double(11.1f64), None), Ok(false));
+    // The eu range is ignored
+    assert_eq!(filter.deadband_compare(&Variant::Double(10f64), &Variant::Double(11f64), Some((0f64, 1f64))), Ok(true));
+
+    let filter = DataChangeFilter {
+        deadband_value: -1f64,
+        ..filter
+    };
+    assert_eq!(filter.deadband_compare(&Variant::Double(10f64), &Variant::Double(11f64), None), Err(StatusCode::BadDeadbandFilterInvalid));
+}
+
+#[test]
+fn deadband_compare_percent() {
+    let filter = DataChangeFilter {
+        trigger: DataChangeTrigger::StatusValue,
+        deadband_type: DeadbandType::Percent as u32,
+        deadband_value: 10f64,
+    };
+    // 10% of a 0-200 range is 20
+    let eu_range = Some((0f64, 200f64));
+    assert_eq!(filter.deadband_compare(&Variant::Double(100f64), &Variant::Double(120f64), eu_range), Ok(true));
+    assert_eq!(filter.deadband_compare(&Variant::Double(100f64), &Variant::Double(80f64), eu_range), Ok(true));
+    assert_eq!(filter.deadband_compare(&Variant::Double(100f64), &Variant::Double(120.1f64), eu_range), Ok(false));
+    // A percent deadband needs a valid eu range
+    assert_eq!(filter.deadband_compare(&Variant::Double(100f64), &Variant::Double(120f64), None), Err(StatusCode::BadDeadbandFilterInvalid));
+    assert_eq!(filter.deadband_compare(&Variant::Double(100f64), &Variant::Double(120f64), Some((200f64, 0f64))), Err(StatusCode::BadDeadbandFilterInvalid));
+}
+
+#[test]
+fn deadband_compare_incomparable() {
+    let filter = DataChangeFilter {
+        trigger: DataChangeTrigger::StatusValue,
+        deadband_type: DeadbandType::Absolute as u32,
+        deadband_value: 1f64,
+    };
+    let v1 = Variant::from("hello");
+    let v2 = Variant::Double(10f64);
+    assert_eq!(filter.deadband_compare(&v1, &v2, None), Err(StatusCode::BadTypeMismatch));
+    assert_eq!(filter.deadband_compare(&v2, &v1, None), Err(StatusCode::BadTypeMismatch));
+    // compare_value treats incomparable values as a change
+    assert_eq!(filter.compare_value(&v1, &v2, None), Ok(false));
+}
+
+#[test]
+fn deadband_compare_invalid() {
+    // A negative deadband is invalid
+    let filter = DataChangeFilter {
+        trigger: DataChangeTrigger::StatusValue,
+        deadband_type: DeadbandType::Absolute as u32,
+        deadband_value: -1f64,
+    };
+    let v1 = Some(Variant::Double(10f64));
+    let v2 = Some(Variant::Double(10.5f64));
+    assert_eq!(filter.compare_value(v1.as_ref().unwrap(), v2.as_ref().unwrap(), None), Err(StatusCode::BadDeadbandFilterInvalid));
+    // The error is treated as a change rather than suppressing the notification
+    assert!(!filter.compare_value_option(&v1, &v2, None));
+    assert!(!filter.compare(&DataValue::new_now(10f64), &DataValue::new_now(10.5f64), None));
+
+    // As is a percent deadband without an engineering unit range
+    let filter = DataChangeFilter {
+        deadband_type: DeadbandType::Percent as u32,
+        deadband_value: 10f64,
+        ..filter
+    };
+    assert_eq!(filter.compare_value(v1.as_ref().unwrap(), v2.as_ref().unwrap(), None), Err(StatusCode::BadDeadbandFilterInvalid));
+    assert!(!filter.compare_value_option(&v1, &v2, None));
+
+    // Identical values are still the same
+    assert!(filter.compare_value_option(&v1, &v1.clone(), None));
+}
+
+#[test]
+fn deadband_compare_array() {
+    let filter = DataChangeFilter {
+        trigger: DataChangeTrigger::StatusValue,
+        deadband_type: DeadbandType::Absolute as u32,
+        deadband_value: 1f64,
+    };
+    let array_2d = |values: Vec<f64>, dimensions: Vec<u32>| Variant::from(Array::new_multi(values.into_iter().map(Variant::from).collect::<Vec<Variant>>(), dimensions));
+    let v1 = array_2d(vec![1f64, 2f64, 3f64, 4f64], vec![2, 2]);
+
+    // Equal 2-D arrays, or ones whose elements are all within the deadband, are the same
+    assert_eq!(filter.deadband_compare(&v1, &array_2d(vec![1f64, 2f64, 3f64, 4f64], vec![2, 2]), None), Ok(true));
+    assert_eq!(filter.deadband_compare(&v1, &array_2d(vec![1.5f64, 2f64, 3f64, 5f64], vec![2, 2]), None), Ok(true));
+
+    // One element over the deadband is a change
+    assert_eq!(filter.deadband_compare(&v1, &array_2d(vec![1f64, 2f64, 3f64, 5.5f64], vec![2, 2]), None), Ok(false));
+
+    // As is a change of dimensions, even with the same values
+    assert_eq!(filter.deadband_compare(&v1, &array_2d(vec![1f64, 2f64, 3f64, 4f64], vec![4, 1]), None), Ok(false));
+    assert_eq!(filter.deadband_compare(&v1, &Variant::from(vec![1f64, 2f64, 3f64, 4f64]), None), Ok(false));
+    assert_eq!(filter.deadband_compare(&Variant::from(vec![1f64, 2f64]), &Variant::from(vec![1f64, 2f64, 3f64]), None), Ok(false));
+
+    // Single dimension arrays are compared the same way
+    assert_eq!(filter.deadband_compare(&Variant::from(vec![1f64, 2f64]), &Variant::from(vec![1.5f64, 2.5f64]), None), Ok(true));
+    assert_eq!(filter.deadband_compare(&Variant::from(vec![1f64, 2f64]), &Variant::from(vec![1f64, 4f64]), None), Ok(false));
+
+    // Arrays without a magnitude cannot be compared under a deadband
+    assert_eq!(filter.deadband_compare(&Variant::from(&["a", "b"][..]), &Variant::from(&["a", "c"][..]), None), Err(StatusCode::BadTypeMismatch));
+}
+
+#[test]
+fn deadband_compare_text() {
+    let absolute = DataChangeFilter {
+        trigger: DataChangeTrigger::StatusValue,
+        deadband_type: DeadbandType::Absolute as u32,
+        deadband_value: 1f64,
+    };
+    let percent = DataChangeFilter {
+        deadband_type: DeadbandType::Percent as u32,
+        ..absolute.clone()
+    };
+    [absolute, percent].iter().for_each(|filter| {
+        // The deadband is ignored and the values are compared exactly, including the locale
+        let v1 = Variant::from(LocalizedText::new("en", "Hello"));
+        assert_eq!(filter.deadband_compare(&v1, &Variant::from(LocalizedText::new("en", "Hello")), None), Ok(true));
+        assert_eq!(filter.deadband_compare(&v1, &Variant::from(LocalizedText::new("en", "Goodbye")), None), Ok(false));
+        assert_eq!(filter.deadband_compare(&v1, &Variant::from(LocalizedText::new("de", "Hello")), None), Ok(false));
+        assert_eq!(filter.compare_value(&v1, &Variant::from(LocalizedText::new("en", "Hello")), None), Ok(true));
+
+        // And including the namespace
+        let v1 = Variant::from(QualifiedName::new(1, "Name"));
+        assert_eq!(filter.deadband_compare(&v1, &Variant::from(QualifiedName::new(1, "Name")), None), Ok(true));
+        assert_eq!(filter.deadband_compare(&v1, &Variant::from(QualifiedName::new(1, "Other")), None), Ok(false));
+        assert_eq!(filter.deadband_compare(&v1, &Variant::from(QualifiedName::new(2, "Name")), None), Ok(false));
+        assert_eq!(filter.compare_value(&v1, &Variant::from(QualifiedName::new(1, "Name")), None), Ok(true));
+
+        // Text is still not comparable to a number
+        assert_eq!(filter.deadband_compare(&Variant::from(LocalizedText::new("en", "Hello")), &Variant::Double(1f64), None), Err(StatusCode::BadTypeMismatch));
+    });
+}
+
+#[test]
+fn data_change_filter_compare_identical() {
+    let filter = DataChangeFilter {
+        trigger: DataChangeTrigger::StatusValue,
+        deadband_type: DeadbandType::Absolute as u32,
+        deadband_value: 1f64,
+    };
+
+    // Identical values are the same without a numeric comparison, even ones that have no magnitude
+    let v1 = Some(Variant::from(vec![1i32, 2, 3]));
+    assert!(filter.compare_value_option(&v1, &Some(Variant::from(vec![1i32, 2, 3])), None));
+    assert!(filter.compare(&DataValue::new_now(vec![1i32, 2, 3]), &DataValue::new_now(vec![1i32, 2, 3]), None));
+
+    // Arrays are compared element by element, skipping the deadband for identical elements
+    let strings = Some(Variant::from(vec!["a".to_string(), "b".to_string()]));
+    assert!(filter.compare_value_option(&strings, &Some(Variant::from(vec!["a".to_string(), "b".to_string()])), None));
+    assert!(!filter.compare_value_option(&strings, &Some(Variant::from(vec!["a".to_string(), "c".to_string()])), None));
+
+    // Values that differ are still compared fully
+    assert!(!filter.compare_value_option(&v1, &Some(Variant::from(vec![1i32, 2, 5])), None));
+    assert!(filter.compare_value_option(&Some(Variant::Double(10f64)), &Some(Variant::Double(10.5f64)), None));
+    assert!(!filter.compare_value_option(&Some(Variant::Double(10f64)), &Some(Variant::Double(12f64)), None));
+
+    // As is the status
+    let mut v2 = DataValue::new_now(vec![1i32, 2, 3]);
+    v2.status = Some(StatusCode::BadUnexpectedError);
+    assert!(!filter.compare(&DataValue::new_now(vec![1i32, 2, 3]), &v2, None));
+}
+
+#[test]
+fn deadband_type_from_u32() {
+    [DeadbandType::None, DeadbandType::Absolute, DeadbandType::Percent].iter().for_each(|deadband_type| {
+        assert_eq!(DeadbandType::from_u32(deadband_type.to_u32()), Ok(*deadband_type));
+    });
+    assert_eq!(DeadbandType::from_u32(0), Ok(DeadbandType::None));
+    assert_eq!(DeadbandType::from_u32(1), Ok(DeadbandType::Absolute));
+    assert_eq!(DeadbandType::from_u32(2), Ok(DeadbandType::Percent));
+    assert_eq!(DeadbandType::from_u32(3), Err(StatusCode::BadDeadbandFilterInvalid));
+
+    // An unknown deadband type is rejected whatever the values
+    let filter = DataChangeFilter {
+        trigger: DataChangeTrigger::StatusValue,
+        deadband_type: 3,
+        deadband_value: 1f64,
+    };
+    assert_eq!(filter.deadband_compare(&Variant::Double(10f64), &Variant::Double(10f64), None), Err(StatusCode::BadDeadbandFilterInvalid));
+    assert_eq!(filter.deadband_compare(&Variant::from("abc"), &Variant::from("abc"), None), Err(StatusCode::BadDeadbandFilterInvalid));
+}
+
 // Straight tests of abs function
 #[test]
 fn deadband_abs() {
@@ -318,6 +559,29 @@ fn deadband_pct() {
     assert_eq!(DataChangeFilter::pct_compare(101.0001f64, 100f64, 0f64, 100f64, 1.0002f64), true);
 }
 
+#[test]
+fn deadband_pct_floor() {
+    let filter = DataChangeFilter {
+        trigger: DataChangeTrigger::StatusValue,
+        deadband_type: DeadbandType::Percent as u32,
+        deadband_value: 10f64,
+    };
+    // A floor of 0.5 over a 0-1 range is a 50% deadband
+    assert_eq!(filter.with_percent_floor(Some((0f64, 1f64)), 0.5f64).deadband_value, 50f64);
+    // The floor is smaller than 10% of a 0-100 range so the deadband is unchanged
+    assert_eq!(filter.with_percent_floor(Some((0f64, 100f64)), 0.5f64).deadband_value, 10f64);
+    // No floor, or no valid range, leaves the deadband unchanged
+    assert_eq!(filter.with_percent_floor(Some((0f64, 1f64)), 0f64).deadband_value, 10f64);
+    assert_eq!(filter.with_percent_floor(None, 0.5f64).deadband_value, 10f64);
+    assert_eq!(filter.with_percent_floor(Some((1f64, 0f64)), 0.5f64).deadband_value, 10f64);
+    // Other deadband types are unaffected
+    let filter = DataChangeFilter {
+        deadband_type: DeadbandType::Absolute as u32,
+        ..filter
+    };
+    assert_eq!(filter.with_percent_floor(Some((0f64, 1f64)), 0.5f64).deadband_value, 10f64);
+}
+
 #[test]
 fn monitored_item_data_change_filter() {
     // create an address space
@@ -425,6 +689,87 @@ fn monitored_item_event_filter() {
     assert_eq!(monitored_item.tick(&now, &address_space, false, false), TickResult::NoChange);
 }
 
+#[test]
+fn monitored_item_modify_to_event_filter() {
+    let mut address_space = make_address_space();
+    let ns = address_space.register_namespace("urn:test").unwrap();
+
+    // An item without a filter reports the event notifier attribute as a data change
+    let mut now = Utc::now();
+    let mut monitored_item = MonitoredItem::new(&now, 1, TimestampsToReturn::Both, &make_create_request(-1f64, 5, test_object_node_id(), AttributeId::EventNotifier, ExtensionObject::null())).unwrap();
+    assert_eq!(monitored_item.tick(&now, &address_space, true, false), TickResult::ReportValueChanged);
+    assert_eq!(monitored_item.notification_queue().len(), 1);
+
+    // Modify the item to have an event filter
+    let request = MonitoredItemModifyRequest {
+        monitored_item_id: 1,
+        requested_parameters: make_create_request_event_filter(-1f64, 5).requested_parameters,
+    };
+    assert!(monitored_item.modify(&address_space, TimestampsToReturn::Both, &request, &MonitoredItemDefaults::default()).is_ok());
+
+    // The queued data change has been discarded
+    assert!(monitored_item.notification_queue().is_empty());
+    now = now + chrono::Duration::milliseconds(100);
+    assert_eq!(monitored_item.tick(&now, &address_space, true, false), TickResult::NoChange);
+
+    // Subsequent notifications are events
+    now = now + chrono::Duration::milliseconds(100);
+    let mut event = BaseEventType::new(&NodeId::new(ns, "Event1"), ObjectTypeId::BaseEventType, "Event1", "", NodeId::objects_folder_id(), DateTime::from(now))
+        .source_node(test_object_node_id());
+    assert!(event.raise(&mut address_space).is_ok());
+    assert_eq!(monitored_item.tick(&now, &address_space, true, false), TickResult::ReportValueChanged);
+    assert_eq!(monitored_item.notification_queue().len(), 1);
+    assert!(monitored_item.notification_queue().iter().all(|n| matches!(n, Notification::Event(_))));
+
+    // Modifying it back forgets the event and samples the attribute afresh
+    let request = MonitoredItemModifyRequest {
+        monitored_item_id: 1,
+        requested_parameters: make_create_request(-1f64, 5, test_object_node_id(), AttributeId::EventNotifier, ExtensionObject::null()).requested_parameters,
+    };
+    assert!(monitored_item.modify(&address_space, TimestampsToReturn::Both, &request, &MonitoredItemDefaults::default()).is_ok());
+    assert!(monitored_item.notification_queue().is_empty());
+    now = now + chrono::Duration::milliseconds(100);
+    assert_eq!(monitored_item.tick(&now, &address_space, true, false), TickResult::ReportValueChanged);
+    assert_eq!(monitored_item.notification_queue().len(), 1);
+    assert!(monitored_item.notification_queue().iter().all(|n| matches!(n, Notification::MonitoredItemNotification(_))));
+}
+
+#[test]
+fn monitored_item_modify_invalid_filter() {
+    let address_space = make_address_space();
+
+    // An item without a filter has a queued data change
+    let now = Utc::now();
+    let mut monitored_item = MonitoredItem::new(&now, 1, TimestampsToReturn::Both, &make_create_request(-1f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null())).unwrap();
+    assert_eq!(monitored_item.tick(&now, &address_space, true, false), TickResult::ReportValueChanged);
+    assert_eq!(monitored_item.notification_queue().len(), 1);
+
+    // Modifying it to an aggregate filter the server does not support fails
+    let filter = ExtensionObject::from_encodable(ObjectId::AggregateFilter_Encoding_DefaultBinary, &AggregateFilter {
+        start_time: DateTime::null(),
+        aggregate_type: ObjectId::AggregateFunction_Interpolative.into(),
+        processing_interval: 1000f64,
+        aggregate_configuration: AggregateConfiguration {
+            use_server_capabilities_defaults: true,
+            treat_uncertain_as_bad: false,
+            percent_data_bad: 100,
+            percent_data_good: 100,
+            use_sloped_extrapolation: false,
+        },
+    });
+    let request = MonitoredItemModifyRequest {
+        monitored_item_id: 1,
+        requested_parameters: make_create_request(500f64, 10, test_var_node_id(), AttributeId::Value, filter).requested_parameters,
+    };
+    assert_eq!(monitored_item.modify(&address_space, TimestampsToReturn::Both, &request, &MonitoredItemDefaults::default()).unwrap_err(), StatusCode::BadAggregateNotSupported);
+
+    // The item is unmodified, keeping its filter, settings and queue
+    assert!(monitored_item.validate_filter(&address_space).unwrap().is_null());
+    assert_eq!(monitored_item.sampling_interval(), -1f64);
+    assert_eq!(monitored_item.queue_size(), 5);
+    assert_eq!(monitored_item.notification_queue().len(), 1);
+}
+
 /// Test to ensure create monitored items returns an error for an unknown node id
 #[test]
 fn unknown_node_id() {
@@ -449,6 +794,138 @@ fn unknown_node_id() {
     });
 }
 
+#[test]
+fn monitored_item_set_monitoring_modes() {
+    do_subscription_service_test(|server_state, session, address_space, ss: SubscriptionService, mis: MonitoredItemService| {
+        let subscription_id = {
+            let request = create_subscription_request(0, 0);
+            let response: CreateSubscriptionResponse = supported_message_as!(ss.create_subscription(server_state.clone(), session.clone(), &request), CreateSubscriptionResponse);
+            response.subscription_id
+        };
+        {
+            let mut session = trace_write_lock_unwrap!(session);
+            session.subscriptions_mut().get_mut(subscription_id).unwrap().set_state(SubscriptionState::Normal);
+        }
+
+        let node_ids = (0..3).map(|i| NodeId::new(1, var_name(i))).collect::<Vec<_>>();
+        let request = create_monitored_items_request(subscription_id, node_ids.clone());
+        let response: CreateMonitoredItemsResponse = supported_message_as!(mis.create_monitored_items(server_state.clone(), session.clone(), address_space.clone(), &request), CreateMonitoredItemsResponse);
+        let monitored_item_ids: Vec<u32> = response.results.unwrap().iter().map(|r| r.monitored_item_id).collect();
+
+        let set_monitoring_modes = |monitoring_mode: MonitoringMode, monitored_item_ids: Vec<u32>| {
+            let request = SetMonitoringModeRequest {
+                request_header: RequestHeader::dummy(),
+                subscription_id,
+                monitoring_mode,
+                monitored_item_ids: Some(monitored_item_ids),
+            };
+            let response: SetMonitoringModeResponse = supported_message_as!(mis.set_monitoring_mode(session.clone(), &request), SetMonitoringModeResponse);
+            response.results.unwrap()
+        };
+        let set_values = |v: i32, now: &DateTimeUtc| {
+            let mut address_space = trace_write_lock_unwrap!(address_space);
+            node_ids.iter().for_each(|node_id| {
+                let _ = address_space.set_variable_value(node_id.clone(), v, &DateTime::from(now.clone()), &DateTime::from(now.clone()));
+            });
+        };
+        let reported_values = |response: PublishResponse| {
+            let (notifications, _) = response.notification_message.notifications(&DecodingLimits::default()).unwrap();
+            let mut values = notifications[0].monitored_items.as_ref().unwrap().iter()
+                .map(|n| (n.client_handle, n.value.value.clone().unwrap()))
+                .collect::<Vec<_>>();
+            values.sort_by_key(|v| v.0);
+            values
+        };
+
+        // Initial values
+        let now = publish_tick_response(session.clone(), &ss, address_space.clone(), Utc::now(), chrono::Duration::seconds(2), |response| {
+            assert_eq!(reported_values(response).len(), 3);
+        });
+
+        // Disable two items, the unknown id fails on its own
+        let results = set_monitoring_modes(MonitoringMode::Disabled, vec![monitored_item_ids[0], 999, monitored_item_ids[1]]);
+        assert_eq!(results, vec![StatusCode::Good, StatusCode::BadMonitoredItemIdInvalid, StatusCode::Good]);
+
+        // Only the item still reporting sees the change
+        set_values(10, &now);
+        let now = publish_tick_response(session.clone(), &ss, address_space.clone(), now, chrono::Duration::seconds(2), |response| {
+            assert_eq!(reported_values(response), vec![(2, Variant::Int32(10))]);
+        });
+
+        // Reporting again, both items report the value that changed while they were disabled
+        let results = set_monitoring_modes(MonitoringMode::Reporting, vec![monitored_item_ids[0], monitored_item_ids[1]]);
+        assert_eq!(results, vec![StatusCode::Good, StatusCode::Good]);
+        let _ = publish_tick_response(session.clone(), &ss, address_space.clone(), now, chrono::Duration::seconds(2), |response| {
+            assert_eq!(reported_values(response), vec![(0, Variant::Int32(10)), (1, Variant::Int32(10))]);
+        });
+    });
+}
+
+#[test]
+fn monitored_item_return_diagnostics() {
+    do_subscription_service_test(|server_state, session, address_space, ss: SubscriptionService, mis: MonitoredItemService| {
+        let subscription_id = {
+            let request = create_subscription_request(0, 0);
+            let response: CreateSubscriptionResponse = supported_message_as!(ss.create_subscription(server_state.clone(), session.clone(), &request), CreateSubscriptionResponse);
+            response.subscription_id
+        };
+
+        // Two items of which only the first returns diagnostics
+        let node_ids = (0..2).map(|i| NodeId::new(1, var_name(i))).collect::<Vec<_>>();
+        let request = create_monitored_items_request(subscription_id, node_ids.clone());
+        let response: CreateMonitoredItemsResponse = supported_message_as!(mis.create_monitored_items(server_state.clone(), session.clone(), address_space.clone(), &request), CreateMonitoredItemsResponse);
+        let monitored_item_ids: Vec<u32> = response.results.unwrap().iter().map(|r| r.monitored_item_id).collect();
+        {
+            let mut session = trace_write_lock_unwrap!(session);
+            let subscription = session.subscriptions_mut().get_mut(subscription_id).unwrap();
+            subscription.set_state(SubscriptionState::Normal);
+            assert_eq!(subscription.set_return_diagnostics(monitored_item_ids[0], true), StatusCode::Good);
+        }
+
+        // Both items go bad
+        let now = Utc::now();
+        {
+            let mut address_space = trace_write_lock_unwrap!(address_space);
+            node_ids.iter().for_each(|node_id| {
+                let _ = address_space.find_variable_mut(node_id.clone()).unwrap().set_value_direct(1i32, StatusCode::BadNotReadable, &DateTime::from(now), &DateTime::from(now));
+            });
+        }
+
+        let _ = publish_tick_response(session.clone(), &ss, address_space.clone(), now, chrono::Duration::seconds(2), |response| {
+            let (notifications, _) = response.notification_message.notifications(&DecodingLimits::default()).unwrap();
+            let monitored_items = notifications[0].monitored_items.as_ref().unwrap();
+            let diagnostic_infos = notifications[0].diagnostic_infos.as_ref().unwrap();
+            assert_eq!(monitored_items.len(), 2);
+            assert_eq!(diagnostic_infos.len(), 2);
+            monitored_items.iter().zip(diagnostic_infos.iter()).for_each(|(n, d)| {
+                if n.client_handle == 0 {
+                    assert_eq!(d.inner_status_code, Some(StatusCode::BadNotReadable));
+                    assert!(d.additional_info.is_some());
+                } else {
+                    assert_eq!(*d, DiagnosticInfo::null());
+                }
+            });
+        });
+
+        // Without an item returning diagnostics there are none
+        {
+            let mut session = trace_write_lock_unwrap!(session);
+            let subscription = session.subscriptions_mut().get_mut(subscription_id).unwrap();
+            let _ = subscription.set_return_diagnostics(monitored_item_ids[0], false);
+        }
+        {
+            let mut address_space = trace_write_lock_unwrap!(address_space);
+            node_ids.iter().for_each(|node_id| {
+                let _ = address_space.find_variable_mut(node_id.clone()).unwrap().set_value_direct(2i32, StatusCode::BadNotReadable, &DateTime::from(now), &DateTime::from(now + chrono::Duration::seconds(1)));
+            });
+        }
+        let _ = publish_tick_response(session.clone(), &ss, address_space.clone(), now + chrono::Duration::seconds(2), chrono::Duration::seconds(2), |response| {
+            let (notifications, _) = response.notification_message.notifications(&DecodingLimits::default()).unwrap();
+            assert!(notifications[0].diagnostic_infos.is_none());
+        });
+    });
+}
+
 #[test]
 fn monitored_item_triggers() {
     do_subscription_service_test(|server_state, session, address_space, ss: SubscriptionService, mis: MonitoredItemService| {
@@ -608,3 +1085,2191 @@ fn monitored_item_queue_discard_oldest() {
         assert_first_notification_is_i32(&mut monitored_item, 10);
     }
 }
+
+#[test]
+fn monitored_item_queue_overflow_keeps_latest() {
+    // Queue 1..=7 in a queue of 4. Discarding the oldest keeps the last 4 values, discarding the
+    // newest keeps the first 3 and the latest. Values queued on overflow have the overflow bit.
+    let queued_values = |discard_oldest: bool| -> Vec<(i32, bool)> {
+        let mut monitored_item = MonitoredItem::new(&chrono::Utc::now(), 1, TimestampsToReturn::Both, &make_create_request_data_change_filter(-1f64, 4)).unwrap();
+        monitored_item.set_discard_oldest(discard_oldest);
+        for i in 1..=7 {
+            monitored_item.enqueue_notification_message(MonitoredItemNotification {
+                client_handle: 999,
+                value: DataValue::new_now(i as i32),
+            });
+        }
+        assert!(monitored_item.queue_overflow());
+        monitored_item.all_notifications().unwrap().into_iter().map(|n| {
+            if let Notification::MonitoredItemNotification(MonitoredItemNotification { value: DataValue { value: Some(Variant::Int32(value)), status, .. }, .. }) = n {
+                (value, status.unwrap().contains(StatusCode::OVERFLOW))
+            } else {
+                panic!();
+            }
+        }).collect()
+    };
+    assert_eq!(queued_values(true), vec![(4, false), (5, true), (6, true), (7, true)]);
+    assert_eq!(queued_values(false), vec![(1, false), (2, false), (3, false), (7, true)]);
+}
+
+#[test]
+fn monitored_item_overflow_strategy() {
+    // Queue 1..=7 in a queue of 4 with each strategy, whatever the client's discard policy
+    let queued_values = |discard_oldest: bool, overflow_strategy: Option<OverflowStrategy>| -> Vec<(i32, bool)> {
+        let mut monitored_item = MonitoredItem::new(&chrono::Utc::now(), 1, TimestampsToReturn::Both, &make_create_request_data_change_filter(-1f64, 4)).unwrap();
+        monitored_item.set_discard_oldest(discard_oldest);
+        monitored_item.set_overflow_strategy(overflow_strategy);
+        for i in 1..=7 {
+            monitored_item.enqueue_notification_message(MonitoredItemNotification {
+                client_handle: 999,
+                value: DataValue::new_now(i as i32),
+            });
+        }
+        assert!(monitored_item.queue_overflow());
+        monitored_item.all_notifications().unwrap().into_iter().map(|n| {
+            if let Notification::MonitoredItemNotification(MonitoredItemNotification { value: DataValue { value: Some(Variant::Int32(value)), status, .. }, .. }) = n {
+                (value, status.unwrap().contains(StatusCode::OVERFLOW))
+            } else {
+                panic!();
+            }
+        }).collect()
+    };
+
+    // Without a strategy the client's discard policy applies
+    assert_eq!(queued_values(true, None), vec![(4, false), (5, true), (6, true), (7, true)]);
+    assert_eq!(queued_values(false, None), vec![(1, false), (2, false), (3, false), (7, true)]);
+
+    [true, false].iter().for_each(|discard_oldest| {
+        let discard_oldest = *discard_oldest;
+        assert_eq!(queued_values(discard_oldest, Some(OverflowStrategy::DiscardOldest)), vec![(4, false), (5, true), (6, true), (7, true)]);
+        assert_eq!(queued_values(discard_oldest, Some(OverflowStrategy::DiscardNewest)), vec![(1, false), (2, false), (3, false), (7, true)]);
+        // Each overflow empties the queue, so the 5th value starts it again and 6 and 7 are queued after it
+        assert_eq!(queued_values(discard_oldest, Some(OverflowStrategy::DropAllMarkOverflow)), vec![(5, true), (6, false), (7, false)]);
+    });
+
+    // The strategy reflects the discard policy unless it is overridden
+    let mut monitored_item = MonitoredItem::new(&chrono::Utc::now(), 1, TimestampsToReturn::Both, &make_create_request_data_change_filter(-1f64, 4)).unwrap();
+    monitored_item.set_discard_oldest(false);
+    assert_eq!(monitored_item.overflow_strategy(), OverflowStrategy::DiscardNewest);
+    monitored_item.set_overflow_strategy(Some(OverflowStrategy::DropAllMarkOverflow));
+    assert_eq!(monitored_item.overflow_strategy(), OverflowStrategy::DropAllMarkOverflow);
+    assert_eq!(MonitoredItem::restore(&chrono::Utc::now(), &monitored_item.snapshot(), &MonitoredItemDefaults::default()).unwrap().overflow_strategy(), OverflowStrategy::DropAllMarkOverflow);
+}
+
+/// Records the queue overflows of monitored items
+struct QueueOverflowRecorder {
+    overflows: Vec<(u32, usize)>,
+}
+
+impl QueueOverflow for QueueOverflowRecorder {
+    fn on_queue_overflow(&mut self, monitored_item_id: u32, dropped: usize) {
+        self.overflows.push((monitored_item_id, dropped));
+    }
+}
+
+#[test]
+fn monitored_item_queue_overflow_callback() {
+    let recorder = Arc::new(Mutex::new(QueueOverflowRecorder { overflows: Vec::new() }));
+    // Callbacks are made once the overflows are taken from the item, as the server does after
+    // ticking a session's subscriptions
+    let enqueue = |monitored_item: &mut MonitoredItem, values: std::ops::RangeInclusive<i32>| {
+        values.for_each(|i| {
+            monitored_item.enqueue_notification_message(MonitoredItemNotification {
+                client_handle: 999,
+                value: DataValue::new_now(i),
+            });
+        });
+        monitored_item.take_queue_overflow_events().into_iter().for_each(|e| e.notify());
+    };
+
+    // The callback is made once when the queue goes into overflow, not for every drop after that
+    let mut monitored_item = MonitoredItem::new(&chrono::Utc::now(), 7, TimestampsToReturn::Both, &make_create_request_data_change_filter(-1f64, 4)).unwrap();
+    monitored_item.set_queue_overflow_callback(Some(recorder.clone()));
+    enqueue(&mut monitored_item, 1..=4);
+    assert!(recorder.lock().unwrap().overflows.is_empty());
+    enqueue(&mut monitored_item, 5..=7);
+    assert_eq!(recorder.lock().unwrap().overflows, vec![(7, 1)]);
+
+    // Emptying the queue ends the overflow, so the next one makes the callback again
+    let _ = monitored_item.all_notifications();
+    enqueue(&mut monitored_item, 1..=5);
+    assert_eq!(recorder.lock().unwrap().overflows, vec![(7, 1), (7, 1)]);
+
+    // Dropping everything counts every notification that was dropped
+    let _ = monitored_item.all_notifications();
+    monitored_item.set_overflow_strategy(Some(OverflowStrategy::DropAllMarkOverflow));
+    enqueue(&mut monitored_item, 1..=5);
+    assert_eq!(recorder.lock().unwrap().overflows, vec![(7, 1), (7, 1), (7, 4)]);
+
+    // Without a callback nothing is made
+    let _ = monitored_item.all_notifications();
+    monitored_item.set_queue_overflow_callback(None);
+    enqueue(&mut monitored_item, 1..=5);
+    assert!(monitored_item.queue_overflow());
+    assert_eq!(recorder.lock().unwrap().overflows.len(), 3);
+
+    // A callback that panicked before is skipped rather than panicking again
+    let poisoned = recorder.clone();
+    let _ = std::thread::spawn(move || {
+        let _lock = poisoned.lock().unwrap();
+        panic!("Poisons the callback");
+    }).join();
+    let _ = monitored_item.all_notifications();
+    monitored_item.set_queue_overflow_callback(Some(recorder.clone()));
+    enqueue(&mut monitored_item, 1..=5);
+    assert!(recorder.is_poisoned());
+}
+
+#[test]
+fn subscription_queue_overflow_events() {
+    // Overflows wait on the subscription until they are taken, so nothing is called while the
+    // session is locked
+    let mut address_space = make_address_space();
+    let now = Utc::now();
+    let recorder = Arc::new(Mutex::new(QueueOverflowRecorder { overflows: Vec::new() }));
+    let mut subscriptions = Subscriptions::new(100, 30000);
+    let mut subscription = Subscription::new(Arc::new(RwLock::new(ServerDiagnostics::default())), 1, true, 100f64, 100, 30, 0);
+    let results = subscription.create_monitored_items(&address_space, &now, TimestampsToReturn::Both, &[
+        make_create_request(0f64, 2, test_var_node_id(), AttributeId::Value, ExtensionObject::null()),
+    ], 0, &MonitoredItemDefaults::default());
+    let monitored_item_id = results[0].monitored_item_id;
+    let _ = subscription.set_monitoring_modes(MonitoringMode::Sampling, &[monitored_item_id]);
+    assert_eq!(subscription.set_queue_overflow_callback(monitored_item_id, Some(recorder.clone())), StatusCode::Good);
+    subscriptions.insert(1, subscription);
+
+    // Every tick samples a new value. Nothing is published so the queue overflows
+    for i in 1..=5 {
+        let now = now + chrono::Duration::seconds(i);
+        let timestamp = DateTime::from(now);
+        let _ = address_space.set_variable_value(test_var_node_id(), i as u32, &timestamp, &timestamp);
+        subscriptions.tick(&now, &address_space, TickReason::TickTimerFired).unwrap();
+    }
+    assert!(recorder.lock().unwrap().overflows.is_empty());
+
+    let events = subscriptions.take_queue_overflow_events();
+    assert_eq!(events.len(), 1);
+    events.into_iter().for_each(|e| e.notify());
+    assert_eq!(recorder.lock().unwrap().overflows, vec![(monitored_item_id, 1)]);
+    assert!(subscriptions.take_queue_overflow_events().is_empty());
+}
+
+#[test]
+fn monitored_item_coalesce() {
+    // Many rapid changes within one publishing interval should collapse to a single notification
+    // holding the most recent value.
+    let mut address_space = make_address_space();
+
+    let mut monitored_item = MonitoredItem::new(&chrono::Utc::now(), 1, TimestampsToReturn::Both, &make_create_request(0f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null())).unwrap();
+    monitored_item.set_coalesce(true);
+
+    let mut now = Utc::now();
+    for i in 1..=10u32 {
+        now = now + chrono::Duration::seconds(1);
+        if let &mut NodeType::Variable(ref mut node) = address_space.find_node_mut(&test_var_node_id()).unwrap() {
+            let _ = node.set_value(NumericRange::None, Variant::UInt32(i)).unwrap();
+        } else {
+            panic!("Expected a variable, didn't get one!!");
+        }
+        assert_eq!(monitored_item.tick(&now, &address_space, false, false), TickResult::ReportValueChanged);
+        assert_eq!(monitored_item.notification_queue().len(), 1);
+    }
+    assert!(!monitored_item.queue_overflow());
+
+    let notifications = monitored_item.all_notifications().unwrap();
+    assert_eq!(notifications.len(), 1);
+    if let Notification::MonitoredItemNotification(ref notification) = notifications[0] {
+        assert_eq!(notification.value.value, Some(Variant::UInt32(10)));
+    } else {
+        panic!();
+    }
+
+    // Without coalescing the same changes accumulate up to the queue size
+    monitored_item.set_coalesce(false);
+    for i in 11..=13u32 {
+        now = now + chrono::Duration::seconds(1);
+        if let &mut NodeType::Variable(ref mut node) = address_space.find_node_mut(&test_var_node_id()).unwrap() {
+            let _ = node.set_value(NumericRange::None, Variant::UInt32(i)).unwrap();
+        }
+        assert_eq!(monitored_item.tick(&now, &address_space, false, false), TickResult::ReportValueChanged);
+    }
+    assert_eq!(monitored_item.notification_queue().len(), 3);
+}
+
+#[test]
+fn monitored_item_coalesce_keeps_overflow() {
+    // Coalescing onto a notification that overflowed must not lose the overflow bit
+    let mut address_space = make_address_space();
+
+    let mut monitored_item = MonitoredItem::new(&chrono::Utc::now(), 1, TimestampsToReturn::Both, &make_create_request(0f64, 2, test_var_node_id(), AttributeId::Value, ExtensionObject::null())).unwrap();
+
+    let mut now = Utc::now();
+    for i in 1..=4u32 {
+        now = now + chrono::Duration::seconds(1);
+        if let &mut NodeType::Variable(ref mut node) = address_space.find_node_mut(&test_var_node_id()).unwrap() {
+            let _ = node.set_value(NumericRange::None, Variant::UInt32(i)).unwrap();
+        }
+        if i == 4 {
+            monitored_item.set_coalesce(true);
+        }
+        assert_eq!(monitored_item.tick(&now, &address_space, false, false), TickResult::ReportValueChanged);
+    }
+
+    let notifications = monitored_item.all_notifications().unwrap();
+    assert_eq!(notifications.len(), 2);
+    if let Notification::MonitoredItemNotification(ref notification) = notifications[1] {
+        assert_eq!(notification.value.value, Some(Variant::UInt32(4)));
+        assert!(notification.value.status().contains(StatusCode::OVERFLOW));
+    } else {
+        panic!();
+    }
+}
+
+#[test]
+fn monitored_item_min_notification_interval() {
+    let mut address_space = make_address_space();
+
+    let mut monitored_item = MonitoredItem::new(&chrono::Utc::now(), 1, TimestampsToReturn::Both, &make_create_request(0f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null())).unwrap();
+    monitored_item.set_min_notification_interval(1000f64);
+
+    let set_value = |address_space: &mut AddressSpace, v: u32| {
+        if let &mut NodeType::Variable(ref mut node) = address_space.find_node_mut(&test_var_node_id()).unwrap() {
+            let _ = node.set_value(NumericRange::None, Variant::UInt32(v)).unwrap();
+        } else {
+            panic!("Expected a variable, didn't get one!!");
+        }
+    };
+
+    // First value is reported on the first tick after the sampling interval
+    let now = Utc::now() + chrono::Duration::milliseconds(100);
+    assert_eq!(monitored_item.tick(&now, &address_space, false, false), TickResult::ReportValueChanged);
+    assert_eq!(monitored_item.all_notifications().unwrap().len(), 1);
+
+    // Changes inside the interval are held back
+    set_value(&mut address_space, 1);
+    let _ = monitored_item.tick(&(now + chrono::Duration::milliseconds(100)), &address_space, false, false);
+    assert!(monitored_item.notification_queue().is_empty());
+    set_value(&mut address_space, 2);
+    let _ = monitored_item.tick(&(now + chrono::Duration::milliseconds(200)), &address_space, false, false);
+    assert!(monitored_item.notification_queue().is_empty());
+
+    // Once the interval elapses, the latest value is released even though it hasn't changed since
+    assert_eq!(monitored_item.tick(&(now + chrono::Duration::milliseconds(1000)), &address_space, false, false), TickResult::ReportValueChanged);
+    let notifications = monitored_item.all_notifications().unwrap();
+    assert_eq!(notifications.len(), 1);
+    if let Notification::MonitoredItemNotification(ref notification) = notifications[0] {
+        assert_eq!(notification.value.value, Some(Variant::UInt32(2)));
+    } else {
+        panic!();
+    }
+
+    // Nothing further is pending
+    assert_eq!(monitored_item.tick(&(now + chrono::Duration::milliseconds(3000)), &address_space, false, false), TickResult::NoChange);
+    assert!(monitored_item.notification_queue().is_empty());
+}
+
+#[test]
+fn monitored_item_source_timestamp_hold_off() {
+    // A slow source is polled faster than it updates. Samples carrying the same source timestamp
+    // must not produce duplicate notifications.
+    let mut address_space = make_address_space();
+
+    let mut monitored_item = MonitoredItem::new(&chrono::Utc::now(), 1, TimestampsToReturn::Both, &make_create_request(0f64, 10, test_var_node_id(), AttributeId::Value, ExtensionObject::null())).unwrap();
+    monitored_item.set_source_timestamp_hold_off(true);
+
+    let set_value = |address_space: &mut AddressSpace, v: u32, source_timestamp: &DateTime| {
+        if let &mut NodeType::Variable(ref mut node) = address_space.find_node_mut(&test_var_node_id()).unwrap() {
+            let _ = node.set_value_direct(Variant::UInt32(v), StatusCode::Good, &DateTime::now(), source_timestamp).unwrap();
+        } else {
+            panic!("Expected a variable, didn't get one!!");
+        }
+    };
+
+    let source_timestamp1 = DateTime::from(Utc::now());
+    let source_timestamp2 = DateTime::from(Utc::now() + chrono::Duration::seconds(1));
+
+    // First sample is reported on the first tick after the sampling interval
+    set_value(&mut address_space, 1, &source_timestamp1);
+    let mut now = Utc::now() + chrono::Duration::milliseconds(100);
+    assert_eq!(monitored_item.tick(&now, &address_space, false, false), TickResult::ReportValueChanged);
+    assert_eq!(monitored_item.all_notifications().unwrap().len(), 1);
+
+    // Polling again with the same source timestamp yields nothing, even if the value was rewritten
+    for _ in 0..3 {
+        now = now + chrono::Duration::milliseconds(100);
+        set_value(&mut address_space, 2, &source_timestamp1);
+        assert_eq!(monitored_item.tick(&now, &address_space, false, false), TickResult::NoChange);
+    }
+    assert!(monitored_item.notification_queue().is_empty());
+
+    // Once the source timestamp moves on the new sample is reported
+    now = now + chrono::Duration::milliseconds(100);
+    set_value(&mut address_space, 3, &source_timestamp2);
+    assert_eq!(monitored_item.tick(&now, &address_space, false, false), TickResult::ReportValueChanged);
+    let notifications = monitored_item.all_notifications().unwrap();
+    assert_eq!(notifications.len(), 1);
+    if let Notification::MonitoredItemNotification(ref notification) = notifications[0] {
+        assert_eq!(notification.value.value, Some(Variant::UInt32(3)));
+        assert_eq!(notification.value.source_timestamp, Some(source_timestamp2.clone()));
+    } else {
+        panic!();
+    }
+
+    // Without hold off the rewritten value is reported despite the unchanged source timestamp
+    monitored_item.set_source_timestamp_hold_off(false);
+    now = now + chrono::Duration::milliseconds(100);
+    set_value(&mut address_space, 4, &source_timestamp2);
+    assert_eq!(monitored_item.tick(&now, &address_space, false, false), TickResult::ReportValueChanged);
+}
+
+#[test]
+fn monitored_item_reenabled_reports_latest_value() {
+    let mut address_space = make_address_space();
+
+    let mut monitored_item = MonitoredItem::new(&chrono::Utc::now(), 1, TimestampsToReturn::Both, &make_create_request(1000f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null())).unwrap();
+
+    let set_value = |address_space: &mut AddressSpace, v: u32| {
+        if let &mut NodeType::Variable(ref mut node) = address_space.find_node_mut(&test_var_node_id()).unwrap() {
+            let _ = node.set_value(NumericRange::None, Variant::UInt32(v)).unwrap();
+        } else {
+            panic!("Expected a variable, didn't get one!!");
+        }
+    };
+
+    // Initial value is reported
+    let now = Utc::now() + chrono::Duration::seconds(1);
+    assert_eq!(monitored_item.tick(&now, &address_space, false, false), TickResult::ReportValueChanged);
+    assert_eq!(monitored_item.all_notifications().unwrap().len(), 1);
+
+    // Change the value while the item is disabled, nothing is reported
+    monitored_item.set_monitoring_mode(MonitoringMode::Disabled);
+    set_value(&mut address_space, 7);
+    assert_eq!(monitored_item.tick(&(now + chrono::Duration::seconds(2)), &address_space, false, false), TickResult::NoChange);
+    assert!(monitored_item.notification_queue().is_empty());
+
+    // Re-enabling reports the value that changed while disabled on the next sample
+    monitored_item.set_monitoring_mode(MonitoringMode::Reporting);
+    assert_eq!(monitored_item.tick(&(now + chrono::Duration::seconds(3)), &address_space, false, false), TickResult::ReportValueChanged);
+    let notifications = monitored_item.all_notifications().unwrap();
+    assert_eq!(notifications.len(), 1);
+    if let Notification::MonitoredItemNotification(ref notification) = notifications[0] {
+        assert_eq!(notification.value.value, Some(Variant::UInt32(7)));
+    } else {
+        panic!();
+    }
+
+    // Disabling and re-enabling without a change still reports the current value once
+    monitored_item.set_monitoring_mode(MonitoringMode::Disabled);
+    monitored_item.set_monitoring_mode(MonitoringMode::Reporting);
+    assert_eq!(monitored_item.tick(&(now + chrono::Duration::seconds(5)), &address_space, false, false), TickResult::ReportValueChanged);
+    assert_eq!(monitored_item.all_notifications().unwrap().len(), 1);
+    assert_eq!(monitored_item.tick(&(now + chrono::Duration::seconds(7)), &address_space, false, false), TickResult::NoChange);
+}
+
+#[test]
+fn monitored_item_reset_baseline() {
+    let address_space = make_address_space();
+
+    let mut monitored_item = MonitoredItem::new(&chrono::Utc::now(), 1, TimestampsToReturn::Both, &make_create_request(1000f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null())).unwrap();
+
+    // Initial value is reported, the same value after that is not
+    let now = Utc::now() + chrono::Duration::seconds(1);
+    assert_eq!(monitored_item.tick(&now, &address_space, false, false), TickResult::ReportValueChanged);
+    assert_eq!(monitored_item.all_notifications().unwrap().len(), 1);
+    assert_eq!(monitored_item.tick(&(now + chrono::Duration::seconds(2)), &address_space, false, false), TickResult::NoChange);
+
+    // A reset reports the unchanged value on the next sample, and only once
+    monitored_item.reset_baseline();
+    assert_eq!(monitored_item.tick(&(now + chrono::Duration::seconds(4)), &address_space, false, false), TickResult::ReportValueChanged);
+    let notifications = monitored_item.all_notifications().unwrap();
+    assert_eq!(notifications.len(), 1);
+    if let Notification::MonitoredItemNotification(ref notification) = notifications[0] {
+        assert_eq!(notification.value.value, Some(Variant::UInt32(0)));
+    } else {
+        panic!();
+    }
+    assert_eq!(monitored_item.tick(&(now + chrono::Duration::seconds(6)), &address_space, false, false), TickResult::NoChange);
+}
+
+#[test]
+fn monitored_item_report_uncertain() {
+    let mut address_space = make_address_space();
+
+    let mut monitored_item = MonitoredItem::new(&chrono::Utc::now(), 1, TimestampsToReturn::Both, &make_create_request(0f64, 10, test_var_node_id(), AttributeId::Value, ExtensionObject::null())).unwrap();
+
+    let set_value = |address_space: &mut AddressSpace, v: u32, status: StatusCode| {
+        if let &mut NodeType::Variable(ref mut node) = address_space.find_node_mut(&test_var_node_id()).unwrap() {
+            let now = DateTime::now();
+            let _ = node.set_value_direct(Variant::UInt32(v), status, &now, &now).unwrap();
+        } else {
+            panic!("Expected a variable, didn't get one!!");
+        }
+    };
+
+    // Initial value is reported on the first tick after the sampling interval
+    set_value(&mut address_space, 1, StatusCode::UncertainLastUsableValue);
+    let mut now = Utc::now() + chrono::Duration::milliseconds(100);
+    assert_eq!(monitored_item.tick(&now, &address_space, false, false), TickResult::ReportValueChanged);
+    assert_eq!(monitored_item.all_notifications().unwrap().len(), 1);
+
+    // Option off - the same uncertain value is not a change
+    now = now + chrono::Duration::milliseconds(100);
+    set_value(&mut address_space, 1, StatusCode::UncertainLastUsableValue);
+    assert_eq!(monitored_item.tick(&now, &address_space, false, false), TickResult::NoChange);
+
+    // Option on - every uncertain sample is reported even though the value is the same
+    monitored_item.set_report_uncertain(true);
+    for _ in 0..3 {
+        now = now + chrono::Duration::milliseconds(100);
+        set_value(&mut address_space, 1, StatusCode::UncertainLastUsableValue);
+        assert_eq!(monitored_item.tick(&now, &address_space, false, false), TickResult::ReportValueChanged);
+    }
+    let notifications = monitored_item.all_notifications().unwrap();
+    assert_eq!(notifications.len(), 3);
+    notifications.iter().for_each(|n| {
+        if let Notification::MonitoredItemNotification(ref notification) = n {
+            assert!(notification.value.status().is_uncertain());
+        } else {
+            panic!();
+        }
+    });
+
+    // Option on - good samples still follow the normal rules
+    now = now + chrono::Duration::milliseconds(100);
+    set_value(&mut address_space, 1, StatusCode::Good);
+    assert_eq!(monitored_item.tick(&now, &address_space, false, false), TickResult::NoChange);
+    now = now + chrono::Duration::milliseconds(100);
+    set_value(&mut address_space, 2, StatusCode::Good);
+    assert_eq!(monitored_item.tick(&now, &address_space, false, false), TickResult::ReportValueChanged);
+}
+
+#[test]
+fn monitored_item_eu_range_cached() {
+    let mut address_space = make_address_space();
+
+    // Give the variable an EURange property of 0 to 100
+    let eu_range_id = NodeId::new(1, "EURange");
+    let set_eu_range = |address_space: &mut AddressSpace, low: f64, high: f64| {
+        let range = ExtensionObject::from_encodable(ObjectId::Range_Encoding_DefaultBinary, &Range { low, high });
+        let _ = address_space.find_variable_mut(eu_range_id.clone()).unwrap().set_value(NumericRange::None, range);
+    };
+    VariableBuilder::new(&eu_range_id, "EURange", "EURange")
+        .data_type(DataTypeId::Range)
+        .value(ExtensionObject::null())
+        .property_of(test_var_node_id())
+        .has_type_definition(VariableTypeId::PropertyType)
+        .insert(&mut address_space);
+    set_eu_range(&mut address_space, 0f64, 100f64);
+
+    let set_value = |address_space: &mut AddressSpace, v: u32| {
+        let _ = address_space.find_variable_mut(test_var_node_id()).unwrap().set_value(NumericRange::None, Variant::UInt32(v));
+    };
+
+    // Percent deadband of 10%
+    let filter = ExtensionObject::from_encodable(ObjectId::DataChangeFilter_Encoding_DefaultBinary, &DataChangeFilter {
+        trigger: DataChangeTrigger::StatusValue,
+        deadband_type: DeadbandType::Percent as u32,
+        deadband_value: 10f64,
+    });
+    let mut monitored_item = MonitoredItem::new(&chrono::Utc::now(), 1, TimestampsToReturn::Both, &make_create_request(0f64, 5, test_var_node_id(), AttributeId::Value, filter)).unwrap();
+
+    let mut now = Utc::now();
+    let mut tick = |monitored_item: &mut MonitoredItem, address_space: &AddressSpace| {
+        now = now + chrono::Duration::seconds(1);
+        monitored_item.tick(&now, address_space, false, false)
+    };
+
+    assert_eq!(tick(&mut monitored_item, &address_space), TickResult::ReportValueChanged);
+    let _ = monitored_item.all_notifications();
+
+    // Within 10% of 0..100
+    set_value(&mut address_space, 5);
+    assert_eq!(tick(&mut monitored_item, &address_space), TickResult::NoChange);
+
+    // Outside 10% of 0..100
+    set_value(&mut address_space, 20);
+    assert_eq!(tick(&mut monitored_item, &address_space), TickResult::ReportValueChanged);
+    let _ = monitored_item.all_notifications();
+
+    // Widen the range. The item still uses the cached range across ticks so a change of 30 is reported
+    set_eu_range(&mut address_space, 0f64, 1000f64);
+    (0..10).for_each(|_| { let _ = tick(&mut monitored_item, &address_space); });
+    set_value(&mut address_space, 50);
+    assert_eq!(tick(&mut monitored_item, &address_space), TickResult::ReportValueChanged);
+    let _ = monitored_item.all_notifications();
+
+    // After a refresh, 10% of 0..1000 means a change of 30 is not reported
+    monitored_item.refresh_eu_range(&address_space);
+    set_value(&mut address_space, 80);
+    assert_eq!(tick(&mut monitored_item, &address_space), TickResult::NoChange);
+
+    // A write to the EURange property invalidates the cache, so 10% of 0..100 is used again
+    let range = ExtensionObject::from_encodable(ObjectId::Range_Encoding_DefaultBinary, &Range { low: 0f64, high: 100f64 });
+    assert!(address_space.set_variable_value(eu_range_id.clone(), range, &DateTime::now(), &DateTime::now()));
+    assert!(!monitored_item.on_write(&address_space, &Utc::now(), &eu_range_id, AttributeId::Value, &NumericRange::None));
+    set_value(&mut address_space, 110);
+    assert_eq!(tick(&mut monitored_item, &address_space), TickResult::ReportValueChanged);
+}
+
+#[test]
+fn monitored_item_default_queue_size() {
+    let defaults = MonitoredItemDefaults {
+        queue_size: 5,
+        discard_oldest: false,
+        ..Default::default()
+    };
+
+    // Queue size of 0 takes the defaults
+    let monitored_item = MonitoredItem::new_with_defaults(&chrono::Utc::now(), 1, TimestampsToReturn::Both, &make_create_request(-1f64, 0, test_var_node_id(), AttributeId::Value, ExtensionObject::null()), &defaults).unwrap();
+    assert_eq!(monitored_item.queue_size(), 5);
+    assert!(!monitored_item.discard_oldest());
+
+    // Queue size other than 0 is what the client asked for
+    let monitored_item = MonitoredItem::new_with_defaults(&chrono::Utc::now(), 1, TimestampsToReturn::Both, &make_create_request(-1f64, 3, test_var_node_id(), AttributeId::Value, ExtensionObject::null()), &defaults).unwrap();
+    assert_eq!(monitored_item.queue_size(), 3);
+    assert!(monitored_item.discard_oldest());
+
+    // Defaults are still subject to the max queue size
+    let defaults = MonitoredItemDefaults {
+        queue_size: crate::constants::MAX_DATA_CHANGE_QUEUE_SIZE + 10,
+        discard_oldest: true,
+        ..Default::default()
+    };
+    let monitored_item = MonitoredItem::new_with_defaults(&chrono::Utc::now(), 1, TimestampsToReturn::Both, &make_create_request(-1f64, 0, test_var_node_id(), AttributeId::Value, ExtensionObject::null()), &defaults).unwrap();
+    assert_eq!(monitored_item.queue_size(), crate::constants::MAX_DATA_CHANGE_QUEUE_SIZE);
+}
+
+#[test]
+fn revised_queue_size_uses_defaults() {
+    do_subscription_service_test(|server_state, session, address_space, ss: SubscriptionService, mis: MonitoredItemService| {
+        {
+            let mut server_state = trace_write_lock_unwrap!(server_state);
+            server_state.monitored_item_defaults = MonitoredItemDefaults {
+                queue_size: 4,
+                discard_oldest: false,
+                ..Default::default()
+            };
+        }
+
+        let subscription_id = {
+            let request = create_subscription_request(0, 0);
+            let response: CreateSubscriptionResponse = supported_message_as!(ss.create_subscription(server_state.clone(), session.clone(), &request), CreateSubscriptionResponse);
+            response.subscription_id
+        };
+
+        let mut request = create_monitored_items_request(subscription_id, vec![
+            NodeId::new(1, var_name(1)),
+            NodeId::new(1, var_name(2)),
+        ]);
+        // The first item has no preference, the second asks for 2
+        {
+            let items_to_create = request.items_to_create.as_mut().unwrap();
+            items_to_create[0].requested_parameters.queue_size = 0;
+            items_to_create[1].requested_parameters.queue_size = 2;
+        }
+
+        let response: CreateMonitoredItemsResponse = supported_message_as!(mis.create_monitored_items(server_state.clone(), session.clone(), address_space.clone(), &request), CreateMonitoredItemsResponse);
+        let results = response.results.unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].status_code, StatusCode::Good);
+        assert_eq!(results[0].revised_queue_size, 4);
+        assert_eq!(results[1].status_code, StatusCode::Good);
+        assert_eq!(results[1].revised_queue_size, 2);
+    });
+}
+
+#[test]
+fn subscription_with_monitored_item() {
+    let address_space = make_address_space();
+    let mut subscription = Subscription::new(Arc::new(RwLock::new(ServerDiagnostics::default())), 1, true, 100f64, 100, 30, 0);
+    let results = subscription.create_monitored_items(&address_space, &Utc::now(), TimestampsToReturn::Both, &[
+        make_create_request(-1f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null())
+    ], 0, &MonitoredItemDefaults::default());
+    let monitored_item_id = results[0].monitored_item_id;
+
+    // The per item setters apply to the item with the id
+    assert_eq!(subscription.set_coalesce(monitored_item_id, true), StatusCode::Good);
+    assert!(subscription.snapshot().monitored_items[0].coalesce);
+
+    // An unknown id is reported without calling the function
+    let mut called = false;
+    assert_eq!(subscription.with_monitored_item(monitored_item_id + 1, |_| called = true), StatusCode::BadMonitoredItemIdInvalid);
+    assert!(!called);
+}
+
+#[test]
+fn subscription_max_monitored_items_per_tick() {
+    let address_space = make_address_space();
+    let mut subscription = Subscription::new(Arc::new(RwLock::new(ServerDiagnostics::default())), 1, true, 100f64, 100, 30, 0);
+    let requests = (0..10).map(|_| make_create_request(-1f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null())).collect::<Vec<_>>();
+    let results = subscription.create_monitored_items(&address_space, &Utc::now(), TimestampsToReturn::Both, &requests, 0, &MonitoredItemDefaults::default());
+    let mut monitored_item_ids = results.iter().map(|r| r.monitored_item_id).collect::<Vec<_>>();
+    monitored_item_ids.sort();
+
+    // No budget ticks everything
+    assert_eq!(subscription.monitored_items_to_tick(false).len(), 10);
+
+    // With a budget of 3, 4 cycles must touch every item and 30 cycles must tick each item exactly 9 times
+    subscription.set_max_monitored_items_per_tick(3);
+    let mut ticks = HashMap::new();
+    for cycle in 0..30 {
+        let ticked = subscription.monitored_items_to_tick(false);
+        assert_eq!(ticked.len(), 3);
+        ticked.iter().for_each(|id| *ticks.entry(*id).or_insert(0) += 1);
+        if cycle == 3 {
+            assert_eq!(ticks.len(), 10);
+        }
+    }
+    monitored_item_ids.iter().for_each(|id| assert_eq!(*ticks.get(id).unwrap(), 9));
+
+    // Resending data ticks every item regardless of the budget
+    assert_eq!(subscription.monitored_items_to_tick(true).len(), 10);
+}
+
+#[test]
+fn monitored_item_queue_remaining() {
+    let mut monitored_item = MonitoredItem::new(&chrono::Utc::now(), 1, TimestampsToReturn::Both, &make_create_request_data_change_filter(-1f64, 5)).unwrap();
+    assert_eq!(monitored_item.queue_len(), 0);
+    assert_eq!(monitored_item.queue_remaining(), 5);
+
+    for i in 0..3 {
+        monitored_item.enqueue_notification_message(MonitoredItemNotification {
+            client_handle: 999,
+            value: DataValue::new_now(i as i32),
+        });
+    }
+    assert_eq!(monitored_item.queue_len(), 3);
+    assert_eq!(monitored_item.queue_remaining(), 2);
+
+    assert_first_notification_is_i32(&mut monitored_item, 0);
+    assert_eq!(monitored_item.queue_len(), 2);
+    assert_eq!(monitored_item.queue_remaining(), 3);
+
+    // A full queue has no room left, even after it overflows
+    for i in 3..7 {
+        monitored_item.enqueue_notification_message(MonitoredItemNotification {
+            client_handle: 999,
+            value: DataValue::new_now(i as i32),
+        });
+    }
+    assert_eq!(monitored_item.queue_len(), 5);
+    assert_eq!(monitored_item.queue_remaining(), 0);
+}
+
+#[test]
+fn subscription_ticks_fullest_queues_first() {
+    let address_space = make_address_space();
+    let mut subscription = Subscription::new(Arc::new(RwLock::new(ServerDiagnostics::default())), 1, true, 100f64, 100, 30, 0);
+    let requests = [10, 2, 5].iter().map(|queue_size| make_create_request(-1f64, *queue_size, test_var_node_id(), AttributeId::Value, ExtensionObject::null())).collect::<Vec<_>>();
+    let results = subscription.create_monitored_items(&address_space, &Utc::now(), TimestampsToReturn::Both, &requests, 0, &MonitoredItemDefaults::default());
+    let ids = results.iter().map(|r| r.monitored_item_id).collect::<Vec<_>>();
+
+    // The item with the least room in its queue comes first
+    assert_eq!(subscription.monitored_items_to_tick(false), vec![ids[1], ids[2], ids[0]]);
+}
+
+#[test]
+fn monitored_item_initial_sample() {
+    let address_space = make_address_space();
+    let now = Utc::now();
+
+    // A slow sampling interval would otherwise delay the first value until it elapses
+    let request = make_create_request(5000f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null());
+    let mut monitored_item = MonitoredItem::new(&now, 1, TimestampsToReturn::Both, &request).unwrap();
+    assert!(monitored_item.initial_sample(&address_space, &now));
+    assert_eq!(monitored_item.notification_queue().len(), 1);
+
+    // The first tick reports the initial value even though the sampling interval has not elapsed
+    let now = now + chrono::Duration::milliseconds(100);
+    assert_eq!(monitored_item.tick(&now, &address_space, true, false), TickResult::ReportValueChanged);
+    assert_first_notification_is_u32(&mut monitored_item, 0);
+    assert_eq!(monitored_item.tick(&now, &address_space, true, false), TickResult::NoChange);
+
+    // Items that are not reporting are not sampled on creation
+    let mut request = make_create_request(5000f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null());
+    request.monitoring_mode = MonitoringMode::Sampling;
+    let mut monitored_item = MonitoredItem::new(&now, 2, TimestampsToReturn::Both, &request).unwrap();
+    assert!(!monitored_item.initial_sample(&address_space, &now));
+    assert!(monitored_item.notification_queue().is_empty());
+}
+
+#[test]
+fn monitored_item_initial_value_good_only() {
+    let mut address_space = make_address_space();
+    let now = Utc::now();
+    let set_value = |address_space: &mut AddressSpace, v: u32, status_code: StatusCode| {
+        let _ = address_space.find_variable_mut(test_var_node_id()).unwrap().set_value_direct(v, status_code, &DateTime::now(), &DateTime::now());
+    };
+    let defaults = MonitoredItemDefaults {
+        initial_value_good_only: true,
+        ..Default::default()
+    };
+    let request = make_create_request(0f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null());
+
+    // A bad initial value is reported by default
+    set_value(&mut address_space, 1, StatusCode::BadWaitingForInitialData);
+    let mut monitored_item = MonitoredItem::new(&now, 1, TimestampsToReturn::Both, &request).unwrap();
+    assert!(monitored_item.initial_sample(&address_space, &now));
+    assert_eq!(monitored_item.notification_queue().len(), 1);
+
+    // With the option on the bad initial value is suppressed
+    let mut monitored_item = MonitoredItem::new_with_defaults(&now, 2, TimestampsToReturn::Both, &request, &defaults).unwrap();
+    assert!(!monitored_item.initial_sample(&address_space, &now));
+    assert!(monitored_item.notification_queue().is_empty());
+
+    // The same value is not reported on the next tick but a good value is
+    let now = now + chrono::Duration::milliseconds(100);
+    assert_eq!(monitored_item.tick(&now, &address_space, false, false), TickResult::NoChange);
+    set_value(&mut address_space, 2, StatusCode::Good);
+    let now = now + chrono::Duration::milliseconds(100);
+    assert_eq!(monitored_item.tick(&now, &address_space, false, false), TickResult::ReportValueChanged);
+    assert_first_notification_is_u32(&mut monitored_item, 2);
+
+    // A good initial value is reported with the option on
+    let mut monitored_item = MonitoredItem::new_with_defaults(&now, 3, TimestampsToReturn::Both, &request, &defaults).unwrap();
+    assert!(monitored_item.initial_sample(&address_space, &now));
+    assert_first_notification_is_u32(&mut monitored_item, 2);
+}
+
+#[test]
+fn monitored_item_null_initial_value() {
+    // A node holding a null value reports it as its initial value, then reports changes from it
+    let mut address_space = make_address_space();
+    let now = Utc::now();
+    let timestamp = DateTime::from(now);
+    let _ = address_space.find_variable_mut(test_var_node_id()).unwrap().set_value_direct(Variant::Empty, StatusCode::Good, &timestamp, &timestamp);
+
+    let mut monitored_item = MonitoredItem::new(&now, 1, TimestampsToReturn::Both, &make_create_request(-1f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null())).unwrap();
+    assert!(monitored_item.initial_sample(&address_space, &now));
+    let notifications = monitored_item.all_notifications().unwrap();
+    assert_eq!(notifications.len(), 1);
+    if let Notification::MonitoredItemNotification(ref notification) = notifications[0] {
+        assert_eq!(notification.value.status(), StatusCode::Good);
+        assert_eq!(notification.value.value, Some(Variant::Empty));
+    } else {
+        panic!();
+    }
+
+    // The null value is not reported again
+    let now = now + chrono::Duration::milliseconds(100);
+    assert_eq!(monitored_item.tick(&now, &address_space, true, false), TickResult::NoChange);
+
+    // A value replacing the null is a change, as is going back to null
+    let timestamp = DateTime::from(now);
+    let _ = address_space.find_variable_mut(test_var_node_id()).unwrap().set_value_direct(5u32, StatusCode::Good, &timestamp, &timestamp);
+    let now = now + chrono::Duration::milliseconds(100);
+    assert_eq!(monitored_item.tick(&now, &address_space, true, false), TickResult::ReportValueChanged);
+    assert_first_notification_is_u32(&mut monitored_item, 5);
+
+    let timestamp = DateTime::from(now);
+    let _ = address_space.find_variable_mut(test_var_node_id()).unwrap().set_value_direct(Variant::Empty, StatusCode::Good, &timestamp, &timestamp);
+    let now = now + chrono::Duration::milliseconds(100);
+    assert_eq!(monitored_item.tick(&now, &address_space, true, false), TickResult::ReportValueChanged);
+    let notifications = monitored_item.all_notifications().unwrap();
+    if let Notification::MonitoredItemNotification(ref notification) = notifications[0] {
+        assert_eq!(notification.value.value, Some(Variant::Empty));
+    } else {
+        panic!();
+    }
+}
+
+#[test]
+fn subscription_find_duplicate() {
+    let address_space = make_address_space();
+    let mut subscription = Subscription::new(Arc::new(RwLock::new(ServerDiagnostics::default())), 1, true, 100f64, 100, 30, 0);
+    let deadband_filter = || ExtensionObject::from_encodable(ObjectId::DataChangeFilter_Encoding_DefaultBinary, &DataChangeFilter {
+        trigger: DataChangeTrigger::StatusValue,
+        deadband_type: DeadbandType::Absolute as u32,
+        deadband_value: 1f64,
+    });
+    let results = subscription.create_monitored_items(&address_space, &Utc::now(), TimestampsToReturn::Both, &[
+        make_create_request(1000f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null()),
+        make_create_request(1000f64, 5, test_var_node_id(), AttributeId::Value, deadband_filter()),
+    ], 0, &MonitoredItemDefaults::default());
+    assert!(results.iter().all(|r| r.status_code.is_good()));
+
+    // An identical request is a duplicate, even with a different queue size or client handle
+    let mut request = make_create_request(1000f64, 10, test_var_node_id(), AttributeId::Value, ExtensionObject::null());
+    request.requested_parameters.client_handle = 1;
+    assert_eq!(subscription.find_duplicate(&request), Some(results[0].monitored_item_id));
+    assert_eq!(subscription.find_duplicate(&make_create_request(1000f64, 5, test_var_node_id(), AttributeId::Value, deadband_filter())), Some(results[1].monitored_item_id));
+
+    // A different sampling interval, node, attribute, index range or filter is not
+    assert_eq!(subscription.find_duplicate(&make_create_request(500f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null())), None);
+    assert_eq!(subscription.find_duplicate(&make_create_request(1000f64, 5, NodeId::new(1, 2), AttributeId::Value, ExtensionObject::null())), None);
+    assert_eq!(subscription.find_duplicate(&make_create_request(1000f64, 5, test_var_node_id(), AttributeId::DisplayName, ExtensionObject::null())), None);
+    let mut request = make_create_request(1000f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null());
+    request.item_to_monitor.index_range = UAString::from("1");
+    assert_eq!(subscription.find_duplicate(&request), None);
+    let filter = ExtensionObject::from_encodable(ObjectId::DataChangeFilter_Encoding_DefaultBinary, &DataChangeFilter {
+        trigger: DataChangeTrigger::StatusValue,
+        deadband_type: DeadbandType::Absolute as u32,
+        deadband_value: 2f64,
+    });
+    assert_eq!(subscription.find_duplicate(&make_create_request(1000f64, 5, test_var_node_id(), AttributeId::Value, filter)), None);
+
+    // Of two duplicates the lowest id is found
+    let results2 = subscription.create_monitored_items(&address_space, &Utc::now(), TimestampsToReturn::Both, &[
+        make_create_request(1000f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null()),
+    ], 0, &MonitoredItemDefaults::default());
+    assert!(results2[0].monitored_item_id > results[0].monitored_item_id);
+    assert_eq!(subscription.find_duplicate(&make_create_request(1000f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null())), Some(results[0].monitored_item_id));
+}
+
+#[test]
+fn subscription_initial_sample() {
+    // A freshly created item has a notification queued before any value changes
+    let address_space = make_address_space();
+    let mut subscription = Subscription::new(Arc::new(RwLock::new(ServerDiagnostics::default())), 1, true, 100f64, 100, 30, 0);
+    let results = subscription.create_monitored_items(&address_space, &Utc::now(), TimestampsToReturn::Both, &[
+        make_create_request(5000f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null())
+    ], 0, &MonitoredItemDefaults::default());
+    assert_eq!(results[0].status_code, StatusCode::Good);
+    assert_eq!(subscription.monitored_item_notifications_len(results[0].monitored_item_id), Some(1));
+}
+
+#[test]
+fn monitored_item_node_disappears() {
+    let mut address_space = make_address_space();
+    let now = Utc::now();
+
+    let request = make_create_request(-1f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null());
+    let mut monitored_item = MonitoredItem::new(&now, 1, TimestampsToReturn::Both, &request).unwrap();
+    assert_eq!(monitored_item.tick(&now, &address_space, true, false), TickResult::ReportValueChanged);
+    assert_first_notification_is_u32(&mut monitored_item, 0);
+
+    // Remove the node, expect exactly one bad status notification
+    assert!(address_space.delete(&test_var_node_id(), true));
+    let now = now + chrono::Duration::milliseconds(100);
+    assert_eq!(monitored_item.tick(&now, &address_space, true, false), TickResult::ReportValueChanged);
+    let notifications = monitored_item.all_notifications().unwrap();
+    assert_eq!(notifications.len(), 1);
+    if let Notification::MonitoredItemNotification(ref notification) = notifications[0] {
+        assert_eq!(notification.value.status, Some(StatusCode::BadNodeIdUnknown));
+        assert!(notification.value.value.is_none());
+    } else {
+        panic!();
+    }
+
+    // The item stays quiet while the node is missing
+    for i in 2..5 {
+        let now = now + chrono::Duration::milliseconds(100 * i);
+        assert_eq!(monitored_item.tick(&now, &address_space, true, false), TickResult::NoChange);
+        assert!(monitored_item.notification_queue().is_empty());
+    }
+
+    // Put the node back, the value is reported again
+    VariableBuilder::new(&test_var_node_id(), "test1", "test1")
+        .data_type(DataTypeId::UInt32)
+        .value(5u32)
+        .organized_by(ObjectId::ObjectsFolder)
+        .insert(&mut address_space);
+    let now = now + chrono::Duration::seconds(1);
+    assert_eq!(monitored_item.tick(&now, &address_space, true, false), TickResult::ReportValueChanged);
+    assert_first_notification_is_u32(&mut monitored_item, 5);
+
+    // And if it goes again, the bad status is reported once more
+    assert!(address_space.delete(&test_var_node_id(), true));
+    let now = now + chrono::Duration::seconds(1);
+    assert_eq!(monitored_item.tick(&now, &address_space, true, false), TickResult::ReportValueChanged);
+    assert_eq!(monitored_item.all_notifications().unwrap().len(), 1);
+}
+
+#[test]
+fn monitored_item_timestamp_only_updates() {
+    // A heartbeat source only moves its source timestamp. Only a trigger that includes the
+    // timestamp reports those updates, unless the item reports heartbeats.
+    let triggers = [
+        (None, false, 0),
+        (Some(DataChangeTrigger::Status), false, 0),
+        (Some(DataChangeTrigger::StatusValue), false, 0),
+        (Some(DataChangeTrigger::StatusValueTimestamp), false, 3),
+        (None, true, 3),
+        (Some(DataChangeTrigger::Status), true, 3),
+        (Some(DataChangeTrigger::StatusValue), true, 3),
+        (Some(DataChangeTrigger::StatusValueTimestamp), true, 3),
+    ];
+    triggers.iter().for_each(|(trigger, report_heartbeats, expected_reports)| {
+        let mut address_space = make_address_space();
+        let filter = if let Some(trigger) = trigger {
+            ExtensionObject::from_encodable(ObjectId::DataChangeFilter_Encoding_DefaultBinary, &DataChangeFilter {
+                trigger: *trigger,
+                deadband_type: DeadbandType::None as u32,
+                deadband_value: 0f64,
+            })
+        } else {
+            ExtensionObject::null()
+        };
+        let request = make_create_request(-1f64, 5, test_var_node_id(), AttributeId::Value, filter);
+        let mut now = Utc::now();
+        let mut monitored_item = MonitoredItem::new(&now, 1, TimestampsToReturn::Both, &request).unwrap();
+        monitored_item.set_report_heartbeats(*report_heartbeats);
+        assert_eq!(monitored_item.snapshot().report_heartbeats, *report_heartbeats);
+
+        // Initial value
+        assert_eq!(monitored_item.tick(&now, &address_space, true, false), TickResult::ReportValueChanged);
+        let _ = monitored_item.all_notifications();
+
+        // Repeated timestamp only updates
+        let mut reports = 0;
+        for _ in 0..3 {
+            now = now + chrono::Duration::seconds(1);
+            let timestamp = DateTime::from(now);
+            let _ = address_space.set_variable_value(test_var_node_id(), 0u32, &timestamp, &timestamp);
+            if monitored_item.tick(&now, &address_space, true, false) == TickResult::ReportValueChanged {
+                reports += 1;
+                assert_eq!(monitored_item.all_notifications().unwrap().len(), 1);
+            }
+        }
+        assert_eq!(reports, *expected_reports, "Trigger {:?}, report heartbeats {}", trigger, report_heartbeats);
+
+        // An update that repeats the source timestamp is no heartbeat
+        let timestamp = DateTime::from(now);
+        let _ = address_space.set_variable_value(test_var_node_id(), 0u32, &timestamp, &timestamp);
+        assert_eq!(monitored_item.tick(&now, &address_space, true, false), TickResult::NoChange);
+
+        // A real value change is always reported, except by a status only trigger
+        now = now + chrono::Duration::seconds(1);
+        let timestamp = DateTime::from(now);
+        let _ = address_space.set_variable_value(test_var_node_id(), 1u32, &timestamp, &timestamp);
+        let expected = if *trigger == Some(DataChangeTrigger::Status) { TickResult::NoChange } else { TickResult::ReportValueChanged };
+        assert_eq!(monitored_item.tick(&now, &address_space, true, false), expected, "Trigger {:?}", trigger);
+    });
+}
+
+#[test]
+fn monitored_item_triggers_per_item() {
+    // Each monitored item in a publish batch must be compared using its own trigger
+    do_subscription_service_test(|server_state, session, address_space, ss: SubscriptionService, mis: MonitoredItemService| {
+        let subscription_id = {
+            let request = create_subscription_request(0, 0);
+            let response: CreateSubscriptionResponse = supported_message_as!(ss.create_subscription(server_state.clone(), session.clone(), &request), CreateSubscriptionResponse);
+            response.subscription_id
+        };
+
+        {
+            let mut session = trace_write_lock_unwrap!(session);
+            session.subscriptions_mut().get_mut(subscription_id).unwrap().set_state(SubscriptionState::Normal);
+        }
+
+        // Three items on the same variable, client handles 0, 1, 2 with a different trigger each
+        let node_id = NodeId::new(1, var_name(1));
+        let mut request = create_monitored_items_request(subscription_id, vec![node_id.clone(), node_id.clone(), node_id.clone()]);
+        {
+            let triggers = [DataChangeTrigger::Status, DataChangeTrigger::StatusValue, DataChangeTrigger::StatusValueTimestamp];
+            let items_to_create = request.items_to_create.as_mut().unwrap();
+            items_to_create.iter_mut().zip(triggers.iter()).for_each(|(item, trigger)| {
+                item.requested_parameters.filter = ExtensionObject::from_encodable(ObjectId::DataChangeFilter_Encoding_DefaultBinary, &DataChangeFilter {
+                    trigger: *trigger,
+                    deadband_type: DeadbandType::None as u32,
+                    deadband_value: 0f64,
+                });
+            });
+        }
+        let response: CreateMonitoredItemsResponse = supported_message_as!(mis.create_monitored_items(server_state.clone(), session.clone(), address_space.clone(), &request), CreateMonitoredItemsResponse);
+        response.results.unwrap().iter().for_each(|r| assert_eq!(r.status_code, StatusCode::Good));
+
+        let client_handles = |response: PublishResponse| -> HashSet<u32> {
+            let (notifications, _) = response.notification_message.notifications(&DecodingLimits::default()).unwrap();
+            assert_eq!(notifications.len(), 1);
+            notifications[0].monitored_items.as_ref().unwrap().iter().map(|min| min.client_handle).collect()
+        };
+
+        // Initial values are reported for all items
+        let now = publish_tick_response(session.clone(), &ss, address_space.clone(), Utc::now(), chrono::Duration::seconds(2), |response| {
+            assert_eq!(client_handles(response), [0, 1, 2].iter().cloned().collect());
+        });
+
+        // Value changes, the status trigger ignores it
+        {
+            let mut address_space = trace_write_lock_unwrap!(address_space);
+            let _ = address_space.set_variable_value(node_id.clone(), 100, &DateTime::from(now.clone()), &DateTime::from(now.clone()));
+        }
+        let now = publish_tick_response(session.clone(), &ss, address_space.clone(), now, chrono::Duration::seconds(2), |response| {
+            assert_eq!(client_handles(response), [1, 2].iter().cloned().collect());
+        });
+
+        // Only the source timestamp changes, so only the status / value / timestamp trigger fires
+        {
+            let mut address_space = trace_write_lock_unwrap!(address_space);
+            let _ = address_space.set_variable_value(node_id.clone(), 100, &DateTime::from(now.clone()), &DateTime::from(now.clone()));
+        }
+        let now = publish_tick_response(session.clone(), &ss, address_space.clone(), now, chrono::Duration::seconds(2), |response| {
+            assert_eq!(client_handles(response), [2].iter().cloned().collect());
+        });
+
+        // Nothing changes so nothing is reported
+        let _ = publish_tick_no_response(session.clone(), &ss, address_space.clone(), now, chrono::Duration::seconds(2));
+    });
+}
+
+#[test]
+fn monitored_item_zero_interval_shares_lookup() {
+    let address_space = make_address_space();
+
+    let now = Utc::now();
+    let mut monitored_items = (0..3).map(|i| {
+        MonitoredItem::new(&now, i, TimestampsToReturn::Both, &make_create_request(0f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null())).unwrap()
+    }).collect::<Vec<_>>();
+
+    // Every item samples the node but it is only looked up once per tick
+    [TickResult::ReportValueChanged, TickResult::NoChange].iter().enumerate().for_each(|(i, expected)| {
+        let now = now + chrono::Duration::seconds(i as i64 + 1);
+        let mut sampled_nodes = SampledNodes::default();
+        monitored_items.iter_mut().for_each(|monitored_item| {
+            assert_eq!(monitored_item.tick_with_nodes(&now, &address_space, &mut sampled_nodes, false, false), *expected);
+            let _ = monitored_item.all_notifications();
+        });
+        assert_eq!(sampled_nodes.lookups(), 1);
+    });
+
+    // Items with their own sampling interval share the lookup when they sample in the same tick
+    let mut monitored_item = MonitoredItem::new(&now, 4, TimestampsToReturn::Both, &make_create_request(1000f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null())).unwrap();
+    let mut sampled_nodes = SampledNodes::default();
+    let _ = monitored_item.tick_with_nodes(&(now + chrono::Duration::seconds(1)), &address_space, &mut sampled_nodes, false, false);
+    monitored_items.iter_mut().for_each(|monitored_item| {
+        let _ = monitored_item.tick_with_nodes(&(now + chrono::Duration::seconds(1)), &address_space, &mut sampled_nodes, false, false);
+    });
+    assert_eq!(sampled_nodes.lookups(), 1);
+    assert_eq!(sampled_nodes.reads(), 1);
+}
+
+#[test]
+fn monitored_item_sampling_quantum() {
+    // An item with a zero sampling interval samples once per quantum however often it is ticked
+    let mut address_space = make_address_space();
+    let node_id = NodeId::new(1, 100);
+    let reads = Arc::new(Mutex::new(0));
+    {
+        let reads = reads.clone();
+        let getter = AttrFnGetter::new_boxed(move |_: &NodeId, _: AttributeId, _: NumericRange, _: &QualifiedName, _: f64| -> Result<Option<DataValue>, StatusCode> {
+            *reads.lock().unwrap() += 1;
+            Ok(Some(DataValue::new_now(0f64)))
+        });
+        VariableBuilder::new(&node_id, "quantum", "quantum")
+            .data_type(DataTypeId::Double)
+            .value(0f64)
+            .value_getter(getter)
+            .organized_by(ObjectId::ObjectsFolder)
+            .insert(&mut address_space);
+    }
+
+    let samples = |sampling_quantum: f64| -> usize {
+        *reads.lock().unwrap() = 0;
+        let now = Utc::now();
+        let defaults = MonitoredItemDefaults {
+            sampling_quantum,
+            ..Default::default()
+        };
+        let request = make_create_request(0f64, 5, node_id.clone(), AttributeId::Value, ExtensionObject::null());
+        let mut monitored_item = MonitoredItem::new_with_defaults(&now, 1, TimestampsToReturn::Both, &request, &defaults).unwrap();
+        // Tick every 25ms for a second
+        (1..=40).for_each(|i| {
+            let _ = monitored_item.tick(&(now + chrono::Duration::milliseconds(i * 25)), &address_space, false, false);
+        });
+        *reads.lock().unwrap()
+    };
+
+    assert_eq!(samples(100f64), 10);
+    assert_eq!(samples(250f64), 4);
+    // Without a quantum the item samples on every tick
+    assert_eq!(samples(0f64), 40);
+}
+
+#[test]
+fn monitored_item_effective_sampling_interval() {
+    let now = Utc::now();
+    let effective_sampling_interval = |sampling_interval: f64, sampling_quantum: f64| {
+        let defaults = MonitoredItemDefaults {
+            sampling_quantum,
+            ..Default::default()
+        };
+        let request = make_create_request(sampling_interval, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null());
+        MonitoredItem::new_with_defaults(&now, 1, TimestampsToReturn::Both, &request, &defaults).unwrap().effective_sampling_interval(500f64)
+    };
+
+    // -1 samples at the publishing interval
+    assert_eq!(effective_sampling_interval(-1f64, 0f64), 500f64);
+    assert_eq!(effective_sampling_interval(-1f64, 100f64), 500f64);
+
+    // 0 is the fastest rate, which is the tick quantum or the subscription timer rate without one
+    let timer_rate = crate::constants::SUBSCRIPTION_TIMER_RATE_MS as f64;
+    assert_eq!(effective_sampling_interval(0f64, 0f64), timer_rate);
+    assert_eq!(effective_sampling_interval(0f64, 250f64), 250f64);
+
+    // Too low is raised to the interval the item is ticked at
+    assert_eq!(effective_sampling_interval(crate::constants::MIN_SAMPLING_INTERVAL / 2f64, 0f64), timer_rate);
+    assert_eq!(effective_sampling_interval(timer_rate / 2f64, 0f64), timer_rate);
+    assert_eq!(effective_sampling_interval(50f64, 100f64), 100f64);
+
+    // Other negative values are given the default interval
+    assert_eq!(effective_sampling_interval(-5f64, 0f64), crate::constants::DEFAULT_SAMPLING_INTERVAL);
+
+    // Anything above the minimum is preserved
+    assert_eq!(effective_sampling_interval(250f64, 100f64), 250f64);
+    assert_eq!(effective_sampling_interval(3_600_000f64, 100f64), 3_600_000f64);
+}
+
+#[test]
+fn monitored_item_sampling_interval_stats() {
+    let address_space = make_address_space();
+    let now = Utc::now();
+    let mut monitored_item = MonitoredItem::new(&now, 1, TimestampsToReturn::Both, &make_create_request(0f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null())).unwrap();
+
+    // Nothing is measured until there are two samples
+    let stats = monitored_item.sampling_interval_stats();
+    assert_eq!(stats.count(), 0);
+    assert_eq!(stats.min(), None);
+    assert_eq!(stats.max(), None);
+    assert_eq!(stats.average(), None);
+
+    // Sample at known times, giving intervals of 100ms, 50ms and 150ms
+    [100, 200, 250, 400].iter().for_each(|ms| {
+        let _ = monitored_item.tick(&(now + chrono::Duration::milliseconds(*ms)), &address_space, false, false);
+    });
+    let stats = monitored_item.sampling_interval_stats();
+    assert_eq!(stats.count(), 3);
+    assert_eq!(stats.min(), Some(50f64));
+    assert_eq!(stats.max(), Some(150f64));
+    assert_eq!(stats.average(), Some(100f64));
+}
+
+#[test]
+fn monitored_item_shared_sample() {
+    // Three items on one node read its value once per tick, each applying its own filter
+    let mut address_space = make_address_space();
+    let node_id = NodeId::new(1, 100);
+    let value = Arc::new(Mutex::new(0f64));
+    let reads = Arc::new(Mutex::new(0));
+    {
+        let value = value.clone();
+        let reads = reads.clone();
+        let getter = AttrFnGetter::new_boxed(move |_: &NodeId, _: AttributeId, _: NumericRange, _: &QualifiedName, _: f64| -> Result<Option<DataValue>, StatusCode> {
+            *reads.lock().unwrap() += 1;
+            Ok(Some(DataValue {
+                value: Some(Variant::Double(*value.lock().unwrap())),
+                status: Some(StatusCode::Good),
+                source_timestamp: None,
+                source_picoseconds: None,
+                server_timestamp: None,
+                server_picoseconds: None,
+            }))
+        });
+        VariableBuilder::new(&node_id, "shared", "shared")
+            .data_type(DataTypeId::Double)
+            .value(0f64)
+            .value_getter(getter)
+            .organized_by(ObjectId::ObjectsFolder)
+            .insert(&mut address_space);
+    }
+
+    let filter = |trigger: DataChangeTrigger, deadband_type: DeadbandType, deadband_value: f64| {
+        ExtensionObject::from_encodable(ObjectId::DataChangeFilter_Encoding_DefaultBinary, &DataChangeFilter {
+            trigger,
+            deadband_type: deadband_type as u32,
+            deadband_value,
+        })
+    };
+    let now = Utc::now();
+    let mut monitored_items = [
+        ExtensionObject::null(),
+        filter(DataChangeTrigger::StatusValue, DeadbandType::Absolute, 5f64),
+        filter(DataChangeTrigger::Status, DeadbandType::None, 0f64),
+    ].iter().enumerate().map(|(i, filter)| {
+        MonitoredItem::new(&now, i as u32 + 1, TimestampsToReturn::Both, &make_create_request(1000f64, 5, node_id.clone(), AttributeId::Value, filter.clone())).unwrap()
+    }).collect::<Vec<_>>();
+
+    // Sets the value, ticks every item in one quantum and returns which items reported
+    let mut tick = |v: f64, seconds: i64| -> Vec<bool> {
+        *value.lock().unwrap() = v;
+        let now = now + chrono::Duration::seconds(seconds);
+        let mut sampled_nodes = SampledNodes::default();
+        let reported = monitored_items.iter_mut().map(|monitored_item| {
+            let reported = monitored_item.tick_with_nodes(&now, &address_space, &mut sampled_nodes, false, false) == TickResult::ReportValueChanged;
+            let _ = monitored_item.all_notifications();
+            reported
+        }).collect();
+        assert_eq!(sampled_nodes.reads(), 1);
+        reported
+    };
+
+    assert_eq!(tick(0f64, 1), vec![true, true, true]);
+    assert_eq!(*reads.lock().unwrap(), 1);
+    // A change within the deadband is only reported by the item without a filter
+    assert_eq!(tick(2f64, 2), vec![true, false, false]);
+    assert_eq!(*reads.lock().unwrap(), 2);
+    // A change beyond the deadband is reported by both value filters but not the status one
+    assert_eq!(tick(10f64, 3), vec![true, true, false]);
+    assert_eq!(*reads.lock().unwrap(), 3);
+}
+
+#[test]
+fn monitored_item_id_wraps_without_collision() {
+    let address_space = make_address_space();
+    let mut subscription = Subscription::new(Arc::new(RwLock::new(ServerDiagnostics::default())), 1, true, 100f64, 100, 30, 0);
+    let create = |subscription: &mut Subscription, node_id: NodeId| {
+        let results = subscription.create_monitored_items(&address_space, &Utc::now(), TimestampsToReturn::Both, &[
+            make_create_request(-1f64, 5, node_id, AttributeId::Value, ExtensionObject::null())
+        ], 0, &MonitoredItemDefaults::default());
+        (results[0].status_code, results[0].monitored_item_id)
+    };
+
+    // Items 1 and 2 stay alive across the wrap
+    assert_eq!(create(&mut subscription, test_var_node_id()), (StatusCode::Good, 1));
+    assert_eq!(create(&mut subscription, test_var_node_id()), (StatusCode::Good, 2));
+
+    subscription.set_next_monitored_item_id(u32::MAX);
+    assert_eq!(create(&mut subscription, test_var_node_id()), (StatusCode::Good, u32::MAX));
+
+    // A failed create does not use up an id
+    assert_eq!(create(&mut subscription, NodeId::new(1, "unknown")).0, StatusCode::BadNodeIdUnknown);
+
+    // The id wraps to 1 but skips the live items
+    assert_eq!(create(&mut subscription, test_var_node_id()), (StatusCode::Good, 3));
+
+    // Once an item is deleted its id can be issued again after the next wrap
+    assert_eq!(subscription.delete_monitored_items(&[1]), vec![StatusCode::Good]);
+    subscription.set_next_monitored_item_id(u32::MAX);
+    assert_eq!(create(&mut subscription, test_var_node_id()), (StatusCode::Good, 1));
+    assert_eq!(subscription.monitored_items_len(), 4);
+}
+
+#[test]
+fn subscription_snapshot_restore() {
+    let address_space = make_address_space();
+    let diagnostics = Arc::new(RwLock::new(ServerDiagnostics::default()));
+    let mut subscription = Subscription::new(diagnostics.clone(), 7, true, 500f64, 100, 30, 3);
+    let results = subscription.create_monitored_items(&address_space, &Utc::now(), TimestampsToReturn::Source, &[
+        make_create_request(-1f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null()),
+        make_create_request_data_change_filter(2000f64, 10),
+    ], 0, &MonitoredItemDefaults::default());
+    let (id1, id2) = (results[0].monitored_item_id, results[1].monitored_item_id);
+    assert_eq!(subscription.set_coalesce(id1, true), StatusCode::Good);
+    let _ = subscription.set_triggering(id1, &[id2], &[]);
+    let _ = subscription.tick(&Utc::now(), &address_space, TickReason::TickTimerFired, false);
+
+    // Round trip the snapshot through a serialized form
+    let snapshot = subscription.snapshot();
+    assert_eq!(snapshot.monitored_items.len(), 2);
+    let json = serde_json::to_string(&snapshot).unwrap();
+    let snapshot2: SubscriptionSnapshot = serde_json::from_str(&json).unwrap();
+    assert_eq!(snapshot, snapshot2);
+
+    // The restored subscription has the same configuration
+    let mut restored = Subscription::restore(diagnostics, &Utc::now(), &snapshot2, &MonitoredItemDefaults::default()).unwrap();
+    assert_eq!(restored.subscription_id(), 7);
+    assert_eq!(restored.monitored_items_len(), 2);
+    assert_eq!(restored.snapshot(), snapshot);
+
+    // The restored items have nothing queued until they are next sampled and new items don't
+    // reuse their ids
+    assert_eq!(restored.monitored_item_notifications_len(id1), Some(0));
+    let results = restored.create_monitored_items(&address_space, &Utc::now(), TimestampsToReturn::Both, &[
+        make_create_request(-1f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null()),
+    ], 0, &MonitoredItemDefaults::default());
+    assert_eq!(results[0].monitored_item_id, id2 + 1);
+
+    // A snapshot holding an invalid monitoring mode is rejected
+    let mut snapshot3 = snapshot.clone();
+    snapshot3.monitored_items[0].monitoring_mode = 7;
+    assert_eq!(Subscription::restore(Arc::new(RwLock::new(ServerDiagnostics::default())), &Utc::now(), &snapshot3, &MonitoredItemDefaults::default()).unwrap_err(), StatusCode::BadDecodingError);
+
+    // Restored items take the server's configured defaults, e.g. the quantum they sample at
+    let defaults = MonitoredItemDefaults {
+        sampling_quantum: 250f64,
+        ..Default::default()
+    };
+    let monitored_item = MonitoredItem::new(&Utc::now(), 1, TimestampsToReturn::Both, &make_create_request(100f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null())).unwrap();
+    let restored = MonitoredItem::restore(&Utc::now(), &monitored_item.snapshot(), &defaults).unwrap();
+    assert_eq!(restored.effective_sampling_interval(500f64), 250f64);
+}
+
+#[test]
+fn monitored_item_percent_deadband_floor() {
+    let mut address_space = make_address_space();
+
+    // A variable with a tiny EURange
+    let var_node_id = NodeId::new(1, "Tiny");
+    VariableBuilder::new(&var_node_id, "Tiny", "Tiny")
+        .data_type(DataTypeId::Double)
+        .value(0f64)
+        .organized_by(ObjectId::ObjectsFolder)
+        .insert(&mut address_space);
+    let range = ExtensionObject::from_encodable(ObjectId::Range_Encoding_DefaultBinary, &Range { low: 0f64, high: 1e-6 });
+    VariableBuilder::new(&NodeId::new(1, "TinyEURange"), "EURange", "EURange")
+        .data_type(DataTypeId::Range)
+        .value(range)
+        .property_of(var_node_id.clone())
+        .has_type_definition(VariableTypeId::PropertyType)
+        .insert(&mut address_space);
+
+    let set_value = |address_space: &mut AddressSpace, v: f64| {
+        let _ = address_space.find_variable_mut(var_node_id.clone()).unwrap().set_value(NumericRange::None, Variant::Double(v));
+    };
+
+    // Percent deadband of 1%, i.e. a change of 1e-8 over the range
+    let filter = ExtensionObject::from_encodable(ObjectId::DataChangeFilter_Encoding_DefaultBinary, &DataChangeFilter {
+        trigger: DataChangeTrigger::StatusValue,
+        deadband_type: DeadbandType::Percent as u32,
+        deadband_value: 1f64,
+    });
+    let defaults = MonitoredItemDefaults {
+        percent_deadband_floor: 1e-7,
+        ..Default::default()
+    };
+    let mut monitored_item = MonitoredItem::new_with_defaults(&chrono::Utc::now(), 1, TimestampsToReturn::Both, &make_create_request(0f64, 5, var_node_id.clone(), AttributeId::Value, filter), &defaults).unwrap();
+
+    let mut now = Utc::now();
+    let mut tick = |monitored_item: &mut MonitoredItem, address_space: &AddressSpace| {
+        now = now + chrono::Duration::seconds(1);
+        monitored_item.tick(&now, address_space, false, false)
+    };
+
+    assert_eq!(tick(&mut monitored_item, &address_space), TickResult::ReportValueChanged);
+    let _ = monitored_item.all_notifications();
+
+    // Noise above the 1% deadband but below the floor is suppressed
+    [5e-8, -3e-8, 8e-8, 2e-8].iter().for_each(|v| {
+        set_value(&mut address_space, *v);
+        assert_eq!(tick(&mut monitored_item, &address_space), TickResult::NoChange);
+    });
+
+    // A change beyond the floor is reported
+    set_value(&mut address_space, 5e-7);
+    assert_eq!(tick(&mut monitored_item, &address_space), TickResult::ReportValueChanged);
+}
+
+#[test]
+fn monitored_item_preserves_source_timestamp() {
+    let mut address_space = make_address_space();
+
+    // The device supplied the source timestamp, the server received the value later
+    let source_timestamp = DateTime::ymd_hms(2020, 1, 2, 3, 4, 5);
+    let server_timestamp = DateTime::ymd_hms(2020, 1, 2, 3, 4, 6);
+    let _ = address_space.find_variable_mut(test_var_node_id()).unwrap().set_value_direct(Variant::UInt32(7), StatusCode::Good, &server_timestamp, &source_timestamp);
+
+    let mut monitored_item = MonitoredItem::new(&chrono::Utc::now(), 1, TimestampsToReturn::Both, &make_create_request(-1f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null())).unwrap();
+    let now = Utc::now();
+    assert_eq!(monitored_item.tick(&now, &address_space, true, false), TickResult::ReportValueChanged);
+    let notifications = monitored_item.all_notifications().unwrap();
+    if let Notification::MonitoredItemNotification(ref notification) = notifications[0] {
+        assert_eq!(notification.value.source_timestamp, Some(source_timestamp.clone()));
+        assert_eq!(notification.value.server_timestamp, Some(server_timestamp));
+    } else {
+        panic!();
+    }
+
+    // A getter that only supplies the source timestamp has the server timestamp stamped by the server
+    let getter_source_timestamp = source_timestamp.clone();
+    let getter = AttrFnGetter::new_boxed(move |_: &NodeId, _: AttributeId, _: NumericRange, _: &QualifiedName, _: f64| -> Result<Option<DataValue>, StatusCode> {
+        Ok(Some(DataValue {
+            value: Some(Variant::UInt32(8)),
+            status: Some(StatusCode::Good),
+            source_timestamp: Some(getter_source_timestamp.clone()),
+            source_picoseconds: None,
+            server_timestamp: None,
+            server_picoseconds: None,
+        }))
+    });
+    address_space.find_variable_mut(test_var_node_id()).unwrap().set_value_getter(getter);
+    let now = now + chrono::Duration::seconds(1);
+    assert_eq!(monitored_item.tick(&now, &address_space, true, false), TickResult::ReportValueChanged);
+    let notifications = monitored_item.all_notifications().unwrap();
+    if let Notification::MonitoredItemNotification(ref notification) = notifications[0] {
+        assert_eq!(notification.value.value, Some(Variant::UInt32(8)));
+        assert_eq!(notification.value.source_timestamp, Some(source_timestamp));
+        assert_eq!(notification.value.server_timestamp, Some(DateTime::from(now)));
+    } else {
+        panic!();
+    }
+}
+
+#[test]
+fn monitored_item_negative_sampling_interval() {
+    let address_space = make_address_space();
+    let mut subscription = Subscription::new(Arc::new(RwLock::new(ServerDiagnostics::default())), 1, true, 100f64, 100, 30, 0);
+    let results = subscription.create_monitored_items(&address_space, &Utc::now(), TimestampsToReturn::Both, &[
+        make_create_request(-1f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null()),
+        make_create_request(-5f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null()),
+    ], 0, &MonitoredItemDefaults::default());
+
+    // -1 is kept to mean the publishing interval, other negatives are revised to the default
+    assert_eq!(results[0].status_code, StatusCode::Good);
+    assert_eq!(results[0].revised_sampling_interval, -1f64);
+    assert_eq!(results[1].status_code, StatusCode::Good);
+    assert_eq!(results[1].revised_sampling_interval, crate::constants::DEFAULT_SAMPLING_INTERVAL);
+
+    // -1 samples only when the publishing interval elapses
+    let mut monitored_item = MonitoredItem::new(&chrono::Utc::now(), 1, TimestampsToReturn::Both, &make_create_request(-1f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null())).unwrap();
+    let now = Utc::now();
+    assert_eq!(monitored_item.tick(&now, &address_space, false, false), TickResult::NoChange);
+    assert_eq!(monitored_item.tick(&now, &address_space, true, false), TickResult::ReportValueChanged);
+
+    // -5 samples on its own default interval regardless of the publishing interval
+    let mut monitored_item = MonitoredItem::new(&now, 2, TimestampsToReturn::Both, &make_create_request(-5f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null())).unwrap();
+    assert_eq!(monitored_item.tick(&(now + chrono::Duration::milliseconds(500)), &address_space, true, false), TickResult::NoChange);
+    assert_eq!(monitored_item.tick(&(now + chrono::Duration::milliseconds(1000)), &address_space, false, false), TickResult::ReportValueChanged);
+}
+
+#[test]
+fn monitored_item_data_change_trigger() {
+    let now = Utc::now();
+    let filter = |trigger: DataChangeTrigger| ExtensionObject::from_encodable(ObjectId::DataChangeFilter_Encoding_DefaultBinary, &DataChangeFilter {
+        trigger,
+        deadband_type: DeadbandType::None as u32,
+        deadband_value: 0f64,
+    });
+
+    // Each trigger is accepted and kept for comparison
+    [DataChangeTrigger::Status, DataChangeTrigger::StatusValue, DataChangeTrigger::StatusValueTimestamp].iter().for_each(|trigger| {
+        assert_eq!(DataChangeTrigger::from_u32(*trigger as u32), Ok(*trigger));
+        let monitored_item = MonitoredItem::new(&now, 1, TimestampsToReturn::Both, &make_create_request(-1f64, 5, test_var_node_id(), AttributeId::Value, filter(*trigger))).unwrap();
+        let data_change_filter = monitored_item.snapshot().filter.decode_inner::<DataChangeFilter>(&DecodingLimits::default()).unwrap();
+        assert_eq!(data_change_filter.trigger, *trigger);
+    });
+
+    // An unknown trigger is an invalid filter
+    assert_eq!(DataChangeTrigger::from_u32(3), Err(StatusCode::BadDeadbandFilterInvalid));
+    let mut body = filter(DataChangeTrigger::Status).decode_inner::<DataChangeFilter>(&DecodingLimits::default()).unwrap().encode_to_vec();
+    body[0] = 3;
+    let invalid = ExtensionObject {
+        node_id: ObjectId::DataChangeFilter_Encoding_DefaultBinary.into(),
+        body: ExtensionObjectEncoding::ByteString(ByteString::from(body)),
+    };
+    assert_eq!(MonitoredItem::new(&now, 1, TimestampsToReturn::Both, &make_create_request(-1f64, 5, test_var_node_id(), AttributeId::Value, invalid)).unwrap_err(), StatusCode::BadDeadbandFilterInvalid);
+
+    // So is a filter that cannot be decoded
+    let body = filter(DataChangeTrigger::Status).decode_inner::<DataChangeFilter>(&DecodingLimits::default()).unwrap().encode_to_vec();
+    let truncated = ExtensionObject {
+        node_id: ObjectId::DataChangeFilter_Encoding_DefaultBinary.into(),
+        body: ExtensionObjectEncoding::ByteString(ByteString::from(&body[..body.len() - 1])),
+    };
+    assert_eq!(MonitoredItem::new(&now, 1, TimestampsToReturn::Both, &make_create_request(-1f64, 5, test_var_node_id(), AttributeId::Value, truncated)).unwrap_err(), StatusCode::BadDeadbandFilterInvalid);
+}
+
+#[test]
+fn monitored_item_data_change_filter_attribute() {
+    let filter = || ExtensionObject::from_encodable(ObjectId::DataChangeFilter_Encoding_DefaultBinary, &DataChangeFilter {
+        trigger: DataChangeTrigger::StatusValue,
+        deadband_type: DeadbandType::None as u32,
+        deadband_value: 0f64,
+    });
+    let now = Utc::now();
+
+    // A data change filter is allowed on the value but not on another attribute
+    assert!(MonitoredItem::new(&now, 1, TimestampsToReturn::Both, &make_create_request(-1f64, 5, test_var_node_id(), AttributeId::Value, filter())).is_ok());
+    assert_eq!(MonitoredItem::new(&now, 2, TimestampsToReturn::Both, &make_create_request(-1f64, 5, test_var_node_id(), AttributeId::DisplayName, filter())).unwrap_err(), StatusCode::BadFilterNotAllowed);
+    assert!(MonitoredItem::new(&now, 3, TimestampsToReturn::Both, &make_create_request(-1f64, 5, test_var_node_id(), AttributeId::DisplayName, ExtensionObject::null())).is_ok());
+
+    // Nor can an item on another attribute be modified to have one
+    let address_space = make_address_space();
+    let mut monitored_item = MonitoredItem::new(&now, 3, TimestampsToReturn::Both, &make_create_request(-1f64, 5, test_var_node_id(), AttributeId::DisplayName, ExtensionObject::null())).unwrap();
+    let request = MonitoredItemModifyRequest {
+        monitored_item_id: 3,
+        requested_parameters: MonitoringParameters {
+            client_handle: 999,
+            sampling_interval: -1f64,
+            filter: filter(),
+            queue_size: 5,
+            discard_oldest: true,
+        },
+    };
+    assert_eq!(monitored_item.modify(&address_space, TimestampsToReturn::Both, &request, &MonitoredItemDefaults::default()).unwrap_err(), StatusCode::BadFilterNotAllowed);
+}
+
+#[test]
+fn monitored_item_filter_unsupported_or_not_allowed() {
+    let now = Utc::now();
+    let create = |attribute_id: AttributeId, filter: ExtensionObject| {
+        let node_id = if attribute_id == AttributeId::EventNotifier { test_object_node_id() } else { test_var_node_id() };
+        MonitoredItem::new(&now, 1, TimestampsToReturn::Both, &make_create_request(-1f64, 5, node_id, attribute_id, filter))
+    };
+    let event_filter = || make_create_request_event_filter(-1f64, 5).requested_parameters.filter;
+    let aggregate_filter = || ExtensionObject::from_encodable(ObjectId::AggregateFilter_Encoding_DefaultBinary, &AggregateFilter {
+        start_time: DateTime::null(),
+        aggregate_type: ObjectId::AggregateFunction_Average.into(),
+        processing_interval: 1000f64,
+        aggregate_configuration: AggregateConfiguration {
+            use_server_capabilities_defaults: true,
+            treat_uncertain_as_bad: false,
+            percent_data_bad: 100,
+            percent_data_good: 100,
+            use_sloped_extrapolation: false,
+        },
+    });
+
+    // A filter type the server does not support is unsupported on any attribute
+    let unsupported = || ExtensionObject::from_encodable(ObjectId::ReadValueId_Encoding_DefaultBinary, &ReadValueId::from(test_var_node_id()));
+    assert_eq!(create(AttributeId::Value, unsupported()).unwrap_err(), StatusCode::BadMonitoredItemFilterUnsupported);
+    assert_eq!(create(AttributeId::EventNotifier, unsupported()).unwrap_err(), StatusCode::BadMonitoredItemFilterUnsupported);
+
+    // A supported filter is not allowed on an attribute it does not apply to
+    assert!(create(AttributeId::EventNotifier, event_filter()).is_ok());
+    assert_eq!(create(AttributeId::Value, event_filter()).unwrap_err(), StatusCode::BadFilterNotAllowed);
+    assert!(create(AttributeId::Value, aggregate_filter()).is_ok());
+    assert_eq!(create(AttributeId::EventNotifier, aggregate_filter()).unwrap_err(), StatusCode::BadFilterNotAllowed);
+    assert_eq!(create(AttributeId::DisplayName, aggregate_filter()).unwrap_err(), StatusCode::BadFilterNotAllowed);
+}
+
+#[test]
+fn monitored_item_supported_filter_types() {
+    let supported_filter_types = MonitoredItem::supported_filter_types();
+    // Data change filters are always supported
+    assert!(supported_filter_types.contains(&ObjectId::DataChangeFilter_Encoding_DefaultBinary.into()));
+    assert!(supported_filter_types.contains(&ObjectId::EventFilter_Encoding_DefaultBinary.into()));
+    assert!(supported_filter_types.contains(&ObjectId::AggregateFilter_Encoding_DefaultBinary.into()));
+    assert!(!supported_filter_types.contains(&ObjectId::ReadValueId_Encoding_DefaultBinary.into()));
+}
+
+#[test]
+fn monitored_item_timestamps_to_return() {
+    let now = Utc::now();
+    let timestamps_to_return = |timestamps_to_return: TimestampsToReturn, attribute_id: AttributeId| {
+        let request = make_create_request(-1f64, 5, test_var_node_id(), attribute_id, ExtensionObject::null());
+        MonitoredItem::new(&now, 1, timestamps_to_return, &request).unwrap().timestamps_to_return()
+    };
+
+    // The value preserves whatever was requested
+    assert_eq!(timestamps_to_return(TimestampsToReturn::Source, AttributeId::Value), TimestampsToReturn::Source);
+    assert_eq!(timestamps_to_return(TimestampsToReturn::Both, AttributeId::Value), TimestampsToReturn::Both);
+    assert_eq!(timestamps_to_return(TimestampsToReturn::Server, AttributeId::Value), TimestampsToReturn::Server);
+
+    // Other attributes have no source timestamp
+    assert_eq!(timestamps_to_return(TimestampsToReturn::Source, AttributeId::DisplayName), TimestampsToReturn::Server);
+    assert_eq!(timestamps_to_return(TimestampsToReturn::Both, AttributeId::DisplayName), TimestampsToReturn::Server);
+    assert_eq!(timestamps_to_return(TimestampsToReturn::Server, AttributeId::DisplayName), TimestampsToReturn::Server);
+    assert_eq!(timestamps_to_return(TimestampsToReturn::Neither, AttributeId::DisplayName), TimestampsToReturn::Neither);
+
+    // Modify applies the same rule
+    let address_space = make_address_space();
+    let mut monitored_item = MonitoredItem::new(&now, 1, TimestampsToReturn::Server, &make_create_request(-1f64, 5, test_var_node_id(), AttributeId::DisplayName, ExtensionObject::null())).unwrap();
+    let request = MonitoredItemModifyRequest {
+        monitored_item_id: 1,
+        requested_parameters: make_create_request(-1f64, 5, test_var_node_id(), AttributeId::DisplayName, ExtensionObject::null()).requested_parameters,
+    };
+    assert!(monitored_item.modify(&address_space, TimestampsToReturn::Both, &request, &MonitoredItemDefaults::default()).is_ok());
+    assert_eq!(monitored_item.timestamps_to_return(), TimestampsToReturn::Server);
+}
+
+#[test]
+fn monitored_item_unavailable_attribute() {
+    let address_space = make_address_space();
+    let now = Utc::now();
+
+    let sample = |attribute_id: AttributeId| -> Vec<DataValue> {
+        let mut monitored_item = MonitoredItem::new(&now, 1, TimestampsToReturn::Both, &make_create_request(-1f64, 5, test_var_node_id(), attribute_id, ExtensionObject::null())).unwrap();
+        assert_eq!(monitored_item.tick(&now, &address_space, true, false), TickResult::ReportValueChanged);
+        let notifications = monitored_item.all_notifications().unwrap();
+        // The null value is only reported once
+        assert_eq!(monitored_item.tick(&(now + chrono::Duration::milliseconds(100)), &address_space, true, false), TickResult::NoChange);
+        notifications.into_iter().map(|n| match n {
+            Notification::MonitoredItemNotification(n) => n.value,
+            _ => panic!()
+        }).collect()
+    };
+
+    // A variable supports a description but this one has none
+    let values = sample(AttributeId::Description);
+    assert_eq!(values.len(), 1);
+    assert_eq!(values[0].status, Some(StatusCode::Good));
+    assert!(values[0].value.is_none());
+
+    // A variable does not have an event notifier at all
+    let values = sample(AttributeId::EventNotifier);
+    assert_eq!(values.len(), 1);
+    assert_eq!(values[0].status, Some(StatusCode::BadAttributeIdInvalid));
+    assert!(values[0].value.is_none());
+}
+
+#[test]
+fn monitored_item_decode_create_request() {
+    let encode = |request: &MonitoredItemCreateRequest| -> Vec<u8> {
+        let mut data = Vec::with_capacity(request.byte_len());
+        let _ = request.encode(&mut data).unwrap();
+        data
+    };
+    let decode = |data: &[u8]| MonitoredItem::decode_create_request(data, &DecodingLimits::default());
+
+    // A valid request decodes to the same request
+    let request = make_create_request_data_change_filter(100f64, 5);
+    let data = encode(&request);
+    assert_eq!(decode(&data).unwrap(), request);
+
+    // Truncated or trailing bytes
+    assert!(decode(&data[..data.len() - 1]).is_err());
+    let mut trailing = data.clone();
+    trailing.push(0);
+    assert_eq!(decode(&trailing).unwrap_err(), StatusCode::BadDecodingError);
+
+    // Bad node id
+    let mut request = make_create_request(100f64, 5, NodeId::null(), AttributeId::Value, ExtensionObject::null());
+    assert_eq!(decode(&encode(&request)).unwrap_err(), StatusCode::BadNodeIdInvalid);
+
+    // Bad parameters
+    request.item_to_monitor.node_id = test_var_node_id();
+    request.item_to_monitor.attribute_id = 999;
+    assert_eq!(decode(&encode(&request)).unwrap_err(), StatusCode::BadAttributeIdInvalid);
+    request.item_to_monitor.attribute_id = AttributeId::Value as u32;
+    request.item_to_monitor.index_range = UAString::from("1:x");
+    assert_eq!(decode(&encode(&request)).unwrap_err(), StatusCode::BadIndexRangeInvalid);
+    request.item_to_monitor.index_range = UAString::null();
+    request.requested_parameters.sampling_interval = f64::NAN;
+    assert_eq!(decode(&encode(&request)).unwrap_err(), StatusCode::BadInvalidArgument);
+    request.requested_parameters.sampling_interval = 100f64;
+    assert!(decode(&encode(&request)).is_ok());
+
+    // Bad filters
+    let mut request = make_create_request_data_change_filter(100f64, 5);
+    request.item_to_monitor.attribute_id = AttributeId::DisplayName as u32;
+    assert_eq!(decode(&encode(&request)).unwrap_err(), StatusCode::BadFilterNotAllowed);
+    request.item_to_monitor.attribute_id = AttributeId::Value as u32;
+    request.requested_parameters.filter = ExtensionObject::from_encodable(ObjectId::ReadValueId_Encoding_DefaultBinary, &request.item_to_monitor);
+    assert_eq!(decode(&encode(&request)).unwrap_err(), StatusCode::BadMonitoredItemFilterUnsupported);
+
+    // The service rejects the same requests when creating items
+    let address_space = make_address_space();
+    let mut subscription = Subscription::new(Arc::new(RwLock::new(ServerDiagnostics::default())), 1, true, 100f64, 100, 30, 0);
+    let mut bad_index_range = make_create_request(100f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null());
+    bad_index_range.item_to_monitor.index_range = UAString::from("1:x");
+    let mut bad_sampling_interval = make_create_request(100f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null());
+    bad_sampling_interval.requested_parameters.sampling_interval = f64::NAN;
+    let results = subscription.create_monitored_items(&address_space, &Utc::now(), TimestampsToReturn::Both, &[bad_index_range, bad_sampling_interval], 0, &MonitoredItemDefaults::default());
+    let status_codes = results.iter().map(|r| r.status_code).collect::<Vec<_>>();
+    assert_eq!(status_codes, vec![StatusCode::BadIndexRangeInvalid, StatusCode::BadInvalidArgument]);
+    assert_eq!(subscription.monitored_items_len(), 0);
+}
+
+#[test]
+fn monitored_item_matches() {
+    let monitored_item = MonitoredItem::new(&chrono::Utc::now(), 1, TimestampsToReturn::Both, &make_create_request(-1f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null())).unwrap();
+    assert!(monitored_item.matches(&test_var_node_id(), AttributeId::Value, &NumericRange::None));
+    assert!(monitored_item.matches(&test_var_node_id(), AttributeId::Value, &NumericRange::Index(3)));
+    assert!(!monitored_item.matches(&test_var_node_id(), AttributeId::DisplayName, &NumericRange::None));
+    assert!(!monitored_item.matches(&NodeId::new(1, 2), AttributeId::Value, &NumericRange::None));
+
+    // An item monitoring part of an array only matches writes that overlap its range
+    let mut request = make_create_request(-1f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null());
+    request.item_to_monitor.index_range = UAString::from("2:4");
+    let monitored_item = MonitoredItem::new(&chrono::Utc::now(), 1, TimestampsToReturn::Both, &request).unwrap();
+    assert!(monitored_item.matches(&test_var_node_id(), AttributeId::Value, &NumericRange::None));
+    assert!(monitored_item.matches(&test_var_node_id(), AttributeId::Value, &NumericRange::Index(4)));
+    assert!(monitored_item.matches(&test_var_node_id(), AttributeId::Value, &NumericRange::Range(0, 2)));
+    assert!(!monitored_item.matches(&test_var_node_id(), AttributeId::Value, &NumericRange::Index(5)));
+    assert!(!monitored_item.matches(&test_var_node_id(), AttributeId::Value, &NumericRange::Range(5, 9)));
+    assert!(!monitored_item.matches(&test_var_node_id(), AttributeId::DataType, &NumericRange::Index(3)));
+}
+
+#[test]
+fn monitored_item_push_on_write() {
+    let mut address_space = make_address_space();
+    let now = Utc::now();
+
+    // A long sampling interval means polling alone would not see the write for a minute
+    let mut monitored_item = MonitoredItem::new(&now, 1, TimestampsToReturn::Both, &make_create_request(60000f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null())).unwrap();
+    let mut polled_item = MonitoredItem::new(&now, 2, TimestampsToReturn::Both, &make_create_request(60000f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null())).unwrap();
+    monitored_item.set_push_on_write(true);
+    assert!(monitored_item.on_write(&address_space, &now, &test_var_node_id(), AttributeId::Value, &NumericRange::None));
+    let _ = monitored_item.all_notifications();
+
+    let _ = address_space.set_variable_value(test_var_node_id(), 99u32, &DateTime::now(), &DateTime::now());
+
+    // Writes to other nodes or attributes are ignored
+    assert!(!monitored_item.on_write(&address_space, &now, &NodeId::new(1, 2), AttributeId::Value, &NumericRange::None));
+    assert!(!monitored_item.on_write(&address_space, &now, &test_var_node_id(), AttributeId::DisplayName, &NumericRange::None));
+    assert!(monitored_item.notification_queue().is_empty());
+
+    // A write to the monitored value is queued immediately
+    assert!(monitored_item.on_write(&address_space, &now, &test_var_node_id(), AttributeId::Value, &NumericRange::None));
+    assert_eq!(monitored_item.notification_queue().len(), 1);
+    if let Notification::MonitoredItemNotification(ref notification) = monitored_item.notification_queue()[0] {
+        assert_eq!(notification.value.value, Some(Variant::UInt32(99)));
+    } else {
+        panic!();
+    }
+
+    // An item without push mode waits for its sampling interval
+    assert!(!polled_item.on_write(&address_space, &now, &test_var_node_id(), AttributeId::Value, &NumericRange::None));
+    assert!(polled_item.notification_queue().is_empty());
+
+    // A disabled item ignores writes
+    monitored_item.set_monitoring_mode(MonitoringMode::Disabled);
+    let _ = address_space.set_variable_value(test_var_node_id(), 100u32, &DateTime::now(), &DateTime::now());
+    assert!(!monitored_item.on_write(&address_space, &now, &test_var_node_id(), AttributeId::Value, &NumericRange::None));
+}
+
+#[test]
+fn monitored_item_ignore_deadband() {
+    let mut address_space = make_address_space();
+    let now = Utc::now();
+
+    // Two items with an absolute deadband of 5, one of which ignores it
+    let filter = ExtensionObject::from_encodable(ObjectId::DataChangeFilter_Encoding_DefaultBinary, &DataChangeFilter {
+        trigger: DataChangeTrigger::StatusValue,
+        deadband_type: DeadbandType::Absolute as u32,
+        deadband_value: 5f64,
+    });
+    let mut deadband_item = MonitoredItem::new(&now, 1, TimestampsToReturn::Both, &make_create_request(-1f64, 5, test_var_node_id(), AttributeId::Value, filter.clone())).unwrap();
+    let mut exact_item = MonitoredItem::new(&now, 2, TimestampsToReturn::Both, &make_create_request(-1f64, 5, test_var_node_id(), AttributeId::Value, filter)).unwrap();
+    exact_item.set_ignore_deadband(true);
+    assert_eq!(deadband_item.tick(&now, &address_space, true, false), TickResult::ReportValueChanged);
+    assert_eq!(exact_item.tick(&now, &address_space, true, false), TickResult::ReportValueChanged);
+    let _ = deadband_item.all_notifications();
+    let _ = exact_item.all_notifications();
+
+    // A change within the deadband is only reported by the item that ignores it
+    let _ = address_space.set_variable_value(test_var_node_id(), 2u32, &DateTime::now(), &DateTime::now());
+    let now = now + chrono::Duration::milliseconds(100);
+    assert_eq!(deadband_item.tick(&now, &address_space, true, false), TickResult::NoChange);
+    assert_eq!(exact_item.tick(&now, &address_space, true, false), TickResult::ReportValueChanged);
+    assert_first_notification_is_u32(&mut exact_item, 2);
+
+    // An unchanged value is still not reported
+    let now = now + chrono::Duration::milliseconds(100);
+    assert_eq!(exact_item.tick(&now, &address_space, true, false), TickResult::NoChange);
+
+    // The deadband applies again once the flag is cleared
+    assert!(exact_item.snapshot().ignore_deadband);
+    exact_item.set_ignore_deadband(false);
+    let _ = address_space.set_variable_value(test_var_node_id(), 4u32, &DateTime::now(), &DateTime::now());
+    let now = now + chrono::Duration::milliseconds(100);
+    assert_eq!(exact_item.tick(&now, &address_space, true, false), TickResult::NoChange);
+}
+
+#[test]
+fn monitored_item_strict_type_compare() {
+    let mut address_space = make_address_space();
+    let now = Utc::now();
+    let mut monitored_item = MonitoredItem::new(&now, 1, TimestampsToReturn::Both, &make_create_request(-1f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null())).unwrap();
+
+    let mut tick = |monitored_item: &mut MonitoredItem, value: Variant, i: i64| {
+        let _ = address_space.set_variable_value(test_var_node_id(), value, &DateTime::now(), &DateTime::now());
+        let now = now + chrono::Duration::milliseconds(100 * i);
+        let result = monitored_item.tick(&now, &address_space, true, false);
+        let _ = monitored_item.all_notifications();
+        result
+    };
+
+    assert_eq!(tick(&mut monitored_item, Variant::UInt16(5), 1), TickResult::ReportValueChanged);
+    // The same value read as a different type is not a change
+    assert_eq!(tick(&mut monitored_item, Variant::Int32(5), 2), TickResult::NoChange);
+    assert_eq!(tick(&mut monitored_item, Variant::Int32(6), 3), TickResult::ReportValueChanged);
+
+    // Unless the item compares types strictly
+    monitored_item.set_strict_type_compare(true);
+    assert!(monitored_item.snapshot().strict_type_compare);
+    assert_eq!(tick(&mut monitored_item, Variant::UInt16(6), 4), TickResult::ReportValueChanged);
+    assert_eq!(tick(&mut monitored_item, Variant::UInt16(6), 5), TickResult::NoChange);
+}
+
+#[test]
+fn session_max_queued_notifications() {
+    let mut address_space = make_address_space();
+    let now = Utc::now();
+
+    // Two subscriptions, each with 3 sampling items that can queue 5 notifications apiece
+    let mut subscriptions = Subscriptions::new(100, 30000);
+    subscriptions.set_max_queued_notifications(10);
+    (1..=2).for_each(|subscription_id| {
+        let mut subscription = Subscription::new(Arc::new(RwLock::new(ServerDiagnostics::default())), subscription_id, true, 100f64, 100, 30, 0);
+        let requests = (0..3).map(|_| make_create_request(0f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null())).collect::<Vec<_>>();
+        let results = subscription.create_monitored_items(&address_space, &now, TimestampsToReturn::Both, &requests, 0, &MonitoredItemDefaults::default());
+        let monitored_item_ids = results.iter().map(|r| r.monitored_item_id).collect::<Vec<_>>();
+        let _ = subscription.set_monitoring_modes(MonitoringMode::Sampling, &monitored_item_ids);
+        subscriptions.insert(subscription_id, subscription);
+    });
+
+    // Every tick samples a new value. Nothing is published so the queues only grow
+    for i in 1..=10 {
+        let now = now + chrono::Duration::seconds(i);
+        let timestamp = DateTime::from(now);
+        let _ = address_space.set_variable_value(test_var_node_id(), i as u32, &timestamp, &timestamp);
+        subscriptions.tick(&now, &address_space, TickReason::TickTimerFired).unwrap();
+        assert!(subscriptions.queued_notifications_len() <= 10);
+    }
+
+    // The session cap stops the queues at 10 rather than 30 and the items report the overflow
+    assert_eq!(subscriptions.queued_notifications_len(), 10);
+    assert!(subscriptions.subscriptions().values().any(|s| s.any_queue_overflow()));
+
+    // Lifting the cap lets the item queues fill to their own sizes
+    subscriptions.set_max_queued_notifications(0);
+    for i in 11..=20 {
+        let now = now + chrono::Duration::seconds(i);
+        let timestamp = DateTime::from(now);
+        let _ = address_space.set_variable_value(test_var_node_id(), i as u32, &timestamp, &timestamp);
+        subscriptions.tick(&now, &address_space, TickReason::TickTimerFired).unwrap();
+    }
+    assert_eq!(subscriptions.queued_notifications_len(), 30);
+}
+
+#[test]
+fn monitored_item_validate_attribute() {
+    let address_space = make_address_space();
+    let mut subscription = Subscription::new(Arc::new(RwLock::new(ServerDiagnostics::default())), 1, true, 100f64, 100, 30, 0);
+    let results = subscription.create_monitored_items(&address_space, &Utc::now(), TimestampsToReturn::Both, &[
+        make_create_request(-1f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null()),
+        make_create_request(-1f64, 5, test_var_node_id(), AttributeId::DisplayName, ExtensionObject::null()),
+        make_create_request(-1f64, 5, test_object_node_id(), AttributeId::EventNotifier, ExtensionObject::null()),
+        // Objects have no value and variables do not notify events
+        make_create_request(-1f64, 5, test_object_node_id(), AttributeId::Value, ExtensionObject::null()),
+        make_create_request(-1f64, 5, test_var_node_id(), AttributeId::EventNotifier, ExtensionObject::null()),
+    ], 0, &MonitoredItemDefaults::default());
+    let status_codes = results.iter().map(|r| r.status_code).collect::<Vec<_>>();
+    assert_eq!(status_codes, vec![StatusCode::Good, StatusCode::Good, StatusCode::Good, StatusCode::BadAttributeIdInvalid, StatusCode::BadAttributeIdInvalid]);
+    assert_eq!(subscription.monitored_items_len(), 3);
+
+    assert!(MonitoredItem::is_monitorable_attribute(NodeClass::Method, AttributeId::Executable));
+    assert!(MonitoredItem::is_monitorable_attribute(NodeClass::View, AttributeId::EventNotifier));
+    assert!(MonitoredItem::is_monitorable_attribute(NodeClass::VariableType, AttributeId::Value));
+    assert!(!MonitoredItem::is_monitorable_attribute(NodeClass::VariableType, AttributeId::AccessLevel));
+    assert!(!MonitoredItem::is_monitorable_attribute(NodeClass::DataType, AttributeId::Symmetric));
+}
+
+#[test]
+fn monitored_item_value_not_readable() {
+    let mut address_space = make_address_space();
+    let write_only_node_id = NodeId::new(1, "WriteOnly");
+    VariableBuilder::new(&write_only_node_id, "WriteOnly", "WriteOnly")
+        .data_type(DataTypeId::UInt32)
+        .value(0u32)
+        .access_level(AccessLevel::CURRENT_WRITE)
+        .organized_by(ObjectId::ObjectsFolder)
+        .insert(&mut address_space);
+
+    // The value of a readable node can be monitored, that of a node that is not readable cannot,
+    // though its other attributes can
+    let mut subscription = Subscription::new(Arc::new(RwLock::new(ServerDiagnostics::default())), 1, true, 100f64, 100, 30, 0);
+    let results = subscription.create_monitored_items(&address_space, &Utc::now(), TimestampsToReturn::Both, &[
+        make_create_request(-1f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null()),
+        make_create_request(-1f64, 5, write_only_node_id.clone(), AttributeId::Value, ExtensionObject::null()),
+        make_create_request(-1f64, 5, write_only_node_id, AttributeId::DisplayName, ExtensionObject::null()),
+    ], 0, &MonitoredItemDefaults::default());
+    let status_codes = results.iter().map(|r| r.status_code).collect::<Vec<_>>();
+    assert_eq!(status_codes, vec![StatusCode::Good, StatusCode::BadNotReadable, StatusCode::Good]);
+    assert_eq!(subscription.monitored_items_len(), 2);
+}
+
+#[test]
+fn monitored_item_percent_deadband_uses_eu_range() {
+    let mut address_space = make_address_space();
+
+    // An analog item with an instrument range of 0 to 1000 and an EURange of 0 to 100
+    let var_node_id = NodeId::new(1, "Gauge");
+    VariableBuilder::new(&var_node_id, "Gauge", "Gauge")
+        .data_type(DataTypeId::Double)
+        .value(0f64)
+        .organized_by(ObjectId::ObjectsFolder)
+        .insert(&mut address_space);
+    [("GaugeInstrumentRange", "InstrumentRange", 1000f64), ("GaugeEURange", "EURange", 100f64)].iter().for_each(|(id, name, high)| {
+        let range = ExtensionObject::from_encodable(ObjectId::Range_Encoding_DefaultBinary, &Range { low: 0f64, high: *high });
+        VariableBuilder::new(&NodeId::new(1, *id), *name, *name)
+            .data_type(DataTypeId::Range)
+            .value(range)
+            .property_of(var_node_id.clone())
+            .has_type_definition(VariableTypeId::PropertyType)
+            .insert(&mut address_space);
+    });
+
+    let set_value = |address_space: &mut AddressSpace, v: f64| {
+        let _ = address_space.find_variable_mut(var_node_id.clone()).unwrap().set_value(NumericRange::None, Variant::Double(v));
+    };
+
+    // A 10% deadband is a change of 10 over the EURange, or 100 if the instrument range were used
+    let filter = ExtensionObject::from_encodable(ObjectId::DataChangeFilter_Encoding_DefaultBinary, &DataChangeFilter {
+        trigger: DataChangeTrigger::StatusValue,
+        deadband_type: DeadbandType::Percent as u32,
+        deadband_value: 10f64,
+    });
+    let mut monitored_item = MonitoredItem::new(&chrono::Utc::now(), 1, TimestampsToReturn::Both, &make_create_request(0f64, 5, var_node_id.clone(), AttributeId::Value, filter)).unwrap();
+
+    let mut now = Utc::now();
+    let mut tick = |monitored_item: &mut MonitoredItem, address_space: &AddressSpace| {
+        now = now + chrono::Duration::seconds(1);
+        monitored_item.tick(&now, address_space, false, false)
+    };
+
+    assert_eq!(tick(&mut monitored_item, &address_space), TickResult::ReportValueChanged);
+    let _ = monitored_item.all_notifications();
+
+    set_value(&mut address_space, 5f64);
+    assert_eq!(tick(&mut monitored_item, &address_space), TickResult::NoChange);
+    set_value(&mut address_space, 50f64);
+    assert_eq!(tick(&mut monitored_item, &address_space), TickResult::ReportValueChanged);
+}
+
+#[test]
+fn monitored_item_filter_from_extension_object() {
+    assert_eq!(FilterType::from_extension_object(&ExtensionObject::null()).unwrap(), FilterType::None);
+
+    let data_change_filter = DataChangeFilter {
+        trigger: DataChangeTrigger::StatusValue,
+        deadband_type: DeadbandType::Absolute as u32,
+        deadband_value: 2f64,
+    };
+    let filter = ExtensionObject::from_encodable(ObjectId::DataChangeFilter_Encoding_DefaultBinary, &data_change_filter);
+    assert_eq!(FilterType::from_extension_object(&filter).unwrap(), FilterType::DataChangeFilter(data_change_filter.clone()));
+
+    let event_filter = EventFilter {
+        where_clause: ContentFilter { elements: None },
+        select_clauses: Some(vec![SimpleAttributeOperand::new(ObjectTypeId::BaseEventType, "EventId", AttributeId::Value, UAString::null())]),
+    };
+    let filter = ExtensionObject::from_encodable(ObjectId::EventFilter_Encoding_DefaultBinary, &event_filter);
+    assert_eq!(FilterType::from_extension_object(&filter).unwrap(), FilterType::EventFilter(event_filter));
+
+    let aggregate_filter = AggregateFilter {
+        start_time: DateTime::ymd_hms(2020, 1, 2, 3, 4, 5),
+        aggregate_type: ObjectId::AggregateFunction_Average.into(),
+        processing_interval: 1000f64,
+        aggregate_configuration: AggregateConfiguration {
+            use_server_capabilities_defaults: true,
+            treat_uncertain_as_bad: false,
+            percent_data_bad: 100,
+            percent_data_good: 100,
+            use_sloped_extrapolation: false,
+        },
+    };
+    let filter = ExtensionObject::from_encodable(ObjectId::AggregateFilter_Encoding_DefaultBinary, &aggregate_filter);
+    assert_eq!(FilterType::from_extension_object(&filter).unwrap(), FilterType::AggregateFilter(aggregate_filter.clone()));
+
+    // Aggregates the server does not compute are rejected
+    let address_space = make_address_space();
+    let mut subscription = Subscription::new(Arc::new(RwLock::new(ServerDiagnostics::default())), 1, true, 100f64, 100, 30, 0);
+    let filter = ExtensionObject::from_encodable(ObjectId::AggregateFilter_Encoding_DefaultBinary, &AggregateFilter {
+        aggregate_type: ObjectId::AggregateFunction_Interpolative.into(),
+        ..aggregate_filter
+    });
+    let results = subscription.create_monitored_items(&address_space, &Utc::now(), TimestampsToReturn::Both, &[
+        make_create_request(-1f64, 5, test_var_node_id(), AttributeId::Value, filter),
+    ], 0, &MonitoredItemDefaults::default());
+    assert_eq!(results[0].status_code, StatusCode::BadAggregateNotSupported);
+
+    // Type ids that are not monitoring filters are unsupported
+    let filter = ExtensionObject::from_encodable(ObjectId::ReadValueId_Encoding_DefaultBinary, &ReadValueId::from(test_var_node_id()));
+    assert_eq!(FilterType::from_extension_object(&filter).unwrap_err(), StatusCode::BadMonitoredItemFilterUnsupported);
+    let filter = ExtensionObject::from_encodable(NodeId::new(1, "MyFilter"), &data_change_filter);
+    assert_eq!(FilterType::from_extension_object(&filter).unwrap_err(), StatusCode::BadMonitoredItemFilterUnsupported);
+}
+
+#[test]
+fn monitored_item_aggregate_filter() {
+    let mut address_space = make_address_space();
+
+    let var_node_id = NodeId::new(1, "Flow");
+    VariableBuilder::new(&var_node_id, "Flow", "Flow")
+        .data_type(DataTypeId::Double)
+        .value(0f64)
+        .organized_by(ObjectId::ObjectsFolder)
+        .insert(&mut address_space);
+
+    // Samples are taken every second and aggregated over intervals of 3 seconds
+    let start_time = DateTime::ymd_hms(2020, 1, 2, 3, 4, 5);
+    let samples = [1f64, 2f64, 6f64, 10f64, 4f64, 7f64, 0f64];
+
+    let mut aggregate = |aggregate_type: ObjectId| -> Vec<(DateTime, f64)> {
+        let filter = ExtensionObject::from_encodable(ObjectId::AggregateFilter_Encoding_DefaultBinary, &AggregateFilter {
+            start_time: start_time.clone(),
+            aggregate_type: aggregate_type.into(),
+            processing_interval: 3000f64,
+            aggregate_configuration: AggregateConfiguration {
+                use_server_capabilities_defaults: true,
+                treat_uncertain_as_bad: false,
+                percent_data_bad: 100,
+                percent_data_good: 100,
+                use_sloped_extrapolation: false,
+            },
+        });
+        let mut now = start_time.as_chrono();
+        let mut monitored_item = MonitoredItem::new(&(now - chrono::Duration::seconds(1)), 1, TimestampsToReturn::Both, &make_create_request(0f64, 10, var_node_id.clone(), AttributeId::Value, filter)).unwrap();
+        let filter_result = monitored_item.validate_filter(&address_space).unwrap();
+        assert_eq!(filter_result.node_id, ObjectId::AggregateFilterResult_Encoding_DefaultBinary.into());
+
+        samples.iter().for_each(|sample| {
+            let _ = address_space.find_variable_mut(var_node_id.clone()).unwrap().set_value(NumericRange::None, Variant::Double(*sample));
+            let _ = monitored_item.tick(&now, &address_space, false, false);
+            now = now + chrono::Duration::seconds(1);
+        });
+
+        monitored_item.all_notifications().unwrap().into_iter().map(|n| {
+            if let Notification::MonitoredItemNotification(n) = n {
+                assert_eq!(n.value.status, Some(StatusCode::Good));
+                let value = n.value.value.unwrap().as_f64().unwrap();
+                (n.value.source_timestamp.unwrap(), value)
+            } else {
+                panic!("Expected a data change");
+            }
+        }).collect()
+    };
+
+    // One value is reported for each interval that has elapsed, stamped with the interval start
+    let second_interval = DateTime::from(start_time.as_chrono() + chrono::Duration::seconds(3));
+    assert_eq!(aggregate(ObjectId::AggregateFunction_Average), vec![(start_time.clone(), 3f64), (second_interval.clone(), 7f64)]);
+    assert_eq!(aggregate(ObjectId::AggregateFunction_Minimum), vec![(start_time.clone(), 1f64), (second_interval.clone(), 4f64)]);
+    assert_eq!(aggregate(ObjectId::AggregateFunction_Maximum), vec![(start_time.clone(), 6f64), (second_interval, 10f64)]);
+}
+
+#[test]
+fn monitored_item_aggregate_filter_skipped_intervals() {
+    let mut address_space = make_address_space();
+
+    let var_node_id = NodeId::new(1, "Flow");
+    VariableBuilder::new(&var_node_id, "Flow", "Flow")
+        .data_type(DataTypeId::Double)
+        .value(0f64)
+        .organized_by(ObjectId::ObjectsFolder)
+        .insert(&mut address_space);
+
+    let start_time = DateTime::ymd_hms(2020, 1, 2, 3, 4, 5);
+    let filter = ExtensionObject::from_encodable(ObjectId::AggregateFilter_Encoding_DefaultBinary, &AggregateFilter {
+        start_time: start_time.clone(),
+        aggregate_type: ObjectId::AggregateFunction_Average.into(),
+        processing_interval: 3000f64,
+        aggregate_configuration: AggregateConfiguration {
+            use_server_capabilities_defaults: true,
+            treat_uncertain_as_bad: false,
+            percent_data_bad: 100,
+            percent_data_good: 100,
+            use_sloped_extrapolation: false,
+        },
+    });
+    let now = start_time.as_chrono();
+    let mut monitored_item = MonitoredItem::new(&(now - chrono::Duration::seconds(1)), 1, TimestampsToReturn::Both, &make_create_request(0f64, 3, var_node_id.clone(), AttributeId::Value, filter)).unwrap();
+    assert!(monitored_item.validate_filter(&address_space).is_ok());
+
+    let mut tick = |monitored_item: &mut MonitoredItem, seconds: i64, sample: f64| {
+        let _ = address_space.find_variable_mut(var_node_id.clone()).unwrap().set_value(NumericRange::None, Variant::Double(sample));
+        let _ = monitored_item.tick(&(now + chrono::Duration::seconds(seconds)), &address_space, false, false);
+    };
+
+    let interval = |n: i64| DateTime::from(now + chrono::Duration::seconds(n * 3));
+    let statuses = |monitored_item: &mut MonitoredItem| -> Vec<(DateTime, Option<StatusCode>)> {
+        monitored_item.all_notifications().unwrap().into_iter().map(|n| {
+            if let Notification::MonitoredItemNotification(n) = n {
+                (n.value.source_timestamp.unwrap(), n.value.status)
+            } else {
+                panic!("Expected a data change");
+            }
+        }).collect()
+    };
+
+    tick(&mut monitored_item, 0, 2f64);
+    tick(&mut monitored_item, 3, 4f64);
+    assert_eq!(statuses(&mut monitored_item), vec![(interval(0), Some(StatusCode::Good))]);
+
+    // The intervals that elapsed between two samples are reported as having no data
+    tick(&mut monitored_item, 12, 6f64);
+    assert_eq!(statuses(&mut monitored_item), vec![
+        (interval(1), Some(StatusCode::Good)),
+        (interval(2), Some(StatusCode::BadNoData)),
+        (interval(3), Some(StatusCode::BadNoData)),
+    ]);
+
+    // Only the most recent skipped intervals that fit in the queue are reported
+    tick(&mut monitored_item, 42, 8f64);
+    assert_eq!(statuses(&mut monitored_item), vec![
+        (interval(4), Some(StatusCode::Good)),
+        (interval(12), Some(StatusCode::BadNoData)),
+        (interval(13), Some(StatusCode::BadNoData)),
+    ]);
+}
+
+#[test]
+fn monitored_item_stagger_first_samples() {
+    let address_space = make_address_space();
+    let now = Utc::now();
+
+    // Returns the tick, in tenths of the sampling interval, on which each item first samples
+    let first_samples = |stagger_first_samples: bool| -> Vec<usize> {
+        let defaults = MonitoredItemDefaults {
+            stagger_first_samples,
+            ..Default::default()
+        };
+        (1..=10).map(|monitored_item_id| {
+            let mut monitored_item = MonitoredItem::new_with_defaults(&now, monitored_item_id, TimestampsToReturn::Both, &make_create_request(1000f64, 5, test_var_node_id(), AttributeId::Value, ExtensionObject::null()), &defaults).unwrap();
+            (1..=10).find(|i| {
+                let now = now + chrono::Duration::milliseconds(*i as i64 * 100);
+                monitored_item.tick(&now, &address_space, false, false) == TickResult::ReportValueChanged
+            }).unwrap()
+        }).collect()
+    };
+
+    // Without staggering every item samples when the first interval has elapsed
+    assert!(first_samples(false).iter().all(|i| *i == 10));
+
+    // Staggered items sample at different points within the first interval
+    let staggered = first_samples(true);
+    let distinct = staggered.iter().collect::<HashSet<_>>();
+    assert!(distinct.len() > 5);
+    assert!(staggered.iter().all(|i| *i <= 10));
+
+    // The offsets depend only on the item ids
+    assert_eq!(first_samples(true), staggered);
+}
+
+#[test]
+fn monitored_item_simulation_clock() {
+    // Notifications are stamped with the time of the server's clock, not the wall clock
+    do_subscription_service_test(|server_state, session, address_space, ss: SubscriptionService, mis: MonitoredItemService| {
+        let start = Utc.ymd(2001, 1, 1).and_hms(0, 0, 0);
+        let clock = Arc::new(SimulationClock::new(start));
+        {
+            let mut server_state = trace_write_lock_unwrap!(server_state);
+            server_state.set_clock(clock.clone());
+            assert_eq!(server_state.now(), start);
+        }
+
+        // A getter that supplies a new value each time but leaves the server timestamp to the server
+        let node_id = NodeId::new(1, var_name(1));
+        {
+            let mut value = 0u32;
+            let getter = AttrFnGetter::new_boxed(move |_: &NodeId, _: AttributeId, _: NumericRange, _: &QualifiedName, _: f64| -> Result<Option<DataValue>, StatusCode> {
+                value += 1;
+                Ok(Some(DataValue {
+                    value: Some(Variant::UInt32(value)),
+                    status: Some(StatusCode::Good),
+                    source_timestamp: None,
+                    source_picoseconds: None,
+                    server_timestamp: None,
+                    server_picoseconds: None,
+                }))
+            });
+            let mut address_space = trace_write_lock_unwrap!(address_space);
+            address_space.find_variable_mut(node_id.clone()).unwrap().set_value_getter(getter);
+        }
+
+        let subscription_id = {
+            let request = create_subscription_request(0, 0);
+            let response: CreateSubscriptionResponse = supported_message_as!(ss.create_subscription(server_state.clone(), session.clone(), &request), CreateSubscriptionResponse);
+            response.subscription_id
+        };
+
+        // The queue holds the initial value and the first sampled value
+        let mut request = create_monitored_items_request(subscription_id, vec![node_id]);
+        request.items_to_create.as_mut().unwrap()[0].requested_parameters.queue_size = 2;
+        let response: CreateMonitoredItemsResponse = supported_message_as!(mis.create_monitored_items(server_state.clone(), session.clone(), address_space.clone(), &request), CreateMonitoredItemsResponse);
+        assert_eq!(response.results.unwrap()[0].status_code, StatusCode::Good);
+
+        // Tick the subscription with the time from the server's clock, as the server does
+        let tick = |duration: chrono::Duration| -> Vec<DateTime> {
+            publish_request(&clock.now(), session.clone(), address_space.clone(), &ss);
+            clock.advance(duration);
+            {
+                let now = trace_read_lock_unwrap!(server_state).now();
+                let mut session = trace_write_lock_unwrap!(session);
+                let address_space = trace_read_lock_unwrap!(address_space);
+                let _ = session.tick_subscriptions(&now, &address_space, TickReason::TickTimerFired);
+            }
+            let response = publish_response(session.clone());
+            let (notifications, _) = response.notification_message.notifications(&DecodingLimits::default()).unwrap();
+            notifications[0].monitored_items.as_ref().unwrap().iter().map(|n| n.value.server_timestamp.clone().unwrap()).collect()
+        };
+
+        // The initial value is stamped with the simulated time the item was created, the value
+        // sampled by the tick with the simulated time of the tick
+        assert_eq!(tick(chrono::Duration::seconds(2)), vec![
+            DateTime::from(start),
+            DateTime::from(start + chrono::Duration::seconds(2)),
+        ]);
+        assert_eq!(tick(chrono::Duration::seconds(3)), vec![
+            DateTime::from(start + chrono::Duration::seconds(5)),
+        ]);
+    });
+}
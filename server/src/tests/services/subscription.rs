@@ -5,11 +5,16 @@ use chrono::Utc;
 use crate::{
     prelude::*,
     services::{
+        attribute::AttributeService,
         monitored_item::MonitoredItemService,
+        session::SessionService,
         subscription::SubscriptionService,
     },
     state::ServerState,
-    subscriptions::subscription::*,
+    subscriptions::{
+        monitored_item::MonitoredItemDefaults,
+        subscription::*,
+    },
 };
 
 use super::*;
@@ -320,4 +325,248 @@ fn republish() {
         let response: ServiceFault = supported_message_as!(ss.republish(session.clone(), &request), ServiceFault);
         assert_eq!(response.response_header.service_result, StatusCode::BadMessageNotAvailable);
     })
-}
\ No newline at end of file
+}
+
+#[test]
+fn close_session_deletes_subscriptions() {
+    do_subscription_service_test(|server_state, session, address_space, ss, mis| {
+        // Create a subscription with a monitored item
+        let subscription_id = create_subscription(server_state.clone(), session.clone(), &ss);
+        create_monitored_item(subscription_id, var_node_id(1), server_state.clone(), session.clone(), address_space.clone(), &mis);
+
+        // Add a notification to the retransmission queue and a subscription owned by a different session
+        let other_subscription_id = subscription_id + 100;
+        {
+            let mut session = trace_write_lock_unwrap!(session);
+            let session_id = session.session_id().clone();
+            let subscriptions = session.subscriptions_mut();
+            let subscription = subscriptions.get_mut(subscription_id).unwrap();
+            assert_eq!(subscription.session_id(), &session_id);
+            assert_eq!(subscription.monitored_items_len(), 1);
+
+            let notification = NotificationMessage::data_change(1, DateTime::now(), vec![MonitoredItemNotification {
+                client_handle: 1,
+                value: Variant::Empty.into(),
+            }], vec![]);
+            subscriptions.retransmission_queue().insert((subscription_id, notification.sequence_number), notification);
+
+            let diagnostics = trace_read_lock_unwrap!(server_state).diagnostics.clone();
+            let mut other_subscription = Subscription::new(diagnostics, other_subscription_id, true, 100f64, 100, 30, 0);
+            other_subscription.set_session_id(NodeId::new(1, "OtherSession"));
+            subscriptions.insert(other_subscription_id, other_subscription);
+        }
+
+        // Close the session
+        let request = CloseSessionRequest {
+            request_header: RequestHeader::dummy(),
+            delete_subscriptions: true,
+        };
+        let _: CloseSessionResponse = supported_message_as!(SessionService::new().close_session(server_state.clone(), session.clone(), address_space.clone(), &request), CloseSessionResponse);
+
+        // The session's subscription, its monitored items and queued notifications are gone
+        let mut session = trace_write_lock_unwrap!(session);
+        let subscriptions = session.subscriptions_mut();
+        assert!(!subscriptions.contains(subscription_id));
+        assert!(subscriptions.retransmission_queue().is_empty());
+        // A subscription belonging to another session is untouched
+        assert!(subscriptions.contains(other_subscription_id));
+        assert_eq!(subscriptions.len(), 1);
+    })
+}
+
+#[test]
+fn close_session_keeps_subscriptions() {
+    do_subscription_service_test(|server_state, session, address_space, ss, mis| {
+        // Create a subscription with a monitored item
+        let subscription_id = create_subscription(server_state.clone(), session.clone(), &ss);
+        create_monitored_item(subscription_id, var_node_id(1), server_state.clone(), session.clone(), address_space.clone(), &mis);
+
+        // Close the session without deleting subscriptions
+        let request = CloseSessionRequest {
+            request_header: RequestHeader::dummy(),
+            delete_subscriptions: false,
+        };
+        let _: CloseSessionResponse = supported_message_as!(SessionService::new().close_session(server_state.clone(), session.clone(), address_space.clone(), &request), CloseSessionResponse);
+
+        // The subscription and its monitored item are still there
+        let mut session = trace_write_lock_unwrap!(session);
+        let subscriptions = session.subscriptions_mut();
+        assert!(subscriptions.contains(subscription_id));
+        assert_eq!(subscriptions.get_mut(subscription_id).unwrap().monitored_items_len(), 1);
+    })
+}
+
+#[test]
+fn transfer_subscription() {
+    do_subscription_service_test(|server_state, session, address_space, ss, mis| {
+        // Create a subscription with a monitored item. The item's initial value is queued on it.
+        let subscription_id = create_subscription(server_state.clone(), session.clone(), &ss);
+        create_monitored_item(subscription_id, var_node_id(1), server_state.clone(), session.clone(), address_space.clone(), &mis);
+
+        // A notification the client has not acknowledged yet
+        let unacknowledged = NotificationMessage::data_change(1, DateTime::now(), vec![MonitoredItemNotification {
+            client_handle: 1,
+            value: Variant::Empty.into(),
+        }], vec![]);
+        {
+            let mut session = trace_write_lock_unwrap!(session);
+            let subscriptions = session.subscriptions_mut();
+            assert_eq!(subscriptions.get_mut(subscription_id).unwrap().monitored_item_notifications_len(1), Some(1));
+            subscriptions.retransmission_queue().insert((subscription_id, unacknowledged.sequence_number), unacknowledged.clone());
+        }
+
+        // Transfer it to another session
+        let secure_channel: SecureChannel = (SecurityPolicy::None, MessageSecurityMode::None).into();
+        let session2 = Arc::new(RwLock::new(Session::new_no_certificate_store(secure_channel)));
+        {
+            let mut session = trace_write_lock_unwrap!(session);
+            let mut session2 = trace_write_lock_unwrap!(session2);
+            assert_eq!(session.transfer_subscription(subscription_id, &mut session2), StatusCode::Good);
+            // Transferring again fails because the first session no longer has it
+            assert_eq!(session.transfer_subscription(subscription_id, &mut session2), StatusCode::BadSubscriptionIdInvalid);
+
+            let subscriptions = session.subscriptions_mut();
+            assert!(!subscriptions.contains(subscription_id));
+            assert!(subscriptions.retransmission_queue().is_empty());
+
+            // The new session owns the subscription, its queued notification and the unacknowledged one
+            let session2_id = session2.session_id().clone();
+            let subscriptions = session2.subscriptions_mut();
+            let subscription = subscriptions.get_mut(subscription_id).unwrap();
+            assert_eq!(subscription.session_id(), &session2_id);
+            assert_eq!(subscription.monitored_item_notifications_len(1), Some(1));
+            subscription.set_state(SubscriptionState::Normal);
+            assert_eq!(subscriptions.retransmission_queue().get(&(subscription_id, unacknowledged.sequence_number)), Some(&unacknowledged));
+        }
+
+        // A publish on the new session delivers the notification queued before the transfer
+        let now = Utc::now();
+        let _ = ss.async_publish(&now, session2.clone(), address_space.clone(), 1001, &publish_request(None));
+        let mut session2 = trace_write_lock_unwrap!(session2);
+        {
+            let address_space = trace_read_lock_unwrap!(address_space);
+            let _ = session2.tick_subscriptions(&now.add(chrono::Duration::seconds(2)), &address_space, TickReason::TickTimerFired);
+        }
+        let response = session2.subscriptions_mut().publish_response_queue().pop_back().unwrap().response;
+        let response: PublishResponse = supported_message_as!(response, PublishResponse);
+        assert_eq!(response.subscription_id, subscription_id);
+
+        let notification_data = response.notification_message.notification_data.unwrap();
+        let data_change = notification_data[0].decode_inner::<DataChangeNotification>(&DecodingLimits::default()).unwrap();
+        let monitored_items = data_change.monitored_items.unwrap();
+        assert_eq!(monitored_items.len(), 1);
+        assert_eq!(monitored_items[0].value.value, Some(Variant::Int32(1)));
+    })
+}
+
+#[test]
+fn transfer_subscription_after_close_session() {
+    for delete_subscriptions in &[false, true] {
+        do_subscription_service_test(|server_state, session, address_space, ss, mis| {
+            // Create a subscription with a monitored item. The item's initial value is queued on it.
+            let subscription_id = create_subscription(server_state.clone(), session.clone(), &ss);
+            create_monitored_item(subscription_id, var_node_id(1), server_state.clone(), session.clone(), address_space.clone(), &mis);
+
+            // Close the session
+            let request = CloseSessionRequest {
+                request_header: RequestHeader::dummy(),
+                delete_subscriptions: *delete_subscriptions,
+            };
+            let _: CloseSessionResponse = supported_message_as!(SessionService::new().close_session(server_state.clone(), session.clone(), address_space.clone(), &request), CloseSessionResponse);
+
+            // The subscription can only be transferred to another session if the close kept it
+            let secure_channel: SecureChannel = (SecurityPolicy::None, MessageSecurityMode::None).into();
+            let session2 = Arc::new(RwLock::new(Session::new_no_certificate_store(secure_channel)));
+            let mut session = trace_write_lock_unwrap!(session);
+            let mut session2 = trace_write_lock_unwrap!(session2);
+            if *delete_subscriptions {
+                assert_eq!(session.transfer_subscription(subscription_id, &mut session2), StatusCode::BadSubscriptionIdInvalid);
+                assert!(!session2.subscriptions_mut().contains(subscription_id));
+            } else {
+                assert_eq!(session.transfer_subscription(subscription_id, &mut session2), StatusCode::Good);
+                // The queued initial value travels with the subscription
+                let subscription = session2.subscriptions_mut().get_mut(subscription_id).unwrap();
+                assert_eq!(subscription.monitored_item_notifications_len(1), Some(1));
+            }
+        });
+    }
+}
+
+#[test]
+fn push_on_write_reaches_every_session() {
+    do_subscription_service_test(|server_state, session, address_space, _, _| {
+        let node_id = var_node_id(1);
+        {
+            let mut address_space = trace_write_lock_unwrap!(address_space);
+            let node = address_space.find_node_mut(&node_id).unwrap();
+            let _ = node.as_mut_node().set_attribute(AttributeId::AccessLevel, Variant::from((AccessLevel::CURRENT_READ | AccessLevel::CURRENT_WRITE).bits())).unwrap();
+            let _ = node.as_mut_node().set_attribute(AttributeId::UserAccessLevel, Variant::from((UserAccessLevel::CURRENT_READ | UserAccessLevel::CURRENT_WRITE).bits())).unwrap();
+        }
+
+        // Another session monitors the node in push mode. The item samples once a minute so only
+        // a write makes it sample again.
+        let secure_channel: SecureChannel = (SecurityPolicy::None, MessageSecurityMode::None).into();
+        let session2 = Arc::new(RwLock::new(Session::new_no_certificate_store(secure_channel)));
+        let subscription_id = 1;
+        let now = Utc::now();
+        let monitored_item_id = {
+            let address_space = trace_read_lock_unwrap!(address_space);
+            let diagnostics = trace_read_lock_unwrap!(server_state).diagnostics.clone();
+            let mut subscription = Subscription::new(diagnostics, subscription_id, true, 100f64, 100, 30, 0);
+            let mut item_to_create = create_monitored_items_request(subscription_id, vec![node_id.clone()]).items_to_create.unwrap().remove(0);
+            item_to_create.requested_parameters.sampling_interval = 60000f64;
+            item_to_create.requested_parameters.queue_size = 10;
+            let results = subscription.create_monitored_items(&address_space, &now, TimestampsToReturn::Both, &[item_to_create], 0, &MonitoredItemDefaults::default());
+            let monitored_item_id = results[0].monitored_item_id;
+            assert_eq!(subscription.set_push_on_write(monitored_item_id, true), StatusCode::Good);
+
+            let mut session2 = trace_write_lock_unwrap!(session2);
+            let subscriptions = session2.subscriptions_mut();
+            subscriptions.insert(subscription_id, subscription);
+            // Catch up with writes made while the server was set up
+            let _ = subscriptions.process_writes(&address_space, &now);
+            monitored_item_id
+        };
+        let notifications_len = || {
+            let mut session2 = trace_write_lock_unwrap!(session2);
+            session2.subscriptions_mut().get_mut(subscription_id).unwrap().monitored_item_notifications_len(monitored_item_id).unwrap()
+        };
+        // The initial value
+        assert_eq!(notifications_len(), 1);
+
+        // The first session writes the node through the Write service
+        let request = WriteRequest {
+            request_header: RequestHeader::dummy(),
+            nodes_to_write: Some(vec![WriteValue {
+                node_id: node_id.clone(),
+                attribute_id: AttributeId::Value as u32,
+                index_range: UAString::null(),
+                value: DataValue::new_now(100i32),
+            }]),
+        };
+        let response: WriteResponse = supported_message_as!(AttributeService::new().write(server_state.clone(), session.clone(), address_space.clone(), &request), WriteResponse);
+        assert_eq!(response.results.unwrap()[0], StatusCode::Good);
+
+        // The other session's item samples the write when its subscriptions next tick
+        {
+            let address_space = trace_read_lock_unwrap!(address_space);
+            let mut session2 = trace_write_lock_unwrap!(session2);
+            assert_eq!(session2.subscriptions_mut().process_writes(&address_space, &now), 1);
+        }
+        assert_eq!(notifications_len(), 2);
+
+        // A value set on the address space directly is seen too
+        {
+            let mut address_space = trace_write_lock_unwrap!(address_space);
+            assert!(address_space.set_variable_value(node_id.clone(), 200i32, &DateTime::now(), &DateTime::now()));
+            let mut session2 = trace_write_lock_unwrap!(session2);
+            assert_eq!(session2.subscriptions_mut().process_writes(&address_space, &now), 1);
+        }
+        assert_eq!(notifications_len(), 3);
+
+        // Writes are only passed on once
+        let address_space = trace_read_lock_unwrap!(address_space);
+        let mut session2 = trace_write_lock_unwrap!(session2);
+        assert_eq!(session2.subscriptions_mut().process_writes(&address_space, &now), 0);
+    })
+}
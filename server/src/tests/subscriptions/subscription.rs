@@ -1,6 +1,14 @@
 use std::sync::{Arc, RwLock};
 
+use chrono::Utc;
+
+use opcua_types::{
+    DecodingLimits, status_code::StatusCode,
+    service_types::StatusChangeNotification,
+};
+
 use crate::{
+    address_space::AddressSpace,
     subscriptions::subscription::{Subscription, SubscriptionState, SubscriptionStateParams, TickReason, HandledState, UpdateStateAction},
     diagnostics::ServerDiagnostics,
 };
@@ -307,11 +315,14 @@ fn update_state_13() {
         publishing_timer_expired: false,
     };
 
+    s.set_current_lifetime_count(10);
+
     let update_state_result = s.update_state(tick_reason, p);
 
     assert_eq!(update_state_result.handled_state, HandledState::KeepAlive13);
     assert_eq!(update_state_result.update_state_action, UpdateStateAction::None);
     assert_eq!(s.state(), SubscriptionState::KeepAlive);
+    assert_eq!(s.lifetime_counter(), s.max_lifetime_count());
 }
 
 #[test]
@@ -435,3 +446,57 @@ fn update_state_27() {
     assert_eq!(s.lifetime_counter(), 1);
     assert_eq!(s.message_sent(), false);
 }
+
+#[test]
+fn keep_alive_counter_limit() {
+    // A subscription with publish requests but nothing to report sends a keep alive every
+    // keep alive count publishing intervals and its lifetime never runs out
+    let address_space = AddressSpace::new();
+    let mut s = Subscription::new(Arc::new(RwLock::new(ServerDiagnostics::default())), 1, true, 1000f64, 5, 3, 0);
+    let start = Utc::now();
+    s.set_publishing_interval_start(&start);
+    s.tick(&start, &address_space, TickReason::TickTimerFired, true);
+    assert_eq!(s.state(), SubscriptionState::Normal);
+
+    let mut keep_alives = Vec::new();
+    for i in 1..=11 {
+        let now = start + chrono::Duration::seconds(i);
+        s.tick(&now, &address_space, TickReason::ReceivePublishRequest, true);
+        s.tick(&now, &address_space, TickReason::TickTimerFired, true);
+        while let Some(notification) = s.take_notification() {
+            assert!(notification.notification_data.is_none());
+            keep_alives.push(i);
+        }
+        assert_ne!(s.state(), SubscriptionState::Closed);
+    }
+    assert_eq!(keep_alives, vec![1, 5, 8, 11]);
+    assert_eq!(s.lifetime_counter(), 4);
+}
+
+#[test]
+fn lifetime_counter_limit() {
+    // A subscription without publish requests counts down its lifetime each publishing interval
+    // and closes with a status change when it runs out
+    let address_space = AddressSpace::new();
+    let mut s = Subscription::new(Arc::new(RwLock::new(ServerDiagnostics::default())), 1, true, 1000f64, 5, 3, 0);
+    let start = Utc::now();
+    s.set_publishing_interval_start(&start);
+    s.tick(&start, &address_space, TickReason::TickTimerFired, false);
+    assert_eq!(s.state(), SubscriptionState::Normal);
+
+    for i in 1..=4 {
+        let now = start + chrono::Duration::seconds(i);
+        s.tick(&now, &address_space, TickReason::TickTimerFired, false);
+        assert_eq!(s.state(), SubscriptionState::Late);
+        assert_eq!(s.lifetime_counter(), 5 - i as u32);
+        assert!(s.take_notification().is_none());
+    }
+
+    let now = start + chrono::Duration::seconds(5);
+    s.tick(&now, &address_space, TickReason::TickTimerFired, false);
+    assert_eq!(s.state(), SubscriptionState::Closed);
+    let notification = s.take_notification().unwrap();
+    let notification_data = notification.notification_data.unwrap();
+    let status_change = notification_data[0].decode_inner::<StatusChangeNotification>(&DecodingLimits::default()).unwrap();
+    assert_eq!(status_change.status, StatusCode::BadTimeout);
+}
@@ -59,7 +59,7 @@ _.each(basic_types_import_map, (types, module) => {
 // Types that will be marked as serializable
 let serde_supported_types = ["ReadValueId", "DataChangeFilter", "EventFilter", "SimpleAttributeOperand", "ContentFilter",
     "ContentFilterElement", "MonitoredItemNotification", "ServerDiagnosticsSummaryDataType", "EventFieldList",
-    "DataChangeTrigger", "FilterOperator", "TimestampsToReturn", "MonitoringMode"];
+    "DataChangeTrigger", "FilterOperator", "TimestampsToReturn", "MonitoringMode", "AggregateFilter", "AggregateConfiguration"];
 
 // The map from OPC UA types to their corresponding Rust types.
 let type_name_mappings = {
@@ -282,7 +282,7 @@ impl BinaryEncoder<${enum_type.name}> for ${enum_type.name} {
         contents += `
             v => {
                 error!("Invalid value {} for enum ${enum_type.name}", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
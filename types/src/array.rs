@@ -44,6 +44,16 @@ impl Array {
         !self.dimensions.is_empty()
     }
 
+    /// Returns the dimensions of the array. A single dimension array without dimensions has
+    /// the number of its values as its one dimension.
+    pub fn shape(&self) -> Vec<u32> {
+        if self.has_dimensions() {
+            self.dimensions.clone()
+        } else {
+            vec![self.values.len() as u32]
+        }
+    }
+
     pub fn encoding_mask(&self) -> u8 {
         let mut encoding_mask = if self.values.is_empty() {
             0u8
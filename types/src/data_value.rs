@@ -14,6 +14,7 @@ use crate::{
     localized_text::LocalizedText,
     node_id::NodeId,
     qualified_name::QualifiedName,
+    service_types::TimestampsToReturn,
     status_codes::StatusCode,
     string::UAString,
     variant::Variant,
@@ -36,6 +37,31 @@ bitflags! {
     }
 }
 
+/// The largest valid picoseconds value. Picoseconds count 10 picosecond intervals within the
+/// 100 nanosecond resolution of a `DateTime`, so the valid range is 0 to 9999.
+pub const MAX_PICOSECONDS: i16 = 9999;
+
+/// Clamps picoseconds into the valid range for encoding
+fn clamp_picoseconds(picoseconds: i16) -> i16 {
+    if picoseconds < 0 || picoseconds > MAX_PICOSECONDS {
+        warn!("Picoseconds value {} is outside the valid range 0 to {} and has been clamped", picoseconds, MAX_PICOSECONDS);
+        picoseconds.max(0).min(MAX_PICOSECONDS)
+    } else {
+        picoseconds
+    }
+}
+
+/// Decodes picoseconds, rejecting values outside the valid range
+fn decode_picoseconds<S: Read>(stream: &mut S, decoding_limits: &DecodingLimits) -> EncodingResult<i16> {
+    let picoseconds = i16::decode(stream, decoding_limits)?;
+    if picoseconds < 0 || picoseconds > MAX_PICOSECONDS {
+        error!("Picoseconds value {} is outside the valid range 0 to {}", picoseconds, MAX_PICOSECONDS);
+        Err(StatusCode::BadDecodingError)
+    } else {
+        Ok(picoseconds)
+    }
+}
+
 /// A data value is a value of a variable in the OPC UA server and contains information about its
 /// value, status and change timestamps.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -49,20 +75,25 @@ pub struct DataValue {
     /// The source timestamp associated with the value.
     /// Not present if the SourceTimestamp bit in the EncodingMask is False.
     pub source_timestamp: Option<DateTime>,
-    /// The number of 10 picosecond intervals for the SourceTimestamp.
+    /// The number of 10 picosecond intervals for the SourceTimestamp, 0 to 9999.
+    /// Values outside the range are clamped when encoding and rejected when decoding.
     /// Not present if the SourcePicoSeconds bit in the EncodingMask is False.
     /// If the source timestamp is missing the picoseconds are ignored.
     pub source_picoseconds: Option<i16>,
     /// The Server timestamp associated with the value.
     /// Not present if the ServerTimestamp bit in the EncodingMask is False.
     pub server_timestamp: Option<DateTime>,
-    /// The number of 10 picosecond intervals for the ServerTimestamp.
+    /// The number of 10 picosecond intervals for the ServerTimestamp, 0 to 9999.
+    /// Values outside the range are clamped when encoding and rejected when decoding.
     /// Not present if the ServerPicoSeconds bit in the EncodingMask is False.
     /// If the Server timestamp is missing the picoseconds are ignored.
     pub server_picoseconds: Option<i16>,
 }
 
 impl BinaryEncoder<DataValue> for DataValue {
+    /// The length is recomputed on every call rather than memoized. The fields are public so
+    /// there is no way to tell when a cached length has gone stale, and the cost is dominated by
+    /// walking the value, which `encode` has to do anyway.
     fn byte_len(&self) -> usize {
         let mut size = 1;
         let encoding_mask = self.encoding_mask();
@@ -102,13 +133,13 @@ impl BinaryEncoder<DataValue> for DataValue {
         if encoding_mask.contains(DataValueFlags::HAS_SOURCE_TIMESTAMP) {
             size += self.source_timestamp.as_ref().unwrap().encode(stream)?;
             if encoding_mask.contains(DataValueFlags::HAS_SOURCE_PICOSECONDS) {
-                size += self.source_picoseconds.as_ref().unwrap().encode(stream)?;
+                size += clamp_picoseconds(self.source_picoseconds.unwrap()).encode(stream)?;
             }
         }
         if encoding_mask.contains(DataValueFlags::HAS_SERVER_TIMESTAMP) {
             size += self.server_timestamp.as_ref().unwrap().encode(stream)?;
             if encoding_mask.contains(DataValueFlags::HAS_SERVER_PICOSECONDS) {
-                size += self.server_picoseconds.as_ref().unwrap().encode(stream)?;
+                size += clamp_picoseconds(self.server_picoseconds.unwrap()).encode(stream)?;
             }
         }
         Ok(size)
@@ -123,7 +154,8 @@ impl BinaryEncoder<DataValue> for DataValue {
         } else {
             None
         };
-        // Status
+        // Status. Only present if the mask says so, whether or not there is a value. Encoders may
+        // omit a Good status so an absent status is left as None, which `status()` reads as Good.
         let status = if encoding_mask.contains(DataValueFlags::HAS_STATUS) {
             let status = StatusCode::from_bits_truncate(u32::decode(stream, decoding_limits)?);
             Some(status)
@@ -137,7 +169,7 @@ impl BinaryEncoder<DataValue> for DataValue {
             None
         };
         let source_picoseconds = if encoding_mask.contains(DataValueFlags::HAS_SOURCE_PICOSECONDS) {
-            Some(i16::decode(stream, decoding_limits)?)
+            Some(decode_picoseconds(stream, decoding_limits)?)
         } else {
             None
         };
@@ -148,7 +180,7 @@ impl BinaryEncoder<DataValue> for DataValue {
             None
         };
         let server_picoseconds = if encoding_mask.contains(DataValueFlags::HAS_SERVER_PICOSECONDS) {
-            Some(i16::decode(stream, decoding_limits)?)
+            Some(decode_picoseconds(stream, decoding_limits)?)
         } else {
             None
         };
@@ -365,6 +397,45 @@ impl DataValue {
         self.status.map_or(StatusCode::Good, |s| s)
     }
 
+    /// Merges this data value with a newer one for the same item, e.g. when coalescing queued
+    /// notifications. The result is the newer value and timestamps, but the overflow, semantics
+    /// changed and structure changed bits of the older status are kept so that they are not lost
+    /// with the value they arrived on. The other info bits describe the value itself so they come
+    /// from the newer value alone.
+    pub fn merge_latest(self, newer: DataValue) -> DataValue {
+        let carried_bits = self.status().bitflags() & (StatusCode::OVERFLOW | StatusCode::SEMANTICS_CHANGED | StatusCode::STRUCTURE_CHANGED);
+        let mut merged = newer;
+        if !carried_bits.is_empty() {
+            merged.status = Some(merged.status() | carried_bits);
+        }
+        merged
+    }
+
+    /// Returns a copy of the data value holding only the timestamps, and their picoseconds, that
+    /// the client asked for. The omitted timestamps are None so they are left out of the encoding
+    /// mask and the encoded value. `Invalid` is treated as `Neither`.
+    pub fn trimmed_for(&self, timestamps_to_return: TimestampsToReturn) -> DataValue {
+        let mut data_value = self.clone();
+        match timestamps_to_return {
+            TimestampsToReturn::Neither | TimestampsToReturn::Invalid => {
+                data_value.source_timestamp = None;
+                data_value.source_picoseconds = None;
+                data_value.server_timestamp = None;
+                data_value.server_picoseconds = None;
+            }
+            TimestampsToReturn::Server => {
+                data_value.source_timestamp = None;
+                data_value.source_picoseconds = None;
+            }
+            TimestampsToReturn::Source => {
+                data_value.server_timestamp = None;
+                data_value.server_picoseconds = None;
+            }
+            TimestampsToReturn::Both => {}
+        }
+        data_value
+    }
+
     /// Test if the value held by this data value is known to be good
     /// Anything other than Good is assumed to be invalid.
     pub fn is_valid(&self) -> bool {
@@ -247,6 +247,19 @@ impl DateTime {
         self.date_time
     }
 
+    /// Converts a chrono time to a date time. The time is truncated to whole ticks and clamped to
+    /// the OPC UA range, so a time before the epoch becomes null and a time after the endtimes
+    /// becomes the endtimes. Converting back with `as_chrono()` yields the normalized time.
+    pub fn from_chrono(date_time: &DateTimeUtc) -> DateTime {
+        if *date_time <= Self::epoch_chrono() {
+            DateTime::null()
+        } else if *date_time >= Self::endtimes_chrono() {
+            DateTime::endtimes()
+        } else {
+            DateTime::from(*date_time)
+        }
+    }
+
     /// The OPC UA epoch - Jan 1 1601 00:00:00
     fn epoch_chrono() -> DateTimeUtc {
         Utc.ymd(MIN_YEAR as i32, 1, 1).and_hms(0, 0, 0)
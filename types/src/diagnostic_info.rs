@@ -167,7 +167,7 @@ impl BinaryEncoder<DiagnosticInfo> for DiagnosticInfo {
         }
         if encoding_mask.contains(DiagnosticInfoMask::HAS_INNER_DIAGNOSTIC_INFO) {
             // Read inner diagnostic info
-            diagnostic_info.inner_diagnostic_info = Some(Box::new(DiagnosticInfo::decode(stream, decoding_limits)?));
+            diagnostic_info.inner_diagnostic_info = Some(Box::new(DiagnosticInfo::decode(stream, &decoding_limits.nested()?)?));
         }
         Ok(diagnostic_info)
     }
@@ -30,6 +30,10 @@ pub struct DecodingLimits {
     pub max_byte_string_length: usize,
     /// Maximum number of array elements. 0 actually means 0, i.e. no array permitted
     pub max_array_length: usize,
+    /// Maximum depth that values may be nested, e.g. an extension object whose body holds another
+    /// extension object. Stops a crafted message from recursing without bound. The limits for a
+    /// nested value have this reduced by one.
+    pub max_decoding_depth: usize,
 }
 
 impl Default for DecodingLimits {
@@ -39,6 +43,7 @@ impl Default for DecodingLimits {
             max_string_length: constants::MAX_STRING_LENGTH,
             max_byte_string_length: constants::MAX_BYTE_STRING_LENGTH,
             max_array_length: constants::MAX_ARRAY_LENGTH,
+            max_decoding_depth: constants::MAX_DECODING_DEPTH,
         }
     }
 }
@@ -52,6 +57,22 @@ impl DecodingLimits {
             max_string_length: 0,
             max_byte_string_length: 0,
             max_array_length: 0,
+            max_decoding_depth: constants::MAX_DECODING_DEPTH,
+        }
+    }
+
+    /// Returns the limits for decoding a value nested within the value being decoded. Decoders
+    /// that recurse must decode the nested value with these limits. Returns `BadDecodingError` if
+    /// the nested value would be deeper than the maximum depth.
+    pub fn nested(&self) -> EncodingResult<DecodingLimits> {
+        if self.max_decoding_depth == 0 {
+            error!("Decoding depth exceeds the limit");
+            Err(StatusCode::BadDecodingError)
+        } else {
+            Ok(DecodingLimits {
+                max_decoding_depth: self.max_decoding_depth - 1,
+                ..*self
+            })
         }
     }
 }
@@ -79,6 +100,28 @@ pub trait BinaryEncoder<T> {
     }
 }
 
+/// OPC UA Binary Decoding interface for code which only reads values, e.g. from a borrowed or
+/// streaming input, and has no need for the encoding side. It is implemented for every type that
+/// implements `BinaryEncoder`.
+///
+/// The functions are not called `decode` so they do not clash with `BinaryEncoder::decode` when
+/// both traits are in scope.
+pub trait BinaryDecoder: Sized {
+    /// Decodes an instance from the read stream using the default decoding limits.
+    fn decode_default<S: Read>(stream: &mut S) -> EncodingResult<Self> {
+        Self::decode_limited(stream, &DecodingLimits::default())
+    }
+    /// Decodes an instance from the read stream, returning `BadDecodingError` if the decoding
+    /// limits are exceeded.
+    fn decode_limited<S: Read>(stream: &mut S, decoding_limits: &DecodingLimits) -> EncodingResult<Self>;
+}
+
+impl<T> BinaryDecoder for T where T: BinaryEncoder<T> {
+    fn decode_limited<S: Read>(stream: &mut S, decoding_limits: &DecodingLimits) -> EncodingResult<Self> {
+        T::decode(stream, decoding_limits)
+    }
+}
+
 /// Converts an IO encoding error (and logs when in error) into an EncodingResult
 pub fn process_encode_io_result(result: Result<usize>) -> EncodingResult<usize> {
     result.map_err(|err| {
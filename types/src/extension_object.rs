@@ -141,13 +141,16 @@ impl ExtensionObject {
     /// Decodes the inner content of the extension object and returns it. The node id is ignored
     /// for decoding. The caller supplies the binary encoder impl that should be used to extract
     /// the data. Errors result in a decoding error.
+    ///
+    /// The content is one level deeper than the extension object, so decoding extension objects
+    /// nested beyond the maximum depth of the decoding limits is an error.
     pub fn decode_inner<T>(&self, decoding_limits: &DecodingLimits) -> EncodingResult<T> where T: BinaryEncoder<T> {
         match self.body {
             ExtensionObjectEncoding::ByteString(ref byte_string) => {
                 if let Some(ref value) = byte_string.value {
-                    // let value = value.clone();
+                    let decoding_limits = decoding_limits.nested()?;
                     let mut stream = Cursor::new(value);
-                    T::decode(&mut stream, decoding_limits)
+                    T::decode(&mut stream, &decoding_limits)
                 } else {
                     Err(StatusCode::BadDecodingError)
                 }
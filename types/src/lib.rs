@@ -43,6 +43,8 @@ pub mod constants {
     pub const MAX_STRING_LENGTH: usize = 65535;
     /// Maximum size of a byte string in bytes
     pub const MAX_BYTE_STRING_LENGTH: usize = 65535;
+    /// Maximum depth that decoded values may be nested, e.g. extension objects within extension objects
+    pub const MAX_DECODING_DEPTH: usize = 100;
     /// Maximum size of a certificate to send
     pub const MAX_CERTIFICATE_LENGTH: u32 = 32767;
 
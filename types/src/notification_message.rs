@@ -5,10 +5,11 @@
 ///! Helpers for NotificationMessage types
 
 use crate::{
+    byte_string::ByteString,
     date_time::DateTime,
     diagnostic_info::DiagnosticInfo,
-    encoding::DecodingLimits,
-    extension_object::ExtensionObject,
+    encoding::{BinaryEncoder, DecodingLimits, EncodingResult, write_i32},
+    extension_object::{ExtensionObject, ExtensionObjectEncoding},
     node_id::NodeId,
     node_ids::ObjectId,
     service_types::{
@@ -23,18 +24,48 @@ impl NotificationMessage {
     /// neither will panic. Notification data can have up to 2 elements to covers the case in
     /// table 158 where a subscription contains monitored items for events and data.
     pub fn data_change(sequence_number: u32, publish_time: DateTime, data_change_notifications: Vec<MonitoredItemNotification>, event_notifications: Vec<EventFieldList>) -> NotificationMessage {
+        Self::data_change_with_diagnostics(sequence_number, publish_time, data_change_notifications, None, event_notifications)
+    }
+
+    /// Does the same as `data_change()` except the data change notification carries diagnostic
+    /// infos. If supplied there must be one for each data change notification, in the same order.
+    ///
+    /// Without diagnostic infos the data changes are encoded with
+    /// `DataChangeNotification::encode_batch()` into a body of exactly the right size that the
+    /// message takes over as it is.
+    pub fn data_change_with_diagnostics(sequence_number: u32, publish_time: DateTime, data_change_notifications: Vec<MonitoredItemNotification>, diagnostic_infos: Option<Vec<DiagnosticInfo>>, event_notifications: Vec<EventFieldList>) -> NotificationMessage {
         if data_change_notifications.is_empty() && event_notifications.is_empty() {
             panic!("No notifications supplied to data_change()");
         }
 
         let mut notification_data = Vec::with_capacity(2);
         if !data_change_notifications.is_empty() {
-            let data_change_notification = DataChangeNotification {
-                monitored_items: Some(data_change_notifications),
-                diagnostic_infos: None,
-            };
-            trace!("data change notification = {:?}", data_change_notification);
-            notification_data.push(ExtensionObject::from_encodable(ObjectId::DataChangeNotification_Encoding_DefaultBinary, &data_change_notification));
+            trace!("data change notifications = {:?}", data_change_notifications);
+            if diagnostic_infos.is_some() {
+                let data_change_notification = DataChangeNotification {
+                    monitored_items: Some(data_change_notifications),
+                    diagnostic_infos,
+                };
+                notification_data.push(ExtensionObject::from_encodable(ObjectId::DataChangeNotification_Encoding_DefaultBinary, &data_change_notification));
+            } else {
+                let mut body = Vec::new();
+                match DataChangeNotification::encode_batch(&data_change_notifications, &mut body) {
+                    Ok(_) => {
+                        notification_data.push(ExtensionObject {
+                            node_id: ObjectId::DataChangeNotification_Encoding_DefaultBinary.into(),
+                            body: ExtensionObjectEncoding::ByteString(ByteString::from(body)),
+                        });
+                    }
+                    Err(err) => {
+                        error!("Data change notifications could not be encoded as a batch, error = {}", err);
+                        let data_change_notification = DataChangeNotification {
+                            monitored_items: Some(data_change_notifications),
+                            diagnostic_infos: None,
+                        };
+                        notification_data.push(ExtensionObject::from_encodable(ObjectId::DataChangeNotification_Encoding_DefaultBinary, &data_change_notification));
+                    }
+                }
+            }
         }
         if !event_notifications.is_empty() {
             let event_notification_list = EventNotificationList {
@@ -108,3 +139,27 @@ impl NotificationMessage {
         }
     }
 }
+
+impl DataChangeNotification {
+    /// Returns the encoded length of a data change notification holding the monitored item
+    /// notifications and no diagnostic infos.
+    pub fn batch_byte_len(monitored_items: &[MonitoredItemNotification]) -> usize {
+        // Array length of the items, the items, null diagnostic infos array
+        4 + monitored_items.iter().map(|n| n.byte_len()).sum::<usize>() + 4
+    }
+
+    /// Appends the encoding of a data change notification holding the monitored item notifications
+    /// and no diagnostic infos to the buffer. The output is identical to encoding a
+    /// `DataChangeNotification` but nothing is copied into an intermediate struct and the buffer
+    /// grows once at most, so a buffer that is cleared and reused for each publish stops allocating
+    /// once it reaches the size of the largest batch. Returns the number of bytes written.
+    pub fn encode_batch(monitored_items: &[MonitoredItemNotification], buffer: &mut Vec<u8>) -> EncodingResult<usize> {
+        buffer.reserve(Self::batch_byte_len(monitored_items));
+        let mut size = write_i32(buffer, monitored_items.len() as i32)?;
+        for notification in monitored_items {
+            size += notification.encode(buffer)?;
+        }
+        size += write_i32(buffer, -1)?;
+        Ok(size)
+    }
+}
@@ -113,6 +113,25 @@ fn invalid_numeric_ranges() {
     }
 }
 
+#[test]
+fn numeric_range_overlaps() {
+    let overlaps = |r1: &str, r2: &str| r1.parse::<NumericRange>().unwrap().overlaps(&r2.parse::<NumericRange>().unwrap());
+    assert!(overlaps("", "5"));
+    assert!(overlaps("5", ""));
+    assert!(overlaps("5", "5"));
+    assert!(overlaps("2:5", "5"));
+    assert!(overlaps("2:5", "0:2"));
+    assert!(overlaps("2:5", "3:4"));
+    assert!(!overlaps("5", "6"));
+    assert!(!overlaps("2:5", "6:9"));
+    assert!(!overlaps("2:5", "0:1"));
+    // Every dimension must overlap
+    assert!(overlaps("1:2,0:1", "2,1"));
+    assert!(!overlaps("1:2,0:1", "2,3"));
+    // Different dimensions are assumed to overlap
+    assert!(overlaps("1:2,0:1", "5"));
+}
+
 const MAX_INDICES: usize = 10;
 
 impl FromStr for NumericRange {
@@ -219,6 +238,33 @@ impl NumericRange {
         }
     }
 
+    /// Tests if two ranges could select any of the same elements. No range means the whole value,
+    /// so it overlaps everything. Multi-dimensional ranges overlap when every dimension overlaps.
+    /// Ranges with a different number of dimensions cannot be compared so are assumed to overlap.
+    pub fn overlaps(&self, other: &NumericRange) -> bool {
+        match (self, other) {
+            (NumericRange::None, _) | (_, NumericRange::None) => true,
+            (NumericRange::MultipleRanges(ref r1), NumericRange::MultipleRanges(ref r2)) => {
+                r1.len() != r2.len() || r1.iter().zip(r2.iter()).all(|(r1, r2)| r1.overlaps(r2))
+            }
+            (NumericRange::MultipleRanges(_), _) | (_, NumericRange::MultipleRanges(_)) => true,
+            (r1, r2) => {
+                let (min1, max1) = r1.bounds();
+                let (min2, max2) = r2.bounds();
+                min1 <= max2 && min2 <= max1
+            }
+        }
+    }
+
+    /// The first and last index of a single index or range
+    fn bounds(&self) -> (u32, u32) {
+        match self {
+            NumericRange::Index(idx) => (*idx, *idx),
+            NumericRange::Range(min, max) => (*min, *max),
+            _ => panic!("Range has no bounds {:?}", self),
+        }
+    }
+
     /// Tests if the range is basically valid, i.e. that the min < max, that multiple ranges
     /// doesn't point to multiple ranges
     pub fn is_valid(&self) -> bool {
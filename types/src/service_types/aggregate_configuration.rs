@@ -15,7 +15,7 @@ use crate::{
     node_ids::ObjectId,
 };
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct AggregateConfiguration {
     pub use_server_capabilities_defaults: bool,
     pub treat_uncertain_as_bad: bool,
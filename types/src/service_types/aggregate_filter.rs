@@ -16,7 +16,7 @@ use crate::{
     service_types::AggregateConfiguration,
 };
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct AggregateFilter {
     pub start_time: DateTime,
     pub aggregate_type: NodeId,
@@ -42,7 +42,7 @@ impl BinaryEncoder<NodeIdType> for NodeIdType {
             5 => Ok(Self::ByteString),
             v => {
                 error!("Invalid value {} for enum NodeIdType", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -73,7 +73,7 @@ impl BinaryEncoder<NamingRuleType> for NamingRuleType {
             3 => Ok(Self::Constraint),
             v => {
                 error!("Invalid value {} for enum NamingRuleType", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -106,7 +106,7 @@ impl BinaryEncoder<OpenFileMode> for OpenFileMode {
             8 => Ok(Self::Append),
             v => {
                 error!("Invalid value {} for enum OpenFileMode", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -143,7 +143,7 @@ impl BinaryEncoder<IdentityCriteriaType> for IdentityCriteriaType {
             6 => Ok(Self::AuthenticatedUser),
             v => {
                 error!("Invalid value {} for enum IdentityCriteriaType", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -180,7 +180,7 @@ impl BinaryEncoder<TrustListMasks> for TrustListMasks {
             15 => Ok(Self::All),
             v => {
                 error!("Invalid value {} for enum TrustListMasks", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -213,7 +213,7 @@ impl BinaryEncoder<PubSubState> for PubSubState {
             3 => Ok(Self::Error),
             v => {
                 error!("Invalid value {} for enum PubSubState", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -242,7 +242,7 @@ impl BinaryEncoder<DataSetFieldFlags> for DataSetFieldFlags {
             1 => Ok(Self::PromotedField),
             v => {
                 error!("Invalid value {} for enum DataSetFieldFlags", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -281,7 +281,7 @@ impl BinaryEncoder<DataSetFieldContentMask> for DataSetFieldContentMask {
             32 => Ok(Self::RawData),
             v => {
                 error!("Invalid value {} for enum DataSetFieldContentMask", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -312,7 +312,7 @@ impl BinaryEncoder<OverrideValueHandling> for OverrideValueHandling {
             2 => Ok(Self::OverrideValue),
             v => {
                 error!("Invalid value {} for enum OverrideValueHandling", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -343,7 +343,7 @@ impl BinaryEncoder<DataSetOrderingType> for DataSetOrderingType {
             2 => Ok(Self::AscendingWriterIdSingle),
             v => {
                 error!("Invalid value {} for enum DataSetOrderingType", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -392,7 +392,7 @@ impl BinaryEncoder<UadpNetworkMessageContentMask> for UadpNetworkMessageContentM
             1024 => Ok(Self::PromotedFields),
             v => {
                 error!("Invalid value {} for enum UadpNetworkMessageContentMask", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -431,7 +431,7 @@ impl BinaryEncoder<UadpDataSetMessageContentMask> for UadpDataSetMessageContentM
             32 => Ok(Self::SequenceNumber),
             v => {
                 error!("Invalid value {} for enum UadpDataSetMessageContentMask", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -470,7 +470,7 @@ impl BinaryEncoder<JsonNetworkMessageContentMask> for JsonNetworkMessageContentM
             32 => Ok(Self::ReplyTo),
             v => {
                 error!("Invalid value {} for enum JsonNetworkMessageContentMask", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -507,7 +507,7 @@ impl BinaryEncoder<JsonDataSetMessageContentMask> for JsonDataSetMessageContentM
             16 => Ok(Self::Status),
             v => {
                 error!("Invalid value {} for enum JsonDataSetMessageContentMask", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -542,7 +542,7 @@ impl BinaryEncoder<BrokerTransportQualityOfService> for BrokerTransportQualityOf
             4 => Ok(Self::ExactlyOnce),
             v => {
                 error!("Invalid value {} for enum BrokerTransportQualityOfService", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -577,7 +577,7 @@ impl BinaryEncoder<DiagnosticsLevel> for DiagnosticsLevel {
             4 => Ok(Self::Debug),
             v => {
                 error!("Invalid value {} for enum DiagnosticsLevel", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -606,7 +606,7 @@ impl BinaryEncoder<PubSubDiagnosticsCounterClassification> for PubSubDiagnostics
             1 => Ok(Self::Error),
             v => {
                 error!("Invalid value {} for enum PubSubDiagnosticsCounterClassification", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -639,7 +639,7 @@ impl BinaryEncoder<IdType> for IdType {
             3 => Ok(Self::Opaque),
             v => {
                 error!("Invalid value {} for enum IdType", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -682,7 +682,7 @@ impl BinaryEncoder<NodeClass> for NodeClass {
             128 => Ok(Self::View),
             v => {
                 error!("Invalid value {} for enum NodeClass", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -743,7 +743,7 @@ impl BinaryEncoder<PermissionType> for PermissionType {
             65536 => Ok(Self::AddNode),
             v => {
                 error!("Invalid value {} for enum PermissionType", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -784,7 +784,7 @@ impl BinaryEncoder<AccessLevelType> for AccessLevelType {
             64 => Ok(Self::TimestampWrite),
             v => {
                 error!("Invalid value {} for enum AccessLevelType", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -831,7 +831,7 @@ impl BinaryEncoder<AccessLevelExType> for AccessLevelExType {
             1024 => Ok(Self::WriteFullArrayOnly),
             v => {
                 error!("Invalid value {} for enum AccessLevelExType", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -864,7 +864,7 @@ impl BinaryEncoder<EventNotifierType> for EventNotifierType {
             8 => Ok(Self::HistoryWrite),
             v => {
                 error!("Invalid value {} for enum EventNotifierType", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -897,7 +897,7 @@ impl BinaryEncoder<AccessRestrictionType> for AccessRestrictionType {
             4 => Ok(Self::SessionRequired),
             v => {
                 error!("Invalid value {} for enum AccessRestrictionType", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -928,7 +928,7 @@ impl BinaryEncoder<StructureType> for StructureType {
             2 => Ok(Self::Union),
             v => {
                 error!("Invalid value {} for enum StructureType", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -961,7 +961,7 @@ impl BinaryEncoder<ApplicationType> for ApplicationType {
             3 => Ok(Self::DiscoveryServer),
             v => {
                 error!("Invalid value {} for enum ApplicationType", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -994,7 +994,7 @@ impl BinaryEncoder<MessageSecurityMode> for MessageSecurityMode {
             3 => Ok(Self::SignAndEncrypt),
             v => {
                 error!("Invalid value {} for enum MessageSecurityMode", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -1027,7 +1027,7 @@ impl BinaryEncoder<UserTokenType> for UserTokenType {
             3 => Ok(Self::IssuedToken),
             v => {
                 error!("Invalid value {} for enum UserTokenType", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -1056,7 +1056,7 @@ impl BinaryEncoder<SecurityTokenRequestType> for SecurityTokenRequestType {
             1 => Ok(Self::Renew),
             v => {
                 error!("Invalid value {} for enum SecurityTokenRequestType", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -1151,7 +1151,7 @@ impl BinaryEncoder<NodeAttributesMask> for NodeAttributesMask {
             26501356 => Ok(Self::View),
             v => {
                 error!("Invalid value {} for enum NodeAttributesMask", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -1230,7 +1230,7 @@ impl BinaryEncoder<AttributeWriteMask> for AttributeWriteMask {
             33554432 => Ok(Self::AccessLevelEx),
             v => {
                 error!("Invalid value {} for enum AttributeWriteMask", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -1263,7 +1263,7 @@ impl BinaryEncoder<BrowseDirection> for BrowseDirection {
             3 => Ok(Self::Invalid),
             v => {
                 error!("Invalid value {} for enum BrowseDirection", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -1308,7 +1308,7 @@ impl BinaryEncoder<BrowseResultMask> for BrowseResultMask {
             60 => Ok(Self::TargetInfo),
             v => {
                 error!("Invalid value {} for enum BrowseResultMask", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -1369,7 +1369,7 @@ impl BinaryEncoder<FilterOperator> for FilterOperator {
             17 => Ok(Self::BitwiseOr),
             v => {
                 error!("Invalid value {} for enum FilterOperator", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -1403,7 +1403,7 @@ impl BinaryEncoder<TimestampsToReturn> for TimestampsToReturn {
             3 => Ok(Self::Neither),
             v => {
                 error!("Invalid value {} for enum TimestampsToReturn", v);
-                Ok(Self::Invalid)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -1436,7 +1436,7 @@ impl BinaryEncoder<HistoryUpdateType> for HistoryUpdateType {
             4 => Ok(Self::Delete),
             v => {
                 error!("Invalid value {} for enum HistoryUpdateType", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -1469,7 +1469,7 @@ impl BinaryEncoder<PerformUpdateType> for PerformUpdateType {
             4 => Ok(Self::Remove),
             v => {
                 error!("Invalid value {} for enum PerformUpdateType", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -1500,7 +1500,7 @@ impl BinaryEncoder<MonitoringMode> for MonitoringMode {
             2 => Ok(Self::Reporting),
             v => {
                 error!("Invalid value {} for enum MonitoringMode", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -1531,7 +1531,7 @@ impl BinaryEncoder<DataChangeTrigger> for DataChangeTrigger {
             2 => Ok(Self::StatusValueTimestamp),
             v => {
                 error!("Invalid value {} for enum DataChangeTrigger", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -1562,7 +1562,7 @@ impl BinaryEncoder<DeadbandType> for DeadbandType {
             2 => Ok(Self::Percent),
             v => {
                 error!("Invalid value {} for enum DeadbandType", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -1599,7 +1599,7 @@ impl BinaryEncoder<RedundancySupport> for RedundancySupport {
             5 => Ok(Self::HotAndMirrored),
             v => {
                 error!("Invalid value {} for enum RedundancySupport", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -1640,7 +1640,7 @@ impl BinaryEncoder<ServerState> for ServerState {
             7 => Ok(Self::Unknown),
             v => {
                 error!("Invalid value {} for enum ServerState", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -1675,7 +1675,7 @@ impl BinaryEncoder<ModelChangeStructureVerbMask> for ModelChangeStructureVerbMas
             16 => Ok(Self::DataTypeChanged),
             v => {
                 error!("Invalid value {} for enum ModelChangeStructureVerbMask", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -1706,7 +1706,7 @@ impl BinaryEncoder<AxisScaleEnumeration> for AxisScaleEnumeration {
             2 => Ok(Self::Ln),
             v => {
                 error!("Invalid value {} for enum AxisScaleEnumeration", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -1741,7 +1741,7 @@ impl BinaryEncoder<ExceptionDeviationFormat> for ExceptionDeviationFormat {
             4 => Ok(Self::Unknown),
             v => {
                 error!("Invalid value {} for enum ExceptionDeviationFormat", v);
-                Err(StatusCode::BadUnexpectedError)
+                Err(StatusCode::BadDecodingError)
             }
         }
     }
@@ -50,9 +50,55 @@ impl UserTokenPolicy {
     }
 }
 
+impl DataChangeTrigger {
+    /// Returns the trigger for the value held in a `DataChangeFilter`.
+    ///
+    /// # Errors
+    ///
+    /// BadDeadbandFilterInvalid if the value is not a known trigger.
+    pub fn from_u32(trigger: u32) -> Result<DataChangeTrigger, StatusCode> {
+        match trigger {
+            0 => Ok(DataChangeTrigger::Status),
+            1 => Ok(DataChangeTrigger::StatusValue),
+            2 => Ok(DataChangeTrigger::StatusValueTimestamp),
+            _ => Err(StatusCode::BadDeadbandFilterInvalid)
+        }
+    }
+}
+
+impl DeadbandType {
+    /// Returns the deadband type for the value held in a `DataChangeFilter`.
+    ///
+    /// # Errors
+    ///
+    /// BadDeadbandFilterInvalid if the value is not a known deadband type.
+    pub fn from_u32(deadband_type: u32) -> Result<DeadbandType, StatusCode> {
+        match deadband_type {
+            0 => Ok(DeadbandType::None),
+            1 => Ok(DeadbandType::Absolute),
+            2 => Ok(DeadbandType::Percent),
+            _ => Err(StatusCode::BadDeadbandFilterInvalid)
+        }
+    }
+
+    pub fn to_u32(self) -> u32 {
+        self as u32
+    }
+}
+
 impl DataChangeFilter {
-    /// Compares one data value to another and returns true if they differ, according to their trigger
-    /// type of status, status/value or status/value/timestamp
+    /// Returns the deadband type of the filter, or BadDeadbandFilterInvalid if it is unknown
+    pub fn deadband_type(&self) -> std::result::Result<DeadbandType, StatusCode> {
+        DeadbandType::from_u32(self.deadband_type)
+    }
+
+    /// Compares one data value to another and returns true if they are the same, according to their trigger
+    /// type of status, status/value or status/value/timestamp.
+    ///
+    /// The server timestamp is never compared since the server stamps it on every read. The
+    /// status/value/timestamp trigger compares the source timestamp. It is the only trigger that
+    /// reports a source which updates its timestamp while its value stays the same, e.g. a
+    /// heartbeat, so other triggers are not flooded by such updates.
     pub fn compare(&self, v1: &DataValue, v2: &DataValue, eu_range: Option<(f64, f64)>) -> bool {
         match self.trigger {
             DataChangeTrigger::Status => {
@@ -65,12 +111,15 @@ impl DataChangeFilter {
             DataChangeTrigger::StatusValueTimestamp => {
                 v1.status == v2.status &&
                     self.compare_value_option(&v1.value, &v2.value, eu_range) &&
-                    v1.server_timestamp == v2.server_timestamp
+                    v1.source_timestamp == v2.source_timestamp
             }
         }
     }
 
     /// Compares two variant values to each other. Returns true if they are considered the "same".
+    /// Scalars of the same type that are equal are the same without being tested against the
+    /// deadband. Values that cannot be tested against the deadband, e.g. because the deadband is
+    /// invalid, are treated as a change so the notification is not lost.
     pub fn compare_value_option(&self, v1: &Option<Variant>, v2: &Option<Variant>, eu_range: Option<(f64, f64)>) -> bool {
         match (v1, v2) {
             (Some(_), None) | (None, Some(_)) => {
@@ -80,9 +129,15 @@ impl DataChangeFilter {
                 // If it's always none then it hasn't changed
                 true
             }
+            (Some(v1), Some(v2)) if !v1.is_array() && v1.type_id() == v2.type_id() && v1 == v2 => {
+                // An identical scalar, e.g. a static value sampled again, is the same whatever the
+                // deadband so skip the numeric comparison. Arrays are not compared here since the
+                // deadband compares them element by element anyway.
+                true
+            }
             (Some(v1), Some(v2)) => {
                 // Otherwise test the filter
-                self.compare_value(v1, v2, eu_range).unwrap_or(true)
+                self.compare_value(v1, v2, eu_range).unwrap_or(false)
             }
         }
     }
@@ -100,20 +155,58 @@ impl DataChangeFilter {
     /// BadDeadbandFilterInvalid indicates the deadband settings were invalid, e.g. an invalid
     /// type, or the args were invalid. A (low, high) range must be supplied for a percentage deadband compare.
     pub fn compare_value(&self, v1: &Variant, v2: &Variant, eu_range: Option<(f64, f64)>) -> std::result::Result<bool, StatusCode> {
-        // TODO be able to compare arrays of numbers
-        if self.deadband_type == DeadbandType::None as u32 {
-            // Straight comparison of values
+        match self.deadband_compare(v1, v2, eu_range) {
+            // Values that cannot be compared numerically are treated as a change
+            Err(StatusCode::BadTypeMismatch) => Ok(false),
+            result => result
+        }
+    }
+
+    /// Compares two values according to the deadband type of the filter. Returns `Ok(true)` if
+    /// the values are within the deadband, i.e. there is no change, and `Ok(false)` if they are
+    /// outside of it. A deadband type of none is a straight comparison of the values, except that
+    /// numbers of differing types but the same magnitude are the same. Localized text and
+    /// qualified names have no magnitude so the deadband is ignored for them and they are compared
+    /// exactly, including their locale or namespace. Arrays are compared element by element and
+    /// must have the same dimensions.
+    ///
+    /// The eu_range is the engineering unit range and is only used by a percent deadband.
+    ///
+    /// # Errors
+    ///
+    /// BadTypeMismatch indicates an absolute or percent deadband was asked for on values that
+    /// are not numeric. BadDeadbandFilterInvalid indicates the deadband settings were invalid, e.g.
+    /// an unknown type, a negative deadband value, or a missing or empty eu_range for a percent deadband.
+    pub fn deadband_compare(&self, v1: &Variant, v2: &Variant, eu_range: Option<(f64, f64)>) -> std::result::Result<bool, StatusCode> {
+        let deadband_type = self.deadband_type()?;
+        if deadband_type == DeadbandType::None {
+            // Straight comparison of values, widening numbers so a change of type is not a change
+            Ok(v1.numerically_eq(v2))
+        } else if let (Variant::Array(a1), Variant::Array(a2)) = (v1, v2) {
+            // A change to the size or dimensions of an array is always a change. Otherwise the
+            // array is within the deadband only if every element is, taking multi dimensional
+            // arrays element by element in the order they are stored.
+            if a1.values.len() != a2.values.len() || a1.shape() != a2.shape() {
+                return Ok(false);
+            }
+            for (v1, v2) in a1.values.iter().zip(a2.values.iter()) {
+                // Identical elements are the same whatever the deadband
+                if v1 != v2 && !self.deadband_compare(v1, v2, eu_range)? {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        } else if let (Variant::LocalizedText(_), Variant::LocalizedText(_)) | (Variant::QualifiedName(_), Variant::QualifiedName(_)) = (v1, v2) {
             Ok(v1 == v2)
         } else {
-            // Absolute
             match (v1.as_f64(), v2.as_f64()) {
-                (None, _) | (_, None) => Ok(false),
+                (None, _) | (_, None) => Err(StatusCode::BadTypeMismatch),
                 (Some(v1), Some(v2)) => {
                     if self.deadband_value < 0f64 {
                         Err(StatusCode::BadDeadbandFilterInvalid)
-                    } else if self.deadband_type == DeadbandType::Absolute as u32 {
+                    } else if deadband_type == DeadbandType::Absolute {
                         Ok(DataChangeFilter::abs_compare(v1, v2, self.deadband_value))
-                    } else if self.deadband_type == DeadbandType::Percent as u32 {
+                    } else {
                         match eu_range {
                             None => Err(StatusCode::BadDeadbandFilterInvalid),
                             Some((low, high)) => {
@@ -124,15 +217,31 @@ impl DataChangeFilter {
                                 }
                             }
                         }
-                    } else {
-                        // Type is not recognized
-                        Err(StatusCode::BadDeadbandFilterInvalid)
                     }
                 }
             }
         }
     }
 
+    /// Returns a copy of the filter whose percent deadband is raised, if necessary, so that it is
+    /// never less than an absolute change of `floor` over the eu_range. A tiny range otherwise
+    /// makes the threshold so small that floating point noise is reported as a change. Filters
+    /// of other deadband types, or without a valid range, are returned unchanged.
+    pub fn with_percent_floor(&self, eu_range: Option<(f64, f64)>, floor: f64) -> DataChangeFilter {
+        let mut filter = self.clone();
+        if self.deadband_type() == Ok(DeadbandType::Percent) && floor > 0f64 {
+            if let Some((low, high)) = eu_range {
+                if low < high {
+                    let floor_pct = 100f64 * floor / (high - low);
+                    if floor_pct > filter.deadband_value {
+                        filter.deadband_value = floor_pct;
+                    }
+                }
+            }
+        }
+        filter
+    }
+
     /// Compares the difference between v1 and v2 to the threshold. The two values are considered equal
     /// if their difference is less than or equal to the threshold.
     pub fn abs_compare(v1: f64, v2: f64, threshold_diff: f64) -> bool {
@@ -250,6 +359,19 @@ impl<'a> From<(u16, &'a str)> for ReadValueId {
     }
 }
 
+impl ReadValueId {
+    /// Creates a read value id for the attribute of a node. An empty index range is stored as a
+    /// null string so it encodes as one.
+    pub fn new<T>(node_id: T, attribute_id: AttributeId, index_range: &str, data_encoding: QualifiedName) -> ReadValueId where T: Into<NodeId> {
+        ReadValueId {
+            node_id: node_id.into(),
+            attribute_id: attribute_id as u32,
+            index_range: if index_range.is_empty() { UAString::null() } else { UAString::from(index_range) },
+            data_encoding,
+        }
+    }
+}
+
 impl Default for AnonymousIdentityToken {
     fn default() -> Self {
         AnonymousIdentityToken {
@@ -16,6 +16,22 @@ use serde::{
 
 pub use crate::{encoding::*, status_codes::StatusCode};
 
+/// The limit bits of a status code, which say whether a value is at a limit of its source
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum LimitBits {
+    /// The value is free to change
+    None,
+    /// The value is at the lower limit of the source
+    Low,
+    /// The value is at the upper limit of the source
+    High,
+    /// The value is constant and cannot change
+    Constant,
+}
+
+/// Mask of the info bits - the limit bits, the overflow bit and the historian bits
+const INFO_BITS_MASK: u32 = 0x0000_03ff;
+
 // The bitflags! macro implements Debug for StatusCode but it fouls the display because status
 // codes are a combination of bits and unique values.
 
@@ -23,7 +39,9 @@ impl fmt::Display for StatusCode {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         // Displays the StatusCode as it's name, or its name+bitflags
         let bits = self.bitflags();
-        if bits.is_empty() {
+        if !self.is_known() {
+            write!(f, "Unknown(0x{:08X})", self.bits())
+        } else if bits.is_empty() {
             write!(f, "{}", self.name())
         } else {
             write!(f, "{}+{:?}", self.name(), bits)
@@ -70,6 +88,57 @@ impl StatusCode {
     pub fn is_good(&self) -> bool {
         !self.is_bad() && !self.is_uncertain()
     }
+
+    /// Tests if the status part of the code is one defined by OPC UA, i.e. it has a symbolic name
+    pub fn is_known(&self) -> bool {
+        // Only the names in the generated table parse back to a status code
+        self.name().parse::<StatusCode>().is_ok()
+    }
+
+    /// Returns the base code without any bit flags. This is the same as `status()`.
+    pub fn base_code(&self) -> StatusCode {
+        self.status()
+    }
+
+    /// Returns the info bits of the status code - the limit bits, the overflow bit and the
+    /// historian bits. Unlike `bitflags()` it leaves out the semantics / structure changed bits.
+    pub fn info_bits(&self) -> StatusCode {
+        StatusCode::from_bits_truncate(self.bits() & INFO_BITS_MASK)
+    }
+
+    /// Returns the status code with the overflow bit set or cleared
+    pub fn with_overflow(self, overflow: bool) -> StatusCode {
+        if overflow {
+            self | StatusCode::OVERFLOW
+        } else {
+            self - StatusCode::OVERFLOW
+        }
+    }
+
+    /// Returns the limit bits of the status code
+    pub fn limit_bits(&self) -> LimitBits {
+        let limit_bits = *self & StatusCode::LIMIT_CONSTANT;
+        if limit_bits == StatusCode::LIMIT_CONSTANT {
+            LimitBits::Constant
+        } else if limit_bits == StatusCode::LIMIT_HIGH {
+            LimitBits::High
+        } else if limit_bits == StatusCode::LIMIT_LOW {
+            LimitBits::Low
+        } else {
+            LimitBits::None
+        }
+    }
+
+    /// Returns the status code with its limit bits replaced
+    pub fn with_limit(self, limit_bits: LimitBits) -> StatusCode {
+        let status_code = self - StatusCode::LIMIT_CONSTANT;
+        match limit_bits {
+            LimitBits::None => status_code,
+            LimitBits::Low => status_code | StatusCode::LIMIT_LOW,
+            LimitBits::High => status_code | StatusCode::LIMIT_HIGH,
+            LimitBits::Constant => status_code | StatusCode::LIMIT_CONSTANT,
+        }
+    }
 }
 
 // It would be very nice to be able to override the default implementation in bitflags! macro
@@ -144,4 +213,42 @@ fn status_code() {
 
     assert_eq!((StatusCode::BadDecodingError | StatusCode::HISTORICAL_CALCULATED).status(), StatusCode::BadDecodingError);
     assert_eq!((StatusCode::BadDecodingError | StatusCode::HISTORICAL_CALCULATED).bitflags(), StatusCode::HISTORICAL_CALCULATED);
-}
\ No newline at end of file
+}
+
+#[test]
+fn status_code_name() {
+    assert_eq!(StatusCode::Good.name(), "Good");
+    assert_eq!(StatusCode::BadFilterNotAllowed.name(), "BadFilterNotAllowed");
+    assert_eq!(StatusCode::UncertainLastUsableValue.name(), "UncertainLastUsableValue");
+    // The name ignores bit flags
+    assert_eq!((StatusCode::BadNodeIdUnknown | StatusCode::OVERFLOW).name(), "BadNodeIdUnknown");
+    assert_eq!(format!("{}", StatusCode::BadSecurityChecksFailed), "BadSecurityChecksFailed");
+
+    // An unknown code has no name
+    let unknown = StatusCode::from_bits_truncate(0x8FFF_0000);
+    assert!(!unknown.is_known());
+    assert!(StatusCode::BadFilterNotAllowed.is_known());
+    assert_eq!(format!("{}", unknown), "Unknown(0x8FFF0000)");
+}
+
+#[test]
+fn status_code_info_bits() {
+    let status_code = StatusCode::UncertainLastUsableValue.with_overflow(true).with_limit(LimitBits::High);
+    assert_eq!(status_code.base_code(), StatusCode::UncertainLastUsableValue);
+    assert_eq!(status_code.info_bits(), StatusCode::OVERFLOW | StatusCode::LIMIT_HIGH);
+    assert!(status_code.contains(StatusCode::OVERFLOW));
+    assert_eq!(status_code.limit_bits(), LimitBits::High);
+
+    // Limits replace each other and can be cleared along with the overflow bit
+    let status_code = status_code.with_limit(LimitBits::Low);
+    assert_eq!(status_code.limit_bits(), LimitBits::Low);
+    assert_eq!(status_code.with_limit(LimitBits::Constant).limit_bits(), LimitBits::Constant);
+    let status_code = status_code.with_limit(LimitBits::None).with_overflow(false);
+    assert_eq!(status_code.limit_bits(), LimitBits::None);
+    assert_eq!(status_code, StatusCode::UncertainLastUsableValue);
+
+    // The semantics / structure changed bits are not info bits
+    let status_code = StatusCode::Good | StatusCode::SEMANTICS_CHANGED | StatusCode::HISTORICAL_CALCULATED;
+    assert_eq!(status_code.info_bits(), StatusCode::HISTORICAL_CALCULATED);
+    assert_eq!(status_code.bitflags(), StatusCode::SEMANTICS_CHANGED | StatusCode::HISTORICAL_CALCULATED);
+}
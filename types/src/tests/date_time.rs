@@ -1,5 +1,7 @@
 use std::str::FromStr;
 
+use chrono::{TimeZone, Utc};
+
 use crate::*;
 
 #[test]
@@ -56,4 +58,31 @@ fn string() {
     // Note: This parsing is potentially lossy so now != now2 and will be off by a small amount
     // so this code may have to change to compare an interval delta
     assert_eq!(now, now2);
-}
\ No newline at end of file
+}
+
+#[test]
+fn chrono_round_trip() {
+    // Times within the OPC UA range survive the round trip
+    let dt = DateTime::ymd_hms_nano(2020, 3, 4, 5, 6, 7, 800);
+    assert_eq!(DateTime::from_chrono(&dt.as_chrono()), dt);
+    let now = DateTime::now();
+    assert_eq!(DateTime::from_chrono(&now.as_chrono()), now);
+
+    // Chrono times are truncated to whole ticks
+    let chrono_time = Utc.ymd(2020, 3, 4).and_hms_nano(5, 6, 7, 899);
+    let dt = DateTime::from_chrono(&chrono_time);
+    assert_eq!(dt, DateTime::ymd_hms_nano(2020, 3, 4, 5, 6, 7, 800));
+    assert_eq!(dt.as_chrono(), Utc.ymd(2020, 3, 4).and_hms_nano(5, 6, 7, 800));
+
+    // The null date time is the epoch in both directions
+    let null = DateTime::null();
+    assert_eq!(null.as_chrono(), Utc.ymd(1601, 1, 1).and_hms(0, 0, 0));
+    assert!(DateTime::from_chrono(&null.as_chrono()).is_null());
+
+    // Times outside the range are clamped to null or the endtimes
+    assert!(DateTime::from_chrono(&Utc.ymd(1500, 6, 1).and_hms(0, 0, 0)).is_null());
+    let endtimes = DateTime::from_chrono(&Utc.ymd(10000, 1, 1).and_hms(0, 0, 0));
+    assert_eq!(endtimes, DateTime::endtimes());
+    assert_eq!(endtimes.checked_ticks(), DateTime::endtimes_ticks());
+    assert_eq!(DateTime::from_chrono(&endtimes.as_chrono()), endtimes);
+}
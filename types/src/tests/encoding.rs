@@ -405,6 +405,236 @@ fn variant_multi_dimension_array() {
     serialize_test(v);
 }
 
+#[test]
+fn variant_date_time() {
+    // Scalar is the DateTime encoding mask followed by the 64-bit tick count
+    let dt = DateTime::ymd_hms_nano(2020, 2, 29, 12, 30, 15, 123_456_700);
+    let mut expected = vec![DataTypeId::DateTime as u8];
+    expected.extend_from_slice(&dt.checked_ticks().to_le_bytes());
+    serialize_and_compare(Variant::from(dt.clone()), &expected);
+    serialize_test(Variant::from(dt));
+
+    // Array of timestamps as would be returned by a historical read
+    let values = vec![
+        Variant::from(DateTime::epoch()),
+        Variant::from(DateTime::ymd_hms(2001, 9, 11, 8, 46, 0)),
+        Variant::from(DateTime::ymd_hms_nano(2020, 2, 29, 12, 30, 15, 123_456_700)),
+        Variant::from(DateTime::endtimes()),
+    ];
+    let v = serialize_test_and_return(Variant::from(values));
+    if let Variant::Array(ref array) = v {
+        assert_eq!(array.values.len(), 4);
+        assert!(array.values.iter().all(|v| v.type_id() == VariantTypeId::DateTime));
+    } else {
+        panic!("Expected an array, got {:?}", v);
+    }
+}
+
+#[test]
+fn variant_data_value() {
+    // A data value with every field
+    let data_value = DataValue {
+        value: Some(Variant::from(100i32)),
+        status: Some(StatusCode::BadUnexpectedError),
+        source_timestamp: Some(DateTime::ymd_hms(2020, 2, 29, 12, 30, 15)),
+        source_picoseconds: Some(123),
+        server_timestamp: Some(DateTime::ymd_hms(2020, 2, 29, 12, 30, 16)),
+        server_picoseconds: Some(456),
+    };
+    let v = serialize_test_and_return(Variant::from(data_value.clone()));
+    assert_eq!(v.type_id(), VariantTypeId::DataValue);
+    assert_eq!(v, Variant::from(data_value));
+
+    // A data value with just some fields, which are absent from the encoding
+    let data_value = DataValue {
+        value: Some(Variant::from("Hello")),
+        status: None,
+        source_timestamp: None,
+        source_picoseconds: None,
+        server_timestamp: Some(DateTime::ymd_hms(2020, 2, 29, 12, 30, 16)),
+        server_picoseconds: None,
+    };
+    let mut expected = vec![DataTypeId::DataValue as u8];
+    expected.extend_from_slice(&data_value.encode_to_vec());
+    serialize_and_compare(Variant::from(data_value.clone()), &expected);
+    serialize_test(Variant::from(data_value));
+
+    // A data value with no fields at all
+    serialize_and_compare(Variant::from(DataValue::null()), &[DataTypeId::DataValue as u8, 0]);
+
+    // An array of data values
+    serialize_test(Variant::from(vec![Variant::from(DataValue::new_now(1u8)), Variant::from(DataValue::null())]));
+
+    // Data values nested within data values are bound by the decoding depth
+    let nested = (0..constants::MAX_DECODING_DEPTH + 50).fold(DataValue::null(), |inner, _| DataValue::from(Variant::from(inner)));
+    assert_eq!(Variant::decode_default(&mut Cursor::new(Variant::from(nested).encode_to_vec())).unwrap_err(), StatusCode::BadDecodingError);
+}
+
+#[test]
+fn data_value_large_array_byte_len() {
+    // A large array value, as a chunked message might carry
+    let values = (0..100_000).map(|i| Variant::Int32(i)).collect::<Vec<Variant>>();
+    let mut data_value = DataValue::new_now(Variant::from(values));
+
+    // The length is stable across repeated calls and matches the encoding
+    let byte_len = data_value.byte_len();
+    assert_eq!(byte_len, data_value.byte_len());
+    assert_eq!(byte_len, data_value.encode_to_vec().len());
+    // Mask, variant mask, array length, 4 bytes per element, status, timestamps and picoseconds
+    assert_eq!(byte_len, 1 + 1 + 4 + 100_000 * 4 + 4 + (8 + 2) * 2);
+
+    // Changing the value changes the length
+    let values = (0..50_000).map(|i| Variant::from(format!("{}", i))).collect::<Vec<Variant>>();
+    data_value.value = Some(Variant::from(values));
+    data_value.status = Some(StatusCode::BadDataLost);
+    let byte_len = data_value.byte_len();
+    let encoded = data_value.encode_to_vec();
+    assert_eq!(byte_len, encoded.len());
+
+    // The array is larger than the default limit so raise it to decode
+    let decoding_limits = DecodingLimits {
+        max_array_length: 100_000,
+        ..DecodingLimits::default()
+    };
+    assert_eq!(DataValue::decode(&mut Cursor::new(encoded), &decoding_limits).unwrap(), data_value);
+}
+
+#[test]
+fn data_value_picoseconds() {
+    let now = DateTime::now();
+    let data_value = DataValue {
+        value: None,
+        status: None,
+        source_timestamp: Some(now.clone()),
+        source_picoseconds: Some(MAX_PICOSECONDS),
+        server_timestamp: Some(now.clone()),
+        server_picoseconds: Some(0),
+    };
+    serialize_test(data_value.clone());
+
+    // Out of range values are clamped on encode
+    let mut out_of_range = data_value.clone();
+    out_of_range.source_picoseconds = Some(10000);
+    out_of_range.server_picoseconds = Some(-1);
+    let decoded = DataValue::decode_default(&mut Cursor::new(out_of_range.encode_to_vec())).unwrap();
+    assert_eq!(decoded.source_picoseconds, Some(MAX_PICOSECONDS));
+    assert_eq!(decoded.server_picoseconds, Some(0));
+
+    // Out of range values on the wire are a decoding error. Mask, source timestamp, then the
+    // source picoseconds as 10000.
+    let mut bytes = vec![0x14];
+    bytes.extend(now.encode_to_vec());
+    bytes.extend(10000i16.encode_to_vec());
+    assert_eq!(DataValue::decode_default(&mut Cursor::new(bytes)).unwrap_err(), StatusCode::BadDecodingError);
+
+    // And the max valid value decodes
+    let mut bytes = vec![0x14];
+    bytes.extend(now.encode_to_vec());
+    bytes.extend(9999i16.encode_to_vec());
+    assert_eq!(DataValue::decode_default(&mut Cursor::new(bytes)).unwrap().source_picoseconds, Some(9999));
+}
+
+#[test]
+fn data_value_decode_without_status() {
+    // A value and source timestamp with no status, as sent by encoders that omit a Good status
+    let mut bytes = vec![0x01u8 | 0x04u8];
+    bytes.extend(Variant::Double(1.5).encode_to_vec());
+    bytes.extend(DateTime::ymd(2020, 1, 1).encode_to_vec());
+
+    let data_value = DataValue::decode_default(&mut Cursor::new(bytes.clone())).unwrap();
+    assert_eq!(data_value.value, Some(Variant::Double(1.5)));
+    assert_eq!(data_value.source_timestamp, Some(DateTime::ymd(2020, 1, 1)));
+    assert!(data_value.server_timestamp.is_none());
+
+    // The status is absent and is taken to be Good
+    assert!(data_value.status.is_none());
+    assert_eq!(data_value.status(), StatusCode::Good);
+    assert!(data_value.is_valid());
+
+    // Encoding it again produces the same bytes
+    assert_eq!(data_value.encode_to_vec(), bytes);
+}
+
+#[test]
+fn data_value_trimmed_for() {
+    let data_value = DataValue {
+        value: Some(Variant::UInt32(1)),
+        status: Some(StatusCode::Good),
+        source_timestamp: Some(DateTime::ymd(2020, 1, 1)),
+        source_picoseconds: Some(10),
+        server_timestamp: Some(DateTime::ymd(2020, 1, 2)),
+        server_picoseconds: Some(20),
+    };
+    let timestamps = |v: &DataValue| (v.source_timestamp.is_some(), v.source_picoseconds.is_some(), v.server_timestamp.is_some(), v.server_picoseconds.is_some());
+
+    let trimmed = data_value.trimmed_for(TimestampsToReturn::Both);
+    assert_eq!(trimmed, data_value);
+
+    let trimmed = data_value.trimmed_for(TimestampsToReturn::Source);
+    assert_eq!(timestamps(&trimmed), (true, true, false, false));
+    assert_eq!(trimmed.source_timestamp, data_value.source_timestamp);
+
+    let trimmed = data_value.trimmed_for(TimestampsToReturn::Server);
+    assert_eq!(timestamps(&trimmed), (false, false, true, true));
+    assert_eq!(trimmed.server_timestamp, data_value.server_timestamp);
+
+    // Neither leaves only the value and status, so the encoding is the same as a value without
+    // timestamps
+    let trimmed = data_value.trimmed_for(TimestampsToReturn::Neither);
+    assert_eq!(timestamps(&trimmed), (false, false, false, false));
+    assert_eq!(trimmed.value, data_value.value);
+    assert_eq!(trimmed.status, data_value.status);
+    let untimed = DataValue {
+        value: Some(Variant::UInt32(1)),
+        status: Some(StatusCode::Good),
+        ..DataValue::null()
+    };
+    assert_eq!(trimmed.encode_to_vec(), untimed.encode_to_vec());
+    assert_eq!(trimmed.byte_len(), untimed.byte_len());
+
+    let trimmed = data_value.trimmed_for(TimestampsToReturn::Invalid);
+    assert_eq!(timestamps(&trimmed), (false, false, false, false));
+}
+
+#[test]
+fn data_value_merge_latest() {
+    let older = DataValue {
+        value: Some(Variant::UInt32(1)),
+        status: Some(StatusCode::Good | StatusCode::OVERFLOW | StatusCode::LIMIT_HIGH),
+        source_timestamp: Some(DateTime::ymd(2020, 1, 1)),
+        source_picoseconds: None,
+        server_timestamp: Some(DateTime::ymd(2020, 1, 1)),
+        server_picoseconds: None,
+    };
+    let newer = DataValue {
+        value: Some(Variant::UInt32(2)),
+        status: Some(StatusCode::UncertainLastUsableValue),
+        source_timestamp: Some(DateTime::ymd(2020, 1, 2)),
+        source_picoseconds: None,
+        server_timestamp: Some(DateTime::ymd(2020, 1, 2)),
+        server_picoseconds: None,
+    };
+
+    // The newer value wins but the older overflow bit is preserved. The limit bit belonged to the
+    // older value so it is not carried over.
+    let merged = older.clone().merge_latest(newer.clone());
+    assert_eq!(merged.value, newer.value);
+    assert_eq!(merged.source_timestamp, newer.source_timestamp);
+    assert_eq!(merged.server_timestamp, newer.server_timestamp);
+    assert_eq!(merged.status(), StatusCode::UncertainLastUsableValue | StatusCode::OVERFLOW);
+
+    // A newer value without a status gains one to hold the overflow bit
+    let merged = older.merge_latest(DataValue::value_only(3u32));
+    assert_eq!(merged.value, Some(Variant::UInt32(3)));
+    assert_eq!(merged.status, Some(StatusCode::Good | StatusCode::OVERFLOW));
+
+    // Nothing to carry leaves the newer value untouched
+    let merged = DataValue::value_only(1u32).merge_latest(newer.clone());
+    assert_eq!(merged, newer);
+    let merged = DataValue::value_only(1u32).merge_latest(DataValue::value_only(2u32));
+    assert_eq!(merged.status, None);
+}
+
 #[test]
 fn diagnostic_info() {
     let mut d = DiagnosticInfo {
@@ -462,3 +692,365 @@ fn argument() {
         description: LocalizedText::new("foo", "bar"),
     });
 }
+
+#[test]
+fn read_value_id() {
+    // Defaults from a node id
+    serialize_test(ReadValueId::from(NodeId::new(1, "Hello")));
+
+    // Index range and data encoding
+    serialize_test(ReadValueId::new(NodeId::new(2, 100), AttributeId::Value, "1:2,0:3", QualifiedName::new(0, "DefaultBinary")));
+    serialize_test(ReadValueId::new(NodeId::new(2, 100), AttributeId::BrowseName, "5", QualifiedName::new(3, "DefaultXml")));
+
+    // An empty index range is a null string, encoded with a length of -1
+    let v = ReadValueId::new(ObjectId::Server, AttributeId::Value, "", QualifiedName::null());
+    assert!(v.index_range.is_null());
+    serialize_and_compare(v, &[
+        // Node id, numeric ns 0, 2253
+        0x01, 0x00, 0xcd, 0x08,
+        // Attribute id
+        0x0d, 0x00, 0x00, 0x00,
+        // Index range, null
+        0xff, 0xff, 0xff, 0xff,
+        // Data encoding, ns 0, null name
+        0x00, 0x00, 0xff, 0xff, 0xff, 0xff,
+    ]);
+}
+
+#[test]
+fn monitored_item_notification() {
+    // Client handle as a UInt32 followed by the DataValue
+    let notification = MonitoredItemNotification {
+        client_handle: 0x7856_3412,
+        value: DataValue {
+            value: Some(Variant::Int32(42)),
+            status: Some(StatusCode::Good | StatusCode::OVERFLOW),
+            source_timestamp: None,
+            source_picoseconds: None,
+            server_timestamp: None,
+            server_picoseconds: None,
+        },
+    };
+    serialize_and_compare(notification.clone(), &[
+        0x12, 0x34, 0x56, 0x78,
+        0x03,
+        0x06, 0x2a, 0x00, 0x00, 0x00,
+        0x80, 0x00, 0x00, 0x00,
+    ]);
+
+    // The overflow bit survives the round trip
+    let notification = serialize_test_and_return(notification);
+    let status = notification.value.status.unwrap();
+    assert!(status.contains(StatusCode::OVERFLOW));
+    assert_eq!(status.status(), StatusCode::Good);
+
+    // A full data value with timestamps
+    let now = DateTime::now();
+    serialize_test(MonitoredItemNotification {
+        client_handle: 1,
+        value: DataValue {
+            value: Some(Variant::from("Hello")),
+            status: Some(StatusCode::BadOutOfRange | StatusCode::OVERFLOW),
+            source_timestamp: Some(now.clone()),
+            source_picoseconds: Some(10),
+            server_timestamp: Some(now),
+            server_picoseconds: Some(20),
+        },
+    });
+}
+
+#[test]
+fn data_change_notification_with_diagnostics() {
+    let monitored_items = vec![
+        MonitoredItemNotification {
+            client_handle: 1,
+            value: DataValue::value_only(10i32),
+        },
+        MonitoredItemNotification {
+            client_handle: 2,
+            value: DataValue {
+                status: Some(StatusCode::BadNotReadable),
+                ..DataValue::null()
+            },
+        },
+    ];
+    let diagnostic_infos = vec![
+        DiagnosticInfo::null(),
+        DiagnosticInfo {
+            symbolic_id: Some(3),
+            additional_info: Some(UAString::from("Sensor offline")),
+            inner_status_code: Some(StatusCode::BadNotReadable),
+            inner_diagnostic_info: Some(Box::new(DiagnosticInfo {
+                symbolic_id: Some(4),
+                inner_status_code: Some(StatusCode::BadCommunicationError),
+                ..DiagnosticInfo::null()
+            })),
+            ..DiagnosticInfo::null()
+        },
+    ];
+
+    // The diagnostic infos survive the round trip alongside the notifications they describe
+    let message = NotificationMessage::data_change_with_diagnostics(1, DateTime::now(), monitored_items.clone(), Some(diagnostic_infos.clone()), vec![]);
+    let message = serialize_test_and_return(message);
+    let (data_changes, _) = message.notifications(&DecodingLimits::default()).unwrap();
+    assert_eq!(data_changes[0].monitored_items.as_ref().unwrap(), &monitored_items);
+    assert_eq!(data_changes[0].diagnostic_infos.as_ref().unwrap(), &diagnostic_infos);
+
+    // Without them the message is the same as a plain data change
+    let message = NotificationMessage::data_change_with_diagnostics(1, DateTime::now(), monitored_items.clone(), None, vec![]);
+    let (data_changes, _) = message.notifications(&DecodingLimits::default()).unwrap();
+    assert!(data_changes[0].diagnostic_infos.is_none());
+}
+
+#[test]
+fn data_change_notification_encode_batch() {
+    let now = DateTime::now();
+    let monitored_items = (0..10).map(|i| MonitoredItemNotification {
+        client_handle: i,
+        value: DataValue {
+            value: Some(Variant::from(format!("Value {}", i))),
+            status: Some(StatusCode::Good),
+            source_timestamp: Some(now.clone()),
+            source_picoseconds: None,
+            server_timestamp: if i % 2 == 0 { Some(now.clone()) } else { None },
+            server_picoseconds: None,
+        },
+    }).collect::<Vec<_>>();
+
+    // The batch is the item count, each item encoded on its own, and a null diagnostics array
+    let mut expected = Vec::new();
+    let _ = write_i32(&mut expected, monitored_items.len() as i32);
+    monitored_items.iter().for_each(|n| expected.extend(n.encode_to_vec()));
+    let _ = write_i32(&mut expected, -1);
+
+    let mut buffer = Vec::new();
+    assert_eq!(DataChangeNotification::encode_batch(&monitored_items, &mut buffer).unwrap(), expected.len());
+    assert_eq!(DataChangeNotification::batch_byte_len(&monitored_items), expected.len());
+    assert_eq!(buffer, expected);
+
+    // Which is the same as encoding the notification struct
+    let notification = DataChangeNotification {
+        monitored_items: Some(monitored_items.clone()),
+        diagnostic_infos: None,
+    };
+    assert_eq!(buffer, notification.encode_to_vec());
+
+    // A notification message holds the batch encoding as its body
+    let message = NotificationMessage::data_change(1, now.clone(), monitored_items[..5].to_vec(), vec![]);
+    let notification_data = message.notification_data.as_ref().unwrap();
+    assert_eq!(notification_data[0], ExtensionObject::from_encodable(ObjectId::DataChangeNotification_Encoding_DefaultBinary, &DataChangeNotification {
+        monitored_items: Some(monitored_items[..5].to_vec()),
+        diagnostic_infos: None,
+    }));
+    let (data_changes, _) = message.notifications(&DecodingLimits::default()).unwrap();
+    assert_eq!(data_changes[0].monitored_items.as_ref().unwrap(), &monitored_items[..5]);
+
+    // Reusing the buffer for a smaller batch does not allocate
+    let capacity = buffer.capacity();
+    buffer.clear();
+    assert!(DataChangeNotification::encode_batch(&monitored_items[..5], &mut buffer).is_ok());
+    assert_eq!(buffer.capacity(), capacity);
+    let decoded = DataChangeNotification::decode(&mut Cursor::new(buffer), &DecodingLimits::default()).unwrap();
+    assert_eq!(decoded.monitored_items.unwrap(), &monitored_items[..5]);
+}
+
+#[test]
+fn create_monitored_items_request_response() {
+    let item_to_create = |node_id: NodeId, client_handle: u32, filter: ExtensionObject| MonitoredItemCreateRequest {
+        item_to_monitor: ReadValueId {
+            node_id,
+            attribute_id: AttributeId::Value as u32,
+            index_range: UAString::null(),
+            data_encoding: QualifiedName::null(),
+        },
+        monitoring_mode: MonitoringMode::Reporting,
+        requested_parameters: MonitoringParameters {
+            client_handle,
+            sampling_interval: 250f64,
+            filter,
+            queue_size: 10,
+            discard_oldest: true,
+        },
+    };
+    let request = CreateMonitoredItemsRequest {
+        request_header: RequestHeader::dummy(),
+        subscription_id: 7,
+        timestamps_to_return: TimestampsToReturn::Both,
+        items_to_create: Some(vec![
+            item_to_create(NodeId::new(2, "Temperature"), 1, ExtensionObject::null()),
+            item_to_create(NodeId::new(2, 1000), 2, ExtensionObject::from_encodable(ObjectId::DataChangeFilter_Encoding_DefaultBinary, &DataChangeFilter {
+                trigger: DataChangeTrigger::StatusValue,
+                deadband_type: DeadbandType::Absolute as u32,
+                deadband_value: 0.5f64,
+            })),
+        ]),
+    };
+    let request = serialize_test_and_return(request);
+    assert_eq!(request.items_to_create.as_ref().unwrap().len(), 2);
+
+    let response = CreateMonitoredItemsResponse {
+        response_header: ResponseHeader::new_good(&request.request_header),
+        results: Some(vec![
+            MonitoredItemCreateResult {
+                status_code: StatusCode::Good,
+                monitored_item_id: 1,
+                revised_sampling_interval: 250f64,
+                revised_queue_size: 10,
+                filter_result: ExtensionObject::null(),
+            },
+            MonitoredItemCreateResult {
+                status_code: StatusCode::BadNodeIdUnknown,
+                monitored_item_id: 0,
+                revised_sampling_interval: 0f64,
+                revised_queue_size: 0,
+                filter_result: ExtensionObject::null(),
+            },
+        ]),
+        diagnostic_infos: None,
+    };
+    let response = serialize_test_and_return(response);
+    let results = response.results.unwrap();
+    assert_eq!(results.len(), request.items_to_create.unwrap().len());
+    assert_eq!(results[1].status_code, StatusCode::BadNodeIdUnknown);
+}
+
+#[test]
+fn monitoring_mode() {
+    serialize_and_compare(MonitoringMode::Disabled, &[0x00, 0x00, 0x00, 0x00]);
+    serialize_and_compare(MonitoringMode::Sampling, &[0x01, 0x00, 0x00, 0x00]);
+    serialize_and_compare(MonitoringMode::Reporting, &[0x02, 0x00, 0x00, 0x00]);
+    serialize_test(MonitoringMode::Disabled);
+    serialize_test(MonitoringMode::Sampling);
+    serialize_test(MonitoringMode::Reporting);
+
+    let decoding_limits = DecodingLimits::default();
+    let bytes = 3i32.encode_to_vec();
+    assert_eq!(MonitoringMode::decode(&mut Cursor::new(&bytes), &decoding_limits).unwrap_err(), StatusCode::BadDecodingError);
+    let bytes = (-1i32).encode_to_vec();
+    assert_eq!(MonitoringMode::decode(&mut Cursor::new(&bytes), &decoding_limits).unwrap_err(), StatusCode::BadDecodingError);
+}
+
+#[test]
+fn timestamps_to_return() {
+    serialize_and_compare(TimestampsToReturn::Source, &[0x00, 0x00, 0x00, 0x00]);
+    serialize_and_compare(TimestampsToReturn::Server, &[0x01, 0x00, 0x00, 0x00]);
+    serialize_and_compare(TimestampsToReturn::Both, &[0x02, 0x00, 0x00, 0x00]);
+    serialize_and_compare(TimestampsToReturn::Neither, &[0x03, 0x00, 0x00, 0x00]);
+    serialize_test(TimestampsToReturn::Source);
+    serialize_test(TimestampsToReturn::Server);
+    serialize_test(TimestampsToReturn::Both);
+    serialize_test(TimestampsToReturn::Neither);
+
+    // Out of range values are rejected
+    let decoding_limits = DecodingLimits::default();
+    let bytes = 5i32.encode_to_vec();
+    assert_eq!(TimestampsToReturn::decode(&mut Cursor::new(&bytes), &decoding_limits).unwrap_err(), StatusCode::BadDecodingError);
+    let bytes = 4i32.encode_to_vec();
+    assert_eq!(TimestampsToReturn::decode(&mut Cursor::new(&bytes), &decoding_limits).unwrap_err(), StatusCode::BadDecodingError);
+}
+
+#[test]
+fn binary_decoder() {
+    let v = DataValue {
+        value: Some(Variant::from("Hello world")),
+        status: Some(StatusCode::BadNodeIdUnknown),
+        source_timestamp: Some(DateTime::ymd(2020, 1, 1)),
+        source_picoseconds: Some(100),
+        server_timestamp: Some(DateTime::ymd(2020, 1, 2)),
+        server_picoseconds: Some(200),
+    };
+    let bytes = v.encode_to_vec();
+
+    // The encoder's decode and both decoder paths produce the same value
+    let decoding_limits = DecodingLimits::default();
+    let v1 = <DataValue as BinaryEncoder<DataValue>>::decode(&mut Cursor::new(&bytes), &decoding_limits).unwrap();
+    let v2 = DataValue::decode_limited(&mut Cursor::new(&bytes), &decoding_limits).unwrap();
+    let v3 = DataValue::decode_default(&mut Cursor::new(&bytes)).unwrap();
+    assert_eq!(v, v1);
+    assert_eq!(v1, v2);
+    assert_eq!(v2, v3);
+
+    // Limits are honoured
+    let mut decoding_limits = DecodingLimits::default();
+    decoding_limits.max_string_length = 5;
+    assert_eq!(DataValue::decode_limited(&mut Cursor::new(&bytes), &decoding_limits).unwrap_err(), StatusCode::BadDecodingError);
+}
+
+/// A value holding an optional nested value in the body of an extension object
+#[derive(Debug, PartialEq)]
+struct NestedValue {
+    inner: Option<Box<NestedValue>>,
+}
+
+impl NestedValue {
+    fn with_depth(depth: usize) -> NestedValue {
+        (0..depth).fold(NestedValue { inner: None }, |inner, _| NestedValue { inner: Some(Box::new(inner)) })
+    }
+}
+
+impl BinaryEncoder<NestedValue> for NestedValue {
+    fn byte_len(&self) -> usize {
+        if let Some(ref inner) = self.inner {
+            // Node id, encoding byte, body length and body
+            NodeId::new(1, 1).byte_len() + 1 + 4 + inner.byte_len()
+        } else {
+            ExtensionObject::null().byte_len()
+        }
+    }
+
+    fn encode<S: std::io::Write>(&self, stream: &mut S) -> EncodingResult<usize> {
+        let extension_object = if let Some(ref inner) = self.inner {
+            ExtensionObject::from_encodable(NodeId::new(1, 1), inner.as_ref())
+        } else {
+            ExtensionObject::null()
+        };
+        extension_object.encode(stream)
+    }
+
+    fn decode<S: std::io::Read>(stream: &mut S, decoding_limits: &DecodingLimits) -> EncodingResult<Self> {
+        let extension_object = ExtensionObject::decode(stream, decoding_limits)?;
+        let inner = if extension_object.is_null() {
+            None
+        } else {
+            Some(Box::new(extension_object.decode_inner::<NestedValue>(decoding_limits)?))
+        };
+        Ok(NestedValue { inner })
+    }
+}
+
+#[test]
+fn decoding_depth_extension_object() {
+    let decoding_limits = DecodingLimits {
+        max_decoding_depth: 10,
+        ..DecodingLimits::default()
+    };
+
+    // Nesting up to the limit decodes
+    let value = NestedValue::with_depth(10);
+    let decoded = NestedValue::decode(&mut Cursor::new(value.encode_to_vec()), &decoding_limits).unwrap();
+    assert_eq!(decoded, value);
+
+    // One level more is rejected
+    let value = NestedValue::with_depth(11);
+    assert_eq!(NestedValue::decode(&mut Cursor::new(value.encode_to_vec()), &decoding_limits).unwrap_err(), StatusCode::BadDecodingError);
+
+    // A deeply nested value is rejected under the default limits
+    let value = NestedValue::with_depth(constants::MAX_DECODING_DEPTH + 50);
+    assert_eq!(NestedValue::decode_default(&mut Cursor::new(value.encode_to_vec())).unwrap_err(), StatusCode::BadDecodingError);
+}
+
+#[test]
+fn decoding_depth_diagnostic_info() {
+    let diagnostic_info = (0..200).fold(DiagnosticInfo::default(), |inner, _| DiagnosticInfo {
+        inner_diagnostic_info: Some(Box::new(inner)),
+        ..DiagnosticInfo::default()
+    });
+    let bytes = diagnostic_info.encode_to_vec();
+    assert_eq!(DiagnosticInfo::decode_default(&mut Cursor::new(bytes.clone())).unwrap_err(), StatusCode::BadDecodingError);
+
+    let decoding_limits = DecodingLimits {
+        max_decoding_depth: 200,
+        ..DecodingLimits::default()
+    };
+    assert_eq!(DiagnosticInfo::decode(&mut Cursor::new(bytes), &decoding_limits).unwrap(), diagnostic_info);
+}
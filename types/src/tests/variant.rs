@@ -6,6 +6,9 @@ use crate::{
     DataTypeId,
     numeric_range::NumericRange,
     status_code::StatusCode,
+    array::Array,
+    extension_object::ExtensionObject,
+    service_types::ReadValueId,
     variant::{Variant, VariantTypeId},
 };
 
@@ -919,4 +922,72 @@ fn variant_bytestring_to_bytearray() {
     assert_eq!(v[3], Variant::Byte(0x4));
 }
 
+#[test]
+fn variant_structurally_eq_nested_arrays() {
+    let nested = |v: u32| Variant::from(Array::new_single(vec![
+        Variant::from(Array::new_single(vec![Variant::UInt32(1), Variant::UInt32(2)])),
+        Variant::from(Array::new_single(vec![Variant::UInt32(3), Variant::UInt32(v)])),
+    ]));
+    assert!(nested(4).structurally_eq(&nested(4)));
+    assert!(!nested(4).structurally_eq(&nested(5)));
+
+    // Lengths and dimensions must agree
+    let v1 = Variant::from(Array::new_single(vec![Variant::UInt32(1), Variant::UInt32(2)]));
+    let v2 = Variant::from(Array::new_single(vec![Variant::UInt32(1)]));
+    assert!(!v1.structurally_eq(&v2));
+    let v2 = Variant::from(Array::new_multi(vec![Variant::UInt32(1), Variant::UInt32(2)], vec![2u32]));
+    assert!(v1.structurally_eq(&v2));
+    let v1 = Variant::from(Array::new_multi(vec![Variant::UInt32(1), Variant::UInt32(2), Variant::UInt32(3), Variant::UInt32(4)], vec![2u32, 2u32]));
+    let v2 = Variant::from(Array::new_multi(vec![Variant::UInt32(1), Variant::UInt32(2), Variant::UInt32(3), Variant::UInt32(4)], vec![1u32, 4u32]));
+    assert!(!v1.structurally_eq(&v2));
+
+    // NaN is the same value as NaN, including inside arrays
+    assert!(Variant::Double(std::f64::NAN).structurally_eq(&Variant::Double(std::f64::NAN)));
+    assert!(Variant::Float(std::f32::NAN).structurally_eq(&Variant::Float(std::f32::NAN)));
+    let v1 = Variant::from(Array::new_single(vec![Variant::Double(1.0), Variant::Double(std::f64::NAN)]));
+    assert!(v1.structurally_eq(&v1.clone()));
+    assert!(!Variant::Double(1.0).structurally_eq(&Variant::Double(std::f64::NAN)));
+    assert!(!Variant::Double(1.0).structurally_eq(&Variant::Float(1.0)));
+}
+
+#[test]
+fn variant_numerically_eq() {
+    // Numbers of differing types but equal magnitude are the same
+    assert!(Variant::UInt16(5).numerically_eq(&Variant::Int32(5)));
+    assert!(Variant::Int32(5).numerically_eq(&Variant::UInt16(5)));
+    assert!(!Variant::UInt16(5).structurally_eq(&Variant::Int32(5)));
+    assert!(!Variant::UInt16(5).numerically_eq(&Variant::Int32(6)));
+    assert!(Variant::Int64(-1).numerically_eq(&Variant::SByte(-1)));
+    assert!(!Variant::UInt64(std::u64::MAX).numerically_eq(&Variant::Int64(-1)));
+    assert!(Variant::Byte(2).numerically_eq(&Variant::Double(2.0)));
+    assert!(!Variant::Byte(2).numerically_eq(&Variant::Double(2.5)));
+    assert!(Variant::Double(1.0).numerically_eq(&Variant::Float(1.0)));
+    assert!(Variant::Double(std::f64::NAN).numerically_eq(&Variant::Float(std::f32::NAN)));
+    // Arrays are widened element by element
+    assert!(Variant::from(vec![1u16, 2u16]).numerically_eq(&Variant::from(vec![1i32, 2i32])));
+    assert!(!Variant::from(vec![1u16, 2u16]).numerically_eq(&Variant::from(vec![1i32, 3i32])));
+    // Non-numeric values are not converted
+    assert!(!Variant::from("5").numerically_eq(&Variant::Int32(5)));
+    assert!(Variant::from("abc").numerically_eq(&Variant::from("abc")));
+}
+
+#[test]
+fn variant_structurally_eq_extension_objects() {
+    let read_value_id = |node_id: NodeId| ReadValueId {
+        node_id,
+        attribute_id: 13,
+        index_range: UAString::null(),
+        data_encoding: QualifiedName::new(0, "Default Binary"),
+    };
+    let extension_object = |node_id: NodeId| Variant::from(ExtensionObject::from_encodable(NodeId::new(0, 628), &read_value_id(node_id)));
+    let v1 = extension_object(NodeId::new(2, "Device.Sensor.Value"));
+    assert!(v1.structurally_eq(&extension_object(NodeId::new(2, "Device.Sensor.Value"))));
+    // Differs deep inside the encoded body
+    assert!(!v1.structurally_eq(&extension_object(NodeId::new(2, "Device.Sensor.Valve"))));
+
+    let v1 = Variant::from(Array::new_single(vec![extension_object(NodeId::new(2, 1))]));
+    assert!(v1.structurally_eq(&Variant::from(Array::new_single(vec![extension_object(NodeId::new(2, 1))]))));
+    assert!(!v1.structurally_eq(&Variant::from(Array::new_single(vec![extension_object(NodeId::new(2, 2))]))));
+}
+
 // TODO arrays
@@ -13,6 +13,7 @@ use std::str::FromStr;
 use crate::{
     array::*,
     byte_string::ByteString,
+    data_value::DataValue,
     date_time::DateTime,
     encoding::*,
     extension_object::ExtensionObject,
@@ -80,6 +81,8 @@ pub enum Variant {
     ExpandedNodeId(Box<ExpandedNodeId>),
     /// ExtensionObject
     ExtensionObject(Box<ExtensionObject>),
+    /// DataValue
+    DataValue(Box<DataValue>),
     /// Single dimension array which can contain any scalar type, all the same type. Nested
     /// arrays will be rejected.
     Array(Box<Array>),
@@ -113,6 +116,7 @@ pub enum VariantTypeId {
     NodeId,
     ExpandedNodeId,
     ExtensionObject,
+    DataValue,
     Array,
 }
 
@@ -143,6 +147,7 @@ impl TryFrom<&NodeId> for VariantTypeId {
                     type_id if type_id == DataTypeId::StatusCode as u32 => Ok(VariantTypeId::StatusCode),
                     type_id if type_id == DataTypeId::QualifiedName as u32 => Ok(VariantTypeId::QualifiedName),
                     type_id if type_id == DataTypeId::LocalizedText as u32 => Ok(VariantTypeId::LocalizedText),
+                    type_id if type_id == DataTypeId::DataValue as u32 => Ok(VariantTypeId::DataValue),
                     _ => Err(())
                 }
             } else {
@@ -339,6 +344,12 @@ impl From<ExtensionObject> for Variant {
     }
 }
 
+impl From<DataValue> for Variant {
+    fn from(v: DataValue) -> Self {
+        Variant::DataValue(Box::new(v))
+    }
+}
+
 impl<'a, 'b> From<&'a [&'b str]> for Variant {
     fn from(v: &'a [&'b str]) -> Self {
         let values: Vec<Variant> = v.iter().map(|v| Variant::from(*v)).collect();
@@ -512,6 +523,7 @@ impl BinaryEncoder<Variant> for Variant {
             Variant::QualifiedName(value) => value.byte_len(),
             Variant::LocalizedText(value) => value.byte_len(),
             Variant::ExtensionObject(value) => value.byte_len(),
+            Variant::DataValue(value) => value.byte_len(),
             Variant::Array(array) => {
                 // Array length
                 let mut size = 4;
@@ -558,6 +570,7 @@ impl BinaryEncoder<Variant> for Variant {
             Variant::QualifiedName(value) => value.encode(stream)?,
             Variant::LocalizedText(value) => value.encode(stream)?,
             Variant::ExtensionObject(value) => value.encode(stream)?,
+            Variant::DataValue(value) => value.encode(stream)?,
             Variant::Array(array) => {
                 let mut size = write_i32(stream, array.values.len() as i32)?;
                 for value in array.values.iter() {
@@ -701,6 +714,7 @@ impl Variant {
             Variant::QualifiedName(value) => value.byte_len(),
             Variant::LocalizedText(value) => value.byte_len(),
             Variant::ExtensionObject(value) => value.byte_len(),
+            Variant::DataValue(value) => value.byte_len(),
             _ => {
                 error!("Cannot compute length of this type (probably nested array)");
                 0
@@ -734,6 +748,7 @@ impl Variant {
             Variant::QualifiedName(value) => value.encode(stream),
             Variant::LocalizedText(value) => value.encode(stream),
             Variant::ExtensionObject(value) => value.encode(stream),
+            Variant::DataValue(value) => value.encode(stream),
             _ => {
                 warn!("Cannot encode this variant value type (probably nested array)");
                 Err(StatusCode::BadEncodingError)
@@ -790,6 +805,9 @@ impl Variant {
             Self::from(LocalizedText::decode(stream, decoding_limits)?)
         } else if encoding_mask == 22 {
             Self::from(ExtensionObject::decode(stream, decoding_limits)?)
+        } else if Self::test_encoding_flag(encoding_mask, DataTypeId::DataValue) {
+            // The data value holds a variant of its own so it is decoded a level deeper
+            Self::from(DataValue::decode(stream, &decoding_limits.nested()?)?)
         } else {
             Variant::Empty
         };
@@ -1293,6 +1311,7 @@ impl Variant {
             Variant::QualifiedName(_) => VariantTypeId::QualifiedName,
             Variant::LocalizedText(_) => VariantTypeId::LocalizedText,
             Variant::ExtensionObject(_) => VariantTypeId::ExtensionObject,
+            Variant::DataValue(_) => VariantTypeId::DataValue,
             Variant::Array(_) => VariantTypeId::Array,
         }
     }
@@ -1317,6 +1336,60 @@ impl Variant {
         }
     }
 
+    /// Tests if two variants hold the same value, for detecting a change in value. Unlike `==`,
+    /// NaN compares equal to NaN, a single dimension array compares equal whether or not its
+    /// dimensions are set and extension objects are compared by their encoded bytes. Arrays are
+    /// compared element by element with the same rules.
+    pub fn structurally_eq(&self, other: &Variant) -> bool {
+        self.value_eq(other, false)
+    }
+
+    /// Tests if two variants hold the same value in the same way as `structurally_eq()` except that
+    /// numeric values of differing types are widened to a common type before they are compared,
+    /// so `UInt16(5)` is the same as `Int32(5)`. Integers are compared exactly and a comparison
+    /// involving a float is made as a double.
+    pub fn numerically_eq(&self, other: &Variant) -> bool {
+        self.value_eq(other, true)
+    }
+
+    fn value_eq(&self, other: &Variant, widen: bool) -> bool {
+        match (self, other) {
+            (Variant::Float(v1), Variant::Float(v2)) => v1 == v2 || (v1.is_nan() && v2.is_nan()),
+            (Variant::Double(v1), Variant::Double(v2)) => v1 == v2 || (v1.is_nan() && v2.is_nan()),
+            (Variant::ExtensionObject(v1), Variant::ExtensionObject(v2)) => v1.encode_to_vec() == v2.encode_to_vec(),
+            (Variant::Array(v1), Variant::Array(v2)) => {
+                v1.values.len() == v2.values.len() &&
+                    v1.shape() == v2.shape() &&
+                    v1.values.iter().zip(v2.values.iter()).all(|(v1, v2)| v1.value_eq(v2, widen))
+            }
+            (v1, v2) if widen && v1.is_numeric() && v2.is_numeric() => {
+                match (v1.as_i128(), v2.as_i128()) {
+                    (Some(v1), Some(v2)) => v1 == v2,
+                    _ => {
+                        let (v1, v2) = (v1.as_f64().unwrap(), v2.as_f64().unwrap());
+                        v1 == v2 || (v1.is_nan() && v2.is_nan())
+                    }
+                }
+            }
+            (v1, v2) => v1 == v2
+        }
+    }
+
+    /// Returns the value of an integer variant widened to an i128, which holds any of them
+    fn as_i128(&self) -> Option<i128> {
+        match *self {
+            Variant::SByte(value) => Some(value as i128),
+            Variant::Byte(value) => Some(value as i128),
+            Variant::Int16(value) => Some(value as i128),
+            Variant::UInt16(value) => Some(value as i128),
+            Variant::Int32(value) => Some(value as i128),
+            Variant::UInt32(value) => Some(value as i128),
+            Variant::Int64(value) => Some(value as i128),
+            Variant::UInt64(value) => Some(value as i128),
+            _ => None
+        }
+    }
+
     pub fn is_array_of_type(&self, variant_type: VariantTypeId) -> bool {
         // A non-numeric value in the array means it is not numeric
         match self {
@@ -1404,6 +1477,7 @@ impl Variant {
             Variant::StatusCode(_) => Some(DataTypeId::StatusCode.into()),
             Variant::QualifiedName(_) => Some(DataTypeId::QualifiedName.into()),
             Variant::LocalizedText(_) => Some(DataTypeId::LocalizedText.into()),
+            Variant::DataValue(_) => Some(DataTypeId::DataValue.into()),
             _ => None
         }
     }
@@ -1434,6 +1508,7 @@ impl Variant {
             Variant::QualifiedName(_) => DataTypeId::QualifiedName as u8,
             Variant::LocalizedText(_) => DataTypeId::LocalizedText as u8,
             Variant::ExtensionObject(_) => 22, // DataTypeId::ExtensionObject as u8,
+            Variant::DataValue(_) => DataTypeId::DataValue as u8,
             Variant::Array(array) => {
                 let mut encoding_mask = if array.values.is_empty() {
                     0u8